@@ -1,10 +1,15 @@
-use crate::callable::{Callable, LoxCallable};
+use crate::callable::{types::FUNCTION, Arity, Callable, LoxCallable};
 use crate::class::{Class, InstanceRc, LoxClass, MethodMap, NativeMethod};
 use crate::constants::ARRAY_CLASS;
 use crate::eval::{Interpreter, RuntimeError, ValueRes};
+use crate::gc::{Trace, Tracer};
 use crate::location::Loc;
-use crate::value::{types::INT, Value};
+use crate::value::{
+    types::{ARRAY, INT, STRING},
+    Value,
+};
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::rc::Rc;
 use std::slice::Iter;
@@ -66,6 +71,55 @@ impl Array {
             Err(RuntimeError::IndexOutOfBounds(loc, index, len))
         }
     }
+
+    pub fn insert(&mut self, index: i64, val: Value, loc: Loc) -> Result<(), RuntimeError> {
+        let len = self.len();
+        if index >= 0 && (index as usize) <= len {
+            self.data.insert(index as usize, val);
+            Ok(())
+        } else {
+            Err(RuntimeError::IndexOutOfBounds(loc, index, len))
+        }
+    }
+
+    pub fn remove(&mut self, index: i64, loc: Loc) -> ValueRes {
+        let len = self.len();
+        if index >= 0 && (index as usize) < len {
+            Ok(self.data.remove(index as usize))
+        } else {
+            Err(RuntimeError::IndexOutOfBounds(loc, index, len))
+        }
+    }
+
+    pub fn reverse(&mut self) {
+        self.data.reverse();
+    }
+
+    pub fn elements(&self) -> Vec<Value> {
+        self.data.clone()
+    }
+
+    pub fn set_elements(&mut self, data: Vec<Value>) {
+        self.data = data;
+    }
+}
+
+impl Trace for RefCell<Array> {
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        match self.try_borrow() {
+            Ok(array) => {
+                array.data.iter().for_each(|val| tracer.value(val));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut array) = self.try_borrow_mut() {
+            array.data.clear();
+        }
+    }
 }
 
 impl Display for Array {
@@ -105,6 +159,23 @@ impl ArrayClass {
         Self::add_pop_method(&mut methods);
         Self::add_get_method(&mut methods);
         Self::add_set_method(&mut methods);
+        Self::add_map_method(&mut methods);
+        Self::add_filter_method(&mut methods);
+        Self::add_reduce_method(&mut methods);
+        Self::add_for_each_method(&mut methods);
+        Self::add_find_method(&mut methods);
+        Self::add_find_index_method(&mut methods);
+        Self::add_some_method(&mut methods);
+        Self::add_every_method(&mut methods);
+        Self::add_sort_method(&mut methods);
+        Self::add_reverse_method(&mut methods);
+        Self::add_slice_method(&mut methods);
+        Self::add_concat_method(&mut methods);
+        Self::add_index_of_method(&mut methods);
+        Self::add_contains_method(&mut methods);
+        Self::add_insert_method(&mut methods);
+        Self::add_remove_method(&mut methods);
+        Self::add_join_method(&mut methods);
 
         let metainstance =
             Class::create_metainstance(ARRAY_CLASS, uuid, None, static_methods, &methods, &getters);
@@ -183,6 +254,343 @@ impl ArrayClass {
         )
     }
 
+    fn add_map_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("map", 1, |inter, mut args: Vec<Value>, instance, loc| {
+                let array = instance.array().unwrap();
+                let callback = expect_callable(args.pop().unwrap(), loc)?;
+                let elements = array.borrow().elements();
+                let mut mapped = Vec::with_capacity(elements.len());
+                for (i, el) in elements.into_iter().enumerate() {
+                    mapped.push(call_callback(inter, &callback, el, i, &array, loc)?);
+                }
+
+                Ok(Array::new(inter, mapped).into())
+            }),
+        )
+    }
+
+    fn add_filter_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("filter", 1, |inter, mut args: Vec<Value>, instance, loc| {
+                let array = instance.array().unwrap();
+                let callback = expect_callable(args.pop().unwrap(), loc)?;
+                let elements = array.borrow().elements();
+                let mut filtered = vec![];
+                for (i, el) in elements.into_iter().enumerate() {
+                    if call_callback(inter, &callback, el.clone(), i, &array, loc)?.is_truthy() {
+                        filtered.push(el);
+                    }
+                }
+
+                Ok(Array::new(inter, filtered).into())
+            }),
+        )
+    }
+
+    fn add_reduce_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("reduce", 2, |inter, mut args: Vec<Value>, instance, loc| {
+                let array = instance.array().unwrap();
+                let mut acc = args.pop().unwrap();
+                let callback = expect_callable(args.pop().unwrap(), loc)?;
+                let elements = array.borrow().elements();
+                for (i, el) in elements.into_iter().enumerate() {
+                    let args = vec![
+                        acc,
+                        el,
+                        Value::Integer(i as i64),
+                        Value::Array(Rc::clone(&array)),
+                    ];
+                    acc = call_with_args(inter, &callback, args, loc)?;
+                }
+
+                Ok(acc)
+            }),
+        )
+    }
+
+    fn add_for_each_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new(
+                "forEach",
+                1,
+                |inter, mut args: Vec<Value>, instance, loc| {
+                    let array = instance.array().unwrap();
+                    let callback = expect_callable(args.pop().unwrap(), loc)?;
+                    let elements = array.borrow().elements();
+                    for (i, el) in elements.into_iter().enumerate() {
+                        call_callback(inter, &callback, el, i, &array, loc)?;
+                    }
+
+                    Ok(Value::Nil)
+                },
+            ),
+        )
+    }
+
+    fn add_find_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("find", 1, |inter, mut args: Vec<Value>, instance, loc| {
+                let array = instance.array().unwrap();
+                let callback = expect_callable(args.pop().unwrap(), loc)?;
+                let elements = array.borrow().elements();
+                for (i, el) in elements.into_iter().enumerate() {
+                    if call_callback(inter, &callback, el.clone(), i, &array, loc)?.is_truthy() {
+                        return Ok(el);
+                    }
+                }
+
+                Ok(Value::Nil)
+            }),
+        )
+    }
+
+    fn add_find_index_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new(
+                "findIndex",
+                1,
+                |inter, mut args: Vec<Value>, instance, loc| {
+                    let array = instance.array().unwrap();
+                    let callback = expect_callable(args.pop().unwrap(), loc)?;
+                    let elements = array.borrow().elements();
+                    for (i, el) in elements.into_iter().enumerate() {
+                        if call_callback(inter, &callback, el, i, &array, loc)?.is_truthy() {
+                            return Ok(Value::Integer(i as i64));
+                        }
+                    }
+
+                    Ok(Value::Integer(-1))
+                },
+            ),
+        )
+    }
+
+    fn add_some_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("some", 1, |inter, mut args: Vec<Value>, instance, loc| {
+                let array = instance.array().unwrap();
+                let callback = expect_callable(args.pop().unwrap(), loc)?;
+                let elements = array.borrow().elements();
+                for (i, el) in elements.into_iter().enumerate() {
+                    if call_callback(inter, &callback, el, i, &array, loc)?.is_truthy() {
+                        return Ok(Value::Boolean(true));
+                    }
+                }
+
+                Ok(Value::Boolean(false))
+            }),
+        )
+    }
+
+    fn add_every_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("every", 1, |inter, mut args: Vec<Value>, instance, loc| {
+                let array = instance.array().unwrap();
+                let callback = expect_callable(args.pop().unwrap(), loc)?;
+                let elements = array.borrow().elements();
+                for (i, el) in elements.into_iter().enumerate() {
+                    if !call_callback(inter, &callback, el, i, &array, loc)?.is_truthy() {
+                        return Ok(Value::Boolean(false));
+                    }
+                }
+
+                Ok(Value::Boolean(true))
+            }),
+        )
+    }
+
+    fn add_sort_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::with_arity(
+                "sort",
+                Arity::Range(0, 1),
+                |inter, mut args: Vec<Value>, instance, loc| {
+                    let array = instance.array().unwrap();
+                    let comparator = match args.pop() {
+                        Some(val) => Some(expect_callable(val, loc)?),
+                        None => None,
+                    };
+
+                    let elements = array.borrow().elements();
+                    let sorted = merge_sort(elements, &mut |left, right| match &comparator {
+                        Some(comparator) => {
+                            let args = vec![left.clone(), right.clone()];
+                            let res = call_with_args(inter, comparator, args, loc)?;
+                            match res.number() {
+                                Some(num) if num < 0.0 => Ok(Ordering::Less),
+                                Some(num) if num > 0.0 => Ok(Ordering::Greater),
+                                Some(_) => Ok(Ordering::Equal),
+                                None => Err(RuntimeError::generic(
+                                    loc,
+                                    "The comparator must return a number",
+                                )),
+                            }
+                        }
+                        None => compare(left, right, loc),
+                    })?;
+                    array.borrow_mut().set_elements(sorted);
+
+                    Ok(Value::Array(array))
+                },
+            ),
+        )
+    }
+
+    fn add_reverse_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("reverse", 0, |_inter, _args, instance, _loc| {
+                let array = instance.array().unwrap();
+                array.borrow_mut().reverse();
+                Ok(Value::Array(array))
+            }),
+        )
+    }
+
+    fn add_slice_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("slice", 2, |inter, mut args: Vec<Value>, instance, loc| {
+                let array = instance.array().unwrap();
+                let end = expect_int(args.pop().unwrap(), loc)?;
+                let start = expect_int(args.pop().unwrap(), loc)?;
+                let elements = array.borrow().elements();
+                let len = elements.len();
+                for &index in &[start, end] {
+                    if index < 0 || (index as usize) > len {
+                        return Err(RuntimeError::IndexOutOfBounds(loc, index, len));
+                    }
+                }
+
+                let slice = elements
+                    .into_iter()
+                    .skip(start as usize)
+                    .take((end - start).max(0) as usize)
+                    .collect();
+                Ok(Array::new(inter, slice).into())
+            }),
+        )
+    }
+
+    fn add_concat_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("concat", 1, |inter, mut args: Vec<Value>, instance, loc| {
+                let array = instance.array().unwrap();
+                let other = match args.pop().unwrap() {
+                    Value::Array(other) => other.borrow().elements(),
+                    val => return Err(RuntimeError::expected_type(loc, ARRAY, val)),
+                };
+
+                let mut elements = array.borrow().elements();
+                elements.extend(other);
+                Ok(Array::new(inter, elements).into())
+            }),
+        )
+    }
+
+    fn add_index_of_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new(
+                "indexOf",
+                1,
+                |_inter, mut args: Vec<Value>, instance, _loc| {
+                    let array = instance.array().unwrap();
+                    let val = args.pop().unwrap();
+                    let index = array
+                        .borrow()
+                        .iter()
+                        .position(|el| el.equal(&val).is_truthy())
+                        .map(|index| index as i64)
+                        .unwrap_or(-1);
+                    Ok(Value::Integer(index))
+                },
+            ),
+        )
+    }
+
+    fn add_contains_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new(
+                "contains",
+                1,
+                |_inter, mut args: Vec<Value>, instance, _loc| {
+                    let array = instance.array().unwrap();
+                    let val = args.pop().unwrap();
+                    let contains = array.borrow().iter().any(|el| el.equal(&val).is_truthy());
+                    Ok(Value::Boolean(contains))
+                },
+            ),
+        )
+    }
+
+    fn add_insert_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new(
+                "insert",
+                2,
+                |_inter, mut args: Vec<Value>, instance, loc| {
+                    let array = instance.array().unwrap();
+                    let val = args.pop().unwrap();
+                    let index = expect_int(args.pop().unwrap(), loc)?;
+                    array.borrow_mut().insert(index, val, loc)?;
+                    Ok(Value::Nil)
+                },
+            ),
+        )
+    }
+
+    fn add_remove_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new(
+                "remove",
+                1,
+                |_inter, mut args: Vec<Value>, instance, loc| {
+                    let array = instance.array().unwrap();
+                    let index = expect_int(args.pop().unwrap(), loc)?;
+                    let val = array.borrow_mut().remove(index, loc)?;
+                    Ok(val)
+                },
+            ),
+        )
+    }
+
+    fn add_join_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("join", 1, |_inter, mut args: Vec<Value>, instance, loc| {
+                let array = instance.array().unwrap();
+                let separator = match args.pop().unwrap() {
+                    Value::Str(separator) => separator,
+                    val => return Err(RuntimeError::expected_type(loc, STRING, val)),
+                };
+
+                let joined = array
+                    .borrow()
+                    .iter()
+                    .map(Value::to_string)
+                    .collect::<Vec<_>>()
+                    .join(&separator);
+                Ok(joined.into())
+            }),
+        )
+    }
+
     fn add_new_smethod(static_methods: &mut MethodMap) {
         Class::add_native_method(
             static_methods,
@@ -193,6 +601,98 @@ impl ArrayClass {
     }
 }
 
+fn expect_int(val: Value, loc: Loc) -> Result<i64, RuntimeError> {
+    match val {
+        Value::Integer(int) => Ok(int),
+        val => Err(RuntimeError::expected_type(loc, INT, val)),
+    }
+}
+
+fn expect_callable(val: Value, loc: Loc) -> Result<Callable, RuntimeError> {
+    match val {
+        Value::Callable(callable) => Ok(callable),
+        val => Err(RuntimeError::expected_type(loc, FUNCTION, val)),
+    }
+}
+
+/// Calls a callback with an element, its index and the array itself,
+/// passing only as many of those as the callback accepts.
+fn call_callback(
+    inter: &mut Interpreter,
+    callback: &Callable,
+    el: Value,
+    index: usize,
+    array: &ArrayRc,
+    loc: Loc,
+) -> ValueRes {
+    let args = vec![
+        el,
+        Value::Integer(index as i64),
+        Value::Array(Rc::clone(array)),
+    ];
+    call_with_args(inter, callback, args, loc)
+}
+
+fn call_with_args(
+    inter: &mut Interpreter,
+    callback: &Callable,
+    mut args: Vec<Value>,
+    loc: Loc,
+) -> ValueRes {
+    let arity = callback.arity();
+    args.truncate(arity.max());
+    if arity.accepts(args.len()) {
+        callback.call(inter, args, loc)
+    } else {
+        Err(RuntimeError::mismatching_arity(loc, arity, args.len()))
+    }
+}
+
+fn compare(left: &Value, right: &Value, loc: Loc) -> Result<Ordering, RuntimeError> {
+    match (left, right) {
+        (Value::Str(left), Value::Str(right)) => Ok(left.cmp(right)),
+        (left, right) => match (left.number(), right.number()) {
+            (Some(l), Some(r)) => Ok(l.partial_cmp(&r).unwrap_or(Ordering::Equal)),
+            _ => Err(RuntimeError::UnsupportedOperands(
+                loc,
+                String::from("<"),
+                String::from(left.get_type()),
+                String::from(right.get_type()),
+            )),
+        },
+    }
+}
+
+/// A stable merge sort whose comparator is allowed to fail, unlike the one
+/// in the standard library, since Lox comparators can raise runtime errors.
+fn merge_sort<F>(mut values: Vec<Value>, cmp: &mut F) -> Result<Vec<Value>, RuntimeError>
+where
+    F: FnMut(&Value, &Value) -> Result<Ordering, RuntimeError>,
+{
+    if values.len() <= 1 {
+        return Ok(values);
+    }
+
+    let right = values.split_off(values.len() / 2);
+    let left = merge_sort(values, cmp)?;
+    let right = merge_sort(right, cmp)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if cmp(l, r)? == Ordering::Greater {
+            merged.push(right.next().unwrap());
+        } else {
+            merged.push(left.next().unwrap());
+        }
+    }
+
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
 impl LoxClass for Rc<ArrayClass> {
     fn name(&self) -> &str {
         ARRAY_CLASS
@@ -220,8 +720,8 @@ impl LoxClass for Rc<ArrayClass> {
 }
 
 impl LoxCallable for Rc<ArrayClass> {
-    fn arity(&self) -> usize {
-        2
+    fn arity(&self) -> Arity {
+        Arity::Fixed(2)
     }
 
     fn call(&self, inter: &mut Interpreter, mut args: Vec<Value>, loc: Loc) -> ValueRes {
@@ -1,9 +1,14 @@
 use crate::class::{BoundMethod, Class, InstanceRc, LoxClass};
-use crate::eval::{Env, Environ, GlobalEnviron, Interpreter, RuntimeInterrupt, ValueRes};
+use crate::eval::{
+    Env, Environ, GlobalEnv, GlobalEnviron, Interpreter, RuntimeInterrupt, ValueRes,
+};
+use crate::gc::{self, Trace, Tracer};
 use crate::location::Loc;
 use crate::stmt::Stmt;
 use crate::value::Value;
+use crate::vm::Closure;
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::ops::Add;
 use std::rc::Rc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -11,25 +16,46 @@ use std::time::{SystemTime, UNIX_EPOCH};
 pub enum Callable {
     Native(NativeFunction),
     Function(Rc<Function>),
+    Closure(Rc<Closure>),
     BoundMethod(BoundMethod),
     Class(Class),
 }
 
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub enum NativeFunction {
     Clock,
     Str,
+    Gc,
+    Host(Rc<HostFunction>),
 }
 
+/// A native function provided by the application embedding the
+/// interpreter.
+pub struct HostFunction {
+    pub name: String,
+    pub arity: Arity,
+    pub fun: HostFn,
+}
+
+pub type HostFn = Box<dyn Fn(&mut Interpreter, Vec<Value>, Loc) -> ValueRes>;
+
 #[derive(Debug)]
 pub struct Function {
     pub name: Option<String>,
     params: Vec<String>,
     body: Vec<Stmt>,
     pub closure: Option<Env>,
+    globals: GlobalEnv,
     is_init: bool,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Arity {
+    Fixed(usize),
+    Range(usize, usize),
+    Variadic(usize),
+}
+
 pub mod types {
     pub const NATIVE: &str = "native_fn";
     pub const FUNCTION: &str = "function";
@@ -37,7 +63,7 @@ pub mod types {
 }
 
 pub trait LoxCallable: Into<Callable> {
-    fn arity(&self) -> usize;
+    fn arity(&self) -> Arity;
     fn call(&self, inter: &mut Interpreter, args: Vec<Value>, loc: Loc) -> ValueRes;
 }
 
@@ -47,6 +73,7 @@ impl Callable {
         match self {
             Native(_) => types::NATIVE,
             Function(_) => types::FUNCTION,
+            Closure(_) => types::FUNCTION,
             BoundMethod(_) => types::FUNCTION,
             Class(_) => types::CLASS,
         }
@@ -66,6 +93,7 @@ impl Display for Callable {
         match self {
             Native(function) => Display::fmt(function, f),
             Function(function) => Display::fmt(function, f),
+            Closure(closure) => Display::fmt(closure, f),
             BoundMethod(method) => Display::fmt(method, f),
             Class(class) => Display::fmt(class, f),
         }
@@ -73,11 +101,12 @@ impl Display for Callable {
 }
 
 impl LoxCallable for Callable {
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         use Callable::*;
         match self {
             Native(function) => function.arity(),
             Function(function) => function.arity(),
+            Closure(closure) => closure.arity(),
             BoundMethod(method) => method.arity(),
             Class(class) => class.arity(),
         }
@@ -88,6 +117,7 @@ impl LoxCallable for Callable {
         match self {
             Native(function) => function.call(inter, args, loc),
             Function(function) => function.call(inter, args, loc),
+            Closure(closure) => closure.call(inter, args, loc),
             BoundMethod(method) => method.call(inter, args, loc),
             Class(class) => class.call(inter, args, loc),
         }
@@ -100,6 +130,7 @@ impl PartialEq for Callable {
         match (self, other) {
             (Native(left), Native(right)) => left == right,
             (Function(left), Function(right)) => Rc::ptr_eq(left, right),
+            (Closure(left), Closure(right)) => Rc::ptr_eq(left, right),
             (BoundMethod(left), BoundMethod(right)) => left == right,
             (Class(left), Class(right)) => left == right,
             (_, _) => false,
@@ -108,29 +139,84 @@ impl PartialEq for Callable {
 }
 
 impl NativeFunction {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         use NativeFunction::*;
         match self {
             Clock => "clock",
             Str => "str",
+            Gc => "gc",
+            Host(host) => &host.name,
         }
     }
 }
 
 impl LoxCallable for NativeFunction {
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         use NativeFunction::*;
         match self {
-            Clock => 0,
-            Str => 1,
+            Clock => Arity::Fixed(0),
+            Str => Arity::Fixed(1),
+            Gc => Arity::Fixed(0),
+            Host(host) => host.arity,
         }
     }
 
     fn call(&self, inter: &mut Interpreter, args: Vec<Value>, loc: Loc) -> ValueRes {
         use NativeFunction::*;
-        match self {
+        inter.with_frame(self.name(), loc, |inter| match self {
             Clock => clock(inter, args, loc),
             Str => val_to_str(inter, args, loc),
+            Gc => collect_garbage(inter, args, loc),
+            Host(host) => (host.fun)(inter, args, loc),
+        })
+    }
+}
+
+impl Arity {
+    pub fn accepts(self, args: usize) -> bool {
+        match self {
+            Arity::Fixed(arity) => args == arity,
+            Arity::Range(min, max) => args >= min && args <= max,
+            Arity::Variadic(min) => args >= min,
+        }
+    }
+
+    pub fn max(self) -> usize {
+        match self {
+            Arity::Fixed(arity) => arity,
+            Arity::Range(_min, max) => max,
+            Arity::Variadic(_min) => usize::MAX,
+        }
+    }
+
+    /// The arity followed by "argument" or "arguments", whichever reads
+    /// right after it.
+    pub fn arguments(self) -> String {
+        match self {
+            Arity::Fixed(1) | Arity::Variadic(1) => format!("{} argument", self),
+            _ => format!("{} arguments", self),
+        }
+    }
+}
+
+impl Add<usize> for Arity {
+    type Output = Self;
+
+    fn add(self, rhs: usize) -> Self {
+        match self {
+            Arity::Fixed(arity) => Arity::Fixed(arity + rhs),
+            Arity::Range(min, max) => Arity::Range(min + rhs, max + rhs),
+            Arity::Variadic(min) => Arity::Variadic(min + rhs),
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Arity::Fixed(arity) => write!(f, "{}", arity),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+            Arity::Variadic(min) => write!(f, "at least {}", min),
         }
     }
 }
@@ -141,6 +227,35 @@ impl Display for NativeFunction {
     }
 }
 
+impl HostFunction {
+    pub fn new<F>(name: &str, arity: Arity, fun: F) -> Self
+    where
+        F: Fn(&mut Interpreter, Vec<Value>, Loc) -> ValueRes + 'static,
+    {
+        Self {
+            name: String::from(name),
+            arity,
+            fun: Box::new(fun),
+        }
+    }
+}
+
+impl PartialEq for HostFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Debug for HostFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("HostFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .field("fun", &String::from("<host function>"))
+            .finish()
+    }
+}
+
 impl From<NativeFunction> for Callable {
     fn from(native_fn: NativeFunction) -> Self {
         Callable::Native(native_fn)
@@ -153,6 +268,7 @@ impl Function {
         params: Vec<String>,
         body: &[Stmt],
         closure: &Option<Env>,
+        globals: &GlobalEnv,
         is_init: bool,
     ) -> Self {
         Self {
@@ -160,16 +276,23 @@ impl Function {
             params,
             body: body.to_vec(),
             closure: closure.as_ref().map(Rc::clone),
+            globals: Rc::clone(globals),
             is_init,
         }
     }
 
-    pub fn new_anon(params: Vec<String>, body: &[Stmt], closure: &Option<Env>) -> Self {
+    pub fn new_anon(
+        params: Vec<String>,
+        body: &[Stmt],
+        closure: &Option<Env>,
+        globals: &GlobalEnv,
+    ) -> Self {
         Self {
             name: None,
             params,
             body: body.to_vec(),
             closure: closure.as_ref().map(Rc::clone),
+            globals: Rc::clone(globals),
             is_init: false,
         }
     }
@@ -178,27 +301,39 @@ impl Function {
         &self,
         inter: &mut Interpreter,
         args: Vec<Value>,
-        _loc: Loc,
+        loc: Loc,
         closure: &Option<Env>,
     ) -> ValueRes {
-        let env = Environ::with_enclosing(closure);
-        for arg in args.into_iter() {
-            env.borrow_mut().define(arg);
-        }
+        inter.with_frame(self.frame_name(), loc, |inter| {
+            let env = Environ::with_enclosing(closure);
+            for arg in args.into_iter() {
+                env.borrow_mut().define(arg);
+            }
 
-        match inter.execute_block(&self.body, env) {
-            Ok(()) => Ok(if self.is_init {
-                self.get_this(closure)
-            } else {
-                Value::Nil
-            }),
-            Err(RuntimeInterrupt::Return(ret)) => Ok(if self.is_init {
-                self.get_this(closure)
-            } else {
-                ret
-            }),
-            Err(error) => Err(error.expect_error()),
-        }
+            // Functions imported from other modules keep seeing their own globals
+            let prev_globals = std::mem::replace(&mut inter.globals, Rc::clone(&self.globals));
+            let res = inter.execute_block(&self.body, env);
+            inter.globals = prev_globals;
+
+            match res {
+                Ok(()) => Ok(if self.is_init {
+                    self.get_this(closure)
+                } else {
+                    Value::Nil
+                }),
+                Err(RuntimeInterrupt::Return(ret)) => Ok(if self.is_init {
+                    self.get_this(closure)
+                } else {
+                    ret
+                }),
+                Err(RuntimeInterrupt::Throw(val, loc)) => Err(inter.stash_exception(val, loc)),
+                Err(error) => Err(error.expect_error()),
+            }
+        })
+    }
+
+    fn frame_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<anonymous fn>")
     }
 
     fn get_this(&self, closure: &Option<Env>) -> Value {
@@ -207,8 +342,8 @@ impl Function {
 }
 
 impl LoxCallable for Function {
-    fn arity(&self) -> usize {
-        self.params.len()
+    fn arity(&self) -> Arity {
+        Arity::Fixed(self.params.len())
     }
 
     fn call(&self, inter: &mut Interpreter, args: Vec<Value>, loc: Loc) -> ValueRes {
@@ -227,7 +362,16 @@ impl Display for Function {
 
 impl From<Function> for Callable {
     fn from(function: Function) -> Self {
-        Callable::Function(Rc::new(function))
+        Callable::Function(gc::alloc(function))
+    }
+}
+
+impl Trace for Function {
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        if let Some(closure) = &self.closure {
+            tracer.rc(closure);
+        }
+        true
     }
 }
 
@@ -237,13 +381,18 @@ impl From<BoundMethod> for Callable {
     }
 }
 
-pub fn define_native_functions(globals: &mut GlobalEnviron) {
+pub fn define_native_functions(globals: &mut GlobalEnviron, host_functions: &[Rc<HostFunction>]) {
     define_native(globals, NativeFunction::Clock);
     define_native(globals, NativeFunction::Str);
+    define_native(globals, NativeFunction::Gc);
+    for host in host_functions {
+        define_native(globals, NativeFunction::Host(Rc::clone(host)));
+    }
 }
 
 fn define_native(globals: &mut GlobalEnviron, function: NativeFunction) {
-    globals.define(function.name(), function.into());
+    let name = String::from(function.name());
+    globals.define(&name, function.into());
 }
 
 fn clock(_inter: &mut Interpreter, _args: Vec<Value>, _loc: Loc) -> ValueRes {
@@ -256,6 +405,11 @@ fn clock(_inter: &mut Interpreter, _args: Vec<Value>, _loc: Loc) -> ValueRes {
     Ok(in_ms.into())
 }
 
+fn collect_garbage(_inter: &mut Interpreter, _args: Vec<Value>, _loc: Loc) -> ValueRes {
+    let collected = gc::collect();
+    Ok(Value::Integer(collected as i64))
+}
+
 fn val_to_str(_inter: &mut Interpreter, args: Vec<Value>, _loc: Loc) -> ValueRes {
     let value = args.first().unwrap();
     Ok(value.to_string().into())
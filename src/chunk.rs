@@ -0,0 +1,143 @@
+use crate::location::Loc;
+use crate::stmt::FunctionKind;
+use crate::value::Value;
+use std::rc::Rc;
+
+/// A single VM instruction. Operands index into the constant pool of the
+/// chunk, into the stack slots of the current frame or hold jump offsets.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OpCode {
+    Constant(u16),
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal(u16),
+    SetLocal(u16),
+    GetGlobal(u16),
+    DefineGlobal(u16),
+    SetGlobal(u16),
+    GetUpvalue(u16),
+    SetUpvalue(u16),
+    GetProperty(u16),
+    SetProperty(u16),
+    GetSuper(u16),
+    GetIndex,
+    SetIndex,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Not,
+    Negate,
+    Print,
+    Jump(u16),
+    JumpIfFalse(u16),
+    Loop(u16),
+    Call(u8),
+    Closure(u16),
+    CloseUpvalue,
+    Return,
+    Array(u16),
+    Map(u16),
+    Inherit,
+    Class(u16, u16, bool),
+    IterInit,
+    IterNext(u16),
+    Import(u16),
+    ImportName(u16, u16),
+    Throw,
+    SetupCatch(u16),
+    SetupFinally(u16),
+    PopHandler,
+    EndFinally,
+}
+
+#[derive(Debug)]
+pub enum Constant {
+    Value(Value),
+    Name(String),
+    Function(Rc<Prototype>),
+}
+
+/// Compiled code together with its constant pool and the location each
+/// instruction was compiled from.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<OpCode>,
+    pub locs: Vec<Loc>,
+    pub constants: Vec<Constant>,
+}
+
+/// Where a closure finds each of its upvalues when it is created: a local
+/// slot of the enclosing frame, or an upvalue of the enclosing closure.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UpvalueSlot {
+    pub index: u16,
+    pub is_local: bool,
+}
+
+/// A compiled function, shared by all the closures created from it.
+#[derive(Debug)]
+pub struct Prototype {
+    pub name: Option<String>,
+    pub arity: usize,
+    pub kind: FunctionKind,
+    pub is_init: bool,
+    pub chunk: Chunk,
+    pub upvalues: Vec<UpvalueSlot>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, op: OpCode, loc: Loc) -> usize {
+        self.code.push(op);
+        self.locs.push(loc);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    pub fn name(&self, index: u16) -> &str {
+        match &self.constants[index as usize] {
+            Constant::Name(name) => name,
+            constant => panic!("Expected a name constant. Got: {:?}", constant),
+        }
+    }
+
+    pub fn function(&self, index: u16) -> &Rc<Prototype> {
+        match &self.constants[index as usize] {
+            Constant::Function(proto) => proto,
+            constant => panic!("Expected a function constant. Got: {:?}", constant),
+        }
+    }
+
+    pub fn value(&self, index: u16) -> Value {
+        match &self.constants[index as usize] {
+            Constant::Value(val) => val.clone(),
+            constant => panic!("Expected a value constant. Got: {:?}", constant),
+        }
+    }
+}
+
+impl Prototype {
+    pub fn new(name: Option<&str>, arity: usize, kind: FunctionKind, is_init: bool) -> Self {
+        Self {
+            name: name.map(String::from),
+            arity,
+            kind,
+            is_init,
+            chunk: Chunk::default(),
+            upvalues: Vec::new(),
+        }
+    }
+}
@@ -1,11 +1,17 @@
 use crate::array::{ArrayClass, ArrayRc};
-use crate::callable::{Callable, Function, LoxCallable};
-use crate::constants::INIT_METHOD;
-use crate::eval::{Environ, GlobalEnviron, Interpreter, RuntimeError, ValueRes};
+use crate::callable::{Arity, Callable, Function, LoxCallable};
+use crate::constants::{INIT_METHOD, STRING_CLASS};
+use crate::eval::{Environ, GlobalEnv, Interpreter, RuntimeError, ValueRes};
+use crate::gc::{self, Trace, Tracer};
+use crate::host::NativeClass;
 use crate::location::Loc;
+use crate::map::{MapClass, MapRc};
+use crate::string::StringClass;
 use crate::value::Value;
+use crate::vm::Closure;
+use std::any::Any;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 use std::rc::Rc;
 use uuid::Uuid;
@@ -14,6 +20,9 @@ use uuid::Uuid;
 pub enum Class {
     Generic(Rc<GenericClass>),
     Array(Rc<ArrayClass>),
+    Map(Rc<MapClass>),
+    Str(Rc<StringClass>),
+    Native(Rc<NativeClass>),
 }
 
 pub type MethodMap = HashMap<String, Method>;
@@ -48,6 +57,12 @@ pub struct ClassInstance {
 enum InstanceKind {
     Generic(Rc<GenericClass>),
     Array(ArrayRc),
+    Map(MapRc),
+    Str(Rc<StringClass>, String),
+    Native(Rc<NativeClass>, Box<dyn Any>),
+    /// An imported module, whose exports are read from and written to its
+    /// globals.
+    Module(Rc<GenericClass>, GlobalEnv, HashSet<String>),
 }
 
 pub type InstanceRc = Rc<RefCell<ClassInstance>>;
@@ -56,12 +71,13 @@ pub type InstanceRc = Rc<RefCell<ClassInstance>>;
 pub enum Method {
     Native(Rc<NativeMethod>),
     Function(Rc<Function>),
+    Closure(Rc<Closure>),
     StaticWrapper(Rc<StaticWrapper>),
 }
 
 pub struct NativeMethod {
     pub name: &'static str,
-    pub arity: usize,
+    pub arity: Arity,
     pub fun: NativeMethodFn,
 }
 
@@ -91,6 +107,9 @@ impl Class {
         match self {
             Class::Generic(class) => find_method(class, name),
             Class::Array(class) => find_method(class, name),
+            Class::Map(class) => find_method(class, name),
+            Class::Str(class) => find_method(class, name),
+            Class::Native(class) => find_method(class, name),
         }
     }
 
@@ -98,6 +117,9 @@ impl Class {
         match self {
             Class::Generic(class) => find_getter(class, name),
             Class::Array(class) => find_getter(class, name),
+            Class::Map(class) => find_getter(class, name),
+            Class::Str(class) => find_getter(class, name),
+            Class::Native(class) => find_getter(class, name),
         }
     }
 
@@ -111,9 +133,28 @@ impl Class {
         match self {
             Class::Generic(class) => get_and_bind(class, instance, inter, name, loc),
             Class::Array(class) => get_and_bind(class, instance, inter, name, loc),
+            Class::Map(class) => get_and_bind(class, instance, inter, name, loc),
+            Class::Str(class) => get_and_bind(class, instance, inter, name, loc),
+            Class::Native(class) => get_and_bind(class, instance, inter, name, loc),
         }
     }
 
+    /// The names of the methods and getters of the class, including the
+    /// inherited ones.
+    pub fn member_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .methods()
+            .keys()
+            .chain(self.getters().keys())
+            .cloned()
+            .collect();
+        if let Some(superclass) = self.superclass() {
+            names.extend(superclass.member_names());
+        }
+
+        names
+    }
+
     pub fn add_native_method(methods: &mut MethodMap, method: NativeMethod) {
         let name = String::from(method.name);
         methods.insert(name, method.into());
@@ -134,7 +175,7 @@ impl Class {
         static_methods.extend(static_wrappers);
 
         let supermetaclass = superclass.and_then(|sc| sc.metaclass());
-        let metaclass = Rc::new(GenericClass::new_meta(name, supermetaclass, static_methods));
+        let metaclass = gc::alloc(GenericClass::new_meta(name, supermetaclass, static_methods));
         ClassInstance::new_generic(&metaclass).into()
     }
 
@@ -154,6 +195,9 @@ impl LoxClass for Class {
         match self {
             Class::Generic(class) => class.name(),
             Class::Array(class) => class.name(),
+            Class::Map(class) => class.name(),
+            Class::Str(class) => class.name(),
+            Class::Native(class) => class.name(),
         }
     }
 
@@ -161,6 +205,9 @@ impl LoxClass for Class {
         match self {
             Class::Generic(class) => class.uuid(),
             Class::Array(class) => class.uuid(),
+            Class::Map(class) => class.uuid(),
+            Class::Str(class) => class.uuid(),
+            Class::Native(class) => class.uuid(),
         }
     }
 
@@ -168,6 +215,9 @@ impl LoxClass for Class {
         match self {
             Class::Generic(class) => class.superclass(),
             Class::Array(class) => class.superclass(),
+            Class::Map(class) => class.superclass(),
+            Class::Str(class) => class.superclass(),
+            Class::Native(class) => class.superclass(),
         }
     }
 
@@ -175,6 +225,9 @@ impl LoxClass for Class {
         match self {
             Class::Generic(class) => class.methods(),
             Class::Array(class) => class.methods(),
+            Class::Map(class) => class.methods(),
+            Class::Str(class) => class.methods(),
+            Class::Native(class) => class.methods(),
         }
     }
 
@@ -182,6 +235,9 @@ impl LoxClass for Class {
         match self {
             Class::Generic(class) => class.getters(),
             Class::Array(class) => class.getters(),
+            Class::Map(class) => class.getters(),
+            Class::Str(class) => class.getters(),
+            Class::Native(class) => class.getters(),
         }
     }
 
@@ -189,15 +245,21 @@ impl LoxClass for Class {
         match self {
             Class::Generic(class) => class.metainstance(),
             Class::Array(class) => class.metainstance(),
+            Class::Map(class) => class.metainstance(),
+            Class::Str(class) => class.metainstance(),
+            Class::Native(class) => class.metainstance(),
         }
     }
 }
 
 impl LoxCallable for Class {
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         match self {
             Class::Generic(class) => class.arity(),
             Class::Array(class) => class.arity(),
+            Class::Map(class) => class.arity(),
+            Class::Str(class) => class.arity(),
+            Class::Native(class) => class.arity(),
         }
     }
 
@@ -205,6 +267,9 @@ impl LoxCallable for Class {
         match self {
             Class::Generic(class) => class.call(inter, args, loc),
             Class::Array(class) => class.call(inter, args, loc),
+            Class::Map(class) => class.call(inter, args, loc),
+            Class::Str(class) => class.call(inter, args, loc),
+            Class::Native(class) => class.call(inter, args, loc),
         }
     }
 }
@@ -214,16 +279,22 @@ impl Display for Class {
         match self {
             Class::Generic(class) => Display::fmt(class, f),
             Class::Array(class) => Display::fmt(class, f),
+            Class::Map(class) => Display::fmt(class, f),
+            Class::Str(class) => Display::fmt(class, f),
+            Class::Native(class) => Display::fmt(class, f),
         }
     }
 }
 
 impl PartialEq for Class {
     fn eq(&self, other: &Self) -> bool {
-        use Class::{Array, Generic};
+        use Class::{Array, Generic, Map, Native, Str};
         match (self, other) {
             (Generic(left), Generic(right)) => Rc::ptr_eq(left, right),
             (Array(left), Array(right)) => Rc::ptr_eq(left, right),
+            (Map(left), Map(right)) => Rc::ptr_eq(left, right),
+            (Str(left), Str(right)) => Rc::ptr_eq(left, right),
+            (Native(left), Native(right)) => Rc::ptr_eq(left, right),
             (_, _) => false,
         }
     }
@@ -318,16 +389,6 @@ impl GenericClass {
             metainstance: None,
         }
     }
-
-    pub fn new_empty(name: &str, superclass: Option<Class>) -> Self {
-        Self::new(
-            name,
-            superclass,
-            HashMap::new(),
-            HashMap::new(),
-            HashMap::new(),
-        )
-    }
 }
 
 impl LoxClass for Rc<GenericClass> {
@@ -357,10 +418,10 @@ impl LoxClass for Rc<GenericClass> {
 }
 
 impl LoxCallable for Rc<GenericClass> {
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         find_method(self, INIT_METHOD)
             .map(|m| m.arity())
-            .unwrap_or(0)
+            .unwrap_or(Arity::Fixed(0))
     }
 
     fn call(&self, inter: &mut Interpreter, args: Vec<Value>, loc: Loc) -> ValueRes {
@@ -375,7 +436,7 @@ impl LoxCallable for Rc<GenericClass> {
 
 impl From<GenericClass> for Callable {
     fn from(class: GenericClass) -> Self {
-        Callable::from(Rc::new(class))
+        Callable::from(gc::alloc(class))
     }
 }
 
@@ -413,27 +474,98 @@ impl ClassInstance {
         }
     }
 
+    pub fn from_map(map: MapRc) -> Self {
+        Self {
+            kind: InstanceKind::Map(map),
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn from_string(class: &Rc<StringClass>, string: String) -> Self {
+        Self {
+            kind: InstanceKind::Str(Rc::clone(class), string),
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn from_host(class: &Rc<NativeClass>, data: Box<dyn Any>) -> Self {
+        Self {
+            kind: InstanceKind::Native(Rc::clone(class), data),
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn from_module(
+        class: &Rc<GenericClass>,
+        globals: GlobalEnv,
+        exports: HashSet<String>,
+    ) -> Self {
+        Self {
+            kind: InstanceKind::Module(Rc::clone(class), globals, exports),
+            fields: HashMap::new(),
+        }
+    }
+
     pub fn array(&self) -> Option<ArrayRc> {
         use InstanceKind::*;
         match &self.kind {
-            Generic(_class) => None,
             Array(array) => Some(Rc::clone(&array)),
+            _ => None,
+        }
+    }
+
+    pub fn map(&self) -> Option<MapRc> {
+        use InstanceKind::*;
+        match &self.kind {
+            Map(map) => Some(Rc::clone(map)),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self) -> Option<&str> {
+        use InstanceKind::*;
+        match &self.kind {
+            Str(_class, string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// The Rust state of an instance of a host class, if it is a `T`.
+    pub fn data<T: Any>(&self) -> Option<&T> {
+        use InstanceKind::*;
+        match &self.kind {
+            Native(_class, data) => data.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn data_mut<T: Any>(&mut self) -> Option<&mut T> {
+        use InstanceKind::*;
+        match &mut self.kind {
+            Native(_class, data) => data.downcast_mut(),
+            _ => None,
         }
     }
 
     pub fn class(&self) -> Class {
         use InstanceKind::*;
         match &self.kind {
-            Generic(class) => Rc::clone(class).into(),
+            Generic(class) | Module(class, ..) => Rc::clone(class).into(),
             Array(array) => array.borrow().class().into(),
+            Map(map) => map.borrow().class().into(),
+            Str(class, _string) => Rc::clone(class).into(),
+            Native(class, _data) => Rc::clone(class).into(),
         }
     }
 
     pub fn class_name(&self) -> &str {
         use InstanceKind::*;
         match &self.kind {
-            Generic(class) => &class.name,
+            Generic(class) | Module(class, ..) => &class.name,
             Array(array) => array.borrow().class_name(),
+            Map(map) => map.borrow().class_name(),
+            Str(_class, _string) => STRING_CLASS,
+            Native(class, _data) => class.name(),
         }
     }
 
@@ -443,7 +575,7 @@ impl ClassInstance {
         name: &str,
         loc: Loc,
     ) -> Option<ValueRes> {
-        let field = { instance.borrow().fields.get(name).cloned().map(Ok) };
+        let field = { instance.borrow().field(name).map(Ok) };
 
         field.or_else(|| {
             let class = instance.borrow().class();
@@ -451,8 +583,32 @@ impl ClassInstance {
         })
     }
 
+    pub fn field(&self, name: &str) -> Option<Value> {
+        match &self.kind {
+            InstanceKind::Module(_class, globals, exports) if exports.contains(name) => {
+                globals.borrow().get(name, Loc::default()).ok()
+            }
+            _ => self.fields.get(name).cloned(),
+        }
+    }
+
+    pub fn field_names(&self) -> impl Iterator<Item = &String> {
+        let exports = match &self.kind {
+            InstanceKind::Module(_class, _globals, exports) => Some(exports.iter()),
+            _ => None,
+        };
+        self.fields.keys().chain(exports.into_iter().flatten())
+    }
+
     pub fn set(&mut self, name: &str, val: Value, _loc: Loc) {
-        self.fields.insert(String::from(name), val);
+        match &self.kind {
+            InstanceKind::Module(_class, globals, exports) if exports.contains(name) => {
+                globals.borrow_mut().define(name, val);
+            }
+            _ => {
+                self.fields.insert(String::from(name), val);
+            }
+        }
     }
 }
 
@@ -464,7 +620,58 @@ impl Display for ClassInstance {
 
 impl Into<InstanceRc> for ClassInstance {
     fn into(self) -> InstanceRc {
-        Rc::new(RefCell::new(self))
+        gc::alloc(RefCell::new(self))
+    }
+}
+
+impl Trace for RefCell<ClassInstance> {
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        use InstanceKind::*;
+        let instance = match self.try_borrow() {
+            Ok(instance) => instance,
+            Err(_) => return false,
+        };
+        match &instance.kind {
+            Generic(class) => tracer.rc(class),
+            Array(array) => tracer.rc(array),
+            Map(map) => tracer.rc(map),
+            Str(_class, _string) => (),
+            Native(class, _data) => tracer.rc(class),
+            Module(class, _globals, _exports) => tracer.rc(class),
+        }
+        instance.fields.values().for_each(|val| tracer.value(val));
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut instance) = self.try_borrow_mut() {
+            instance.fields.clear();
+        }
+    }
+}
+
+impl Trace for GenericClass {
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        if let Some(superclass) = &self.superclass {
+            tracer.class(superclass);
+        }
+        self.methods
+            .values()
+            .for_each(|method| tracer.method(method));
+        self.getters
+            .values()
+            .for_each(|method| tracer.method(method));
+        if let Some(metainstance) = &self.metainstance {
+            tracer.rc(metainstance);
+        }
+        true
+    }
+}
+
+impl Trace for StaticWrapper {
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        tracer.method(&self.wrapped);
+        true
     }
 }
 
@@ -479,10 +686,11 @@ impl Debug for NativeMethod {
 }
 
 impl Method {
-    pub fn arity(&self) -> usize {
+    pub fn arity(&self) -> Arity {
         match self {
             Method::Native(native) => native.arity,
             Method::Function(function) => function.arity(),
+            Method::Closure(closure) => closure.arity(),
             Method::StaticWrapper(wrapper) => wrapper.arity(),
         }
     }
@@ -491,6 +699,7 @@ impl Method {
         match self {
             Method::Native(native) => native.name,
             Method::Function(function) => function.name.as_ref().unwrap(),
+            Method::Closure(closure) => closure.name().unwrap(),
             Method::StaticWrapper(wrapper) => wrapper.name(),
         }
     }
@@ -506,6 +715,7 @@ impl PartialEq for Method {
         match (self, other) {
             (Native(left), Native(right)) => Rc::ptr_eq(left, right),
             (Function(left), Function(right)) => Rc::ptr_eq(left, right),
+            (Closure(left), Closure(right)) => Rc::ptr_eq(left, right),
             (_, _) => false,
         }
     }
@@ -519,18 +729,22 @@ impl From<NativeMethod> for Method {
 
 impl From<Function> for Method {
     fn from(function: Function) -> Self {
-        Method::Function(Rc::new(function))
+        Method::Function(gc::alloc(function))
     }
 }
 
 impl From<StaticWrapper> for Method {
     fn from(wrapper: StaticWrapper) -> Self {
-        Method::StaticWrapper(Rc::new(wrapper))
+        Method::StaticWrapper(gc::alloc(wrapper))
     }
 }
 
 impl NativeMethod {
     pub fn new(name: &'static str, arity: usize, fun: NativeMethodFn) -> Self {
+        Self::with_arity(name, Arity::Fixed(arity), fun)
+    }
+
+    pub fn with_arity(name: &'static str, arity: Arity, fun: NativeMethodFn) -> Self {
         Self { name, arity, fun }
     }
 }
@@ -544,7 +758,7 @@ impl StaticWrapper {
         }
     }
 
-    pub fn arity(&self) -> usize {
+    pub fn arity(&self) -> Arity {
         self.wrapped.arity() + 1
     }
 
@@ -564,10 +778,25 @@ impl BoundMethod {
             instance: Rc::clone(instance),
         }
     }
+
+    pub fn closure(&self) -> Option<&Rc<Closure>> {
+        match &self.method {
+            Method::Closure(closure) => Some(closure),
+            _ => None,
+        }
+    }
+
+    pub fn receiver(&self) -> &InstanceRc {
+        &self.instance
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
 }
 
 impl LoxCallable for BoundMethod {
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         self.method.arity()
     }
 
@@ -575,7 +804,9 @@ impl LoxCallable for BoundMethod {
         match &self.method {
             Method::Native(native) => {
                 let fun = native.fun;
-                fun(inter, args, &mut self.instance.borrow_mut(), loc)
+                inter.with_frame(native.name, loc, |inter| {
+                    fun(inter, args, &mut self.instance.borrow_mut(), loc)
+                })
             }
             Method::Function(function) => {
                 let env = Environ::with_enclosing(&function.closure);
@@ -583,10 +814,14 @@ impl LoxCallable for BoundMethod {
                 env.borrow_mut().define(this_val);
                 function.call_with_closure(inter, args, loc, &Some(env))
             }
+            Method::Closure(closure) => {
+                let this_val = Value::Instance(Rc::clone(&self.instance));
+                closure.call_with_receiver(inter, this_val, args, loc)
+            }
             Method::StaticWrapper(wrapper) => {
                 let first_arg = args.remove(0);
                 let val_type = first_arg.get_type();
-                match first_arg.into_instance() {
+                match first_arg.into_instance(inter) {
                     Some(instance) if instance.borrow().class().uuid() == wrapper.class_uuid => {
                         let bound = wrapper.bind(&instance);
                         bound.call(inter, args, loc)
@@ -616,27 +851,3 @@ impl PartialEq for BoundMethod {
         self.method == other.method && Rc::ptr_eq(&self.instance, &other.instance)
     }
 }
-
-pub fn define_native_classes(globals: &mut GlobalEnviron) {
-    define_class(globals, make_map_class());
-}
-
-fn define_class(globals: &mut GlobalEnviron, class: Class) {
-    let name = String::from(class.name());
-    globals.define(&name, class.into());
-}
-
-fn make_map_class() -> Class {
-    let mut class = GenericClass::new_empty("Map", None);
-
-    Class::add_native_method(
-        &mut class.methods,
-        NativeMethod {
-            name: "count",
-            arity: 0,
-            fun: |_inter, _args, instance, _loc| Ok(Value::Integer(instance.fields.len() as i64)),
-        },
-    );
-
-    Class::Generic(Rc::new(class))
-}
@@ -0,0 +1,953 @@
+#[cfg(test)]
+mod tests;
+
+use crate::chunk::{Chunk, Constant, OpCode, Prototype, UpvalueSlot};
+use crate::constants::{INIT_METHOD, SUPER_KEYWORD, THIS_KEYWORD};
+use crate::eval::RuntimeError;
+use crate::expr::{BinOp, Expr, LitExpr, LogOp, Param, Resolution, UnOp, Visitor as ExprVisitor};
+use crate::location::Loc;
+use crate::stmt::{CatchClause, FunctionKind, ImportKind, Stmt, StmtKind, Visitor as StmtVisitor};
+use crate::value::Value;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// Compiles resolved statements into bytecode for the VM, one `Prototype`
+/// per function.
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+}
+
+struct FunctionState {
+    proto: Prototype,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    loops: Vec<LoopState>,
+    tries: Vec<TryState>,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    captured: bool,
+}
+
+struct LoopState {
+    scope_depth: usize,
+    tries: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// A `try` statement the code being compiled is nested in. Jumping out of it
+/// has to drop its handler and run its finally body on the way.
+struct TryState {
+    has_handler: bool,
+    finally: Option<Vec<Stmt>>,
+}
+
+type CompileRes = Result<(), RuntimeError>;
+
+pub fn compile_script(stmts: &[Stmt]) -> Result<Prototype, RuntimeError> {
+    let mut compiler = Compiler::new();
+    for stmt in stmts {
+        compiler.statement(stmt)?;
+    }
+
+    compiler.emit(OpCode::Nil, Loc::default());
+    compiler.emit(OpCode::Return, Loc::default());
+    Ok(compiler.functions.pop().unwrap().proto)
+}
+
+pub fn compile_expr(expr: &Expr) -> Result<Prototype, RuntimeError> {
+    let mut compiler = Compiler::new();
+    compiler.expression(expr)?;
+    compiler.emit(OpCode::Return, expr.loc);
+    Ok(compiler.functions.pop().unwrap().proto)
+}
+
+impl Compiler {
+    fn new() -> Self {
+        let script = Prototype::new(None, 0, FunctionKind::Function, false);
+        Self {
+            functions: vec![FunctionState::new(script, "")],
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) -> CompileRes {
+        expr.accept(self)
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> CompileRes {
+        stmt.accept(self)
+    }
+
+    fn block(&mut self, stmts: &[Stmt], loc: Loc) -> CompileRes {
+        self.begin_scope();
+        for stmt in stmts {
+            self.statement(stmt)?;
+        }
+        self.end_scope(loc);
+        Ok(())
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().proto.chunk
+    }
+
+    fn emit(&mut self, op: OpCode, loc: Loc) -> usize {
+        self.chunk().write(op, loc)
+    }
+
+    fn emit_jump(&mut self, op: fn(u16) -> OpCode, loc: Loc) -> usize {
+        self.emit(op(0), loc)
+    }
+
+    fn patch_jump(&mut self, at: usize) -> CompileRes {
+        let chunk = self.chunk();
+        let loc = chunk.locs[at];
+        let offset = to_operand(chunk.code.len() - at - 1, loc, "Too much code to jump over")?;
+
+        chunk.code[at] = match chunk.code[at] {
+            OpCode::Jump(_) => OpCode::Jump(offset),
+            OpCode::JumpIfFalse(_) => OpCode::JumpIfFalse(offset),
+            OpCode::IterNext(_) => OpCode::IterNext(offset),
+            OpCode::SetupCatch(_) => OpCode::SetupCatch(offset),
+            OpCode::SetupFinally(_) => OpCode::SetupFinally(offset),
+            op => panic!("Expected a jump instruction. Got: {:?}", op),
+        };
+
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, start: usize, loc: Loc) -> CompileRes {
+        let offset = self.chunk().code.len() + 1 - start;
+        let offset = to_operand(offset, loc, "Loop body too large")?;
+        self.emit(OpCode::Loop(offset), loc);
+        Ok(())
+    }
+
+    fn make_constant(&mut self, constant: Constant, loc: Loc) -> Result<u16, RuntimeError> {
+        let index = self.chunk().add_constant(constant);
+        to_operand(index, loc, "Too many constants in one chunk")
+    }
+
+    fn name_constant(&mut self, name: &str, loc: Loc) -> Result<u16, RuntimeError> {
+        let existing = self
+            .chunk()
+            .constants
+            .iter()
+            .position(|constant| match constant {
+                Constant::Name(existing) => existing == name,
+                _ => false,
+            });
+
+        match existing {
+            Some(index) => to_operand(index, loc, "Too many constants in one chunk"),
+            None => self.make_constant(Constant::Name(String::from(name)), loc),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self, loc: Loc) {
+        let state = self.current();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+
+        while let Some(local) = self.current().locals.last() {
+            if local.depth <= depth {
+                break;
+            }
+
+            let op = if local.captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            self.current().locals.pop();
+            self.emit(op, loc);
+        }
+    }
+
+    /// Pops the locals deeper than `depth` without leaving their scopes, for
+    /// code that jumps out of them.
+    fn discard_locals(&mut self, depth: usize, loc: Loc) {
+        let ops: Vec<_> = self
+            .current()
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > depth)
+            .map(|local| {
+                if local.captured {
+                    OpCode::CloseUpvalue
+                } else {
+                    OpCode::Pop
+                }
+            })
+            .collect();
+
+        for op in ops {
+            self.emit(op, loc);
+        }
+    }
+
+    fn add_local(&mut self, name: &str, loc: Loc) -> CompileRes {
+        let state = self.current();
+        to_operand::<u16>(
+            state.locals.len(),
+            loc,
+            "Too many local variables in function",
+        )?;
+        let depth = state.scope_depth;
+        state.locals.push(Local {
+            name: String::from(name),
+            depth,
+            captured: false,
+        });
+
+        Ok(())
+    }
+
+    /// Binds the value on top of the stack to a new variable.
+    fn define_variable(&mut self, name: &str, loc: Loc) -> CompileRes {
+        if self.current().scope_depth > 0 {
+            self.add_local(name, loc)
+        } else {
+            let index = self.name_constant(name, loc)?;
+            self.emit(OpCode::DefineGlobal(index), loc);
+            Ok(())
+        }
+    }
+
+    fn resolve_local(&self, function: usize, name: &str) -> Option<u16> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|index| index as u16)
+    }
+
+    fn resolve_upvalue(
+        &mut self,
+        function: usize,
+        name: &str,
+        loc: Loc,
+    ) -> Result<Option<u16>, RuntimeError> {
+        if function == 0 {
+            return Ok(None);
+        }
+
+        let enclosing = function - 1;
+        if let Some(index) = self.resolve_local(enclosing, name) {
+            self.functions[enclosing].locals[index as usize].captured = true;
+            return self.add_upvalue(function, index, true, loc).map(Some);
+        }
+
+        match self.resolve_upvalue(enclosing, name, loc)? {
+            Some(index) => self.add_upvalue(function, index, false, loc).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn add_upvalue(
+        &mut self,
+        function: usize,
+        index: u16,
+        is_local: bool,
+        loc: Loc,
+    ) -> Result<u16, RuntimeError> {
+        let upvalues = &mut self.functions[function].proto.upvalues;
+        let slot = UpvalueSlot { index, is_local };
+        if let Some(existing) = upvalues.iter().position(|upvalue| *upvalue == slot) {
+            return Ok(existing as u16);
+        }
+
+        upvalues.push(slot);
+        to_operand(
+            upvalues.len() - 1,
+            loc,
+            "Too many closure variables in function",
+        )
+    }
+
+    fn get_variable(&mut self, name: &str, loc: Loc) -> CompileRes {
+        let function = self.functions.len() - 1;
+        let op = if let Some(slot) = self.resolve_local(function, name) {
+            OpCode::GetLocal(slot)
+        } else if let Some(index) = self.resolve_upvalue(function, name, loc)? {
+            OpCode::GetUpvalue(index)
+        } else {
+            OpCode::GetGlobal(self.name_constant(name, loc)?)
+        };
+
+        self.emit(op, loc);
+        Ok(())
+    }
+
+    fn set_variable(&mut self, name: &str, loc: Loc) -> CompileRes {
+        let function = self.functions.len() - 1;
+        let op = if let Some(slot) = self.resolve_local(function, name) {
+            OpCode::SetLocal(slot)
+        } else if let Some(index) = self.resolve_upvalue(function, name, loc)? {
+            OpCode::SetUpvalue(index)
+        } else {
+            OpCode::SetGlobal(self.name_constant(name, loc)?)
+        };
+
+        self.emit(op, loc);
+        Ok(())
+    }
+
+    fn function(
+        &mut self,
+        name: Option<&str>,
+        params: &[Param],
+        body: &[Stmt],
+        kind: FunctionKind,
+        loc: Loc,
+    ) -> CompileRes {
+        let is_init = kind == FunctionKind::Method && name == Some(INIT_METHOD);
+        let receiver = match kind {
+            FunctionKind::Function => "",
+            _ => THIS_KEYWORD,
+        };
+
+        let proto = Prototype::new(name, params.len(), kind, is_init);
+        self.functions.push(FunctionState::new(proto, receiver));
+        self.begin_scope();
+        for param in params {
+            self.add_local(&param.kind, param.loc)?;
+        }
+
+        for stmt in body {
+            self.statement(stmt)?;
+        }
+
+        self.emit_return(loc);
+        let proto = self.functions.pop().unwrap().proto;
+        let index = self.make_constant(Constant::Function(Rc::new(proto)), loc)?;
+        self.emit(OpCode::Closure(index), loc);
+        Ok(())
+    }
+
+    fn emit_return(&mut self, loc: Loc) {
+        if self.current().proto.is_init {
+            self.emit(OpCode::GetLocal(0), loc);
+        } else {
+            self.emit(OpCode::Nil, loc);
+        }
+
+        self.emit(OpCode::Return, loc);
+    }
+
+    /// Drops the handlers and runs the finally bodies of the `try` statements
+    /// being jumped out of, innermost first.
+    fn exit_tries(&mut self, level: usize, loc: Loc) -> CompileRes {
+        for index in (level..self.current().tries.len()).rev() {
+            let exited = self.current().tries.split_off(index);
+            let res = self.exit_try(&exited[0], loc);
+            self.current().tries.extend(exited);
+            res?;
+        }
+
+        Ok(())
+    }
+
+    fn exit_try(&mut self, state: &TryState, loc: Loc) -> CompileRes {
+        if state.has_handler {
+            self.emit(OpCode::PopHandler, loc);
+        }
+
+        match &state.finally {
+            Some(finally) => self.block(finally, loc),
+            None => Ok(()),
+        }
+    }
+
+    fn try_catch(
+        &mut self,
+        body: &[Stmt],
+        (var, catch_body): &CatchClause,
+        loc: Loc,
+    ) -> CompileRes {
+        let handler = self.emit_jump(OpCode::SetupCatch, loc);
+        self.protected(None, |compiler| compiler.block(body, loc))?;
+        self.emit(OpCode::PopHandler, loc);
+        let exit = self.emit_jump(OpCode::Jump, loc);
+
+        // The VM pushes the caught exception before jumping here
+        self.patch_jump(handler)?;
+        self.begin_scope();
+        self.add_local(&var.kind, var.loc)?;
+        for stmt in catch_body {
+            self.statement(stmt)?;
+        }
+        self.end_scope(loc);
+
+        self.patch_jump(exit)
+    }
+
+    fn try_finally<F>(&mut self, finally: &[Stmt], loc: Loc, body: F) -> CompileRes
+    where
+        F: FnOnce(&mut Self) -> CompileRes,
+    {
+        let handler = self.emit_jump(OpCode::SetupFinally, loc);
+        self.protected(Some(finally.to_vec()), body)?;
+        self.emit(OpCode::PopHandler, loc);
+        self.block(finally, loc)?;
+        let exit = self.emit_jump(OpCode::Jump, loc);
+
+        // Reached with the exception kept aside by the VM, which is raised
+        // again once the finally body is done
+        self.patch_jump(handler)?;
+        self.block(finally, loc)?;
+        self.emit(OpCode::EndFinally, loc);
+
+        self.patch_jump(exit)
+    }
+
+    fn protected<F>(&mut self, finally: Option<Vec<Stmt>>, body: F) -> CompileRes
+    where
+        F: FnOnce(&mut Self) -> CompileRes,
+    {
+        self.current().tries.push(TryState {
+            has_handler: true,
+            finally,
+        });
+        let res = body(self);
+        self.current().tries.pop();
+        res
+    }
+
+    fn loop_body<F>(&mut self, body: F) -> Result<LoopState, RuntimeError>
+    where
+        F: FnOnce(&mut Self) -> CompileRes,
+    {
+        let state = self.current();
+        let loop_state = LoopState {
+            scope_depth: state.scope_depth,
+            tries: state.tries.len(),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        };
+        state.loops.push(loop_state);
+
+        let res = body(self);
+        let loop_state = self.current().loops.pop().unwrap();
+        res.map(|()| loop_state)
+    }
+
+    fn jump_out_of_loop(&mut self, loc: Loc, is_break: bool) -> CompileRes {
+        let (scope_depth, tries) = match self.current().loops.last() {
+            Some(state) => (state.scope_depth, state.tries),
+            None => return Err(RuntimeError::generic(loc, "Jump outside of a loop")),
+        };
+
+        self.exit_tries(tries, loc)?;
+        self.discard_locals(scope_depth, loc);
+        let jump = self.emit_jump(OpCode::Jump, loc);
+
+        let state = self.current().loops.last_mut().unwrap();
+        if is_break {
+            state.breaks.push(jump);
+        } else {
+            state.continues.push(jump);
+        }
+
+        Ok(())
+    }
+
+    fn class_method(&mut self, method: &Stmt) -> CompileRes {
+        match &method.kind {
+            StmtKind::Function(name, params, body, kind) => {
+                self.function(Some(name), params, body, *kind, method.loc)
+            }
+            kind => panic!("Expected a method in class body. Got: {:?}", kind),
+        }
+    }
+}
+
+impl FunctionState {
+    fn new(proto: Prototype, receiver: &str) -> Self {
+        // The first slot of every frame holds the callee, or the receiver
+        // of a method call
+        let receiver = Local {
+            name: String::from(receiver),
+            depth: 0,
+            captured: false,
+        };
+
+        Self {
+            proto,
+            locals: vec![receiver],
+            scope_depth: 0,
+            loops: Vec::new(),
+            tries: Vec::new(),
+        }
+    }
+}
+
+fn to_operand<T: TryFrom<usize>>(value: usize, loc: Loc, message: &str) -> Result<T, RuntimeError> {
+    T::try_from(value).map_err(|_| RuntimeError::generic(loc, message))
+}
+
+impl ExprVisitor<()> for Compiler {
+    type Error = RuntimeError;
+
+    fn visit_literal_expr(&mut self, literal: &LitExpr, loc: Loc) -> CompileRes {
+        let op = match literal {
+            LitExpr::Nil => OpCode::Nil,
+            LitExpr::Boolean(true) => OpCode::True,
+            LitExpr::Boolean(false) => OpCode::False,
+            literal => {
+                OpCode::Constant(self.make_constant(Constant::Value(Value::from(literal)), loc)?)
+            }
+        };
+
+        self.emit(op, loc);
+        Ok(())
+    }
+
+    fn visit_function_expr(&mut self, params: &[Param], body: &[Stmt], loc: Loc) -> CompileRes {
+        self.function(None, params, body, FunctionKind::Function, loc)
+    }
+
+    fn visit_unary_expr(&mut self, op: &UnOp, expr: &Expr, loc: Loc) -> CompileRes {
+        self.expression(expr)?;
+        self.emit(
+            match op {
+                UnOp::Negate => OpCode::Negate,
+                UnOp::Not => OpCode::Not,
+            },
+            loc,
+        );
+        Ok(())
+    }
+
+    fn visit_binary_expr(&mut self, left: &Expr, op: &BinOp, right: &Expr, loc: Loc) -> CompileRes {
+        self.expression(left)?;
+        self.expression(right)?;
+        self.emit(
+            match op {
+                BinOp::Add => OpCode::Add,
+                BinOp::Sub => OpCode::Subtract,
+                BinOp::Mul => OpCode::Multiply,
+                BinOp::Div => OpCode::Divide,
+                BinOp::Rem => OpCode::Remainder,
+                BinOp::Equal => OpCode::Equal,
+                BinOp::NotEqual => OpCode::NotEqual,
+                BinOp::Greater => OpCode::Greater,
+                BinOp::GreaterEqual => OpCode::GreaterEqual,
+                BinOp::Less => OpCode::Less,
+                BinOp::LessEqual => OpCode::LessEqual,
+            },
+            loc,
+        );
+        Ok(())
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        op: &LogOp,
+        right: &Expr,
+        loc: Loc,
+    ) -> CompileRes {
+        self.expression(left)?;
+        let end = match op {
+            LogOp::And => self.emit_jump(OpCode::JumpIfFalse, loc),
+            LogOp::Or => {
+                let right_jump = self.emit_jump(OpCode::JumpIfFalse, loc);
+                let end = self.emit_jump(OpCode::Jump, loc);
+                self.patch_jump(right_jump)?;
+                end
+            }
+        };
+
+        self.emit(OpCode::Pop, loc);
+        self.expression(right)?;
+        self.patch_jump(end)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Expr, _loc: Loc) -> CompileRes {
+        self.expression(expr)
+    }
+
+    fn visit_comma_expr(&mut self, left: &Expr, right: &Expr, loc: Loc) -> CompileRes {
+        self.expression(left)?;
+        self.emit(OpCode::Pop, loc);
+        self.expression(right)
+    }
+
+    fn visit_cond_expr(&mut self, cond: &Expr, left: &Expr, right: &Expr, loc: Loc) -> CompileRes {
+        self.expression(cond)?;
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse, loc);
+        self.emit(OpCode::Pop, loc);
+        self.expression(left)?;
+        let end = self.emit_jump(OpCode::Jump, loc);
+        self.patch_jump(else_jump)?;
+        self.emit(OpCode::Pop, loc);
+        self.expression(right)?;
+        self.patch_jump(end)
+    }
+
+    fn visit_variable_expr(
+        &mut self,
+        name: &str,
+        _resolution: &Resolution,
+        loc: Loc,
+    ) -> CompileRes {
+        self.get_variable(name, loc)
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        name: &str,
+        expr: &Expr,
+        _resolution: &Resolution,
+        loc: Loc,
+    ) -> CompileRes {
+        self.expression(expr)?;
+        self.set_variable(name, loc)
+    }
+
+    fn visit_call_expr(&mut self, callee: &Expr, args: &[Expr], loc: Loc) -> CompileRes {
+        self.expression(callee)?;
+        for arg in args {
+            self.expression(arg)?;
+        }
+
+        let argc = to_operand(args.len(), loc, "Too many arguments in call")?;
+        self.emit(OpCode::Call(argc), loc);
+        Ok(())
+    }
+
+    fn visit_get_expr(&mut self, obj: &Expr, name: &str, loc: Loc) -> CompileRes {
+        self.expression(obj)?;
+        let index = self.name_constant(name, loc)?;
+        self.emit(OpCode::GetProperty(index), loc);
+        Ok(())
+    }
+
+    fn visit_set_expr(&mut self, obj: &Expr, name: &str, expr: &Expr, loc: Loc) -> CompileRes {
+        self.expression(obj)?;
+        self.expression(expr)?;
+        let index = self.name_constant(name, loc)?;
+        self.emit(OpCode::SetProperty(index), loc);
+        Ok(())
+    }
+
+    fn visit_array_expr(&mut self, elements: &[Expr], loc: Loc) -> CompileRes {
+        for element in elements {
+            self.expression(element)?;
+        }
+
+        let len = to_operand(elements.len(), loc, "Too many elements in array literal")?;
+        self.emit(OpCode::Array(len), loc);
+        Ok(())
+    }
+
+    fn visit_map_expr(&mut self, entries: &[(Expr, Expr)], loc: Loc) -> CompileRes {
+        for (key, val) in entries {
+            self.expression(key)?;
+            self.expression(val)?;
+        }
+
+        let len = to_operand(entries.len(), loc, "Too many entries in map literal")?;
+        self.emit(OpCode::Map(len), loc);
+        Ok(())
+    }
+
+    fn visit_subscript_get_expr(&mut self, obj: &Expr, index: &Expr, loc: Loc) -> CompileRes {
+        self.expression(obj)?;
+        self.expression(index)?;
+        self.emit(OpCode::GetIndex, loc);
+        Ok(())
+    }
+
+    fn visit_subscript_set_expr(
+        &mut self,
+        obj: &Expr,
+        index: &Expr,
+        expr: &Expr,
+        loc: Loc,
+    ) -> CompileRes {
+        self.expression(obj)?;
+        self.expression(index)?;
+        self.expression(expr)?;
+        self.emit(OpCode::SetIndex, loc);
+        Ok(())
+    }
+
+    fn visit_this_expr(&mut self, _resolution: &Resolution, loc: Loc) -> CompileRes {
+        self.get_variable(THIS_KEYWORD, loc)
+    }
+
+    fn visit_super_expr(&mut self, method: &str, _resolution: &Resolution, loc: Loc) -> CompileRes {
+        self.get_variable(THIS_KEYWORD, loc)?;
+        self.get_variable(SUPER_KEYWORD, loc)?;
+        let index = self.name_constant(method, loc)?;
+        self.emit(OpCode::GetSuper(index), loc);
+        Ok(())
+    }
+}
+
+impl StmtVisitor<()> for Compiler {
+    type Error = RuntimeError;
+
+    fn visit_expression_stmt(&mut self, expr: &Expr, loc: Loc) -> CompileRes {
+        self.expression(expr)?;
+        self.emit(OpCode::Pop, loc);
+        Ok(())
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        cond: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+        loc: Loc,
+    ) -> CompileRes {
+        self.expression(cond)?;
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse, loc);
+        self.emit(OpCode::Pop, loc);
+        self.statement(then_branch)?;
+        let end = self.emit_jump(OpCode::Jump, loc);
+
+        self.patch_jump(else_jump)?;
+        self.emit(OpCode::Pop, loc);
+        if let Some(else_stmt) = else_branch {
+            self.statement(else_stmt)?;
+        }
+
+        self.patch_jump(end)
+    }
+
+    fn visit_print_stmt(&mut self, expr: &Expr, loc: Loc) -> CompileRes {
+        self.expression(expr)?;
+        self.emit(OpCode::Print, loc);
+        Ok(())
+    }
+
+    fn visit_for_stmt(
+        &mut self,
+        cond: &Expr,
+        inc: &Option<Expr>,
+        body: &Stmt,
+        loc: Loc,
+    ) -> CompileRes {
+        let start = self.chunk().code.len();
+        self.expression(cond)?;
+        let exit = self.emit_jump(OpCode::JumpIfFalse, loc);
+        self.emit(OpCode::Pop, loc);
+
+        let loop_state = self.loop_body(|compiler| compiler.statement(body))?;
+        for jump in loop_state.continues {
+            self.patch_jump(jump)?;
+        }
+
+        if let Some(inc_expr) = inc {
+            self.expression(inc_expr)?;
+            self.emit(OpCode::Pop, loc);
+        }
+
+        self.emit_loop(start, loc)?;
+        self.patch_jump(exit)?;
+        self.emit(OpCode::Pop, loc);
+        for jump in loop_state.breaks {
+            self.patch_jump(jump)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_for_in_stmt(
+        &mut self,
+        var: &Param,
+        iterable: &Expr,
+        body: &Stmt,
+        loc: Loc,
+    ) -> CompileRes {
+        self.begin_scope();
+        self.expression(iterable)?;
+        // Leaves the iterated sequence and the position in it on the stack
+        self.emit(OpCode::IterInit, iterable.loc);
+        self.add_local("", loc)?;
+        self.add_local("", loc)?;
+
+        let start = self.chunk().code.len();
+        let exit = self.emit_jump(OpCode::IterNext, iterable.loc);
+        let loop_state = self.loop_body(|compiler| {
+            // Each iteration gets a fresh variable, so closures capture its value
+            compiler.begin_scope();
+            compiler.add_local(&var.kind, var.loc)?;
+            compiler.statement(body)?;
+            compiler.end_scope(loc);
+            Ok(())
+        })?;
+        for jump in loop_state.continues {
+            self.patch_jump(jump)?;
+        }
+
+        self.emit_loop(start, loc)?;
+        self.patch_jump(exit)?;
+        for jump in loop_state.breaks {
+            self.patch_jump(jump)?;
+        }
+
+        self.end_scope(loc);
+        Ok(())
+    }
+
+    fn visit_var_stmt(&mut self, name: &str, init: &Option<Expr>, loc: Loc) -> CompileRes {
+        match init {
+            Some(expr) => self.expression(expr)?,
+            None => {
+                self.emit(OpCode::Nil, loc);
+            }
+        }
+
+        self.define_variable(name, loc)
+    }
+
+    fn visit_block_stmt(&mut self, stmts: &[Stmt], loc: Loc) -> CompileRes {
+        self.block(stmts, loc)
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        body: &[Stmt],
+        kind: FunctionKind,
+        loc: Loc,
+    ) -> CompileRes {
+        if self.current().scope_depth > 0 {
+            // Declared before the body is compiled so that it can call itself
+            self.add_local(name, loc)?;
+            self.function(Some(name), params, body, kind, loc)
+        } else {
+            self.function(Some(name), params, body, kind, loc)?;
+            self.define_variable(name, loc)
+        }
+    }
+
+    fn visit_return_stmt(&mut self, ret: &Option<Expr>, loc: Loc) -> CompileRes {
+        if self.current().proto.is_init {
+            self.emit(OpCode::GetLocal(0), loc);
+        } else if let Some(expr) = ret {
+            self.expression(expr)?;
+        } else {
+            self.emit(OpCode::Nil, loc);
+        }
+
+        if !self.current().tries.is_empty() {
+            // The returned value stays on the stack while finally bodies run
+            self.begin_scope();
+            self.add_local("", loc)?;
+            self.exit_tries(0, loc)?;
+            let state = self.current();
+            state.locals.pop();
+            state.scope_depth -= 1;
+        }
+
+        self.emit(OpCode::Return, loc);
+        Ok(())
+    }
+
+    fn visit_class_stmt(
+        &mut self,
+        name: &str,
+        superclass: &Option<Expr>,
+        methods: &[Stmt],
+        loc: Loc,
+    ) -> CompileRes {
+        // Defined before the methods are compiled so that they can refer to it
+        self.emit(OpCode::Nil, loc);
+        self.define_variable(name, loc)?;
+
+        if let Some(expr) = superclass {
+            self.begin_scope();
+            self.expression(expr)?;
+            self.emit(OpCode::Inherit, expr.loc);
+            self.add_local(SUPER_KEYWORD, expr.loc)?;
+        }
+
+        for method in methods {
+            self.class_method(method)?;
+        }
+
+        let index = self.name_constant(name, loc)?;
+        let count = to_operand(methods.len(), loc, "Too many methods in class")?;
+        self.emit(OpCode::Class(index, count, superclass.is_some()), loc);
+        self.set_variable(name, loc)?;
+        self.emit(OpCode::Pop, loc);
+
+        if superclass.is_some() {
+            self.end_scope(loc);
+        }
+
+        Ok(())
+    }
+
+    fn visit_break_stmt(&mut self, loc: Loc) -> CompileRes {
+        self.jump_out_of_loop(loc, true)
+    }
+
+    fn visit_continue_stmt(&mut self, loc: Loc) -> CompileRes {
+        self.jump_out_of_loop(loc, false)
+    }
+
+    fn visit_throw_stmt(&mut self, expr: &Expr, loc: Loc) -> CompileRes {
+        self.expression(expr)?;
+        self.emit(OpCode::Throw, loc);
+        Ok(())
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        body: &[Stmt],
+        catch: &Option<CatchClause>,
+        finally: &Option<Vec<Stmt>>,
+        loc: Loc,
+    ) -> CompileRes {
+        match (catch, finally) {
+            (Some(catch), None) => self.try_catch(body, catch, loc),
+            (None, Some(finally)) => {
+                self.try_finally(finally, loc, |compiler| compiler.block(body, loc))
+            }
+            // Runs the same as a try/catch nested in the body of a try/finally
+            (Some(catch), Some(finally)) => self.try_finally(finally, loc, |compiler| {
+                compiler.try_catch(body, catch, loc)
+            }),
+            (None, None) => self.block(body, loc),
+        }
+    }
+
+    fn visit_import_stmt(&mut self, path: &str, kind: &ImportKind, loc: Loc) -> CompileRes {
+        let path_index = self.name_constant(path, loc)?;
+        match kind {
+            ImportKind::Module(alias) => {
+                self.emit(OpCode::Import(path_index), loc);
+                self.define_variable(&alias.kind, alias.loc)
+            }
+            ImportKind::Names(names) => {
+                for name in names {
+                    let name_index = self.name_constant(&name.kind, name.loc)?;
+                    self.emit(OpCode::Import(path_index), loc);
+                    self.emit(OpCode::ImportName(name_index, path_index), name.loc);
+                    self.define_variable(&name.kind, name.loc)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use super::*;
+
+#[test]
+fn test_too_many_arguments() {
+    let args = vec![Expr::integer(1, Loc::new(0, 2)); 256];
+    let expr = Expr::call(Expr::variable("f", Loc::new(0, 0)), args, Loc::new(0, 1));
+    assert_eq!(
+        Err(RuntimeError::generic(
+            Loc::new(0, 1),
+            "Too many arguments in call"
+        )),
+        compile_expr(&expr).map(|_| ())
+    );
+
+    let args = vec![Expr::integer(1, Loc::new(0, 2)); 255];
+    let expr = Expr::call(Expr::variable("f", Loc::new(0, 0)), args, Loc::new(0, 1));
+    assert!(compile_expr(&expr).is_ok());
+}
@@ -1,4 +1,18 @@
 pub const THIS_KEYWORD: &str = "this";
 pub const SUPER_KEYWORD: &str = "super";
 pub const INIT_METHOD: &str = "init";
+pub const ITERATOR_METHOD: &str = "iterator";
+pub const HAS_NEXT_METHOD: &str = "hasNext";
+pub const NEXT_METHOD: &str = "next";
 pub const ARRAY_CLASS: &str = "Array";
+pub const MAP_CLASS: &str = "Map";
+pub const STRING_CLASS: &str = "String";
+pub const ERROR_CLASS: &str = "Error";
+pub const TYPE_ERROR: &str = "TypeError";
+pub const INDEX_ERROR: &str = "IndexError";
+pub const KEY_ERROR: &str = "KeyError";
+pub const NAME_ERROR: &str = "NameError";
+pub const ARITHMETIC_ERROR: &str = "ArithmeticError";
+pub const IMPORT_ERROR: &str = "ImportError";
+pub const RECURSION_ERROR: &str = "RecursionError";
+pub const MODULE_CLASS: &str = "Module";
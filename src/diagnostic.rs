@@ -0,0 +1,390 @@
+#[cfg(test)]
+mod tests;
+
+use crate::location::Loc;
+use crate::source::SourceMap;
+use ansi_term::Color::{self, Blue, Red, Yellow};
+use ansi_term::Style;
+use serde_json::{json, Map, Value};
+use std::io::{self, Write};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// How errors and warnings are written: for people to read, or as one JSON
+/// object per line for tools.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+/// A message attached to a location of the source.
+#[derive(PartialEq, Debug)]
+pub struct Label {
+    pub loc: Loc,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// An error or warning as shown to the user: a title line, the source lines
+/// it refers to with their locations underlined, and notes at the end.
+#[derive(PartialEq, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// What kind of diagnostic this is, such as `SyntaxError`.
+    pub kind: String,
+    /// Identifies the error or warning variant, such as `E0201`. It doesn't
+    /// change between versions, so tools can match on it.
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, kind: &str, message: String) -> Self {
+        Self {
+            severity,
+            kind: String::from(kind),
+            code: None,
+            message,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Underlines the location the diagnostic is about.
+    pub fn primary(mut self, loc: Loc, message: &str) -> Self {
+        self.labels.push(Label {
+            loc,
+            message: String::from(message),
+            primary: true,
+        });
+        self
+    }
+
+    /// Points at another location involved in the diagnostic.
+    pub fn secondary(mut self, loc: Loc, message: &str) -> Self {
+        self.labels.push(Label {
+            loc,
+            message: String::from(message),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn note(mut self, note: &str) -> Self {
+        self.notes.push(String::from(note));
+        self
+    }
+
+    /// The location shown in the title, which is the first primary label.
+    pub fn loc(&self) -> Option<Loc> {
+        self.labels
+            .iter()
+            .find(|label| label.primary)
+            .map(|label| label.loc)
+    }
+
+    /// Writes the diagnostic, showing the code its labels point to when
+    /// their source is in `sources`.
+    pub fn render(
+        &self,
+        out: &mut dyn Write,
+        sources: &SourceMap,
+        colored: bool,
+    ) -> io::Result<()> {
+        // Labels are shown from where their code starts
+        let labels: Vec<Label> = self
+            .labels
+            .iter()
+            .map(|label| Label {
+                loc: sources.span_start(label.loc),
+                message: label.message.clone(),
+                primary: label.primary,
+            })
+            .collect();
+        let loc = labels
+            .iter()
+            .find(|label| label.primary)
+            .map(|label| label.loc);
+        let source = loc
+            .and_then(|loc| sources.get(loc.file))
+            .map(|file| file.source.as_str());
+        let lines = source.map_or_else(Vec::new, source_lines);
+
+        // Labels in the file of the main location are shown in its code, the
+        // rest are listed after it.
+        let (mut shown, listed): (Vec<&Label>, Vec<&Label>) = labels.iter().partition(|label| {
+            Some(label.loc.file) == loc.map(|loc| loc.file) && label.loc.line < lines.len()
+        });
+        shown.sort_by_key(|label| label.loc.line);
+
+        let paint = Painter {
+            colored,
+            color: match self.severity {
+                Severity::Error => Red,
+                Severity::Warning => Yellow,
+            },
+            width: shown
+                .iter()
+                .map(|label| (label.loc.line + 1).to_string().len())
+                .max()
+                .unwrap_or(1),
+        };
+
+        writeln!(
+            out,
+            "{}: {}",
+            paint.style(paint.color.bold(), &self.kind),
+            paint.style(Style::new().bold(), &self.message)
+        )?;
+
+        if let Some(loc) = loc {
+            let arrow = paint.style(Blue.bold(), "-->");
+            let loc = sources.display_loc(loc);
+            writeln!(out, "{:width$}{} {}", "", arrow, loc, width = paint.width)?;
+        }
+
+        if !shown.is_empty() {
+            self.render_snippet(out, &paint, &lines, &shown)?;
+        }
+
+        for label in listed.iter().filter(|label| !label.primary) {
+            let loc = paint.style(Blue.bold(), &sources.display_loc(label.loc));
+            writeln!(
+                out,
+                "{:width$} = {}: {}",
+                "",
+                loc,
+                label.message,
+                width = paint.width
+            )?;
+        }
+
+        for note in &self.notes {
+            let note_title = paint.style(Style::new().bold(), "note:");
+            writeln!(
+                out,
+                "{:width$} = {} {}",
+                "",
+                note_title,
+                note,
+                width = paint.width
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The diagnostic as a JSON object. Its location and the ones of its
+    /// labels are given by a file, and the line and column where the code
+    /// they point to starts and ends. Lines start at 1 and columns at 0, and
+    /// the start is the one shown in the rendered output.
+    pub fn to_json(&self, sources: &SourceMap) -> Value {
+        let mut json = Map::new();
+        json.insert(String::from("code"), json!(self.code));
+        json.insert(String::from("severity"), json!(self.severity.name()));
+        json.insert(String::from("kind"), json!(self.kind));
+        json.insert(String::from("message"), json!(self.message));
+        insert_loc(&mut json, self.loc(), sources);
+
+        let labels: Vec<Value> = self
+            .labels
+            .iter()
+            .map(|label| {
+                let mut json = Map::new();
+                insert_loc(&mut json, Some(label.loc), sources);
+                json.insert(String::from("message"), json!(label.message));
+                json.insert(String::from("primary"), json!(label.primary));
+                Value::Object(json)
+            })
+            .collect();
+        json.insert(String::from("labels"), json!(labels));
+        json.insert(String::from("notes"), json!(self.notes));
+
+        Value::Object(json)
+    }
+
+    /// Writes each line with a label, followed by a line per label that
+    /// underlines its code and shows its message.
+    fn render_snippet(
+        &self,
+        out: &mut dyn Write,
+        paint: &Painter,
+        lines: &[(usize, &str)],
+        labels: &[&Label],
+    ) -> io::Result<()> {
+        paint.separator(out)?;
+
+        let mut previous: Option<usize> = None;
+        for label in labels {
+            let line = label.loc.line;
+            let (line_start, text) = lines[line];
+            if previous != Some(line) {
+                if previous.is_some_and(|previous| line > previous + 1) {
+                    writeln!(out, "{}", paint.style(Blue.bold(), "..."))?;
+                }
+                paint.gutter(out, &(line + 1).to_string())?;
+                writeln!(out, "{}", text)?;
+            }
+            previous = Some(line);
+
+            let (marker, style) = if label.primary {
+                ("^", paint.color.bold())
+            } else {
+                ("-", Blue.bold())
+            };
+            let (column, len) = underlined(label.loc, line_start, text);
+            let mut underline = marker.repeat(len);
+            if !label.message.is_empty() {
+                underline = format!("{} {}", underline, label.message);
+            }
+
+            paint.gutter(out, "")?;
+            let indent = indentation(text, column);
+            writeln!(out, "{}{}", indent, paint.style(style, &underline))?;
+        }
+
+        if !self.notes.is_empty() {
+            paint.separator(out)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds the fields that locate `loc` to a JSON object, which are `null` if
+/// there is no location. They cover its span if it has one, and the point
+/// it's reported at otherwise.
+fn insert_loc(json: &mut Map<String, Value>, loc: Option<Loc>, sources: &SourceMap) {
+    let start = loc.map(|loc| sources.span_start(loc));
+    let end = loc.map(|loc| {
+        let end = loc.has_span().then(|| sources.position(loc.file, loc.end));
+        end.flatten().unwrap_or((loc.line, loc.column))
+    });
+
+    json.insert(
+        String::from("file"),
+        json!(loc.and_then(|loc| sources.name(loc.file))),
+    );
+    json.insert(
+        String::from("line"),
+        json!(start.map(|start| start.line + 1)),
+    );
+    json.insert(
+        String::from("column"),
+        json!(start.map(|start| start.column)),
+    );
+    json.insert(String::from("end_line"), json!(end.map(|end| end.0 + 1)));
+    json.insert(String::from("end_column"), json!(end.map(|end| end.1)));
+}
+
+struct Painter {
+    colored: bool,
+    color: Color,
+    /// Width of the largest line number, which the gutter is aligned to.
+    width: usize,
+}
+
+impl Painter {
+    fn gutter(&self, out: &mut dyn Write, number: &str) -> io::Result<()> {
+        let gutter = format!("{:>width$} |", number, width = self.width);
+        write!(out, "{} ", self.style(Blue.bold(), &gutter))
+    }
+
+    /// An empty gutter line, which frames the source lines.
+    fn separator(&self, out: &mut dyn Write) -> io::Result<()> {
+        let gutter = format!("{:width$} |", "", width = self.width);
+        writeln!(out, "{}", self.style(Blue.bold(), &gutter))
+    }
+
+    fn style(&self, style: Style, text: &str) -> String {
+        if self.colored {
+            style.paint(text).to_string()
+        } else {
+            String::from(text)
+        }
+    }
+}
+
+/// The lines of `source`, with the byte offset where each one starts.
+fn source_lines(source: &str) -> Vec<(usize, &str)> {
+    source
+        .split('\n')
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len() + 1;
+            Some((start, line.trim_end_matches('\r')))
+        })
+        .collect()
+}
+
+/// Whitespace that lines up with `column` in `line`, keeping its tabs.
+fn indentation(line: &str, column: usize) -> String {
+    line.chars()
+        .chain(std::iter::repeat(' '))
+        .take(column)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+/// The column where the underline of `loc` starts in `line`, and how many
+/// characters it covers. It covers the part of the span that is in `line`,
+/// or the token at the location if there is no span.
+fn underlined(loc: Loc, line_start: usize, line: &str) -> (usize, usize) {
+    let start = loc.start.max(line_start) - line_start;
+    let end = loc
+        .end
+        .min(line_start + line.len())
+        .saturating_sub(line_start);
+    match (line.get(..start), line.get(start..end)) {
+        (Some(before), Some(code)) if loc.has_span() && !code.is_empty() => {
+            (before.chars().count(), code.chars().count())
+        }
+        _ => (loc.column, token_len(line, loc.column)),
+    }
+}
+
+/// The length of the token starting at `column` in `line`, so that the
+/// whole identifier, number or string gets underlined.
+fn token_len(line: &str, column: usize) -> usize {
+    let mut chars = line.chars().skip(column).peekable();
+    let len = match chars.peek() {
+        Some('"') => {
+            let rest: Vec<char> = chars.collect();
+            rest.iter()
+                .skip(1)
+                .position(|&c| c == '"')
+                .map_or(rest.len(), |end| end + 2)
+        }
+        Some(c) if c.is_ascii_digit() => chars
+            .take_while(|&c| c.is_ascii_digit() || c == '.')
+            .count(),
+        Some(&c) if is_identifier_char(c) => chars.take_while(|&c| is_identifier_char(c)).count(),
+        _ => 1,
+    };
+    len.max(1)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,390 @@
+use super::*;
+use crate::error::{diagnostics, print_err, print_warns, Warning};
+use crate::eval::{Interpreter, RuntimeError};
+use crate::lexer::Scanner;
+use crate::parser::{Parser, ParsingError};
+use crate::resolver::{ResolutionError, Resolver};
+use crate::source::FileId;
+use crate::test_utils::SharedBuffer;
+use crate::Lox;
+use failure::Error;
+use serde_json::json;
+
+fn render(err: Error, sources: &SourceMap) -> String {
+    let mut out = Vec::new();
+    for diagnostic in diagnostics(&err) {
+        diagnostic.render(&mut out, sources, false).unwrap();
+    }
+    String::from_utf8(out).unwrap()
+}
+
+fn test_file(source: &str) -> (SourceMap, FileId) {
+    let mut sources = SourceMap::default();
+    let file = sources.add("test.lox", source);
+    (sources, file)
+}
+
+fn loc(file: FileId, line: usize, column: usize) -> Loc {
+    Loc {
+        file,
+        ..Loc::new(line, column)
+    }
+}
+
+#[test]
+fn test_render_secondary_label() {
+    let (sources, file) = test_file("{\n  var x = 1;\n  var x = 2;\n}");
+    let err =
+        ResolutionError::VarAlreadyInScope(loc(file, 2, 2), String::from("x"), loc(file, 1, 2));
+    assert_eq!(
+        render(err.into(), &sources),
+        "SyntaxError: Variable 'x' already declared in this scope
+ --> test.lox:3:2
+  |
+2 |   var x = 1;
+  |   --- variable declared here
+3 |   var x = 2;
+  |   ^^^
+"
+    );
+}
+
+#[test]
+fn test_render_underlines_token() {
+    let (sources, file) = test_file("var greeting = \"hi\";\nprint greeting + nope;");
+    let err = RuntimeError::UndefinedVariable(loc(file, 1, 17), String::from("nope"));
+    assert_eq!(
+        render(err.into(), &sources),
+        "RuntimeError: Undefined variable 'nope'
+ --> test.lox:2:17
+  |
+2 | print greeting + nope;
+  |                  ^^^^
+"
+    );
+
+    let err = RuntimeError::ExpectedType(
+        loc(file, 0, 15),
+        String::from("number"),
+        String::from("string"),
+    );
+    assert_eq!(
+        render(err.into(), &sources),
+        "TypeError: Expected type 'number'. Got 'string'
+ --> test.lox:1:15
+  |
+1 | var greeting = \"hi\";
+  |                ^^^^
+"
+    );
+}
+
+#[test]
+fn test_render_notes() {
+    let (sources, file) = test_file("class A {\n  init() {\n    return 1;\n  }\n}");
+    let err = ResolutionError::ReturnInInitializer(loc(file, 2, 4));
+    assert_eq!(
+        render(err.into(), &sources),
+        "SyntaxError: Cannot return a value from an initializer
+ --> test.lox:3:4
+  |
+3 |     return 1;
+  |     ^^^^^^
+  |
+  = note: initializers always return 'this'
+"
+    );
+}
+
+#[test]
+fn test_render_distant_lines() {
+    let (sources, file) = test_file("fun f(a,\n\n\n  a) {}");
+    let err =
+        ResolutionError::DuplicateArgumentName(loc(file, 3, 2), String::from("a"), loc(file, 0, 6));
+    assert_eq!(
+        render(err.into(), &sources),
+        "SyntaxError: Duplicate argument 'a' in function definition
+ --> test.lox:4:2
+  |
+1 | fun f(a,
+  |       - argument declared here
+...
+4 |   a) {}
+  |   ^
+"
+    );
+}
+
+#[test]
+fn test_render_multiple_errors() {
+    let (sources, file) = test_file("print ;\nvar = 1;");
+    let err = ParsingError::Multiple(vec![
+        ParsingError::ExpectedExpression(loc(file, 0, 6), String::from("';'")),
+        ParsingError::ExpectedName(
+            loc(file, 1, 4),
+            String::from("variable"),
+            String::from("'='"),
+        ),
+    ]);
+    assert_eq!(
+        render(err.into(), &sources),
+        "SyntaxError: Expected expression. Got ';'
+ --> test.lox:1:6
+  |
+1 | print ;
+  |       ^
+SyntaxError: Expected variable name. Got '='
+ --> test.lox:2:4
+  |
+2 | var = 1;
+  |     ^
+"
+    );
+}
+
+#[test]
+fn test_render_without_source() {
+    let err = ResolutionError::VarAlreadyInScope(Loc::new(2, 2), String::from("x"), Loc::new(1, 2));
+    assert_eq!(
+        render(err.into(), &SourceMap::default()),
+        "SyntaxError: Variable 'x' already declared in this scope
+ --> 3:2
+  = 2:2: variable declared here
+"
+    );
+}
+
+#[test]
+fn test_render_tabs() {
+    let (sources, file) = test_file("\tprint\tnope;");
+    let err = RuntimeError::UndefinedVariable(loc(file, 0, 7), String::from("nope"));
+    assert_eq!(
+        render(err.into(), &sources),
+        "RuntimeError: Undefined variable 'nope'
+ --> test.lox:1:7
+  |
+1 | \tprint\tnope;
+  | \t     \t^^^^
+"
+    );
+}
+
+#[test]
+fn test_render_spans() {
+    let mut inter = Interpreter::new();
+    let input = "var a = 1;\nprint a +\n  \"x\";\nprint -nil;";
+    let file = inter.sources.add("test.lox", input);
+    let tokens = Scanner::new(input, file).scan_tokens().unwrap();
+    let stmts = Parser::new(&tokens).parse().unwrap();
+    Resolver::new().resolve(&stmts).unwrap();
+
+    let err = inter.interpret(&stmts).unwrap_err();
+    assert_eq!(
+        render(err.into(), &inter.sources),
+        "RuntimeError: Unsupported operands for +: 'int' and 'string'
+ --> test.lox:2:6
+  |
+2 | print a +
+  |       ^^^
+"
+    );
+
+    let err = inter.interpret(&stmts[2..]).unwrap_err();
+    assert_eq!(
+        render(err.into(), &inter.sources),
+        "RuntimeError: Unsupported operand for -: 'nil'
+ --> test.lox:4:6
+  |
+4 | print -nil;
+  |       ^^^^
+"
+    );
+}
+
+fn render_json(err: Error, sources: &SourceMap) -> Vec<Value> {
+    let mut out = Vec::new();
+    print_err(&mut out, &err, sources, ErrorFormat::Json, false).unwrap();
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_json_labels() {
+    let (sources, file) = test_file("{\n  var x = 1;\n  var x = 2;\n}");
+    let err = ResolutionError::VarAlreadyInScope(
+        loc(file, 2, 2).with_span(17, 27),
+        String::from("x"),
+        loc(file, 1, 2),
+    );
+    assert_eq!(
+        render_json(err.into(), &sources),
+        vec![json!({
+            "code": "E0202",
+            "severity": "error",
+            "kind": "SyntaxError",
+            "message": "Variable 'x' already declared in this scope",
+            "file": "test.lox",
+            "line": 3,
+            "column": 2,
+            "end_line": 3,
+            "end_column": 12,
+            "labels": [
+                {
+                    "file": "test.lox",
+                    "line": 3,
+                    "column": 2,
+                    "end_line": 3,
+                    "end_column": 12,
+                    "message": "",
+                    "primary": true,
+                },
+                {
+                    "file": "test.lox",
+                    "line": 2,
+                    "column": 2,
+                    "end_line": 2,
+                    "end_column": 2,
+                    "message": "variable declared here",
+                    "primary": false,
+                },
+            ],
+            "notes": [],
+        })]
+    );
+}
+
+#[test]
+fn test_json_multiple_errors() {
+    let err = ParsingError::Multiple(vec![
+        ParsingError::ExpectedExpression(Loc::new(0, 6), String::from("';'")),
+        ParsingError::InvalidAssignmentTarget(Loc::new(1, 0)),
+    ]);
+    let json = render_json(err.into(), &SourceMap::default());
+    assert_eq!(2, json.len());
+    assert_eq!(json!("E0101"), json[0]["code"]);
+    assert_eq!(json!(null), json[0]["file"]);
+    assert_eq!(json!("Expected expression. Got ';'"), json[0]["message"]);
+    assert_eq!(json!("E0115"), json[1]["code"]);
+    assert_eq!(json!(2), json[1]["line"]);
+}
+
+#[test]
+fn test_json_traceback() {
+    let mut inter = Interpreter::new();
+    let input = "fun f() {\n  return -nil;\n}\nf();";
+    let file = inter.sources.add("test.lox", input);
+    let tokens = Scanner::new(input, file).scan_tokens().unwrap();
+    let stmts = Parser::new(&tokens).parse().unwrap();
+    Resolver::new().resolve(&stmts).unwrap();
+
+    let err = inter.interpret(&stmts).unwrap_err();
+    let err = inter.traceback(err);
+    let json = render_json(err.into(), &inter.sources);
+    assert_eq!(json!("E0302"), json[0]["code"]);
+    assert_eq!(
+        json!([
+            {"function": "<script>", "file": "test.lox", "line": 4, "column": 2},
+            {"function": "f", "file": "test.lox", "line": 2, "column": 9},
+        ]),
+        json[0]["traceback"]
+    );
+}
+
+#[test]
+fn test_json_warnings() {
+    let (sources, file) = test_file("{ var unused = 1; }");
+    let warns = [Warning::UnusedVariable(
+        loc(file, 0, 2),
+        String::from("unused"),
+    )];
+    let mut out = Vec::new();
+    print_warns(&mut out, &warns, &sources, ErrorFormat::Json, false).unwrap();
+    let json: Value = serde_json::from_str(&String::from_utf8(out).unwrap()).unwrap();
+    assert_eq!(json!("W0001"), json["code"]);
+    assert_eq!(json!("warning"), json["severity"]);
+    assert_eq!(json!("Unused variable 'unused'"), json["message"]);
+}
+
+#[test]
+fn test_render_multiline_span() {
+    let mut inter = Interpreter::new();
+    let input = "var a = 1;\nvar b = clock(\n  a,\n  a\n);";
+    let file = inter.sources.add("test.lox", input);
+    let tokens = Scanner::new(input, file).scan_tokens().unwrap();
+    let stmts = Parser::new(&tokens).parse().unwrap();
+    Resolver::new().resolve(&stmts).unwrap();
+
+    let err = inter.interpret(&stmts).unwrap_err();
+    let rendered = render(err.into(), &inter.sources);
+    assert!(rendered.contains(
+        " --> test.lox:2:8
+  |
+2 | var b = clock(
+  |         ^^^^^^
+"
+    ));
+}
+
+#[test]
+fn test_json_range_is_the_span() {
+    let mut inter = Interpreter::new();
+    let input = "print \"a\" - nil;\nclock(1);";
+    let file = inter.sources.add("test.lox", input);
+    let tokens = Scanner::new(input, file).scan_tokens().unwrap();
+    let stmts = Parser::new(&tokens).parse().unwrap();
+    Resolver::new().resolve(&stmts).unwrap();
+
+    let range = |json: &Value| {
+        let fields = ["line", "column", "end_line", "end_column"];
+        fields
+            .iter()
+            .map(|field| json[field].clone())
+            .collect::<Vec<_>>()
+    };
+
+    let err = inter.interpret(&stmts).unwrap_err();
+    let json = render_json(err.into(), &inter.sources);
+    assert_eq!(
+        vec![json!(1), json!(6), json!(1), json!(15)],
+        range(&json[0])
+    );
+    assert_eq!(range(&json[0]), range(&json[0]["labels"][0]));
+
+    let err = inter.interpret(&stmts[1..]).unwrap_err();
+    let json = render_json(err.into(), &inter.sources);
+    assert_eq!(
+        vec![json!(2), json!(0), json!(2), json!(8)],
+        range(&json[0])
+    );
+}
+
+#[test]
+fn test_custom_sinks_are_not_colored() {
+    let diagnostics = SharedBuffer::default();
+    let mut lox = Lox::new();
+    lox.set_output(SharedBuffer::default());
+    lox.set_diagnostics(diagnostics.clone());
+    lox.run("{ var unused = 1; }").unwrap();
+    let err = lox.run("print -nil;").unwrap_err();
+    lox.print_err(&err).unwrap();
+
+    let contents = diagnostics.contents();
+    assert!(
+        contents.contains("Warning: Unused variable 'unused'"),
+        "{}",
+        contents
+    );
+    assert!(
+        contents.contains("RuntimeError: Unsupported operand"),
+        "{}",
+        contents
+    );
+    assert!(!contents.contains('\u{1b}'), "{}", contents);
+
+    lox.set_colored_diagnostics(true);
+    lox.print_err(&err).unwrap();
+    assert!(diagnostics.contents().contains('\u{1b}'));
+}
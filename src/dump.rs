@@ -0,0 +1,365 @@
+#[cfg(test)]
+mod tests;
+
+use crate::expr::{Expr, ExprKind, LitExpr, Param, ResolvedLocal};
+use crate::lexer::Token;
+use crate::location::Loc;
+use crate::stmt::{FunctionKind, ImportKind, Stmt, StmtKind};
+use crate::utils::escape_string;
+use serde_json::{json, Map, Value};
+use std::io::{self, Write};
+
+/// How `--dump-ast` and `--dump-resolved` print the syntax tree.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AstFormat {
+    /// One node per line, indented under its parent.
+    Tree,
+    Sexp,
+    Json,
+    /// A Graphviz graph.
+    Dot,
+}
+
+/// What the command line shows about a script instead of running it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Dump {
+    Tokens,
+    Ast(AstFormat),
+    /// The syntax tree, with the scope depth and index each variable was
+    /// resolved to.
+    Resolved(AstFormat),
+}
+
+impl AstFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tree" => Some(AstFormat::Tree),
+            "sexp" => Some(AstFormat::Sexp),
+            "json" => Some(AstFormat::Json),
+            "dot" => Some(AstFormat::Dot),
+            _ => None,
+        }
+    }
+}
+
+/// A node of the syntax tree as it is dumped, which is how the parser built
+/// it, desugared constructs included.
+struct Node {
+    kind: &'static str,
+    /// The name, operator or literal of the node.
+    value: Option<String>,
+    /// Where a variable was resolved to, which is `Some(None)` for globals.
+    resolution: Option<Option<ResolvedLocal>>,
+    loc: Loc,
+    children: Vec<Node>,
+}
+
+pub fn dump_tokens(out: &mut dyn Write, tokens: &[Token]) -> io::Result<()> {
+    for token in tokens {
+        writeln!(out, "{}", token)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the syntax tree of a script, along with the resolution of its
+/// variables if `resolved` is set.
+pub fn dump_ast(
+    out: &mut dyn Write,
+    stmts: &[Stmt],
+    format: AstFormat,
+    resolved: bool,
+) -> io::Result<()> {
+    let root = Node {
+        kind: "Program",
+        value: None,
+        resolution: None,
+        loc: Loc::default(),
+        children: stmts.iter().map(stmt_node).collect(),
+    };
+
+    match format {
+        AstFormat::Tree => write_tree(out, &root, 0, resolved),
+        AstFormat::Sexp => {
+            write_sexp(out, &root, resolved)?;
+            writeln!(out)
+        }
+        AstFormat::Json => writeln!(out, "{:#}", root.to_json(resolved)),
+        AstFormat::Dot => {
+            writeln!(out, "digraph ast {{")?;
+            writeln!(out, "    node [shape=box];")?;
+            write_dot(out, &root, &mut 0, resolved)?;
+            writeln!(out, "}}")
+        }
+    }
+}
+
+impl Node {
+    fn new(kind: &'static str, loc: Loc) -> Self {
+        Node {
+            kind,
+            value: None,
+            resolution: None,
+            loc,
+            children: Vec::new(),
+        }
+    }
+
+    fn value(mut self, value: &str) -> Self {
+        self.value = Some(String::from(value));
+        self
+    }
+
+    fn child(mut self, child: Node) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    fn children(mut self, children: impl IntoIterator<Item = Node>) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    fn resolution(mut self, resolution: Option<ResolvedLocal>) -> Self {
+        self.resolution = Some(resolution);
+        self
+    }
+
+    /// The kind of the node followed by its value and, if `resolved` is set,
+    /// its resolution.
+    fn label(&self, resolved: bool) -> String {
+        let mut label = String::from(self.kind);
+        if let Some(value) = &self.value {
+            label.push(' ');
+            label.push_str(value);
+        }
+
+        match self.resolution {
+            Some(Some(local)) if resolved => {
+                label.push_str(&format!(" [depth {}, index {}]", local.depth, local.index))
+            }
+            Some(None) if resolved => label.push_str(" [global]"),
+            _ => (),
+        }
+
+        label
+    }
+
+    fn to_json(&self, resolved: bool) -> Value {
+        let mut json = Map::new();
+        json.insert(String::from("kind"), json!(self.kind));
+        if let Some(value) = &self.value {
+            json.insert(String::from("value"), json!(value));
+        }
+        if self.kind != "Program" {
+            json.insert(String::from("line"), json!(self.loc.line + 1));
+            json.insert(String::from("column"), json!(self.loc.column));
+        }
+        match self.resolution {
+            Some(Some(local)) if resolved => {
+                let resolution = json!({"depth": local.depth, "index": local.index});
+                json.insert(String::from("resolution"), resolution);
+            }
+            Some(None) if resolved => {
+                json.insert(String::from("resolution"), json!("global"));
+            }
+            _ => (),
+        }
+
+        let children: Vec<Value> = self
+            .children
+            .iter()
+            .map(|child| child.to_json(resolved))
+            .collect();
+        json.insert(String::from("children"), json!(children));
+        Value::Object(json)
+    }
+}
+
+fn write_tree(out: &mut dyn Write, node: &Node, depth: usize, resolved: bool) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    if node.kind == "Program" {
+        writeln!(out, "{}{}", indent, node.label(resolved))?;
+    } else {
+        writeln!(out, "{}{} @{}", indent, node.label(resolved), node.loc)?;
+    }
+
+    for child in &node.children {
+        write_tree(out, child, depth + 1, resolved)?;
+    }
+
+    Ok(())
+}
+
+fn write_sexp(out: &mut dyn Write, node: &Node, resolved: bool) -> io::Result<()> {
+    write!(out, "({}", node.label(resolved))?;
+    for child in &node.children {
+        write!(out, " ")?;
+        write_sexp(out, child, resolved)?;
+    }
+    write!(out, ")")
+}
+
+/// Writes a node and its children, numbering them from `next_id`. Returns
+/// the id of the node.
+fn write_dot(
+    out: &mut dyn Write,
+    node: &Node,
+    next_id: &mut usize,
+    resolved: bool,
+) -> io::Result<usize> {
+    let id = *next_id;
+    *next_id += 1;
+
+    let label = node
+        .label(resolved)
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    writeln!(out, "    n{} [label=\"{}\"];", id, label)?;
+    for child in &node.children {
+        let child_id = write_dot(out, child, next_id, resolved)?;
+        writeln!(out, "    n{} -> n{};", id, child_id)?;
+    }
+
+    Ok(id)
+}
+
+fn stmt_node(stmt: &Stmt) -> Node {
+    let node = |kind| Node::new(kind, stmt.loc);
+    match &stmt.kind {
+        StmtKind::Expression(expr) => node("Expression").child(expr_node(expr)),
+        StmtKind::If(cond, then_branch, else_branch) => node("If")
+            .child(expr_node(cond))
+            .child(stmt_node(then_branch))
+            .children(else_branch.as_deref().map(stmt_node)),
+        StmtKind::Print(expr) => node("Print").child(expr_node(expr)),
+        StmtKind::For(cond, increment, body) => node("For")
+            .child(expr_node(cond))
+            .children(increment.as_ref().map(expr_node))
+            .child(stmt_node(body)),
+        StmtKind::ForIn(var, iterable, body) => node("ForIn")
+            .value(&var.kind)
+            .child(expr_node(iterable))
+            .child(stmt_node(body)),
+        StmtKind::Var(name, init) => node("Var")
+            .value(name)
+            .children(init.as_ref().map(expr_node)),
+        StmtKind::Block(stmts) => node("Block").children(stmts.iter().map(stmt_node)),
+        StmtKind::Function(name, params, body, kind) => {
+            let kind = match kind {
+                FunctionKind::Function => "Function",
+                FunctionKind::Method => "Method",
+                FunctionKind::Getter => "Getter",
+                FunctionKind::StaticMethod => "StaticMethod",
+            };
+            node(kind)
+                .value(&signature(name, params))
+                .children(body.iter().map(stmt_node))
+        }
+        StmtKind::Return(value) => node("Return").children(value.as_ref().map(expr_node)),
+        StmtKind::Class(name, superclass, methods) => node("Class")
+            .value(name)
+            .children(superclass.as_ref().map(expr_node))
+            .children(methods.iter().map(stmt_node)),
+        StmtKind::Break => node("Break"),
+        StmtKind::Continue => node("Continue"),
+        StmtKind::Throw(value) => node("Throw").child(expr_node(value)),
+        StmtKind::Try(body, catch, finally) => {
+            let body = node("Body").children(body.iter().map(stmt_node));
+            let catch = catch.as_ref().map(|(var, body)| {
+                Node::new("Catch", var.loc)
+                    .value(&var.kind)
+                    .children(body.iter().map(stmt_node))
+            });
+            let finally = finally
+                .as_ref()
+                .map(|body| node("Finally").children(body.iter().map(stmt_node)));
+            node("Try").child(body).children(catch).children(finally)
+        }
+        StmtKind::Import(path, kind) => {
+            let names = match kind {
+                ImportKind::Module(name) => vec![Node::new("As", name.loc).value(&name.kind)],
+                ImportKind::Names(names) => names
+                    .iter()
+                    .map(|name| Node::new("Name", name.loc).value(&name.kind))
+                    .collect(),
+            };
+            node("Import")
+                .value(&format!("\"{}\"", escape_string(path)))
+                .children(names)
+        }
+    }
+}
+
+fn expr_node(expr: &Expr) -> Node {
+    let node = |kind| Node::new(kind, expr.loc);
+    match &expr.kind {
+        ExprKind::Literal(literal) => {
+            let value = match literal {
+                LitExpr::Str(string) => format!("\"{}\"", escape_string(string)),
+                literal => literal.to_string(),
+            };
+            node("Literal").value(&value)
+        }
+        ExprKind::Function(params, body) => node("Lambda")
+            .value(&signature("", params))
+            .children(body.iter().map(stmt_node)),
+        ExprKind::Unary(op, operand) => node("Unary")
+            .value(op.to_string())
+            .child(expr_node(operand)),
+        ExprKind::Binary(left, op, right) => node("Binary")
+            .value(op.to_string())
+            .child(expr_node(left))
+            .child(expr_node(right)),
+        ExprKind::Logical(left, op, right) => node("Logical")
+            .value(op.to_string())
+            .child(expr_node(left))
+            .child(expr_node(right)),
+        ExprKind::Grouping(expr) => node("Grouping").child(expr_node(expr)),
+        ExprKind::Comma(left, right) => {
+            node("Comma").child(expr_node(left)).child(expr_node(right))
+        }
+        ExprKind::Conditional(cond, then_expr, else_expr) => node("Conditional")
+            .child(expr_node(cond))
+            .child(expr_node(then_expr))
+            .child(expr_node(else_expr)),
+        ExprKind::Variable(name, resolution) => {
+            node("Variable").value(name).resolution(resolution.get())
+        }
+        ExprKind::Assign(name, value, resolution) => node("Assign")
+            .value(name)
+            .resolution(resolution.get())
+            .child(expr_node(value)),
+        ExprKind::Call(callee, args) => node("Call")
+            .child(expr_node(callee))
+            .children(args.iter().map(expr_node)),
+        ExprKind::Get(obj, name) => node("Get").value(name).child(expr_node(obj)),
+        ExprKind::Set(obj, name, value) => node("Set")
+            .value(name)
+            .child(expr_node(obj))
+            .child(expr_node(value)),
+        ExprKind::Array(elements) => node("Array").children(elements.iter().map(expr_node)),
+        ExprKind::Map(entries) => node("Map").children(entries.iter().map(|(key, value)| {
+            Node::new("Entry", key.loc.extend(value.loc))
+                .child(expr_node(key))
+                .child(expr_node(value))
+        })),
+        ExprKind::SubscriptGet(obj, index) => node("SubscriptGet")
+            .child(expr_node(obj))
+            .child(expr_node(index)),
+        ExprKind::SubscriptSet(obj, index, value) => node("SubscriptSet")
+            .child(expr_node(obj))
+            .child(expr_node(index))
+            .child(expr_node(value)),
+        ExprKind::This(resolution) => node("This").resolution(resolution.get()),
+        ExprKind::Super(method, resolution) => {
+            node("Super").value(method).resolution(resolution.get())
+        }
+    }
+}
+
+/// A function's name followed by its parameters, like `add(a, b)`.
+fn signature(name: &str, params: &[Param]) -> String {
+    let params: Vec<&str> = params.iter().map(|param| param.kind.as_str()).collect();
+    format!("{}({})", name, params.join(", "))
+}
@@ -0,0 +1,82 @@
+use super::*;
+use crate::test_utils::{get_stmts, get_tokens};
+
+fn dump(source: &str, format: AstFormat, resolved: bool) -> String {
+    let (stmts, _) = get_stmts(source);
+    let mut out = Vec::new();
+    dump_ast(&mut out, &stmts, format, resolved).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+const SOURCE: &str = "var a = 1;\n{\n    var b = a;\n    print -b;\n}";
+
+#[test]
+fn test_tokens() {
+    let mut out = Vec::new();
+    dump_tokens(&mut out, &get_tokens("var a = \"s\";")).unwrap();
+    let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+    assert_eq!(6, lines.len());
+    assert_eq!("<Var, var, 1:0>", lines[0]);
+}
+
+#[test]
+fn test_tree() {
+    let expected = r#"Program
+  Var a @1:0
+    Literal 1 @1:8
+  Block @2:0
+    Var b @3:4
+      Variable a @3:12
+    Print @4:4
+      Unary - @4:10
+        Variable b @4:11
+"#;
+    assert_eq!(expected, dump(SOURCE, AstFormat::Tree, false));
+}
+
+#[test]
+fn test_resolved_tree() {
+    let expected = r#"Program
+  Var a @1:0
+    Literal 1 @1:8
+  Block @2:0
+    Var b @3:4
+      Variable a [global] @3:12
+    Print @4:4
+      Unary - @4:10
+        Variable b [depth 0, index 0] @4:11
+"#;
+    assert_eq!(expected, dump(SOURCE, AstFormat::Tree, true));
+}
+
+#[test]
+fn test_sexp() {
+    let source = "fun f(x) { return x * \"a\"; }";
+    let expected = "(Program (Function f(x) (Return (Binary * (Variable x [depth 0, index 0]) \
+                    (Literal \"a\")))))\n";
+    assert_eq!(expected, dump(source, AstFormat::Sexp, true));
+}
+
+#[test]
+fn test_json() {
+    let json: Value = serde_json::from_str(&dump(SOURCE, AstFormat::Json, true)).unwrap();
+    let variable = &json["children"][1]["children"][1]["children"][0]["children"][0];
+    assert_eq!("Variable", variable["kind"]);
+    assert_eq!(json!({"depth": 0, "index": 0}), variable["resolution"]);
+    assert_eq!(4, variable["line"]);
+    assert_eq!(11, variable["column"]);
+}
+
+#[test]
+fn test_dot() {
+    let expected = r#"digraph ast {
+    node [shape=box];
+    n0 [label="Program"];
+    n1 [label="Print"];
+    n2 [label="Literal \"a\\\\b\""];
+    n1 -> n2;
+    n0 -> n1;
+}
+"#;
+    assert_eq!(expected, dump(r#"print "a\\b";"#, AstFormat::Dot, false));
+}
@@ -1,34 +1,255 @@
-use crate::eval::RuntimeError;
+use crate::callable::Arity;
+use crate::diagnostic::{Diagnostic, ErrorFormat, Severity};
+use crate::eval::{RuntimeError, Traceback};
 use crate::lexer::ScanningError;
+use crate::lint::Lint;
 use crate::location::Loc;
-use crate::parser::ParsingError;
+use crate::parser::{ParsingError, MAX_CHAIN_LENGTH, MAX_NESTING_DEPTH};
 use crate::resolver::ResolutionError;
-use ansi_term::Color::{Red, Yellow};
+use crate::source::SourceMap;
 use failure::{Error, Fail};
+use serde_json::json;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{self, Write};
 
 #[derive(Debug, PartialEq)]
 pub enum Warning {
     UnusedVariable(Loc, String),
+    UnusedParameter(Loc, String),
+    UnusedFunction(Loc, String),
+    /// The second location is the statement that makes the code unreachable.
+    UnreachableCode(Loc, Loc),
+    /// The second location is where the outer variable was declared.
+    ShadowedVariable(Loc, String, Loc),
+    AssignmentInCondition(Loc),
+    SelfComparison(Loc, String),
+    UnassignedVariable(Loc, String),
+    /// A global that is never declared, and a similar name that is.
+    UndefinedVariable(Loc, String, Option<String>),
+    /// A call with a number of arguments the callee doesn't take. The last
+    /// location is where the callee is declared, unless it's a builtin.
+    ArityMismatch(Loc, String, Arity, usize, Option<Loc>),
 }
 
-pub fn print_err(err: &Error) {
+/// Prints an error along with the code it refers to, if it is in `sources`,
+/// with colors if `colored` is set. As JSON, runtime errors carry their
+/// traceback in a `traceback` field.
+pub fn print_err(
+    out: &mut dyn Write,
+    err: &Error,
+    sources: &SourceMap,
+    format: ErrorFormat,
+    colored: bool,
+) -> io::Result<()> {
+    let traceback = err
+        .downcast_ref::<Traceback>()
+        .filter(|traceback| !traceback.frames.is_empty());
+    match format {
+        ErrorFormat::Human => {
+            if let Some(traceback) = traceback {
+                print_traceback(out, traceback, sources)?;
+            }
+
+            for diagnostic in diagnostics(err) {
+                diagnostic.render(out, sources, colored)?;
+            }
+        }
+        ErrorFormat::Json => {
+            for diagnostic in diagnostics(err) {
+                let mut json = diagnostic.to_json(sources);
+                if let Some(traceback) = traceback {
+                    let frames: Vec<_> = traceback_lines(traceback)
+                        .into_iter()
+                        .map(|(loc, caller)| {
+                            json!({
+                                "function": caller,
+                                "file": sources.name(loc.file),
+                                "line": loc.line + 1,
+                                "column": loc.column,
+                            })
+                        })
+                        .collect();
+                    json["traceback"] = json!(frames);
+                }
+                writeln!(out, "{}", json)?;
+            }
+        }
+    }
+
+    out.flush()
+}
+
+/// Turns an error into the diagnostics shown to the user, one per error for
+/// the `Multiple` variants.
+pub fn diagnostics(err: &Error) -> Vec<Diagnostic> {
+    let kind = error_type(err);
+    let error = |message: String, code: Option<&'static str>, loc: Option<Loc>| {
+        let diagnostic = Diagnostic::new(Severity::Error, kind, message);
+        let diagnostic = match code {
+            Some(code) => diagnostic.code(code),
+            None => diagnostic,
+        };
+        match loc {
+            Some(loc) => diagnostic.primary(loc, ""),
+            None => diagnostic,
+        }
+    };
+
+    let mut diagnostics = if let Some(err) = err.downcast_ref::<ScanningError>() {
+        match err {
+            ScanningError::Multiple(errors) => errors
+                .iter()
+                .map(|err| error(err.message(), err.code(), err.loc()))
+                .collect(),
+            err => vec![error(err.message(), err.code(), err.loc())],
+        }
+    } else if let Some(err) = err.downcast_ref::<ParsingError>() {
+        match err {
+            ParsingError::Multiple(errors) => errors
+                .iter()
+                .map(|err| error(err.message(), err.code(), err.loc()))
+                .collect(),
+            err => vec![error(err.message(), err.code(), err.loc())],
+        }
+    } else if let Some(err) = err.downcast_ref::<ResolutionError>() {
+        let resolution_error = |err: &ResolutionError| {
+            let diagnostic = error(err.message(), err.code(), err.loc());
+            resolution_labels(diagnostic, err)
+        };
+        match err {
+            ResolutionError::Multiple(errors) => errors.iter().map(resolution_error).collect(),
+            err => vec![resolution_error(err)],
+        }
+    } else if let Some(err) = runtime_error(err) {
+        vec![error(err.message(), Some(err.code()), Some(err.loc()))]
+    } else {
+        vec![error(err.to_string(), None, None)]
+    };
+
     let mut fail = err.as_fail();
-    eprintln!("{}: {}", Red.bold().paint(error_type(err)), fail);
     while let Some(cause) = fail.cause() {
-        eprintln!("> {}", cause);
+        for diagnostic in diagnostics.iter_mut() {
+            diagnostic.notes.push(cause.to_string());
+        }
         fail = cause;
     }
+
+    diagnostics
 }
 
-fn print_warn(warn: &Warning) {
-    eprintln!("{}: {}", Yellow.bold().paint("Warning"), warn);
+fn resolution_labels(diagnostic: Diagnostic, err: &ResolutionError) -> Diagnostic {
+    use ResolutionError::*;
+    match err {
+        VarAlreadyInScope(_, _, declared) => {
+            diagnostic.secondary(*declared, "variable declared here")
+        }
+        DuplicateArgumentName(_, _, declared) => {
+            diagnostic.secondary(*declared, "argument declared here")
+        }
+        ReturnInInitializer(_) => diagnostic.note("initializers always return 'this'"),
+        ThisInStaticMethod(_) => {
+            diagnostic.note("static methods are called on the class, not on an instance")
+        }
+        SuperNoInSubclass(_) => {
+            diagnostic.note("declare a superclass with 'class Name < Superclass'")
+        }
+        DeniedLint(warn) => {
+            let note = format!("the '{}' lint is denied", warn.lint().name());
+            warning_labels(diagnostic, warn).note(&note)
+        }
+        _ => diagnostic,
+    }
+}
+
+/// Lines shown at each end of a traceback. The ones in between are
+/// summarized, so that runaway recursion does not flood the output.
+const TRACEBACK_EDGE_LINES: usize = 10;
+
+/// Prints the calls that led to a runtime error, innermost call last. Each
+/// line tells where the function on the previous line was executing.
+fn print_traceback(
+    out: &mut dyn Write,
+    traceback: &Traceback,
+    sources: &SourceMap,
+) -> io::Result<()> {
+    if traceback.frames.is_empty() {
+        return Ok(());
+    }
+
+    let lines = traceback_lines(traceback);
+    writeln!(out, "Traceback (most recent call last):")?;
+    let hidden = lines.len().saturating_sub(2 * TRACEBACK_EDGE_LINES);
+    for (i, (loc, caller)) in lines.iter().enumerate() {
+        if hidden > 0 && i == TRACEBACK_EDGE_LINES {
+            writeln!(out, "  [{} more calls]", hidden)?;
+        }
+
+        if hidden == 0 || i < TRACEBACK_EDGE_LINES || i >= TRACEBACK_EDGE_LINES + hidden {
+            writeln!(out, "  [{}] in {}", sources.display_loc(*loc), caller)?;
+        }
+    }
+
+    Ok(())
 }
 
-pub fn print_warns(warns: &[Warning]) {
+/// Where each call of a traceback was executing, along with the function
+/// that made it, outermost first.
+fn traceback_lines(traceback: &Traceback) -> Vec<(Loc, &str)> {
+    let frames = &traceback.frames;
+    let locs = frames
+        .iter()
+        .map(|frame| frame.loc)
+        .chain(std::iter::once(traceback.error.loc()));
+    let callers = std::iter::once("<script>").chain(frames.iter().map(|frame| frame.name.as_str()));
+    locs.zip(callers).collect()
+}
+
+pub fn warning_diagnostic(warn: &Warning) -> Diagnostic {
+    let diagnostic = Diagnostic::new(Severity::Warning, "Warning", warn.message())
+        .code(warn.code())
+        .primary(warn.loc(), "");
+    warning_labels(diagnostic, warn)
+}
+
+fn warning_labels(diagnostic: Diagnostic, warn: &Warning) -> Diagnostic {
+    use Warning::*;
+    match warn {
+        UnreachableCode(_, jump) => diagnostic.secondary(*jump, "any code after this is not run"),
+        ShadowedVariable(_, _, outer) => {
+            diagnostic.secondary(*outer, "outer variable declared here")
+        }
+        AssignmentInCondition(_) => {
+            diagnostic.note("use '==' to compare, or wrap the assignment in parentheses")
+        }
+        UnusedVariable(_, _) | UnusedParameter(_, _) | UnusedFunction(_, _) => {
+            diagnostic.note("prefix the name with '_' if it is unused on purpose")
+        }
+        UndefinedVariable(_, _, Some(similar)) => {
+            diagnostic.note(&format!("did you mean '{}'?", similar))
+        }
+        ArityMismatch(_, _, _, _, Some(declared)) => {
+            diagnostic.secondary(*declared, "declared here")
+        }
+        _ => diagnostic,
+    }
+}
+
+pub fn print_warns(
+    out: &mut dyn Write,
+    warns: &[Warning],
+    sources: &SourceMap,
+    format: ErrorFormat,
+    colored: bool,
+) -> io::Result<()> {
     for warn in warns {
-        print_warn(warn);
+        let diagnostic = warning_diagnostic(warn);
+        match format {
+            ErrorFormat::Human => diagnostic.render(out, sources, colored)?,
+            ErrorFormat::Json => writeln!(out, "{}", diagnostic.to_json(sources))?,
+        }
     }
+
+    out.flush()
 }
 
 fn is_type<T: Fail>(err: &Error) -> bool {
@@ -39,10 +260,17 @@ fn is_syntax_err(err: &Error) -> bool {
     is_type::<ScanningError>(err) || is_type::<ParsingError>(err) || is_type::<ResolutionError>(err)
 }
 
+fn runtime_error(err: &Error) -> Option<&RuntimeError> {
+    err.downcast_ref::<RuntimeError>().or_else(|| {
+        err.downcast_ref::<Traceback>()
+            .map(|traceback| &traceback.error)
+    })
+}
+
 fn error_type(err: &Error) -> &'static str {
     if is_syntax_err(err) {
         "SyntaxError"
-    } else if let Some(err) = err.downcast_ref::<RuntimeError>() {
+    } else if let Some(err) = runtime_error(err) {
         match err {
             RuntimeError::ExpectedType(_, _, _) => "TypeError",
             _ => "RuntimeError",
@@ -55,193 +283,442 @@ fn error_type(err: &Error) -> &'static str {
 pub fn exit_code(err: &Error) -> i32 {
     if is_syntax_err(err) {
         65
-    } else if is_type::<RuntimeError>(err) {
+    } else if runtime_error(err).is_some() {
         70
     } else {
         1
     }
 }
 
-impl Display for ScanningError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+impl ScanningError {
+    /// The stable code of the error, or `None` for `Multiple`.
+    pub fn code(&self) -> Option<&'static str> {
         use ScanningError::*;
         match self {
-            UnrecognizedCharacter(character, loc) => {
-                write!(f, "[{}] Unrecognized character '{}'", loc, character)
-            }
-            UnterminatedString(loc) => write!(f, "[{}] Unterminated string", loc),
-            InvalidNumber(number, loc) => write!(f, "[{}] Invalid number {}", loc, number),
-            UnterminatedBlockComment(loc) => write!(f, "[{}] Unterminated block comment", loc),
-            Multiple(errors) => {
-                let error_string: String =
-                    errors.iter().map(|error| format!("\n{}", error)).collect();
-                write!(f, "Multiple errors encountered{}", error_string)
+            UnrecognizedCharacter(_, _) => Some("E0001"),
+            UnterminatedString(_) => Some("E0002"),
+            InvalidNumber(_, _) => Some("E0003"),
+            UnterminatedBlockComment(_) => Some("E0004"),
+            Multiple(_) => None,
+        }
+    }
+
+    /// Where the error happened, or `None` for `Multiple`.
+    pub fn loc(&self) -> Option<Loc> {
+        use ScanningError::*;
+        match self {
+            UnrecognizedCharacter(_, loc)
+            | UnterminatedString(loc)
+            | InvalidNumber(_, loc)
+            | UnterminatedBlockComment(loc) => Some(*loc),
+            Multiple(_) => None,
+        }
+    }
+
+    /// The error message, without its location.
+    pub fn message(&self) -> String {
+        use ScanningError::*;
+        match self {
+            UnrecognizedCharacter(character, _) => {
+                format!("Unrecognized character '{}'", character)
             }
+            UnterminatedString(_) => String::from("Unterminated string"),
+            InvalidNumber(number, _) => format!("Invalid number {}", number),
+            UnterminatedBlockComment(_) => String::from("Unterminated block comment"),
+            Multiple(_) => String::from("Multiple errors encountered"),
         }
     }
 }
 
-impl Display for ParsingError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+impl ParsingError {
+    /// The stable code of the error, or `None` for `Multiple`.
+    pub fn code(&self) -> Option<&'static str> {
+        use ParsingError::*;
+        match self {
+            ExpectedExpression(_, _) => Some("E0101"),
+            ExpectedOpenParen(_, _, _) => Some("E0102"),
+            ExpectedCloseParen(_, _, _) => Some("E0103"),
+            ExpectedOpenBrace(_, _, _) => Some("E0104"),
+            ExpectedCloseBrace(_, _, _) => Some("E0105"),
+            ExpectedCloseBracket(_, _, _) => Some("E0106"),
+            ExpectedColon(_, _) => Some("E0107"),
+            ExpectedMapColon(_, _) => Some("E0108"),
+            ExpectedSemicolon(_, _, _) => Some("E0109"),
+            ExpectedDot(_, _, _) => Some("E0110"),
+            ExpectedName(_, _, _) => Some("E0111"),
+            ExpectedCatchOrFinally(_, _) => Some("E0112"),
+            ExpectedKeyword(_, _, _, _) => Some("E0113"),
+            ExpectedModulePath(_, _) => Some("E0114"),
+            InvalidAssignmentTarget(_) => Some("E0115"),
+            MaximumArgumentsExceeded(_, _) => Some("E0116"),
+            MaximumNestingExceeded(_) => Some("E0117"),
+            MaximumChainExceeded(_) => Some("E0118"),
+            Multiple(_) => None,
+        }
+    }
+
+    /// Where the error happened, or `None` for `Multiple`.
+    pub fn loc(&self) -> Option<Loc> {
+        use ParsingError::*;
+        match self {
+            ExpectedExpression(loc, _)
+            | ExpectedOpenParen(loc, _, _)
+            | ExpectedCloseParen(loc, _, _)
+            | ExpectedOpenBrace(loc, _, _)
+            | ExpectedCloseBrace(loc, _, _)
+            | ExpectedCloseBracket(loc, _, _)
+            | ExpectedColon(loc, _)
+            | ExpectedMapColon(loc, _)
+            | ExpectedSemicolon(loc, _, _)
+            | ExpectedDot(loc, _, _)
+            | ExpectedName(loc, _, _)
+            | ExpectedCatchOrFinally(loc, _)
+            | ExpectedKeyword(loc, _, _, _)
+            | ExpectedModulePath(loc, _)
+            | InvalidAssignmentTarget(loc)
+            | MaximumArgumentsExceeded(loc, _)
+            | MaximumNestingExceeded(loc)
+            | MaximumChainExceeded(loc) => Some(*loc),
+            Multiple(_) => None,
+        }
+    }
+
+    /// The error message, without its location.
+    pub fn message(&self) -> String {
         use ParsingError::*;
         match self {
-            ExpectedExpression(loc, got) => write!(f, "[{}] Expected expression. Got {}", loc, got),
-            ExpectedOpenParen(loc, after, got) => {
-                write!(f, "[{}] Expected '(' after {}. Got {}", loc, after, got)
+            ExpectedExpression(_, got) => format!("Expected expression. Got {}", got),
+            ExpectedOpenParen(_, after, got) => {
+                format!("Expected '(' after {}. Got {}", after, got)
             }
-            ExpectedCloseParen(loc, after, got) => {
-                write!(f, "[{}] Expected ')' after {}. Got {}", loc, after, got)
+            ExpectedCloseParen(_, after, got) => {
+                format!("Expected ')' after {}. Got {}", after, got)
             }
-            ExpectedOpenBrace(loc, before, got) => {
-                write!(f, "[{}] Expected '{{' before {}. Got {}", loc, before, got)
+            ExpectedOpenBrace(_, before, got) => {
+                format!("Expected '{{' before {}. Got {}", before, got)
             }
-            ExpectedCloseBrace(loc, after, got) => {
-                write!(f, "[{}] Expected '}}' after {}. Got {}", loc, after, got)
+            ExpectedCloseBrace(_, after, got) => {
+                format!("Expected '}}' after {}. Got {}", after, got)
             }
-            ExpectedCloseBracket(loc, after, got) => {
-                write!(f, "[{}] Expected ']' after {}. Got {}", loc, after, got)
+            ExpectedCloseBracket(_, after, got) => {
+                format!("Expected ']' after {}. Got {}", after, got)
             }
-            ExpectedColon(loc, got) => write!(
-                f,
-                "[{}] Expected ':' for conditional expression. Got {}",
-                loc, got
-            ),
-            ExpectedSemicolon(loc, after, got) => {
-                write!(f, "[{}] Expected ';' after {}. Got {}", loc, after, got)
+            ExpectedColon(_, got) => {
+                format!("Expected ':' for conditional expression. Got {}", got)
             }
-            ExpectedDot(loc, after, got) => {
-                write!(f, "[{}] Expected '.' after {}. Got {}", loc, after, got)
+            ExpectedMapColon(_, got) => format!("Expected ':' after map key. Got {}", got),
+            ExpectedSemicolon(_, after, got) => {
+                format!("Expected ';' after {}. Got {}", after, got)
             }
-            ExpectedName(loc, kind, got) => {
-                write!(f, "[{}] Expected {} name. Got {}", loc, kind, got)
+            ExpectedDot(_, after, got) => format!("Expected '.' after {}. Got {}", after, got),
+            ExpectedName(_, kind, got) => format!("Expected {} name. Got {}", kind, got),
+            ExpectedKeyword(_, keyword, after, got) => {
+                format!("Expected '{}' after {}. Got {}", keyword, after, got)
             }
-            InvalidAssignmentTarget(loc) => write!(f, "[{}] Invalid assignment target", loc),
-            MaximumArgumentsExceeded(loc, kind) => {
-                write!(f, "[{}] Cannot have more than 255 {}", loc, kind)
+            ExpectedModulePath(_, got) => format!("Expected module path string. Got {}", got),
+            ExpectedCatchOrFinally(_, got) => {
+                format!("Expected 'catch' or 'finally' after try body. Got {}", got)
             }
-            Multiple(errors) => {
-                let error_string: String =
-                    errors.iter().map(|error| format!("\n{}", error)).collect();
-                write!(f, "Multiple errors encountered{}", error_string)
+            InvalidAssignmentTarget(_) => String::from("Invalid assignment target"),
+            MaximumArgumentsExceeded(_, kind) => format!("Cannot have more than 255 {}", kind),
+            MaximumNestingExceeded(_) => {
+                format!("Cannot nest more than {} levels deep", MAX_NESTING_DEPTH)
             }
+            MaximumChainExceeded(_) => {
+                format!("Cannot chain more than {} operators", MAX_CHAIN_LENGTH)
+            }
+            Multiple(_) => String::from("Multiple errors encountered"),
         }
     }
 }
 
-impl Display for RuntimeError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+impl RuntimeError {
+    /// The stable code of the error.
+    pub fn code(&self) -> &'static str {
         use RuntimeError::*;
         match self {
-            Generic(loc, message) => write!(f, "[{}] {}", loc, message),
-            UnsupportedOperand(loc, op, val_type) => write!(
-                f,
-                "[{}] Unsupported operand for {}: '{}'",
-                loc, op, val_type
-            ),
-            UnsupportedOperands(loc, op, left_type, right_type) => write!(
-                f,
-                "[{}] Unsupported operands for {}: '{}' and '{}'",
-                loc, op, left_type, right_type
-            ),
-            DivisionByZero(loc) => write!(f, "[{}] Division or modulo by zero", loc),
-            UndefinedVariable(loc, name) => write!(f, "[{}] Undefined variable '{}'", loc, name),
-            NotACallable(loc, val_type) => {
-                write!(f, "[{}] Type '{}' is not callable", loc, val_type)
-            }
-            MismatchingArity(loc, expected, got) => write!(
-                f,
-                "[{}] Expected {} arguments but got {}",
-                loc, expected, got
+            Generic(_, _) => "E0301",
+            UnsupportedOperand(_, _, _) => "E0302",
+            UnsupportedOperands(_, _, _, _) => "E0303",
+            DivisionByZero(_) => "E0304",
+            UndefinedVariable(_, _) => "E0305",
+            NotACallable(_, _) => "E0306",
+            MismatchingArity(_, _, _) => "E0307",
+            NoProperties(_, _) => "E0308",
+            UndefinedProperty(_, _) => "E0309",
+            NoFields(_, _) => "E0310",
+            SuperclassIsNotClass(_, _) => "E0311",
+            ExpectedType(_, _, _) => "E0312",
+            IndexOutOfBounds(_, _, _) => "E0313",
+            NotAScriptable(_, _) => "E0314",
+            ArrayIndexNotInteger(_, _) => "E0315",
+            InvalidMapKey(_, _) => "E0316",
+            KeyNotFound(_, _) => "E0317",
+            StringIndexNotInteger(_, _) => "E0318",
+            ImmutableString(_) => "E0319",
+            NotIterable(_, _) => "E0320",
+            UncaughtException(_, _) => "E0321",
+            ImportError(_, _, _) => "E0322",
+            UndefinedExport(_, _, _) => "E0323",
+            StackOverflow(_, _) => "E0324",
+        }
+    }
+
+    /// The error message, without its location.
+    pub fn message(&self) -> String {
+        use RuntimeError::*;
+        match self {
+            Generic(_, message) => message.clone(),
+            UnsupportedOperand(_, op, val_type) => {
+                format!("Unsupported operand for {}: '{}'", op, val_type)
+            }
+            UnsupportedOperands(_, op, left_type, right_type) => format!(
+                "Unsupported operands for {}: '{}' and '{}'",
+                op, left_type, right_type
             ),
-            NoProperties(loc, val_type) => {
-                write!(f, "[{}] Type '{}' doesn't have properties", loc, val_type)
+            DivisionByZero(_) => String::from("Division or modulo by zero"),
+            UndefinedVariable(_, name) => format!("Undefined variable '{}'", name),
+            NotACallable(_, val_type) => format!("Type '{}' is not callable", val_type),
+            MismatchingArity(_, expected, got) => {
+                format!("Expected {} arguments but got {}", expected, got)
             }
-            UndefinedProperty(loc, name) => write!(f, "[{}] Undefined property '{}'", loc, name),
-            NoFields(loc, val_type) => {
-                write!(f, "[{}] Type '{}' doesn't have fields", loc, val_type)
+            NoProperties(_, val_type) => format!("Type '{}' doesn't have properties", val_type),
+            UndefinedProperty(_, name) => format!("Undefined property '{}'", name),
+            NoFields(_, val_type) => format!("Type '{}' doesn't have fields", val_type),
+            ExpectedType(_, expected, got) => {
+                format!("Expected type '{}'. Got '{}'", expected, got)
             }
-            ExpectedType(loc, expected, got) => {
-                write!(f, "[{}] Expected type '{}'. Got '{}'", loc, expected, got)
+            SuperclassIsNotClass(_, val_type) => {
+                format!("Superclass must be a class. Got '{}'", val_type)
             }
-            SuperclassIsNotClass(loc, val_type) => write!(
-                f,
-                "[{}] Superclass must be a class. Got '{}'",
-                loc, val_type
-            ),
-            IndexOutOfBounds(loc, index, size) => {
-                write!(f, "[{}] Index {} out of bounds. Size {}", loc, index, size)
+            IndexOutOfBounds(_, index, size) => {
+                format!("Index {} out of bounds. Size {}", index, size)
             }
-            NotAScriptable(loc, val_type) => {
-                write!(f, "[{}] Type '{}' is not scriptable", loc, val_type)
+            NotAScriptable(_, val_type) => format!("Type '{}' is not scriptable", val_type),
+            ArrayIndexNotInteger(_, val_type) => {
+                format!("Array indices must be integers. Got '{}'", val_type)
             }
-            ArrayIndexNotInteger(loc, val_type) => write!(
-                f,
-                "[{}] Array indices must be integers. Got '{}'",
-                loc, val_type
-            ),
+            InvalidMapKey(_, val_type) => {
+                format!("Type '{}' cannot be used as a map key", val_type)
+            }
+            StringIndexNotInteger(_, val_type) => {
+                format!("String indices must be integers. Got '{}'", val_type)
+            }
+            ImmutableString(_) => String::from("Strings are immutable"),
+            ImportError(_, path, reason) => format!("Could not import '{}': {}", path, reason),
+            UndefinedExport(_, name, path) => {
+                format!("Module '{}' has no export named '{}'", path, name)
+            }
+            StackOverflow(_, depth) => format!("Maximum call depth of {} exceeded", depth),
+            UncaughtException(_, exception) => format!("Uncaught exception: {}", exception),
+            NotIterable(_, val_type) => format!("Type '{}' is not iterable", val_type),
+            KeyNotFound(_, key) => format!("Key {} not found in map", key),
         }
     }
 }
 
-impl Display for ResolutionError {
+impl Display for Traceback {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Display::fmt(&self.error, f)
+    }
+}
+
+impl Fail for Traceback {}
+
+impl ResolutionError {
+    /// The stable code of the error, or `None` for `Multiple`.
+    pub fn code(&self) -> Option<&'static str> {
         use ResolutionError::*;
         match self {
-            VarInInitalizer(loc) => write!(
-                f,
-                "[{}] Cannot read local variable in its own initializer",
-                loc
-            ),
-            VarAlreadyInScope(loc, name) => write!(
-                f,
-                "[{}] Variable '{}' already declared in this scope",
-                loc, name
-            ),
-            DuplicateArgumentName(loc, name) => write!(
-                f,
-                "[{}] Duplicate argument '{}' in function definition",
-                loc, name
-            ),
-            DuplicateMethod(loc, class_name, mtype, name) => write!(
-                f,
-                "[{}] Class '{}' already has {} called '{}'",
-                loc, class_name, mtype, name
-            ),
-            ReturnOutsideFun(loc) => write!(f, "[{}] Cannot return from top-level code", loc),
-            ThisOutsideClass(loc) => write!(f, "[{}] Cannot use 'this' outside of a class", loc),
-            ReturnInInitializer(loc) => {
-                write!(f, "[{}] Cannot return a value from an initializer", loc)
-            }
-            ThisInStaticMethod(loc) => write!(f, "[{}] Cannot use 'this' in a static method", loc),
-            ClassInheritsItself(loc, name) => write!(
-                f,
-                "[{}] [class {}] A class cannot inherit from itself",
-                loc, name
-            ),
-            SuperOutsideClass(loc) => write!(f, "[{}] Cannot use 'super' outside of a class", loc),
-            SuperNoInSubclass(loc) => write!(
-                f,
-                "[{}] Cannot use 'super' in a class with no superclass",
-                loc
+            VarInInitalizer(_) => Some("E0201"),
+            VarAlreadyInScope(_, _, _) => Some("E0202"),
+            DuplicateArgumentName(_, _, _) => Some("E0203"),
+            DuplicateMethod(_, _, _, _) => Some("E0204"),
+            ReturnOutsideFun(_) => Some("E0205"),
+            ThisOutsideClass(_) => Some("E0206"),
+            ReturnInInitializer(_) => Some("E0207"),
+            ThisInStaticMethod(_) => Some("E0208"),
+            ClassInheritsItself(_, _) => Some("E0209"),
+            SuperOutsideClass(_) => Some("E0210"),
+            SuperNoInSubclass(_) => Some("E0211"),
+            BreakOutsideLoop(_) => Some("E0212"),
+            ContinueOutsideLoop(_) => Some("E0213"),
+            DeniedLint(warn) => Some(warn.code()),
+            Multiple(_) => None,
+        }
+    }
+
+    /// Where the error happened, or `None` for `Multiple`.
+    pub fn loc(&self) -> Option<Loc> {
+        use ResolutionError::*;
+        match self {
+            VarInInitalizer(loc)
+            | VarAlreadyInScope(loc, _, _)
+            | DuplicateArgumentName(loc, _, _)
+            | DuplicateMethod(loc, _, _, _)
+            | ReturnOutsideFun(loc)
+            | ThisOutsideClass(loc)
+            | ReturnInInitializer(loc)
+            | ThisInStaticMethod(loc)
+            | ClassInheritsItself(loc, _)
+            | SuperOutsideClass(loc)
+            | SuperNoInSubclass(loc)
+            | BreakOutsideLoop(loc)
+            | ContinueOutsideLoop(loc) => Some(*loc),
+            DeniedLint(warn) => Some(warn.loc()),
+            Multiple(_) => None,
+        }
+    }
+
+    /// The error message, without its location.
+    pub fn message(&self) -> String {
+        use ResolutionError::*;
+        match self {
+            VarInInitalizer(_) => String::from("Cannot read local variable in its own initializer"),
+            VarAlreadyInScope(_, name, _) => {
+                format!("Variable '{}' already declared in this scope", name)
+            }
+            DuplicateArgumentName(_, name, _) => {
+                format!("Duplicate argument '{}' in function definition", name)
+            }
+            DuplicateMethod(_, class_name, mtype, name) => format!(
+                "Class '{}' already has {} called '{}'",
+                class_name, mtype, name
             ),
-            BreakOutsideLoop(loc) => write!(f, "[{}] Cannot use 'break' outside of a loop", loc),
-            ContinueOutsideLoop(loc) => {
-                write!(f, "[{}] Cannot use 'continue' outside of a loop", loc)
+            ReturnOutsideFun(_) => String::from("Cannot return from top-level code"),
+            ThisOutsideClass(_) => String::from("Cannot use 'this' outside of a class"),
+            ReturnInInitializer(_) => String::from("Cannot return a value from an initializer"),
+            ThisInStaticMethod(_) => String::from("Cannot use 'this' in a static method"),
+            ClassInheritsItself(_, name) => {
+                format!("[class {}] A class cannot inherit from itself", name)
             }
-            Multiple(errors) => {
-                let error_string: String =
-                    errors.iter().map(|error| format!("\n{}", error)).collect();
-                write!(f, "Multiple errors encountered{}", error_string)
+            SuperOutsideClass(_) => String::from("Cannot use 'super' outside of a class"),
+            SuperNoInSubclass(_) => {
+                String::from("Cannot use 'super' in a class with no superclass")
             }
+            BreakOutsideLoop(_) => String::from("Cannot use 'break' outside of a loop"),
+            ContinueOutsideLoop(_) => String::from("Cannot use 'continue' outside of a loop"),
+            DeniedLint(warn) => warn.message(),
+            Multiple(_) => String::from("Multiple errors encountered"),
         }
     }
 }
 
-impl Display for Warning {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+impl Warning {
+    /// The stable code of the warning.
+    pub fn code(&self) -> &'static str {
+        use Warning::*;
+        match self {
+            UnusedVariable(_, _) => "W0001",
+            UnusedParameter(_, _) => "W0002",
+            UnusedFunction(_, _) => "W0003",
+            UnreachableCode(_, _) => "W0004",
+            ShadowedVariable(_, _, _) => "W0005",
+            AssignmentInCondition(_) => "W0006",
+            SelfComparison(_, _) => "W0007",
+            UnassignedVariable(_, _) => "W0008",
+            UndefinedVariable(_, _, _) => "W0009",
+            ArityMismatch(_, _, _, _, _) => "W0010",
+        }
+    }
+
+    pub fn lint(&self) -> Lint {
         use Warning::*;
         match self {
-            UnusedVariable(loc, name) => write!(f, "[{}] Unused variable '{}'", loc, name),
+            UnusedVariable(_, _) => Lint::UnusedVariable,
+            UnusedParameter(_, _) => Lint::UnusedParameter,
+            UnusedFunction(_, _) => Lint::UnusedFunction,
+            UnreachableCode(_, _) => Lint::UnreachableCode,
+            ShadowedVariable(_, _, _) => Lint::ShadowedVariable,
+            AssignmentInCondition(_) => Lint::AssignmentInCondition,
+            SelfComparison(_, _) => Lint::SelfComparison,
+            UnassignedVariable(_, _) => Lint::UnassignedVariable,
+            UndefinedVariable(_, _, _) => Lint::UndefinedVariable,
+            ArityMismatch(_, _, _, _, _) => Lint::ArityMismatch,
         }
     }
+
+    pub fn loc(&self) -> Loc {
+        use Warning::*;
+        match self {
+            UnusedVariable(loc, _)
+            | UnusedParameter(loc, _)
+            | UnusedFunction(loc, _)
+            | UnreachableCode(loc, _)
+            | ShadowedVariable(loc, _, _)
+            | AssignmentInCondition(loc)
+            | SelfComparison(loc, _)
+            | UnassignedVariable(loc, _)
+            | UndefinedVariable(loc, _, _)
+            | ArityMismatch(loc, _, _, _, _) => *loc,
+        }
+    }
+
+    /// The warning message, without its location.
+    pub fn message(&self) -> String {
+        use Warning::*;
+        match self {
+            UnusedVariable(_, name) => format!("Unused variable '{}'", name),
+            UnusedParameter(_, name) => format!("Unused parameter '{}'", name),
+            UnusedFunction(_, name) => format!("Unused function '{}'", name),
+            UnreachableCode(_, _) => String::from("Unreachable code"),
+            ShadowedVariable(_, name, _) => {
+                format!("Variable '{}' shadows a variable of an outer scope", name)
+            }
+            AssignmentInCondition(_) => String::from("Assignment used as a condition"),
+            SelfComparison(_, name) => format!("Comparison of '{}' with itself", name),
+            UnassignedVariable(_, name) => {
+                format!("Variable '{}' is read but never assigned", name)
+            }
+            UndefinedVariable(_, name, _) => format!("Undefined variable '{}'", name),
+            ArityMismatch(_, name, expected, got, _) => {
+                format!(
+                    "'{}' expects {} but got {}",
+                    name,
+                    expected.arguments(),
+                    got
+                )
+            }
+        }
+    }
+}
+
+/// Displays an error as `[line:column] message`, or each of its errors on
+/// their own line for `Multiple`.
+macro_rules! display_located_error {
+    ($error:ident) => {
+        impl Display for $error {
+            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+                match self {
+                    $error::Multiple(errors) => {
+                        let error_string: String =
+                            errors.iter().map(|error| format!("\n{}", error)).collect();
+                        write!(f, "Multiple errors encountered{}", error_string)
+                    }
+                    error => write!(
+                        f,
+                        "[{}] {}",
+                        error.loc().unwrap_or_default(),
+                        error.message()
+                    ),
+                }
+            }
+        }
+    };
+}
+
+display_located_error!(ScanningError);
+display_located_error!(ParsingError);
+display_located_error!(ResolutionError);
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "[{}] {}", self.loc(), self.message())
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "[{}] {}", self.loc(), self.message())
+    }
 }
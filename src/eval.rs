@@ -2,27 +2,101 @@
 mod tests;
 
 use crate::array::{Array, ArrayClass};
-use crate::callable::{define_native_functions, Function, LoxCallable};
-use crate::class::{define_native_classes, Class, ClassInstance, GenericClass, LoxClass};
-use crate::constants::{INIT_METHOD, SUPER_KEYWORD, THIS_KEYWORD};
-use crate::expr::{BinOp, Expr, LitExpr, LogOp, Param, UnOp, Visitor as ExprVisitor};
+use crate::callable::{
+    define_native_functions, Arity, Function, HostFunction, LoxCallable, NativeFunction,
+};
+use crate::class::{Class, ClassInstance, GenericClass, LoxClass};
+use crate::constants::{
+    ARITHMETIC_ERROR, ERROR_CLASS, HAS_NEXT_METHOD, IMPORT_ERROR, INDEX_ERROR, INIT_METHOD,
+    ITERATOR_METHOD, KEY_ERROR, MODULE_CLASS, NAME_ERROR, NEXT_METHOD, RECURSION_ERROR,
+    SUPER_KEYWORD, THIS_KEYWORD, TYPE_ERROR,
+};
+use crate::diagnostic::ErrorFormat;
+use crate::exception::{self, ErrorClasses};
+use crate::expr::{
+    BinOp, Expr, LitExpr, LogOp, Param, Resolution, ResolvedLocal, UnOp, Visitor as ExprVisitor,
+};
+use crate::gc::{self, Trace, Tracer};
+use crate::host::{HostClass, NativeClass};
+use crate::lint::LintLevels;
 use crate::location::Loc;
+use crate::map::{Map, MapClass, MapKey};
+use crate::module::{self, ModuleRegistry};
 use crate::scriptable::LoxScriptable;
-use crate::stmt::{FunctionKind, Stmt, StmtKind, Visitor as StmtVisitor};
+use crate::source::SourceMap;
+use crate::stmt::{CatchClause, FunctionKind, ImportKind, Stmt, StmtKind, Visitor as StmtVisitor};
+use crate::string::{self, StringClass};
 use crate::value::Value;
+use crate::vm::{self, Vm};
+use indexmap::IndexMap;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::ops::Not;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 pub struct Interpreter {
     env: Option<Env>,
     pub globals: GlobalEnv,
-    locals: HashMap<(String, Loc), ResolvedLocal>,
     pub natives: Natives,
+    pub modules: ModuleRegistry,
+    pub vm: Vm,
+    pub backend: Backend,
+    pub max_call_depth: usize,
+    /// In bytes, see `DEFAULT_MAX_STACK_SIZE`.
+    pub max_stack_size: usize,
+    /// The address of the native stack where the outermost call started.
+    stack_base: usize,
+    /// Where `print` statements write. Buffered, so it must be flushed once
+    /// a script is done.
+    pub output: Box<dyn Write>,
+    /// Where warnings and errors are reported.
+    pub diagnostics: Box<dyn Write>,
+    pub error_format: ErrorFormat,
+    /// Whether human readable diagnostics are colored, which they are by
+    /// default when stderr is a terminal.
+    pub colored_diagnostics: bool,
+    /// How the resolver reports each kind of warning.
+    pub lint_levels: LintLevels,
+    /// The code run so far, which warnings and errors are shown against.
+    pub sources: SourceMap,
+    call_stack: Vec<StackFrame>,
+    traceback: Option<Vec<StackFrame>>,
+    thrown: Option<Value>,
+}
+
+/// A call in progress: the function being run and where it was called from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackFrame {
+    pub name: String,
+    pub loc: Loc,
+}
+
+/// A runtime error along with the calls that were in progress when it was
+/// raised, outermost first.
+#[derive(Debug, PartialEq)]
+pub struct Traceback {
+    pub error: RuntimeError,
+    pub frames: Vec<StackFrame>,
+}
+
+/// Selects how scripts are run: walking the AST directly, or compiling it
+/// to bytecode for the stack VM first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    TreeWalker,
+    Vm,
 }
 
 pub struct Natives {
     pub array_class: Rc<ArrayClass>,
+    pub map_class: Rc<MapClass>,
+    pub string_class: Rc<StringClass>,
+    pub error_classes: ErrorClasses,
+    pub module_class: Rc<GenericClass>,
+    pub host_functions: Vec<Rc<HostFunction>>,
+    pub host_classes: Vec<Rc<NativeClass>>,
 }
 
 #[derive(Debug)]
@@ -39,12 +113,6 @@ pub struct Environ {
 pub type Env = Rc<RefCell<Environ>>;
 pub type GlobalEnv = Rc<RefCell<GlobalEnviron>>;
 
-#[derive(Copy, Clone)]
-struct ResolvedLocal {
-    depth: usize,
-    index: usize,
-}
-
 #[derive(Debug, PartialEq, Fail)]
 pub enum RuntimeError {
     Generic(Loc, String),
@@ -53,7 +121,7 @@ pub enum RuntimeError {
     DivisionByZero(Loc),
     UndefinedVariable(Loc, String),
     NotACallable(Loc, String),
-    MismatchingArity(Loc, usize, usize),
+    MismatchingArity(Loc, Arity, usize),
     NoProperties(Loc, String),
     UndefinedProperty(Loc, String),
     NoFields(Loc, String),
@@ -62,6 +130,15 @@ pub enum RuntimeError {
     IndexOutOfBounds(Loc, i64, usize),
     NotAScriptable(Loc, String),
     ArrayIndexNotInteger(Loc, String),
+    InvalidMapKey(Loc, String),
+    KeyNotFound(Loc, String),
+    StringIndexNotInteger(Loc, String),
+    ImmutableString(Loc),
+    NotIterable(Loc, String),
+    UncaughtException(Loc, String),
+    ImportError(Loc, String, String),
+    UndefinedExport(Loc, String, String),
+    StackOverflow(Loc, usize),
 }
 
 #[derive(Debug)]
@@ -70,19 +147,49 @@ pub enum RuntimeInterrupt {
     Return(Value),
     Break,
     Continue,
+    Throw(Value, Loc),
 }
 
+/// Deep enough for reasonable recursion. The tree walker may hit
+/// `DEFAULT_MAX_STACK_SIZE` first, as it recurses on the native stack.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// How much of the native stack the tree walker uses for calls before
+/// raising a `RecursionError`, measured from where the script entered the
+/// interpreter. It fits the 2 MiB of a thread spawned by `std::thread`.
+pub const DEFAULT_MAX_STACK_SIZE: usize = 1536 * 1024;
+
 pub type ValueRes = Result<Value, RuntimeError>;
 type ExecuteRes = Result<(), RuntimeInterrupt>;
 
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
 impl Interpreter {
     pub fn new() -> Self {
         let globals = GlobalEnviron::new().into();
         let mut inter = Interpreter {
             env: None,
             globals,
-            locals: HashMap::new(),
             natives: Self::create_natives(),
+            modules: ModuleRegistry::new(),
+            vm: Vm::new(),
+            backend: Backend::TreeWalker,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_stack_size: DEFAULT_MAX_STACK_SIZE,
+            stack_base: 0,
+            output: Box::new(BufWriter::new(io::stdout())),
+            diagnostics: Box::new(BufWriter::new(io::stderr())),
+            error_format: ErrorFormat::Human,
+            colored_diagnostics: io::stderr().is_terminal(),
+            lint_levels: LintLevels::default(),
+            sources: SourceMap::default(),
+            call_stack: Vec::new(),
+            traceback: None,
+            thrown: None,
         };
 
         inter.populate_globals();
@@ -92,19 +199,82 @@ impl Interpreter {
     fn create_natives() -> Natives {
         Natives {
             array_class: Rc::new(ArrayClass::new()),
+            map_class: Rc::new(MapClass::new()),
+            string_class: Rc::new(StringClass::new()),
+            error_classes: ErrorClasses::new(),
+            module_class: Rc::new(GenericClass::new(
+                MODULE_CLASS,
+                None,
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+            )),
+            host_functions: Vec::new(),
+            host_classes: Vec::new(),
         }
     }
 
+    /// Makes a Rust function callable from scripts as a global, in the main
+    /// script and in every module loaded afterwards.
+    pub fn register_fn<F>(&mut self, name: &str, arity: Arity, fun: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Value>, Loc) -> ValueRes + 'static,
+    {
+        let host = Rc::new(HostFunction::new(name, arity, fun));
+        self.natives.host_functions.push(Rc::clone(&host));
+        let function = NativeFunction::Host(host);
+        self.globals.borrow_mut().define(name, function.into());
+    }
+
+    pub fn print(&mut self, val: &Value, loc: Loc) -> Result<(), RuntimeError> {
+        writeln!(self.output, "{}", val).map_err(|err| {
+            let message = format!("Could not write output: {}", err);
+            RuntimeError::generic(loc, &message)
+        })
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get(name, Loc::default()).ok()
+    }
+
+    pub fn set_global(&mut self, name: &str, val: Value) {
+        self.globals.borrow_mut().define(name, val);
+    }
+
+    /// Calls a function from the host, with the same arity checks as a call
+    /// made by a script.
+    pub fn call(&mut self, callee: Value, args: Vec<Value>) -> ValueRes {
+        self.traceback = None;
+        self.call_value(callee, args, Loc::default())
+    }
+
+    /// Makes a class implemented in Rust available to scripts as a global, in
+    /// the main script and in every module loaded afterwards.
+    pub fn register_class<C: HostClass + 'static>(&mut self, class: C) {
+        let class = Rc::new(NativeClass::new(Box::new(class)));
+        self.natives.host_classes.push(Rc::clone(&class));
+        Self::define_class(&mut self.globals.borrow_mut(), &class);
+    }
+
     pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<(), RuntimeError> {
+        self.traceback = None;
+        if self.backend == Backend::Vm {
+            let globals = Rc::clone(&self.globals);
+            return vm::run_script(self, stmts, &globals);
+        }
+
         for stmt in stmts {
-            self.execute(stmt).map_err(RuntimeInterrupt::expect_error)?
+            self.execute(stmt).map_err(RuntimeInterrupt::into_error)?
         }
 
         Ok(())
     }
 
     pub fn evaluate(&mut self, expr: &Expr) -> ValueRes {
-        expr.accept(self)
+        match self.backend {
+            Backend::TreeWalker => expr.accept(self),
+            Backend::Vm => vm::evaluate(self, expr),
+        }
     }
 
     fn execute(&mut self, stmt: &Stmt) -> ExecuteRes {
@@ -129,17 +299,13 @@ impl Interpreter {
         Ok(())
     }
 
-    pub fn resolve(&mut self, name: &str, loc: Loc, depth: usize, index: usize) {
-        self.locals
-            .insert((String::from(name), loc), ResolvedLocal::new(depth, index));
-    }
-
-    fn get_resolved_local(&self, name: &str, loc: Loc) -> Option<ResolvedLocal> {
-        self.locals.get(&(name.to_string(), loc)).cloned()
-    }
-
-    fn look_up_variable(&self, name: &str, loc: Loc) -> Result<Value, RuntimeError> {
-        if let Some(ResolvedLocal { depth, index }) = self.get_resolved_local(name, loc) {
+    fn look_up_variable(
+        &self,
+        name: &str,
+        local: Option<ResolvedLocal>,
+        loc: Loc,
+    ) -> Result<Value, RuntimeError> {
+        if let Some(ResolvedLocal { depth, index }) = local {
             Ok(self.local_env().borrow().get_at(depth, index))
         } else {
             self.globals.borrow().get(name, loc)
@@ -163,8 +329,14 @@ impl Interpreter {
         }
     }
 
-    fn assign(&mut self, name: &str, val: Value, loc: Loc) -> Result<(), RuntimeError> {
-        if let Some(ResolvedLocal { depth, index }) = self.get_resolved_local(name, loc) {
+    fn assign(
+        &mut self,
+        name: &str,
+        val: Value,
+        local: Option<ResolvedLocal>,
+        loc: Loc,
+    ) -> Result<(), RuntimeError> {
+        if let Some(ResolvedLocal { depth, index }) = local {
             self.local_env().borrow_mut().assign_at(depth, index, val);
         } else {
             self.globals.borrow_mut().assign(name, val, loc)?;
@@ -173,6 +345,152 @@ impl Interpreter {
         Ok(())
     }
 
+    /// Runs `call` as a call to `name` from `loc`. The first failing call to
+    /// unwind records the call stack, so that the error can be reported
+    /// with the calls that led to it.
+    pub fn with_frame<F>(&mut self, name: &str, loc: Loc, call: F) -> ValueRes
+    where
+        F: FnOnce(&mut Self) -> ValueRes,
+    {
+        self.push_frame(name, loc)?;
+        let res = call(self);
+        if res.is_err() {
+            self.record_traceback();
+        }
+
+        self.pop_frame();
+        res
+    }
+
+    pub fn push_frame(&mut self, name: &str, loc: Loc) -> Result<(), RuntimeError> {
+        if self.call_stack.len() >= self.max_call_depth {
+            return Err(RuntimeError::StackOverflow(loc, self.max_call_depth));
+        }
+
+        // Deep calls can use up the native stack before reaching the maximum
+        // depth, as each one takes several frames of the tree walker
+        let marker = 0u8;
+        let address = &marker as *const u8 as usize;
+        if self.call_stack.is_empty() {
+            self.stack_base = address;
+        } else if self.stack_base.abs_diff(address) > self.max_stack_size {
+            return Err(RuntimeError::StackOverflow(loc, self.call_stack.len()));
+        }
+
+        self.call_stack.push(StackFrame::new(name, loc));
+        Ok(())
+    }
+
+    pub fn pop_frame(&mut self) {
+        self.call_stack.pop();
+    }
+
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    /// Drops the frames of the calls an error unwound through without going
+    /// back through `with_frame`.
+    pub fn unwind_frames(&mut self, depth: usize) {
+        self.call_stack.truncate(depth);
+    }
+
+    pub fn record_traceback(&mut self) {
+        if self.traceback.is_none() {
+            self.traceback = Some(self.call_stack.clone());
+        }
+    }
+
+    pub fn traceback(&mut self, error: RuntimeError) -> Traceback {
+        Traceback {
+            error,
+            frames: self.traceback.take().unwrap_or_default(),
+        }
+    }
+
+    /// Keeps a thrown value aside while it unwinds through a function call,
+    /// since `RuntimeError`s can only carry a description of it.
+    pub fn stash_exception(&mut self, val: Value, loc: Loc) -> RuntimeError {
+        let error = RuntimeError::uncaught_exception(loc, &val);
+        self.thrown = Some(val);
+        error
+    }
+
+    /// Turns an exception unwinding through a finally block back into a
+    /// thrown value, so that the block may catch exceptions of its own
+    /// before it is rethrown.
+    pub fn detach_exception(&mut self, interrupt: RuntimeInterrupt) -> RuntimeInterrupt {
+        match interrupt {
+            RuntimeInterrupt::Error(RuntimeError::UncaughtException(loc, _)) => {
+                let val = self
+                    .thrown
+                    .take()
+                    .expect("Expected a stashed exception to be set");
+                RuntimeInterrupt::Throw(val, loc)
+            }
+            interrupt => interrupt,
+        }
+    }
+
+    pub fn exception_value(&mut self, interrupt: RuntimeInterrupt) -> Value {
+        self.traceback = None;
+        match interrupt {
+            RuntimeInterrupt::Throw(val, _loc) => val,
+            RuntimeInterrupt::Error(RuntimeError::UncaughtException(..)) => self
+                .thrown
+                .take()
+                .expect("Expected a stashed exception to be set"),
+            RuntimeInterrupt::Error(error) => self.natives.error_classes.instance_from(&error),
+            interrupt => panic!(
+                "Expected interrupt to be an exception. Got: {:?}",
+                interrupt
+            ),
+        }
+    }
+
+    pub fn call_value(&mut self, callee: Value, args: Vec<Value>, loc: Loc) -> ValueRes {
+        if let Value::Callable(callable) = callee {
+            if callable.arity().accepts(args.len()) {
+                callable.call(self, args, loc)
+            } else {
+                Err(RuntimeError::mismatching_arity(
+                    loc,
+                    callable.arity(),
+                    args.len(),
+                ))
+            }
+        } else {
+            Err(RuntimeError::not_a_callable(loc, callee))
+        }
+    }
+
+    pub fn call_method(&mut self, obj: Value, name: &str, loc: Loc) -> ValueRes {
+        let val_type = obj.get_type();
+        let instance = obj
+            .into_instance(self)
+            .ok_or_else(|| RuntimeError::no_properties(loc, val_type))?;
+        let method = ClassInstance::get(self, &instance, name, loc)
+            .unwrap_or_else(|| Err(RuntimeError::undefined_property(loc, name)))?;
+        self.call_value(method, vec![], loc)
+    }
+
+    fn execute_for_in<F>(&mut self, body: &Stmt, mut next: F) -> ExecuteRes
+    where
+        F: FnMut(&mut Self) -> Result<Option<Value>, RuntimeError>,
+    {
+        while let Some(val) = next(self)? {
+            let env = Environ::with_enclosing(&self.env);
+            env.borrow_mut().define(val);
+            match self.execute_block(std::slice::from_ref(body), env) {
+                Ok(()) | Err(RuntimeInterrupt::Continue) => (),
+                Err(RuntimeInterrupt::Break) => break,
+                Err(interrupt) => return Err(interrupt),
+            }
+        }
+
+        Ok(())
+    }
+
     fn enclosing_env(&self) -> Option<Env> {
         self.env
             .as_ref()
@@ -180,10 +498,66 @@ impl Interpreter {
     }
 
     fn populate_globals(&mut self) {
-        let mut globals = self.globals.borrow_mut();
-        Self::define_class(&mut globals, &self.natives.array_class);
-        define_native_classes(&mut globals);
-        define_native_functions(&mut globals);
+        let globals = Rc::clone(&self.globals);
+        self.define_natives(&mut globals.borrow_mut());
+    }
+
+    fn define_natives(&self, globals: &mut GlobalEnviron) {
+        Self::define_class(globals, &self.natives.array_class);
+        Self::define_class(globals, &self.natives.map_class);
+        Self::define_class(globals, &self.natives.string_class);
+        for class in self.natives.error_classes.classes() {
+            Self::define_class(globals, class);
+        }
+        for class in &self.natives.host_classes {
+            Self::define_class(globals, class);
+        }
+        define_native_functions(globals, &self.natives.host_functions);
+    }
+
+    /// A global namespace holding only the builtins.
+    pub fn builtin_globals(&self) -> GlobalEnviron {
+        let mut globals = GlobalEnviron::new();
+        self.define_natives(&mut globals);
+        globals
+    }
+
+    /// Runs the statements of a module in a global namespace of its own, and
+    /// returns the module object holding the globals it defined.
+    pub fn run_module(&mut self, stmts: &[Stmt], path: PathBuf) -> ValueRes {
+        let module_globals = self.builtin_globals();
+        let builtins: HashSet<String> = module_globals.values.keys().cloned().collect();
+        let module_globals: GlobalEnv = module_globals.into();
+
+        let prev_globals = std::mem::replace(&mut self.globals, Rc::clone(&module_globals));
+        let prev_env = self.env.take();
+        let prev_path = self.modules.set_current_path(Some(path));
+
+        let res = match self.backend {
+            Backend::TreeWalker => stmts
+                .iter()
+                .try_for_each(|stmt| self.execute(stmt))
+                .map_err(|interrupt| match interrupt {
+                    RuntimeInterrupt::Throw(val, loc) => self.stash_exception(val, loc),
+                    interrupt => interrupt.expect_error(),
+                }),
+            Backend::Vm => vm::run_script(self, stmts, &module_globals),
+        };
+
+        self.globals = prev_globals;
+        self.env = prev_env;
+        self.modules.set_current_path(prev_path);
+        res?;
+
+        let exports = module_globals
+            .borrow()
+            .names()
+            .filter(|name| !builtins.contains(*name))
+            .cloned()
+            .collect();
+        let module =
+            ClassInstance::from_module(&self.natives.module_class, module_globals, exports);
+        Ok(Value::Instance(module.into()))
     }
 
     fn define_class<T>(globals: &mut GlobalEnviron, class: &Rc<T>)
@@ -207,6 +581,24 @@ impl GlobalEnviron {
         self.values.insert(String::from(name), val);
     }
 
+    /// The name of every global, along with its arity if it's callable.
+    pub fn signatures(&self) -> HashMap<String, Option<Arity>> {
+        self.values
+            .iter()
+            .map(|(name, val)| {
+                let arity = match val {
+                    Value::Callable(callable) => Some(callable.arity()),
+                    _ => None,
+                };
+                (name.clone(), arity)
+            })
+            .collect()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.values.keys()
+    }
+
     pub fn get(&self, name: &str, loc: Loc) -> Result<Value, RuntimeError> {
         if let Some(val) = self.values.get(name) {
             Ok(val.clone())
@@ -285,13 +677,37 @@ impl Into<GlobalEnv> for GlobalEnviron {
 
 impl Into<Env> for Environ {
     fn into(self) -> Env {
-        Rc::new(RefCell::new(self))
+        gc::alloc(RefCell::new(self))
+    }
+}
+
+impl Trace for RefCell<Environ> {
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        let env = match self.try_borrow() {
+            Ok(env) => env,
+            Err(_) => return false,
+        };
+        env.values.iter().for_each(|val| tracer.value(val));
+        if let Some(enclosing) = &env.enclosing {
+            tracer.rc(enclosing);
+        }
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut env) = self.try_borrow_mut() {
+            env.values.clear();
+            env.enclosing = None;
+        }
     }
 }
 
-impl ResolvedLocal {
-    pub fn new(depth: usize, index: usize) -> Self {
-        Self { depth, index }
+impl StackFrame {
+    pub fn new(name: &str, loc: Loc) -> Self {
+        Self {
+            name: String::from(name),
+            loc,
+        }
     }
 }
 
@@ -304,7 +720,7 @@ impl ExprVisitor<Value> for Interpreter {
 
     fn visit_function_expr(&mut self, params: &[Param], body: &[Stmt], _loc: Loc) -> ValueRes {
         let params: Vec<_> = params.iter().map(|p| p.kind.clone()).collect();
-        let function = Function::new_anon(params, body, &self.env);
+        let function = Function::new_anon(params, body, &self.env, &self.globals);
         Ok(function.into())
     }
 
@@ -317,7 +733,7 @@ impl ExprVisitor<Value> for Interpreter {
 
         Ok(match op {
             UnOp::Negate => val.negate(loc)?,
-            UnOp::Not => val.not(),
+            UnOp::Not => !val,
         })
     }
 
@@ -364,13 +780,19 @@ impl ExprVisitor<Value> for Interpreter {
         }
     }
 
-    fn visit_variable_expr(&mut self, name: &str, loc: Loc) -> ValueRes {
-        self.look_up_variable(name, loc)
+    fn visit_variable_expr(&mut self, name: &str, resolution: &Resolution, loc: Loc) -> ValueRes {
+        self.look_up_variable(name, resolution.get(), loc)
     }
 
-    fn visit_assign_expr(&mut self, name: &str, expr: &Expr, loc: Loc) -> ValueRes {
+    fn visit_assign_expr(
+        &mut self,
+        name: &str,
+        expr: &Expr,
+        resolution: &Resolution,
+        loc: Loc,
+    ) -> ValueRes {
         let val = self.evaluate(expr)?;
-        self.assign(name, val.clone(), loc)?;
+        self.assign(name, val.clone(), resolution.get(), loc)?;
 
         Ok(val)
     }
@@ -382,25 +804,13 @@ impl ExprVisitor<Value> for Interpreter {
             .map(|a| self.evaluate(a))
             .collect::<Result<_, _>>()?;
 
-        if let Value::Callable(callable) = callee {
-            if args.len() == callable.arity() {
-                callable.call(self, args, loc)
-            } else {
-                Err(RuntimeError::mismatching_arity(
-                    loc,
-                    callable.arity(),
-                    args.len(),
-                ))
-            }
-        } else {
-            Err(RuntimeError::not_a_callable(loc, callee))
-        }
+        self.call_value(callee, args, loc)
     }
 
     fn visit_get_expr(&mut self, obj: &Expr, name: &str, loc: Loc) -> ValueRes {
         let obj = self.evaluate(obj)?;
         let val_type = obj.get_type();
-        if let Some(instance) = obj.into_instance() {
+        if let Some(instance) = obj.into_instance(self) {
             ClassInstance::get(self, &instance, name, loc)
                 .unwrap_or_else(|| Err(RuntimeError::undefined_property(loc, name)))
         } else {
@@ -411,7 +821,7 @@ impl ExprVisitor<Value> for Interpreter {
     fn visit_set_expr(&mut self, obj: &Expr, name: &str, expr: &Expr, loc: Loc) -> ValueRes {
         let obj = self.evaluate(obj)?;
         let val_type = obj.get_type();
-        if let Some(instance) = obj.into_instance() {
+        if let Some(instance) = obj.into_instance(self) {
             let val = self.evaluate(expr)?;
             instance.borrow_mut().set(name, val.clone(), loc);
             Ok(val)
@@ -430,6 +840,18 @@ impl ExprVisitor<Value> for Interpreter {
         Ok(array.into())
     }
 
+    fn visit_map_expr(&mut self, entries: &[(Expr, Expr)], loc: Loc) -> ValueRes {
+        let mut data = IndexMap::with_capacity(entries.len());
+        for (key, val) in entries {
+            let key = MapKey::from_value(self.evaluate(key)?, loc)?;
+            let val = self.evaluate(val)?;
+            data.insert(key, val);
+        }
+
+        let map = Map::new(self, data);
+        Ok(map.into())
+    }
+
     fn visit_subscript_get_expr(&mut self, obj: &Expr, index: &Expr, loc: Loc) -> ValueRes {
         let obj = self.evaluate(obj)?;
         match obj.into_scriptable() {
@@ -459,15 +881,20 @@ impl ExprVisitor<Value> for Interpreter {
         }
     }
 
-    fn visit_this_expr(&mut self, loc: Loc) -> ValueRes {
-        self.look_up_variable(THIS_KEYWORD, loc)
+    fn visit_this_expr(&mut self, resolution: &Resolution, loc: Loc) -> ValueRes {
+        self.look_up_variable(THIS_KEYWORD, resolution.get(), loc)
     }
 
-    fn visit_super_expr(&mut self, method: &str, loc: Loc) -> ValueRes {
-        let superval = self.look_up_variable(SUPER_KEYWORD, loc)?;
+    fn visit_super_expr(&mut self, method: &str, resolution: &Resolution, loc: Loc) -> ValueRes {
+        let local = resolution.get();
+        let superval = self.look_up_variable(SUPER_KEYWORD, local, loc)?;
         let superclass = superval.into_class().unwrap();
-        let obj_val = self.look_up_variable(THIS_KEYWORD, loc)?;
-        let obj = obj_val.into_instance().unwrap();
+        let this = local.map(|ResolvedLocal { depth, .. }| ResolvedLocal {
+            depth: depth - 1,
+            index: 0,
+        });
+        let obj_val = self.look_up_variable(THIS_KEYWORD, this, loc)?;
+        let obj = obj_val.into_instance(self).unwrap();
         superclass
             .get_and_bind(&obj, self, method, loc)
             .unwrap_or_else(|| Err(RuntimeError::undefined_property(loc, method)))
@@ -499,9 +926,9 @@ impl StmtVisitor<()> for Interpreter {
         }
     }
 
-    fn visit_print_stmt(&mut self, expr: &Expr, _loc: Loc) -> ExecuteRes {
+    fn visit_print_stmt(&mut self, expr: &Expr, loc: Loc) -> ExecuteRes {
         let val = self.evaluate(expr)?;
-        println!("{}", val);
+        self.print(&val, loc)?;
         Ok(())
     }
 
@@ -529,6 +956,45 @@ impl StmtVisitor<()> for Interpreter {
         Ok(())
     }
 
+    fn visit_for_in_stmt(
+        &mut self,
+        _var: &Param,
+        iterable: &Expr,
+        body: &Stmt,
+        _loc: Loc,
+    ) -> ExecuteRes {
+        let loc = iterable.loc;
+        let elements = match self.evaluate(iterable)? {
+            Value::Array(array) => array.borrow().elements(),
+            Value::Str(string) => string::chars(&string),
+            Value::Map(map) => map.borrow().keys(),
+            Value::Instance(instance) => {
+                let iterator_fn = ClassInstance::get(self, &instance, ITERATOR_METHOD, loc)
+                    .unwrap_or_else(|| {
+                        Err(RuntimeError::not_iterable(loc, Value::Instance(instance)))
+                    })?;
+                let iterator = self.call_value(iterator_fn, vec![], loc)?;
+
+                return self.execute_for_in(body, |inter| {
+                    if inter
+                        .call_method(iterator.clone(), HAS_NEXT_METHOD, loc)?
+                        .is_truthy()
+                    {
+                        inter
+                            .call_method(iterator.clone(), NEXT_METHOD, loc)
+                            .map(Some)
+                    } else {
+                        Ok(None)
+                    }
+                });
+            }
+            val => return Err(RuntimeError::not_iterable(loc, val).into()),
+        };
+
+        let mut elements = elements.into_iter();
+        self.execute_for_in(body, |_inter| Ok(elements.next()))
+    }
+
     fn visit_var_stmt(&mut self, name: &str, init: &Option<Expr>, _loc: Loc) -> ExecuteRes {
         let init_val = if let Some(expr) = init {
             self.evaluate(expr)?
@@ -553,7 +1019,7 @@ impl StmtVisitor<()> for Interpreter {
         _loc: Loc,
     ) -> ExecuteRes {
         let params: Vec<_> = params.iter().map(|p| p.kind.clone()).collect();
-        let function = Function::new(name, params, body, &self.env, false);
+        let function = Function::new(name, params, body, &self.env, &self.globals, false);
         self.define(name, function.into());
         Ok(())
     }
@@ -588,6 +1054,12 @@ impl StmtVisitor<()> for Interpreter {
         };
 
         self.define(name, Value::Nil);
+        // The class is stored in the variable once its methods are built, so
+        // they can refer to it.
+        let local = self.env.as_ref().map(|env| ResolvedLocal {
+            depth: 0,
+            index: env.borrow().values.len() - 1,
+        });
 
         if let Some(sc) = &superclass {
             self.env = Some(Environ::with_enclosing(&self.env));
@@ -603,7 +1075,8 @@ impl StmtVisitor<()> for Interpreter {
                 StmtKind::Function(name, params, body, kind) => {
                     let params: Vec<_> = params.iter().map(|p| p.kind.clone()).collect();
                     let is_init = kind == &FunctionKind::Method && name == INIT_METHOD;
-                    let method = Function::new(name, params, body, &self.env, is_init).into();
+                    let method =
+                        Function::new(name, params, body, &self.env, &self.globals, is_init).into();
                     let name = name.clone();
 
                     match kind {
@@ -622,7 +1095,7 @@ impl StmtVisitor<()> for Interpreter {
         }
 
         let class = GenericClass::new(name, superclass, methods, getters, static_methods);
-        self.assign(name, Value::Callable(class.into()), loc)?;
+        self.assign(name, Value::Callable(class.into()), local, loc)?;
         Ok(())
     }
 
@@ -633,6 +1106,65 @@ impl StmtVisitor<()> for Interpreter {
     fn visit_continue_stmt(&mut self, _loc: Loc) -> ExecuteRes {
         Err(RuntimeInterrupt::Continue)
     }
+
+    fn visit_import_stmt(&mut self, path: &str, kind: &ImportKind, loc: Loc) -> ExecuteRes {
+        let module = module::import(self, path, loc)?;
+        match kind {
+            ImportKind::Module(alias) => self.define(&alias.kind, module),
+            ImportKind::Names(names) => {
+                let instance = module.into_instance(self).unwrap();
+                for name in names {
+                    let val = instance.borrow().field(&name.kind).ok_or_else(|| {
+                        RuntimeError::UndefinedExport(
+                            name.loc,
+                            name.kind.clone(),
+                            String::from(path),
+                        )
+                    })?;
+                    self.define(&name.kind, val);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_throw_stmt(&mut self, expr: &Expr, loc: Loc) -> ExecuteRes {
+        let val = self.evaluate(expr)?;
+        Err(RuntimeInterrupt::Throw(val, loc))
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        body: &[Stmt],
+        catch: &Option<CatchClause>,
+        finally: &Option<Vec<Stmt>>,
+        _loc: Loc,
+    ) -> ExecuteRes {
+        let res = self.execute_block(body, Environ::with_enclosing(&self.env));
+
+        let res = match (res, catch) {
+            (Err(interrupt), Some((_var, catch_body))) if interrupt.is_exception() => {
+                let exception = self.exception_value(interrupt);
+                let env = Environ::with_enclosing(&self.env);
+                env.borrow_mut().define(exception);
+                self.execute_block(catch_body, env)
+            }
+            (res, _) => res,
+        };
+
+        if let Some(finally_body) = finally {
+            // The finally body may catch exceptions of its own, so the one
+            // still unwinding has to be kept aside while it runs
+            let pending = self.thrown.take();
+            let traceback = self.traceback.take();
+            self.execute_block(finally_body, Environ::with_enclosing(&self.env))?;
+            self.thrown = pending;
+            self.traceback = traceback;
+        }
+
+        res
+    }
 }
 
 macro_rules! arithmethic_operation {
@@ -671,12 +1203,16 @@ macro_rules! comparison_operation {
     };
 }
 
-impl Value {
+impl Not for Value {
+    type Output = Value;
+
     fn not(self) -> Self {
         Value::Boolean(!self.is_truthy())
     }
+}
 
-    fn negate(self, loc: Loc) -> ValueRes {
+impl Value {
+    pub fn negate(self, loc: Loc) -> ValueRes {
         match self {
             Value::Integer(int) => Ok(Value::Integer(-int)),
             Value::Float(float) => Ok(Value::Float(-float)),
@@ -684,7 +1220,7 @@ impl Value {
         }
     }
 
-    fn add(self, rhs: Value, loc: Loc) -> ValueRes {
+    pub fn add(self, rhs: Value, loc: Loc) -> ValueRes {
         use Value::*;
         match (self, rhs) {
             (Integer(left), Integer(right)) => Ok(Integer(left + right)),
@@ -696,15 +1232,15 @@ impl Value {
         }
     }
 
-    fn sub(self, rhs: Value, loc: Loc) -> ValueRes {
+    pub fn sub(self, rhs: Value, loc: Loc) -> ValueRes {
         arithmethic_operation!(-, self, rhs, loc);
     }
 
-    fn mul(self, rhs: Value, loc: Loc) -> ValueRes {
+    pub fn mul(self, rhs: Value, loc: Loc) -> ValueRes {
         arithmethic_operation!(*, self, rhs, loc);
     }
 
-    fn div(self, rhs: Value, loc: Loc) -> ValueRes {
+    pub fn div(self, rhs: Value, loc: Loc) -> ValueRes {
         if rhs.number() == Some(0.0) {
             return Err(RuntimeError::DivisionByZero(loc));
         }
@@ -712,7 +1248,7 @@ impl Value {
         arithmethic_operation!(/, self, rhs, loc);
     }
 
-    fn rem(self, rhs: Value, loc: Loc) -> ValueRes {
+    pub fn rem(self, rhs: Value, loc: Loc) -> ValueRes {
         if rhs.number() == Some(0.0) {
             return Err(RuntimeError::DivisionByZero(loc));
         }
@@ -720,7 +1256,7 @@ impl Value {
         arithmethic_operation!(%, self, rhs, loc);
     }
 
-    fn equal(&self, rhs: &Value) -> Value {
+    pub fn equal(&self, rhs: &Value) -> Value {
         use std::f64::EPSILON;
         use Value::*;
         let eq = |l: f64, r: f64| (l - r).abs() <= EPSILON * 64.0;
@@ -736,27 +1272,28 @@ impl Value {
             (Callable(left), Callable(right)) => left == right,
             (Instance(left), Instance(right)) => Rc::ptr_eq(left, right),
             (Array(left), Array(right)) => Rc::ptr_eq(left, right),
+            (Map(left), Map(right)) => Rc::ptr_eq(left, right),
             (_, _) => false,
         })
     }
 
-    fn not_eq(&self, rhs: &Value) -> Value {
-        self.equal(rhs).not()
+    pub fn not_eq(&self, rhs: &Value) -> Value {
+        !self.equal(rhs)
     }
 
-    fn greater(&self, rhs: &Value, loc: Loc) -> ValueRes {
+    pub fn greater(&self, rhs: &Value, loc: Loc) -> ValueRes {
         comparison_operation!(>, self, rhs, loc);
     }
 
-    fn greater_eq(&self, rhs: &Value, loc: Loc) -> ValueRes {
+    pub fn greater_eq(&self, rhs: &Value, loc: Loc) -> ValueRes {
         comparison_operation!(>=, self, rhs, loc);
     }
 
-    fn less(&self, rhs: &Value, loc: Loc) -> ValueRes {
+    pub fn less(&self, rhs: &Value, loc: Loc) -> ValueRes {
         comparison_operation!(<, self, rhs, loc);
     }
 
-    fn less_eq(&self, rhs: &Value, loc: Loc) -> ValueRes {
+    pub fn less_eq(&self, rhs: &Value, loc: Loc) -> ValueRes {
         comparison_operation!(<=, self, rhs, loc);
     }
 }
@@ -779,7 +1316,7 @@ impl RuntimeError {
         )
     }
 
-    fn undefined_variable(loc: Loc, name: &str) -> Self {
+    pub fn undefined_variable(loc: Loc, name: &str) -> Self {
         Self::UndefinedVariable(loc, String::from(name))
     }
 
@@ -787,23 +1324,93 @@ impl RuntimeError {
         Self::NotACallable(loc, String::from(callee.get_type()))
     }
 
-    fn mismatching_arity(loc: Loc, expected: usize, got: usize) -> Self {
+    pub fn mismatching_arity(loc: Loc, expected: Arity, got: usize) -> Self {
         Self::MismatchingArity(loc, expected, got)
     }
 
-    fn no_properties(loc: Loc, val_type: &str) -> Self {
+    pub fn import_error(loc: Loc, path: &str, reason: &str) -> Self {
+        Self::ImportError(loc, String::from(path), String::from(reason))
+    }
+
+    fn uncaught_exception(loc: Loc, val: &Value) -> Self {
+        Self::UncaughtException(loc, exception::describe(val))
+    }
+
+    pub fn loc(&self) -> Loc {
+        use RuntimeError::*;
+        match self {
+            Generic(loc, ..)
+            | UnsupportedOperand(loc, ..)
+            | UnsupportedOperands(loc, ..)
+            | DivisionByZero(loc)
+            | UndefinedVariable(loc, ..)
+            | NotACallable(loc, ..)
+            | MismatchingArity(loc, ..)
+            | NoProperties(loc, ..)
+            | UndefinedProperty(loc, ..)
+            | NoFields(loc, ..)
+            | SuperclassIsNotClass(loc, ..)
+            | ExpectedType(loc, ..)
+            | IndexOutOfBounds(loc, ..)
+            | NotAScriptable(loc, ..)
+            | ArrayIndexNotInteger(loc, ..)
+            | InvalidMapKey(loc, ..)
+            | KeyNotFound(loc, ..)
+            | StringIndexNotInteger(loc, ..)
+            | ImmutableString(loc)
+            | NotIterable(loc, ..)
+            | UncaughtException(loc, ..)
+            | ImportError(loc, ..)
+            | UndefinedExport(loc, ..)
+            | StackOverflow(loc, ..) => *loc,
+        }
+    }
+
+    /// The name of the `Error` subclass this error is caught as.
+    pub fn kind(&self) -> &'static str {
+        use RuntimeError::*;
+        match self {
+            Generic(..) | UncaughtException(..) => ERROR_CLASS,
+            UnsupportedOperand(..)
+            | UnsupportedOperands(..)
+            | NotACallable(..)
+            | MismatchingArity(..)
+            | NoProperties(..)
+            | NoFields(..)
+            | SuperclassIsNotClass(..)
+            | ExpectedType(..)
+            | NotAScriptable(..)
+            | ArrayIndexNotInteger(..)
+            | InvalidMapKey(..)
+            | StringIndexNotInteger(..)
+            | ImmutableString(..)
+            | NotIterable(..) => TYPE_ERROR,
+            DivisionByZero(..) => ARITHMETIC_ERROR,
+            UndefinedVariable(..) | UndefinedProperty(..) => NAME_ERROR,
+            IndexOutOfBounds(..) => INDEX_ERROR,
+            KeyNotFound(..) => KEY_ERROR,
+            ImportError(..) | UndefinedExport(..) => IMPORT_ERROR,
+            StackOverflow(..) => RECURSION_ERROR,
+        }
+    }
+
+    pub fn not_iterable(loc: Loc, obj: Value) -> Self {
+        Self::NotIterable(loc, String::from(obj.get_type()))
+    }
+
+    pub fn no_properties(loc: Loc, val_type: &str) -> Self {
         Self::NoProperties(loc, String::from(val_type))
     }
 
-    fn undefined_property(loc: Loc, name: &str) -> Self {
+    pub fn undefined_property(loc: Loc, name: &str) -> Self {
         Self::UndefinedProperty(loc, String::from(name))
     }
 
-    fn no_fields(loc: Loc, val_type: &str) -> Self {
+    pub fn no_fields(loc: Loc, val_type: &str) -> Self {
         Self::NoFields(loc, String::from(val_type))
     }
 
-    fn superclass_is_not_class(loc: Loc, val_type: &str) -> Self {
+    pub fn superclass_is_not_class(loc: Loc, val_type: &str) -> Self {
         Self::SuperclassIsNotClass(loc, String::from(val_type))
     }
 
@@ -811,16 +1418,42 @@ impl RuntimeError {
         Self::ExpectedType(loc, String::from(expected), String::from(got.get_type()))
     }
 
-    fn not_a_scriptable(loc: Loc, obj: Value) -> Self {
+    pub fn not_a_scriptable(loc: Loc, obj: Value) -> Self {
         Self::NotAScriptable(loc, String::from(obj.get_type()))
     }
 
     pub fn array_index_not_int(loc: Loc, obj: Value) -> Self {
         Self::ArrayIndexNotInteger(loc, String::from(obj.get_type()))
     }
+
+    pub fn string_index_not_int(loc: Loc, obj: Value) -> Self {
+        Self::StringIndexNotInteger(loc, String::from(obj.get_type()))
+    }
+
+    pub fn invalid_map_key(loc: Loc, key: Value) -> Self {
+        Self::InvalidMapKey(loc, String::from(key.get_type()))
+    }
+
+    pub fn key_not_found(loc: Loc, key: &MapKey) -> Self {
+        Self::KeyNotFound(loc, key.to_string())
+    }
 }
 
 impl RuntimeInterrupt {
+    fn is_exception(&self) -> bool {
+        matches!(
+            self,
+            RuntimeInterrupt::Error(_) | RuntimeInterrupt::Throw(..)
+        )
+    }
+
+    pub fn into_error(self) -> RuntimeError {
+        match self {
+            RuntimeInterrupt::Throw(val, loc) => RuntimeError::uncaught_exception(loc, &val),
+            interrupt => interrupt.expect_error(),
+        }
+    }
+
     pub fn expect_error(self) -> RuntimeError {
         use RuntimeInterrupt::*;
         match self {
@@ -1,402 +1,461 @@
 use super::*;
 use crate::callable::Callable;
+use crate::class::NativeMethod;
+use crate::host::HostClass;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
 use crate::test_utils::*;
-use crate::value::{types::*, Value::*};
+use crate::value::{types::*, FromValue, IntoValue, Value::*};
 
-pub fn env_get(inter: &Interpreter, name: &str) -> ValueRes {
-    inter.globals.borrow().get(name, Loc::default())
-}
+/// Defines the items it is given once per backend, in a module named after
+/// it where `BACKEND` is that backend.
+macro_rules! backends {
+    ($($item:item)*) => {
+        mod tree_walker {
+            use super::*;
 
-fn test_value_res(input: &str, expected: ValueRes) {
-    let expr = get_expr(input);
-    let mut inter = Interpreter::new();
-    match (inter.evaluate(&expr), expected) {
-        (Ok(val), Ok(output)) if val.equal(&output).is_truthy() => {}
-        (got, expected) => assert_eq!(expected, got),
-    }
-}
+            const BACKEND: Backend = Backend::TreeWalker;
 
-#[test]
-fn test_integer_expr() {
-    let expr = get_expr("1996");
-    let mut inter = Interpreter::new();
-    assert_eq!(Ok(Integer(1996)), inter.evaluate(&expr));
-}
+            $($item)*
+        }
 
-#[test]
-fn test_float_expr() {
-    let expr = get_expr("8.5");
-    let mut inter = Interpreter::new();
-    assert_eq!(Ok(Float(8.5)), inter.evaluate(&expr));
-}
+        mod vm {
+            use super::*;
 
-#[test]
-fn test_string_expr() {
-    let expr = get_expr("\"1996\"");
-    let mut inter = Interpreter::new();
-    assert_eq!(Ok("1996".into()), inter.evaluate(&expr));
+            const BACKEND: Backend = Backend::Vm;
+
+            $($item)*
+        }
+    };
 }
 
-#[test]
-fn test_true_expr() {
-    let expr = get_expr("true");
-    let mut inter = Interpreter::new();
-    assert_eq!(Ok(Boolean(true)), inter.evaluate(&expr));
+pub fn env_get(inter: &Interpreter, name: &str) -> ValueRes {
+    inter.globals.borrow().get(name, Loc::default())
 }
 
+/// Writes the given modules into a fresh temporary directory, and makes
+/// imports relative to it.
 #[test]
-fn test_false_expr() {
-    let expr = get_expr("false");
-    let mut inter = Interpreter::new();
-    assert_eq!(Ok(Boolean(false)), inter.evaluate(&expr));
+#[should_panic(expected = "Integer 18446744073709551615 does not fit in i64")]
+fn test_integer_too_large_for_value() {
+    let inter = Interpreter::new();
+    usize::MAX.into_value(&inter);
 }
 
-#[test]
-fn test_nil_expr() {
-    let expr = get_expr("nil");
-    let mut inter = Interpreter::new();
-    assert_eq!(Ok(Nil), inter.evaluate(&expr));
+fn with_modules(inter: &mut Interpreter, modules: &[(&str, &str)]) {
+    let dir = std::env::temp_dir().join(format!("lox-{}", uuid::Uuid::new_v4()));
+    for (path, content) in modules {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    inter.modules.set_current_path(Some(dir.join("main.lox")));
 }
 
-#[test]
-fn test_unary_not_expr() {
-    for (input, output) in vec![
-        ("!true", false),
-        ("!false", true),
-        ("!nil", true),
-        ("!0", false),
-        ("!0.5", false),
-        ("!-100", false),
-        ("!\"string\"", false),
-        ("!\"\"", false),
-    ] {
-        let expr = get_expr(input);
+backends! {
+    fn new_interpreter() -> Interpreter {
         let mut inter = Interpreter::new();
-        assert_eq!(Ok(Boolean(output)), inter.evaluate(&expr));
+        inter.backend = BACKEND;
+        inter
     }
-}
 
-#[test]
-fn test_unary_negate_expr() {
-    for (input, output) in vec![
-        ("-100", Ok(Integer(-100))),
-        ("-100.23", Ok(Float(-100.23))),
-        ("-true", Err(unsup_op("-", BOOL, (0, 0)))),
-        ("-nil", Err(unsup_op("-", NIL, (0, 0)))),
-        ("-\"hi\"", Err(unsup_op("-", STRING, (0, 0)))),
-    ] {
-        test_value_res(input, output);
+    fn get_stmts(input: &str) -> (Vec<Stmt>, Interpreter) {
+        let (stmts, mut inter) = crate::test_utils::get_stmts(input);
+        inter.backend = BACKEND;
+        (stmts, inter)
     }
-}
 
-#[test]
-fn test_binary_add_expr() {
-    for (input, output) in vec![
-        ("30 + 50", Ok(Integer(80))),
-        ("30 + 50.3", Ok(Float(80.3))),
-        ("30.4 + 50.3", Ok(Float(80.7))),
-        ("30.4 + 50", Ok(Float(80.4))),
-        ("\"hello_\" + \"world\"", Ok("hello_world".into())),
-        ("\"hello\" + 3", Err(unsup_ops("+", STRING, INT, (0, 8)))),
-        (
-            "\"hello\" + true",
-            Err(unsup_ops("+", STRING, BOOL, (0, 8))),
-        ),
-        ("3.2 + false", Err(unsup_ops("+", FLOAT, BOOL, (0, 4)))),
-        ("nil + 100", Err(unsup_ops("+", NIL, INT, (0, 4)))),
-        ("true + false", Err(unsup_ops("+", BOOL, BOOL, (0, 5)))),
-        ("nil + nil", Err(unsup_ops("+", NIL, NIL, (0, 4)))),
-    ] {
-        test_value_res(input, output);
+    fn test_value_res(input: &str, expected: ValueRes) {
+        let expr = get_expr(input);
+        let mut inter = new_interpreter();
+        match (inter.evaluate(&expr), expected) {
+            (Ok(val), Ok(output)) if val.equal(&output).is_truthy() => {}
+            (got, expected) => assert_eq!(expected, got),
+        }
     }
-}
 
-#[test]
-fn test_binary_sub_expr() {
-    for (input, output) in vec![
-        ("30 - 50", Ok(Integer(-20))),
-        ("30 - 50.3", Ok(Float(-20.3))),
-        ("30.4 - 50.3", Ok(Float(-19.9))),
-        ("30.4 - 50", Ok(Float(-19.6))),
-        (
-            "\"hello_\" - \"world\"",
-            Err(unsup_ops("-", STRING, STRING, (0, 9))),
-        ),
-        ("\"hello\" - 3", Err(unsup_ops("-", STRING, INT, (0, 8)))),
-        (
-            "\"hello\" - true",
-            Err(unsup_ops("-", STRING, BOOL, (0, 8))),
-        ),
-        ("3.2 - false", Err(unsup_ops("-", FLOAT, BOOL, (0, 4)))),
-        ("nil - 100", Err(unsup_ops("-", NIL, INT, (0, 4)))),
-        ("true - false", Err(unsup_ops("-", BOOL, BOOL, (0, 5)))),
-        ("nil - nil", Err(unsup_ops("-", NIL, NIL, (0, 4)))),
-    ] {
-        test_value_res(input, output);
+    #[test]
+    fn test_integer_expr() {
+        let expr = get_expr("1996");
+        let mut inter = new_interpreter();
+        assert_eq!(Ok(Integer(1996)), inter.evaluate(&expr));
     }
-}
 
-#[test]
-fn test_binary_mul_expr() {
-    for (input, output) in vec![
-        ("30 * 50", Ok(Integer(1500))),
-        ("30 * 50.3", Ok(Float(1509.0))),
-        ("30.4 * 50.3", Ok(Float(1529.12))),
-        ("30.4 * 50", Ok(Float(1520.0))),
-        (
-            "\"hello_\" * \"world\"",
-            Err(unsup_ops("*", STRING, STRING, (0, 9))),
-        ),
-        ("\"hello\" * 3", Err(unsup_ops("*", STRING, INT, (0, 8)))),
-        (
-            "\"hello\" * true",
-            Err(unsup_ops("*", STRING, BOOL, (0, 8))),
-        ),
-        ("3.2 * false", Err(unsup_ops("*", FLOAT, BOOL, (0, 4)))),
-        ("nil * 100", Err(unsup_ops("*", NIL, INT, (0, 4)))),
-        ("true * false", Err(unsup_ops("*", BOOL, BOOL, (0, 5)))),
-        ("nil * nil", Err(unsup_ops("*", NIL, NIL, (0, 4)))),
-    ] {
-        test_value_res(input, output);
+    #[test]
+    fn test_float_expr() {
+        let expr = get_expr("8.5");
+        let mut inter = new_interpreter();
+        assert_eq!(Ok(Float(8.5)), inter.evaluate(&expr));
     }
-}
 
-#[test]
-fn test_binary_div_expr() {
-    for (input, output) in vec![
-        ("50 / 20", Ok(Integer(2))),
-        ("30 / 50.0", Ok(Float(0.6))),
-        ("30.4 / 50.3", Ok(Float(0.6043737574552684))),
-        ("30.4 / 50", Ok(Float(0.608))),
-        (
-            "\"hello_\" / \"world\"",
-            Err(unsup_ops("/", STRING, STRING, (0, 9))),
-        ),
-        ("\"hello\" / 3", Err(unsup_ops("/", STRING, INT, (0, 8)))),
-        (
-            "\"hello\" / true",
-            Err(unsup_ops("/", STRING, BOOL, (0, 8))),
-        ),
-        ("3.2 / false", Err(unsup_ops("/", FLOAT, BOOL, (0, 4)))),
-        ("nil / 100", Err(unsup_ops("/", NIL, INT, (0, 4)))),
-        ("true / false", Err(unsup_ops("/", BOOL, BOOL, (0, 5)))),
-        ("nil / nil", Err(unsup_ops("/", NIL, NIL, (0, 4)))),
-    ] {
-        test_value_res(input, output);
+    #[test]
+    fn test_string_expr() {
+        let expr = get_expr("\"1996\"");
+        let mut inter = new_interpreter();
+        assert_eq!(Ok("1996".into()), inter.evaluate(&expr));
     }
-}
 
-#[test]
-fn test_binary_rem_expr() {
-    for (input, output) in vec![
-        ("30 % 50", Ok(Integer(30))),
-        ("30 % 5", Ok(Integer(0))),
-        ("30 % 4.3", Ok(Float(4.2))),
-        ("34.2 % 8.1", Ok(Float(1.8))),
-        ("34.2 % 8", Ok(Float(2.2))),
-        (
-            "\"hello_\" % \"world\"",
-            Err(unsup_ops("%", STRING, STRING, (0, 9))),
-        ),
-        ("\"hello\" % 3", Err(unsup_ops("%", STRING, INT, (0, 8)))),
-        (
-            "\"hello\" % true",
-            Err(unsup_ops("%", STRING, BOOL, (0, 8))),
-        ),
-        ("3.2 % false", Err(unsup_ops("%", FLOAT, BOOL, (0, 4)))),
-        ("nil % 100", Err(unsup_ops("%", NIL, INT, (0, 4)))),
-        ("true % false", Err(unsup_ops("%", BOOL, BOOL, (0, 5)))),
-        ("nil % nil", Err(unsup_ops("%", NIL, NIL, (0, 4)))),
-    ] {
-        test_value_res(input, output);
+    #[test]
+    fn test_true_expr() {
+        let expr = get_expr("true");
+        let mut inter = new_interpreter();
+        assert_eq!(Ok(Boolean(true)), inter.evaluate(&expr));
     }
-}
 
-#[test]
-fn test_binary_comparison_expr() {
-    for (input, output) in vec![
-        ("50 == 20", Ok(false)),
-        ("10 + 10 == 20", Ok(true)),
-        ("30 == 50.0", Ok(false)),
-        ("30 == 30.0", Ok(true)),
-        ("30.4 == 50.3", Ok(false)),
-        ("30.4 + 50.3 == 80.7", Ok(true)),
-        ("30.4 == 50", Ok(false)),
-        ("true == true", Ok(true)),
-        ("true == !false", Ok(true)),
-        ("\"hello\" == \"hell\" + \"o\"", Ok(true)),
-        ("\"hello\" == 3", Ok(false)),
-        ("nil == true", Ok(false)),
-        ("nil == nil", Ok(true)),
-        ("50 == 20", Ok(false)),
-        ("10 + 10 != 20", Ok(false)),
-        ("30 != 50.0", Ok(true)),
-        ("30 != 30.0", Ok(false)),
-        ("30.4 != 50.3", Ok(true)),
-        ("30.4 + 50.3 != 80.7", Ok(false)),
-        ("30.4 != 50", Ok(true)),
-        ("true != true", Ok(false)),
-        ("true != !false", Ok(false)),
-        ("\"hello\" != \"hell\" + \"o\"", Ok(false)),
-        ("\"hello\" != 3", Ok(true)),
-        ("nil != true", Ok(true)),
-        ("nil != nil", Ok(false)),
-        ("50 > 20", Ok(true)),
-        ("30 > 50.0", Ok(false)),
-        ("30.4 > 50.3", Ok(false)),
-        ("30.4 > 50", Ok(false)),
-        ("50 >= 20", Ok(true)),
-        ("30 >= 50.0", Ok(false)),
-        ("30.4 >= 50.3", Ok(false)),
-        ("30.4 >= 50", Ok(false)),
-        ("30.4 >= 30.4", Ok(true)),
-        ("50 < 20", Ok(false)),
-        ("30 < 50.0", Ok(true)),
-        ("30.4 < 50.3", Ok(true)),
-        ("30.4 < 50", Ok(true)),
-        ("50 <= 20", Ok(false)),
-        ("30 <= 50.0", Ok(true)),
-        ("30.4 <= 50.3", Ok(true)),
-        ("30.4 <= 50", Ok(true)),
-        ("30.4 <= 30.4", Ok(true)),
-        (
-            "\"hello_\" > \"world\"",
-            Err(unsup_ops(">", STRING, STRING, (0, 9))),
-        ),
-        ("\"hello\" < 3", Err(unsup_ops("<", STRING, INT, (0, 8)))),
-        (
-            "\"hello\" >= true",
-            Err(unsup_ops(">=", STRING, BOOL, (0, 8))),
-        ),
-        ("3.2 <= false", Err(unsup_ops("<=", FLOAT, BOOL, (0, 4)))),
-        ("nil > 100", Err(unsup_ops(">", NIL, INT, (0, 4)))),
-        ("true < false", Err(unsup_ops("<", BOOL, BOOL, (0, 5)))),
-        ("nil >= nil", Err(unsup_ops(">=", NIL, NIL, (0, 4)))),
-        (
-            "\"hello_\" <= \"world\"",
-            Err(unsup_ops("<=", STRING, STRING, (0, 9))),
-        ),
-        ("\"hello\" > 3", Err(unsup_ops(">", STRING, INT, (0, 8)))),
-        (
-            "\"hello\" < true",
-            Err(unsup_ops("<", STRING, BOOL, (0, 8))),
-        ),
-        ("3.2 >= false", Err(unsup_ops(">=", FLOAT, BOOL, (0, 4)))),
-        ("nil <= 100", Err(unsup_ops("<=", NIL, INT, (0, 4)))),
-    ] {
-        let output = output.map(Boolean);
-        test_value_res(input, output);
+    #[test]
+    fn test_false_expr() {
+        let expr = get_expr("false");
+        let mut inter = new_interpreter();
+        assert_eq!(Ok(Boolean(false)), inter.evaluate(&expr));
     }
-}
 
-#[test]
-fn test_logical_expr() {
-    for (input, output) in vec![
-        ("true and 5", Integer(5)),
-        ("3.0 and nil", Nil),
-        ("false and 5", Boolean(false)),
-        ("nil and false", Nil),
-        ("3.0 or 5", Float(3.0)),
-        ("\"string\" or false", "string".into()),
-        ("nil or \"car\"", "car".into()),
-        ("false or false", Boolean(false)),
-    ] {
-        test_value_res(input, Ok(output));
+    #[test]
+    fn test_nil_expr() {
+        let expr = get_expr("nil");
+        let mut inter = new_interpreter();
+        assert_eq!(Ok(Nil), inter.evaluate(&expr));
     }
-}
 
-#[test]
-fn test_logical_short_circuit() {
-    for (input, output) in vec![
-        ("true and (hello = 5)", Integer(5)),
-        ("3.0 and (hello = nil)", Nil),
-        ("false and (hello = 5)", "world".into()),
-        ("nil and (hello = false)", "world".into()),
-        ("3.0 or (hello = 5)", "world".into()),
-        ("\"string\" or (hello = false)", "world".into()),
-        ("nil or (hello = \"car\")", "car".into()),
-        ("false or (hello = false)", Boolean(false)),
-    ] {
-        let input = format!(r#"var hello = "world"; {};"#, input);
-        let (stmts, mut inter) = get_stmts(&input);
-        assert_eq!(Ok(()), inter.interpret(&stmts));
-        assert_eq!(Ok(output), env_get(&inter, "hello"));
+    #[test]
+    fn test_unary_not_expr() {
+        for (input, output) in vec![
+            ("!true", false),
+            ("!false", true),
+            ("!nil", true),
+            ("!0", false),
+            ("!0.5", false),
+            ("!-100", false),
+            ("!\"string\"", false),
+            ("!\"\"", false),
+        ] {
+            let expr = get_expr(input);
+            let mut inter = new_interpreter();
+            assert_eq!(Ok(Boolean(output)), inter.evaluate(&expr));
+        }
     }
-}
 
-#[test]
-fn test_conditional_expr() {
-    for (input, output) in vec![
-        ("true ? 30 : 50", Integer(30)),
-        ("\"string\" ? 1.0 : 200", Float(1.0)),
-        ("!3 ? (3 + 5) : (88/11)", Integer(8)),
-        ("nil? true ? 1 : 2 : 0.0 ? 3 : 4", Integer(3)),
-        ("true ? 30 : 50, false ? 7 : 9", Integer(9)),
-    ] {
-        test_value_res(input, Ok(output));
+    #[test]
+    fn test_unary_negate_expr() {
+        for (input, output) in vec![
+            ("-100", Ok(Integer(-100))),
+            ("-100.23", Ok(Float(-100.23))),
+            ("-true", Err(unsup_op("-", BOOL, (0, 0)))),
+            ("-nil", Err(unsup_op("-", NIL, (0, 0)))),
+            ("-\"hi\"", Err(unsup_op("-", STRING, (0, 0)))),
+        ] {
+            test_value_res(input, output);
+        }
     }
-}
 
-#[test]
-fn test_comma_expr() {
-    for (input, output) in vec![
-        ("3434 + 76, (5 * 8 + 2)", Integer(42)),
-        ("30, \"string\", true", Boolean(true)),
-        ("(3 + 5), (4, (7, 88/11))", Integer(8)),
-    ] {
-        test_value_res(input, Ok(output));
+    #[test]
+    fn test_binary_add_expr() {
+        for (input, output) in vec![
+            ("30 + 50", Ok(Integer(80))),
+            ("30 + 50.3", Ok(Float(80.3))),
+            ("30.4 + 50.3", Ok(Float(80.7))),
+            ("30.4 + 50", Ok(Float(80.4))),
+            ("\"hello_\" + \"world\"", Ok("hello_world".into())),
+            ("\"hello\" + 3", Err(unsup_ops("+", STRING, INT, (0, 8)))),
+            (
+                "\"hello\" + true",
+                Err(unsup_ops("+", STRING, BOOL, (0, 8))),
+            ),
+            ("3.2 + false", Err(unsup_ops("+", FLOAT, BOOL, (0, 4)))),
+            ("nil + 100", Err(unsup_ops("+", NIL, INT, (0, 4)))),
+            ("true + false", Err(unsup_ops("+", BOOL, BOOL, (0, 5)))),
+            ("nil + nil", Err(unsup_ops("+", NIL, NIL, (0, 4)))),
+        ] {
+            test_value_res(input, output);
+        }
     }
-}
 
-#[test]
-fn test_division_by_zero() {
-    for (input, col) in vec![
-        ("30/0", 2),
-        ("30/0.0", 2),
-        ("30.0/0.0", 4),
-        ("30.0/0", 4),
-        ("30%0", 2),
-        ("30%0.0", 2),
-        ("30.0%0.0", 4),
-        ("30.0%0", 4),
-    ] {
-        let expr = get_expr(input);
-        let mut inter = Interpreter::new();
-        assert_eq!(
-            Err(RuntimeError::DivisionByZero(Loc::new(0, col),)),
-            inter.evaluate(&expr)
-        );
+    #[test]
+    fn test_binary_sub_expr() {
+        for (input, output) in vec![
+            ("30 - 50", Ok(Integer(-20))),
+            ("30 - 50.3", Ok(Float(-20.3))),
+            ("30.4 - 50.3", Ok(Float(-19.9))),
+            ("30.4 - 50", Ok(Float(-19.6))),
+            (
+                "\"hello_\" - \"world\"",
+                Err(unsup_ops("-", STRING, STRING, (0, 9))),
+            ),
+            ("\"hello\" - 3", Err(unsup_ops("-", STRING, INT, (0, 8)))),
+            (
+                "\"hello\" - true",
+                Err(unsup_ops("-", STRING, BOOL, (0, 8))),
+            ),
+            ("3.2 - false", Err(unsup_ops("-", FLOAT, BOOL, (0, 4)))),
+            ("nil - 100", Err(unsup_ops("-", NIL, INT, (0, 4)))),
+            ("true - false", Err(unsup_ops("-", BOOL, BOOL, (0, 5)))),
+            ("nil - nil", Err(unsup_ops("-", NIL, NIL, (0, 4)))),
+        ] {
+            test_value_res(input, output);
+        }
     }
-}
 
-#[test]
-fn test_empty_stmt() {
-    let input = "";
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-}
+    #[test]
+    fn test_binary_mul_expr() {
+        for (input, output) in vec![
+            ("30 * 50", Ok(Integer(1500))),
+            ("30 * 50.3", Ok(Float(1509.0))),
+            ("30.4 * 50.3", Ok(Float(1529.12))),
+            ("30.4 * 50", Ok(Float(1520.0))),
+            (
+                "\"hello_\" * \"world\"",
+                Err(unsup_ops("*", STRING, STRING, (0, 9))),
+            ),
+            ("\"hello\" * 3", Err(unsup_ops("*", STRING, INT, (0, 8)))),
+            (
+                "\"hello\" * true",
+                Err(unsup_ops("*", STRING, BOOL, (0, 8))),
+            ),
+            ("3.2 * false", Err(unsup_ops("*", FLOAT, BOOL, (0, 4)))),
+            ("nil * 100", Err(unsup_ops("*", NIL, INT, (0, 4)))),
+            ("true * false", Err(unsup_ops("*", BOOL, BOOL, (0, 5)))),
+            ("nil * nil", Err(unsup_ops("*", NIL, NIL, (0, 4)))),
+        ] {
+            test_value_res(input, output);
+        }
+    }
 
-#[test]
-fn test_var_stmt() {
-    let input = r#"var hello = "world";"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("world".into()), env_get(&inter, "hello"));
-}
+    #[test]
+    fn test_binary_div_expr() {
+        for (input, output) in vec![
+            ("50 / 20", Ok(Integer(2))),
+            ("30 / 50.0", Ok(Float(0.6))),
+            ("30.4 / 50.3", Ok(Float(0.6043737574552684))),
+            ("30.4 / 50", Ok(Float(0.608))),
+            (
+                "\"hello_\" / \"world\"",
+                Err(unsup_ops("/", STRING, STRING, (0, 9))),
+            ),
+            ("\"hello\" / 3", Err(unsup_ops("/", STRING, INT, (0, 8)))),
+            (
+                "\"hello\" / true",
+                Err(unsup_ops("/", STRING, BOOL, (0, 8))),
+            ),
+            ("3.2 / false", Err(unsup_ops("/", FLOAT, BOOL, (0, 4)))),
+            ("nil / 100", Err(unsup_ops("/", NIL, INT, (0, 4)))),
+            ("true / false", Err(unsup_ops("/", BOOL, BOOL, (0, 5)))),
+            ("nil / nil", Err(unsup_ops("/", NIL, NIL, (0, 4)))),
+        ] {
+            test_value_res(input, output);
+        }
+    }
 
-#[test]
-fn test_var_assignment() {
-    let input = r#"var hello = "world";
+    #[test]
+    fn test_binary_rem_expr() {
+        for (input, output) in vec![
+            ("30 % 50", Ok(Integer(30))),
+            ("30 % 5", Ok(Integer(0))),
+            ("30 % 4.3", Ok(Float(4.2))),
+            ("34.2 % 8.1", Ok(Float(1.8))),
+            ("34.2 % 8", Ok(Float(2.2))),
+            (
+                "\"hello_\" % \"world\"",
+                Err(unsup_ops("%", STRING, STRING, (0, 9))),
+            ),
+            ("\"hello\" % 3", Err(unsup_ops("%", STRING, INT, (0, 8)))),
+            (
+                "\"hello\" % true",
+                Err(unsup_ops("%", STRING, BOOL, (0, 8))),
+            ),
+            ("3.2 % false", Err(unsup_ops("%", FLOAT, BOOL, (0, 4)))),
+            ("nil % 100", Err(unsup_ops("%", NIL, INT, (0, 4)))),
+            ("true % false", Err(unsup_ops("%", BOOL, BOOL, (0, 5)))),
+            ("nil % nil", Err(unsup_ops("%", NIL, NIL, (0, 4)))),
+        ] {
+            test_value_res(input, output);
+        }
+    }
+
+    #[test]
+    fn test_binary_comparison_expr() {
+        for (input, output) in vec![
+            ("50 == 20", Ok(false)),
+            ("10 + 10 == 20", Ok(true)),
+            ("30 == 50.0", Ok(false)),
+            ("30 == 30.0", Ok(true)),
+            ("30.4 == 50.3", Ok(false)),
+            ("30.4 + 50.3 == 80.7", Ok(true)),
+            ("30.4 == 50", Ok(false)),
+            ("true == true", Ok(true)),
+            ("true == !false", Ok(true)),
+            ("\"hello\" == \"hell\" + \"o\"", Ok(true)),
+            ("\"hello\" == 3", Ok(false)),
+            ("nil == true", Ok(false)),
+            ("nil == nil", Ok(true)),
+            ("50 == 20", Ok(false)),
+            ("10 + 10 != 20", Ok(false)),
+            ("30 != 50.0", Ok(true)),
+            ("30 != 30.0", Ok(false)),
+            ("30.4 != 50.3", Ok(true)),
+            ("30.4 + 50.3 != 80.7", Ok(false)),
+            ("30.4 != 50", Ok(true)),
+            ("true != true", Ok(false)),
+            ("true != !false", Ok(false)),
+            ("\"hello\" != \"hell\" + \"o\"", Ok(false)),
+            ("\"hello\" != 3", Ok(true)),
+            ("nil != true", Ok(true)),
+            ("nil != nil", Ok(false)),
+            ("50 > 20", Ok(true)),
+            ("30 > 50.0", Ok(false)),
+            ("30.4 > 50.3", Ok(false)),
+            ("30.4 > 50", Ok(false)),
+            ("50 >= 20", Ok(true)),
+            ("30 >= 50.0", Ok(false)),
+            ("30.4 >= 50.3", Ok(false)),
+            ("30.4 >= 50", Ok(false)),
+            ("30.4 >= 30.4", Ok(true)),
+            ("50 < 20", Ok(false)),
+            ("30 < 50.0", Ok(true)),
+            ("30.4 < 50.3", Ok(true)),
+            ("30.4 < 50", Ok(true)),
+            ("50 <= 20", Ok(false)),
+            ("30 <= 50.0", Ok(true)),
+            ("30.4 <= 50.3", Ok(true)),
+            ("30.4 <= 50", Ok(true)),
+            ("30.4 <= 30.4", Ok(true)),
+            (
+                "\"hello_\" > \"world\"",
+                Err(unsup_ops(">", STRING, STRING, (0, 9))),
+            ),
+            ("\"hello\" < 3", Err(unsup_ops("<", STRING, INT, (0, 8)))),
+            (
+                "\"hello\" >= true",
+                Err(unsup_ops(">=", STRING, BOOL, (0, 8))),
+            ),
+            ("3.2 <= false", Err(unsup_ops("<=", FLOAT, BOOL, (0, 4)))),
+            ("nil > 100", Err(unsup_ops(">", NIL, INT, (0, 4)))),
+            ("true < false", Err(unsup_ops("<", BOOL, BOOL, (0, 5)))),
+            ("nil >= nil", Err(unsup_ops(">=", NIL, NIL, (0, 4)))),
+            (
+                "\"hello_\" <= \"world\"",
+                Err(unsup_ops("<=", STRING, STRING, (0, 9))),
+            ),
+            ("\"hello\" > 3", Err(unsup_ops(">", STRING, INT, (0, 8)))),
+            (
+                "\"hello\" < true",
+                Err(unsup_ops("<", STRING, BOOL, (0, 8))),
+            ),
+            ("3.2 >= false", Err(unsup_ops(">=", FLOAT, BOOL, (0, 4)))),
+            ("nil <= 100", Err(unsup_ops("<=", NIL, INT, (0, 4)))),
+        ] {
+            let output = output.map(Boolean);
+            test_value_res(input, output);
+        }
+    }
+
+    #[test]
+    fn test_logical_expr() {
+        for (input, output) in vec![
+            ("true and 5", Integer(5)),
+            ("3.0 and nil", Nil),
+            ("false and 5", Boolean(false)),
+            ("nil and false", Nil),
+            ("3.0 or 5", Float(3.0)),
+            ("\"string\" or false", "string".into()),
+            ("nil or \"car\"", "car".into()),
+            ("false or false", Boolean(false)),
+        ] {
+            test_value_res(input, Ok(output));
+        }
+    }
+
+    #[test]
+    fn test_logical_short_circuit() {
+        for (input, output) in vec![
+            ("true and (hello = 5)", Integer(5)),
+            ("3.0 and (hello = nil)", Nil),
+            ("false and (hello = 5)", "world".into()),
+            ("nil and (hello = false)", "world".into()),
+            ("3.0 or (hello = 5)", "world".into()),
+            ("\"string\" or (hello = false)", "world".into()),
+            ("nil or (hello = \"car\")", "car".into()),
+            ("false or (hello = false)", Boolean(false)),
+        ] {
+            let input = format!(r#"var hello = "world"; {};"#, input);
+            let (stmts, mut inter) = get_stmts(&input);
+            assert_eq!(Ok(()), inter.interpret(&stmts));
+            assert_eq!(Ok(output), env_get(&inter, "hello"));
+        }
+    }
+
+    #[test]
+    fn test_conditional_expr() {
+        for (input, output) in vec![
+            ("true ? 30 : 50", Integer(30)),
+            ("\"string\" ? 1.0 : 200", Float(1.0)),
+            ("!3 ? (3 + 5) : (88/11)", Integer(8)),
+            ("nil? true ? 1 : 2 : 0.0 ? 3 : 4", Integer(3)),
+            ("true ? 30 : 50, false ? 7 : 9", Integer(9)),
+        ] {
+            test_value_res(input, Ok(output));
+        }
+    }
+
+    #[test]
+    fn test_comma_expr() {
+        for (input, output) in vec![
+            ("3434 + 76, (5 * 8 + 2)", Integer(42)),
+            ("30, \"string\", true", Boolean(true)),
+            ("(3 + 5), (4, (7, 88/11))", Integer(8)),
+        ] {
+            test_value_res(input, Ok(output));
+        }
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        for (input, col) in vec![
+            ("30/0", 2),
+            ("30/0.0", 2),
+            ("30.0/0.0", 4),
+            ("30.0/0", 4),
+            ("30%0", 2),
+            ("30%0.0", 2),
+            ("30.0%0.0", 4),
+            ("30.0%0", 4),
+        ] {
+            let expr = get_expr(input);
+            let mut inter = new_interpreter();
+            assert_eq!(
+                Err(RuntimeError::DivisionByZero(Loc::new(0, col),)),
+                inter.evaluate(&expr)
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_stmt() {
+        let input = "";
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+    }
+
+    #[test]
+    fn test_var_stmt() {
+        let input = r#"var hello = "world";"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("world".into()), env_get(&inter, "hello"));
+    }
+
+    #[test]
+    fn test_var_assignment() {
+        let input = r#"var hello = "world";
     hello = 1 + 1;
     hello = "Earth";"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("Earth".into()), env_get(&inter, "hello"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("Earth".into()), env_get(&inter, "hello"));
+    }
 
-#[test]
-fn test_var_assignment2() {
-    let input = r#"var i = 0;
+    #[test]
+    fn test_var_assignment2() {
+        let input = r#"var i = 0;
     i += 1;
     i -= 3;
     i *= -15;
@@ -407,141 +466,141 @@ fn test_var_assignment2() {
     var l = k--;
     var m = j++;
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(2.into()), env_get(&inter, "i"));
-    assert_eq!(Ok(4.into()), env_get(&inter, "j"));
-    assert_eq!(Ok(1.into()), env_get(&inter, "k"));
-    assert_eq!(Ok(2.into()), env_get(&inter, "l"));
-    assert_eq!(Ok(3.into()), env_get(&inter, "m"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(2.into()), env_get(&inter, "i"));
+        assert_eq!(Ok(4.into()), env_get(&inter, "j"));
+        assert_eq!(Ok(1.into()), env_get(&inter, "k"));
+        assert_eq!(Ok(2.into()), env_get(&inter, "l"));
+        assert_eq!(Ok(3.into()), env_get(&inter, "m"));
+    }
 
-#[test]
-fn test_var_assignment_in_block() {
-    let input = r#"var hello = "world";
+    #[test]
+    fn test_var_assignment_in_block() {
+        let input = r#"var hello = "world";
     {
         1 + 1; // This does nothing
         hello = "Earth";
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("Earth".into()), env_get(&inter, "hello"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("Earth".into()), env_get(&inter, "hello"));
+    }
 
-#[test]
-fn test_var_shadowing() {
-    let input = r#"var hello = "world";
+    #[test]
+    fn test_var_shadowing() {
+        let input = r#"var hello = "world";
     {
         var hello = "Earth";
         hello = "planet";
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("world".into()), env_get(&inter, "hello"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("world".into()), env_get(&inter, "hello"));
+    }
 
-#[test]
-fn test_if_stmt_true() {
-    let input = r#"var hello;
+    #[test]
+    fn test_if_stmt_true() {
+        let input = r#"var hello;
     if (3 > 2) {
         hello = "world";
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("world".into()), env_get(&inter, "hello"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("world".into()), env_get(&inter, "hello"));
+    }
 
-#[test]
-fn test_if_stmt_false() {
-    let input = r#"var hello;
+    #[test]
+    fn test_if_stmt_false() {
+        let input = r#"var hello;
     if (3 < 2) {
         hello = "world";
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(Nil), env_get(&inter, "hello"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(Nil), env_get(&inter, "hello"));
+    }
 
-#[test]
-fn test_if_else_stmt_true() {
-    let input = r#"var hello;
+    #[test]
+    fn test_if_else_stmt_true() {
+        let input = r#"var hello;
     if (3 > 2) {
         hello = "world";
     } else {
         hello = "Earth";
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("world".into()), env_get(&inter, "hello"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("world".into()), env_get(&inter, "hello"));
+    }
 
-#[test]
-fn test_if_else_stmt_false() {
-    let input = r#"var hello;
+    #[test]
+    fn test_if_else_stmt_false() {
+        let input = r#"var hello;
     if (3 < 2) {
         hello = "world";
     } else {
         hello = "Earth";
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("Earth".into()), env_get(&inter, "hello"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("Earth".into()), env_get(&inter, "hello"));
+    }
 
-#[test]
-fn test_var_print() {
-    let input = r#"var hello = "world";
+    #[test]
+    fn test_var_print() {
+        let input = r#"var hello = "world";
     print hello;"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+    }
 
-#[test]
-fn test_while_stmt() {
-    let input = r#"var i = 0;
+    #[test]
+    fn test_while_stmt() {
+        let input = r#"var i = 0;
     while (i < 10) {
         i += 1;
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(10.into()), env_get(&inter, "i"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(10.into()), env_get(&inter, "i"));
+    }
 
-#[test]
-fn test_for_stmt() {
-    let input = r#"var i;
+    #[test]
+    fn test_for_stmt() {
+        let input = r#"var i;
     for (i = 0; i < 10; ++i) {}"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(10.into()), env_get(&inter, "i"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(10.into()), env_get(&inter, "i"));
+    }
 
-#[test]
-fn test_for_break_while_stmt() {
-    let input = r#"var i = 10;
+    #[test]
+    fn test_for_break_while_stmt() {
+        let input = r#"var i = 10;
     while (true) {
         if (i == 0) break;
         --i;
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(0.into()), env_get(&inter, "i"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(0.into()), env_get(&inter, "i"));
+    }
 
-#[test]
-fn test_for_break_for_stmt() {
-    let input = r#"var i;
+    #[test]
+    fn test_for_break_for_stmt() {
+        let input = r#"var i;
     for (i = 10; ; --i) {
         if (i == 0) break;
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(0.into()), env_get(&inter, "i"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(0.into()), env_get(&inter, "i"));
+    }
 
-#[test]
-fn test_for_continue_while_stmt() {
-    let input = r#"var evens = "";
+    #[test]
+    fn test_for_continue_while_stmt() {
+        let input = r#"var evens = "";
     var i = 0;
     while (i < 10) {
         if (i % 2 == 1) {
@@ -552,65 +611,793 @@ fn test_for_continue_while_stmt() {
         evens += str(i);
         i++;
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("02468".into()), env_get(&inter, "evens"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("02468".into()), env_get(&inter, "evens"));
+    }
 
-#[test]
-fn test_for_continue_for_stmt() {
-    let input = r#"var evens = "";
+    #[test]
+    fn test_for_continue_for_stmt() {
+        let input = r#"var evens = "";
     for (var i = 0; i < 10; i++) {
         if (i % 2 == 1) continue;
         evens += str(i);
     }"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("02468".into()), env_get(&inter, "evens"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("02468".into()), env_get(&inter, "evens"));
+    }
+
+    #[test]
+    fn test_for_in_stmt() {
+        let input = r#"
+    var sum = 0;
+    for (var x in [1, 2, 3]) sum += x;
+    var letters = "";
+    for (var ch in "abc") letters = ch + letters;
+    var keys = "";
+    for (var key in {"a": 1, "b": 2}) keys += key;
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(6.into()), env_get(&inter, "sum"));
+        assert_eq!(Ok("cba".into()), env_get(&inter, "letters"));
+        assert_eq!(Ok("ab".into()), env_get(&inter, "keys"));
+    }
+
+    #[test]
+    fn test_for_in_break_continue() {
+        let input = r#"
+    var evens = "";
+    for (var i in [0, 1, 2, 3, 4, 5, 6, 7]) {
+        if (i == 7) break;
+        if (i % 2 == 1) continue;
+        evens += str(i);
+    }
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("0246".into()), env_get(&inter, "evens"));
+    }
+
+    #[test]
+    fn test_for_in_closures() {
+        let input = r#"
+    var funs = [];
+    for (var x in [1, 2, 3]) funs.push(fun() { return x; });
+    var result = str(funs.map(fun(f) { return f(); }));
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("[1, 2, 3]".into()), env_get(&inter, "result"));
+    }
+
+    #[test]
+    fn test_for_in_iterator_protocol() {
+        let input = r#"
+    class Range {
+        init(start, end) {
+            this.start = start;
+            this.end = end;
+        }
+
+        iterator() {
+            return RangeIterator(this.start, this.end);
+        }
+    }
+
+    class RangeIterator {
+        init(current, end) {
+            this.current = current;
+            this.end = end;
+        }
+
+        hasNext() {
+            return this.current < this.end;
+        }
+
+        next() {
+            this.current += 1;
+            return this.current - 1;
+        }
+    }
+
+    var sum = 0;
+    for (var i in Range(0, 5)) sum += i;
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(10.into()), env_get(&inter, "sum"));
+    }
+
+    #[test]
+    fn test_for_in_not_iterable() {
+        let input = r#"for (var x in 10) print x;"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::NotIterable(
+                Loc::new(0, 14),
+                String::from(INT)
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_throw_catch() {
+        let input = r#"
+    var caught;
+    var after = false;
+    try {
+        throw "oops";
+        after = true;
+    } catch (e) {
+        caught = e;
+    }
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("oops".into()), env_get(&inter, "caught"));
+        assert_eq!(Ok(false.into()), env_get(&inter, "after"));
+    }
+
+    #[test]
+    fn test_throw_through_functions() {
+        let input = r#"
+    fun fail(x) {
+        throw Error("bad value " + str(x));
+    }
+
+    var message;
+    var kind;
+    try {
+        [1, 2].map(fail);
+    } catch (e) {
+        message = e.message;
+        kind = e.kind;
+    }
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("bad value 1".into()), env_get(&inter, "message"));
+        assert_eq!(Ok("Error".into()), env_get(&inter, "kind"));
+    }
+
+    #[test]
+    fn test_catch_runtime_errors() {
+        let input = r#"
+    var kinds = [];
+    var message;
+    var line;
+    try { [1, 2][5]; } catch (e) { kinds.push(e.kind); message = e.message; line = e.line; }
+    try { 1 / 0; } catch (e) { kinds.push(e.kind); }
+    try { 1 + nil; } catch (e) { kinds.push(e.kind); }
+    try { Map()["b"]; } catch (e) { kinds.push(e.kind); }
+    try { undefined; } catch (e) { kinds.push(e.kind); }
+    kinds = str(kinds);
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(
+            Ok("[IndexError, ArithmeticError, TypeError, KeyError, NameError]".into()),
+            env_get(&inter, "kinds")
+        );
+        assert_eq!(
+            Ok("Index 5 out of bounds. Size 2".into()),
+            env_get(&inter, "message")
+        );
+        assert_eq!(Ok(5.into()), env_get(&inter, "line"));
+    }
+
+    #[test]
+    fn test_finally() {
+        let input = r#"
+    var log = "";
+    fun run() {
+        try {
+            log += "try ";
+            return 1;
+        } finally {
+            log += "finally";
+        }
+    }
+    var result = run();
+    for (var i in [1, 2]) {
+        try {
+            break;
+        } finally {
+            log += "!";
+        }
+    }
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(1.into()), env_get(&inter, "result"));
+        assert_eq!(Ok("try finally!".into()), env_get(&inter, "log"));
+    }
+
+    #[test]
+    fn test_error_subclasses() {
+        let input = r#"
+    class ValidationError < Error {
+        init(field) {
+            super.init("Invalid " + field);
+            this.field = field;
+        }
+    }
+
+    var message;
+    var kind;
+    try {
+        throw ValidationError("name");
+    } catch (e) {
+        message = e.message;
+        kind = e.kind;
+    }
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("Invalid name".into()), env_get(&inter, "message"));
+        assert_eq!(Ok("ValidationError".into()), env_get(&inter, "kind"));
+    }
+
+    #[test]
+    fn test_uncaught_exception() {
+        let input = r#"
+    fun fail() {
+        throw TypeError("wrong type");
+    }
+    try {
+        fail();
+    } finally {
+        print "cleanup";
+    }
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::UncaughtException(
+                Loc::new(2, 8),
+                String::from("TypeError: wrong type")
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_traceback() {
+        let input = r#"
+    fun inner() { return 1 + nil; }
+    class Runner { run() { return inner(); } }
+    fun outer() { return Runner().run(); }
+    outer();
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        let error = inter.interpret(&stmts).unwrap_err();
+        let traceback = inter.traceback(error);
+        assert_eq!(
+            vec![
+                StackFrame::new("outer", Loc::new(4, 10)),
+                StackFrame::new("run", Loc::new(3, 38)),
+                StackFrame::new("inner", Loc::new(2, 40)),
+            ],
+            traceback.frames
+        );
+        assert_eq!(Loc::new(1, 27), traceback.error.loc());
+    }
+
+    #[test]
+    fn test_traceback_cleared_when_caught() {
+        let input = r#"
+    fun fail() { throw "oops"; }
+    try { fail(); } catch (e) {}
+    [1][2];
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        let error = inter.interpret(&stmts).unwrap_err();
+        assert_eq!(Vec::<StackFrame>::new(), inter.traceback(error).frames);
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let input = r#"
+    fun recurse() { recurse(); }
+    recurse();
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        inter.max_call_depth = 50;
+        assert_eq!(
+            Err(RuntimeError::StackOverflow(Loc::new(1, 28), 50)),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_catch_stack_overflow() {
+        let input = r#"
+    var depth = 0;
+    fun recurse() { depth += 1; recurse(); }
+    var kind;
+    try { recurse(); } catch (e) { kind = e.kind; }
+    var after = str([1, 2].map(fun (x) { return x * 2; }));
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        inter.max_call_depth = 50;
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(Integer(50)), env_get(&inter, "depth"));
+        assert_eq!(Ok("RecursionError".into()), env_get(&inter, "kind"));
+        assert_eq!(Ok("[2, 4]".into()), env_get(&inter, "after"));
+    }
+
+    #[test]
+    fn test_gc_collects_cycles() {
+        let input = r#"
+    class Node { init() { this.next = this; } }
+    fun make() {
+        var arr = [];
+        arr.push(arr);
+        var map = {};
+        map["self"] = map;
+        var node = Node();
+        fun recurse() { return recurse; }
+        return node;
+    }
+    make();
+    var first = gc();
+    var second = gc();
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        match env_get(&inter, "first") {
+            Ok(Integer(collected)) => assert!(collected > 0),
+            val => panic!("Expected a count. Got: {:?}", val),
+        }
+        assert_eq!(Ok(Integer(0)), env_get(&inter, "second"));
+    }
+
+    #[test]
+    fn test_gc_keeps_reachable_cycles() {
+        let input = r#"
+    class Node {
+        init() {
+            this.next = this;
+            this.get = fun() { return this; };
+        }
+    }
+    fun counter() {
+        var count = 0;
+        fun inc() { count += 1; return count; }
+        return inc;
+    }
+    var node = Node();
+    var inc = counter();
+    gc();
+    var same = node.next.get() == node;
+    inc();
+    var count = inc();
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(Boolean(true)), env_get(&inter, "same"));
+        assert_eq!(Ok(Integer(2)), env_get(&inter, "count"));
+    }
+
+    fn register_sum(inter: &mut Interpreter) {
+        inter.register_fn("sum", Arity::Variadic(1), |_inter, args, loc| {
+            args.into_iter()
+                .try_fold(Integer(0), |acc, arg| acc.add(arg, loc))
+        });
+    }
+
+    #[test]
+    fn test_host_function() {
+        let input = r#"
+    var one = sum(1);
+    var many = sum(1, 2, 3, 4);
+    var mapped = str([1, 2].map(fun(x) { return sum(x, x); }));
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        register_sum(&mut inter);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(Integer(1)), env_get(&inter, "one"));
+        assert_eq!(Ok(Integer(10)), env_get(&inter, "many"));
+        assert_eq!(Ok("[2, 4]".into()), env_get(&inter, "mapped"));
+    }
+
+    #[test]
+    fn test_host_function_arity() {
+        let (stmts, mut inter) = get_stmts("sum();");
+        register_sum(&mut inter);
+        assert_eq!(
+            Err(RuntimeError::MismatchingArity(
+                Loc::new(0, 4),
+                Arity::Variadic(1),
+                0
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_host_function_error() {
+        let input = r#"
+    var kind;
+    try { sum(1, "a"); } catch (e) { kind = e.kind; }
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        register_sum(&mut inter);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("TypeError".into()), env_get(&inter, "kind"));
+    }
+
+    #[test]
+    fn test_host_function_in_module() {
+        let input = r#"
+    import { total } from "lib.lox";
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        register_sum(&mut inter);
+        with_modules(&mut inter, &[("lib.lox", "var total = sum(2, 3);")]);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(Integer(5)), env_get(&inter, "total"));
+    }
+
+    struct Counter;
+
+    impl HostClass for Counter {
+        fn name(&self) -> &str {
+            "Counter"
+        }
+
+        fn arity(&self) -> Arity {
+            Arity::Range(0, 1)
+        }
+
+        fn construct(
+            &self,
+            _inter: &mut Interpreter,
+            args: Vec<Value>,
+            loc: Loc,
+        ) -> Result<Box<dyn std::any::Any>, RuntimeError> {
+            match args.into_iter().next() {
+                None => Ok(Box::new(0i64)),
+                Some(Integer(start)) => Ok(Box::new(start)),
+                Some(val) => Err(RuntimeError::expected_type(loc, INT, val)),
+            }
+        }
+
+        fn methods(&self) -> Vec<NativeMethod> {
+            vec![NativeMethod::new(
+                "inc",
+                1,
+                |_inter, args, instance, loc| {
+                    let count = instance.data_mut::<i64>().unwrap();
+                    match &args[0] {
+                        Integer(by) => *count += by,
+                        val => return Err(RuntimeError::expected_type(loc, INT, val.clone())),
+                    }
+                    Ok(Nil)
+                },
+            )]
+        }
+
+        fn getters(&self) -> Vec<NativeMethod> {
+            vec![NativeMethod::new(
+                "value",
+                0,
+                |_inter, _args, instance, _loc| Ok(Integer(*instance.data::<i64>().unwrap())),
+            )]
+        }
+
+        fn static_methods(&self) -> Vec<NativeMethod> {
+            vec![NativeMethod::new(
+                "describe",
+                0,
+                |_inter, _args, _instance, _loc| Ok("counts things".into()),
+            )]
+        }
+    }
+
+    #[test]
+    fn test_host_class() {
+        let input = r#"
+    var counter = Counter(5);
+    counter.inc(2);
+    Counter.inc(counter, 3);
+    var value = counter.value;
+    var empty = Counter().value;
+    var printed = str(counter);
+    var described = Counter.describe();
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        inter.register_class(Counter);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(Integer(10)), env_get(&inter, "value"));
+        assert_eq!(Ok(Integer(0)), env_get(&inter, "empty"));
+        assert_eq!(
+            Ok("<instance of Counter>".into()),
+            env_get(&inter, "printed")
+        );
+        assert_eq!(Ok("counts things".into()), env_get(&inter, "described"));
+    }
+
+    #[test]
+    fn test_host_class_constructor_error() {
+        let (stmts, mut inter) = get_stmts(r#"Counter("a");"#);
+        inter.register_class(Counter);
+        assert_eq!(
+            Err(RuntimeError::ExpectedType(
+                Loc::new(0, 11),
+                String::from(INT),
+                String::from(STRING)
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_host_call() {
+        let input = r#"
+    var calls = 0;
+    fun handle(event) { calls += 1; return event * factor; }
+    fun fail() { throw "oops"; }
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        inter.set_global("factor", Integer(3));
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+
+        let handle = inter.get_global("handle").unwrap();
+        assert_eq!(Ok(Integer(6)), inter.call(handle.clone(), vec![Integer(2)]));
+        assert_eq!(
+            Ok(Integer(12)),
+            inter.call(handle.clone(), vec![Integer(4)])
+        );
+        assert_eq!(Some(Integer(2)), inter.get_global("calls"));
+        assert_eq!(
+            Err(RuntimeError::MismatchingArity(
+                Loc::default(),
+                Arity::Fixed(1),
+                0
+            )),
+            inter.call(handle, vec![])
+        );
+
+        let fail = inter.get_global("fail").unwrap();
+        assert_eq!(
+            Err(RuntimeError::UncaughtException(
+                Loc::new(3, 17),
+                String::from("oops")
+            )),
+            inter.call(fail, vec![])
+        );
+        assert_eq!(None, inter.get_global("missing"));
+    }
+
+    #[test]
+    fn test_value_conversions() {
+        let inter = new_interpreter();
+        let loc = Loc::default();
+        let array = vec![1, 2, 3].into_value(&inter);
+        assert_eq!(Ok(vec![1, 2, 3]), Vec::<i64>::from_value(array, loc));
+        assert_eq!(Ok(2.5), f64::from_value(Float(2.5), loc));
+        assert_eq!(Ok(2.0), f64::from_value(Integer(2), loc));
+        assert_eq!(Ok(None), Option::<String>::from_value(Nil, loc));
+        assert_eq!(
+            Ok(Some(String::from("a"))),
+            Option::<String>::from_value("a".into(), loc)
+        );
+        assert_eq!(Nil, None::<bool>.into_value(&inter));
+        assert_eq!(
+            Err(RuntimeError::ExpectedType(
+                loc,
+                String::from(INT),
+                String::from(STRING)
+            )),
+            i64::from_value("1".into(), loc)
+        );
+        assert_eq!(
+            Err(RuntimeError::generic(loc, "Integer -1 does not fit in u32")),
+            u32::from_value(Integer(-1), loc)
+        );
+    }
+
+    #[test]
+    fn test_print_output() {
+        let input = r#"
+    print 1;
+    print "two";
+    print [3, nil];
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        let output = SharedBuffer::default();
+        inter.output = Box::new(output.clone());
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!("1\ntwo\n[3, nil]\n", output.contents());
+    }
+
+    #[test]
+    fn test_resolution_across_runs() {
+        // Like REPL lines, every input starts at line 0, so the `a` read by the
+        // closure and the one printed later are at the same location.
+        let mut inter = new_interpreter();
+        let output = SharedBuffer::default();
+        inter.output = Box::new(output.clone());
+        let inputs = [
+            "var get;",
+            "{ var a = 1; get = fun() { return a; }; }",
+            "{ var b = 2; var a = 3;     print a; }",
+            "var result = get();",
+        ];
+        for input in &inputs {
+            let tokens = get_tokens(input);
+            let stmts = Parser::new(&tokens).parse().unwrap();
+            Resolver::new().resolve(&stmts).unwrap();
+            assert_eq!(Ok(()), inter.interpret(&stmts));
+        }
+
+        assert_eq!("3\n", output.contents());
+        assert_eq!(Ok(Value::Integer(1)), env_get(&inter, "result"));
+    }
+
+    #[test]
+    fn test_module_warnings_go_to_diagnostics() {
+        let (stmts, mut inter) = get_stmts(r#"import "lib.lox" as lib;"#);
+        let diagnostics = SharedBuffer::default();
+        inter.diagnostics = Box::new(diagnostics.clone());
+        with_modules(&mut inter, &[("lib.lox", "{ var unused = 1; }")]);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        let contents = diagnostics.contents();
+        assert!(contents.contains("Unused variable 'unused'"));
+        assert!(contents.contains("{ var unused = 1; }"));
+    }
+
+    #[test]
+    fn test_import_module() {
+        let input = r#"
+    import "math.lox" as math;
+    var sum = math.add(math.one, 2);
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        with_modules(
+            &mut inter,
+            &[("math.lox", "var one = 1; fun add(a, b) { return a + b; }")],
+        );
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(3.into()), env_get(&inter, "sum"));
+    }
+
+    #[test]
+    fn test_module_changes_are_visible() {
+        let input = r#"
+    import "counter.lox" as counter;
+    var before = counter.x;
+    counter.inc();
+    var after = counter.x;
+    counter.x = 20;
+    var got = counter.get();
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        let counter = "var x = 10; fun inc() { x = x + 1; } fun get() { return x; }";
+        with_modules(&mut inter, &[("counter.lox", counter)]);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(10.into()), env_get(&inter, "before"));
+        assert_eq!(Ok(11.into()), env_get(&inter, "after"));
+        assert_eq!(Ok(20.into()), env_get(&inter, "got"));
+    }
+
+    #[test]
+    fn test_import_names() {
+        let input = r#"
+    var secret = 0;
+    import { getSecret, Box } from "lib/secret.lox";
+    var got = getSecret();
+    var boxed = Box(5).value;
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        with_modules(
+            &mut inter,
+            &[
+                (
+                    "lib/secret.lox",
+                    r#"import "box.lox" as box;
+                var secret = 42;
+                fun getSecret() { return secret; }
+                var Box = box.Box;"#,
+                ),
+                (
+                    "lib/box.lox",
+                    "class Box { init(value) { this.value = value; } }",
+                ),
+            ],
+        );
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(42.into()), env_get(&inter, "got"));
+        assert_eq!(Ok(5.into()), env_get(&inter, "boxed"));
+        assert_eq!(Ok(0.into()), env_get(&inter, "secret"));
+    }
+
+    #[test]
+    fn test_import_runs_once() {
+        let input = r#"
+    import "counter.lox" as first;
+    first.count += 1;
+    import "counter.lox" as second;
+    var count = second.count;
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        with_modules(&mut inter, &[("counter.lox", "var count = 0;")]);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(1.into()), env_get(&inter, "count"));
+    }
 
-#[test]
-fn test_function_stmt() {
-    let input = r#"
+    #[test]
+    fn test_import_errors() {
+        let input = r#"
+    var kinds = [];
+    try { import "missing.lox" as missing; } catch (e) { kinds.push(e.kind); }
+    try { import { nope } from "empty.lox"; } catch (e) { kinds.push(e.kind); }
+    try { import "cycle.lox" as cycle; } catch (e) { kinds.push(e.kind); }
+    kinds = str(kinds);
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        with_modules(
+            &mut inter,
+            &[
+                ("empty.lox", ""),
+                ("cycle.lox", r#"import "cycle.lox" as cycle;"#),
+            ],
+        );
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(
+            Ok("[ImportError, ImportError, ImportError]".into()),
+            env_get(&inter, "kinds")
+        );
+    }
+
+    #[test]
+    fn test_function_stmt() {
+        let input = r#"
     fun my_fun() {}
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    let fun = env_get(&inter, "my_fun").unwrap();
-    match fun {
-        Value::Callable(Callable::Function(fun)) => {
-            assert_eq!(fun.name, Some(String::from("my_fun")));
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        let fun = env_get(&inter, "my_fun").unwrap();
+        match fun {
+            Value::Callable(Callable::Function(fun)) => {
+                assert_eq!(fun.name, Some(String::from("my_fun")));
+            }
+            Value::Callable(Callable::Closure(fun)) => {
+                assert_eq!(fun.name(), Some("my_fun"));
+            }
+            val => panic!("Not a function! {:?}", val),
         }
-        val => panic!("Not a function! {:?}", val),
     }
-}
 
-#[test]
-fn test_function_call() {
-    let input = r#"
+    #[test]
+    fn test_function_call() {
+        let input = r#"
     fun salute(name) { return "Hello, " + name; }
     var salutation = salute("World!");
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("Hello, World!".into()), env_get(&inter, "salutation"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("Hello, World!".into()), env_get(&inter, "salutation"));
+    }
 
-#[test]
-fn test_function_call_native() {
-    let input = r#"
+    #[test]
+    fn test_function_call_native() {
+        let input = r#"
     var time = clock();
     var age = str(23);
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert!(matches!(env_get(&inter, "time").unwrap(), Integer(_)));
-    assert_eq!(Ok("23".into()), env_get(&inter, "age"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert!(matches!(env_get(&inter, "time").unwrap(), Integer(_)));
+        assert_eq!(Ok("23".into()), env_get(&inter, "age"));
+    }
 
-#[test]
-fn test_local_function() {
-    let input = r#"
+    #[test]
+    fn test_local_function() {
+        let input = r#"
     fun outer() {
         fun inner() {
             return "Inner!";
@@ -622,14 +1409,14 @@ fn test_local_function() {
     var inner = outer();
     var res = inner();
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("Inner!".into()), env_get(&inter, "res"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("Inner!".into()), env_get(&inter, "res"));
+    }
 
-#[test]
-fn test_closures() {
-    let input = r#"
+    #[test]
+    fn test_closures() {
+        let input = r#"
     var global_get;
     var global_set;
 
@@ -653,15 +1440,38 @@ fn test_closures() {
     global_set();
     var after = global_get();
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("initial".into()), env_get(&inter, "before"));
-    assert_eq!(Ok("updated".into()), env_get(&inter, "after"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("initial".into()), env_get(&inter, "before"));
+        assert_eq!(Ok("updated".into()), env_get(&inter, "after"));
+    }
 
-#[test]
-fn test_anon_function() {
-    let input = r#"
+    #[test]
+    fn test_closures_share_open_variables() {
+        let input = r#"
+    fun outer() {
+        var count = 0;
+        fun inc() { count += 1; }
+        inc();
+        inc();
+        var seen = count;
+        inc();
+        return [seen, fun() { return count; }];
+    }
+
+    var res = outer();
+    var seen = res[0];
+    var closed = res[1]();
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(Integer(2)), env_get(&inter, "seen"));
+        assert_eq!(Ok(Integer(3)), env_get(&inter, "closed"));
+    }
+
+    #[test]
+    fn test_anon_function() {
+        let input = r#"
     fun outer() {
         return fun () {
             return "Inner!";
@@ -671,24 +1481,24 @@ fn test_anon_function() {
     var inner = outer();
     var res = inner();
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("Inner!".into()), env_get(&inter, "res"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("Inner!".into()), env_get(&inter, "res"));
+    }
 
-#[test]
-fn test_local_resolution() {
-    let input = r#" var global_x;
+    #[test]
+    fn test_local_resolution() {
+        let input = r#" var global_x;
     {var x = 1; {{{{{ x = 3; { global_x = x; }}}}}}}
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(3.into()), env_get(&inter, "global_x"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(3.into()), env_get(&inter, "global_x"));
+    }
 
-#[test]
-fn test_locals() {
-    let input = r#"
+    #[test]
+    fn test_locals() {
+        let input = r#"
     var global_x;
     var global_y;
     var global_z;
@@ -707,44 +1517,44 @@ fn test_locals() {
         global_z = x;
     }
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(3.into()), env_get(&inter, "global_x"));
-    assert_eq!(Ok(true.into()), env_get(&inter, "global_y"));
-    assert_eq!(Ok(5.into()), env_get(&inter, "global_z"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(3.into()), env_get(&inter, "global_x"));
+        assert_eq!(Ok(true.into()), env_get(&inter, "global_y"));
+        assert_eq!(Ok(5.into()), env_get(&inter, "global_z"));
+    }
 
-#[test]
-fn test_class() {
-    let input = r#"
+    #[test]
+    fn test_class() {
+        let input = r#"
     class MyClass {}
     var inst = MyClass();
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert!(matches!(
-        env_get(&inter, "MyClass"),
-        Ok(Value::Callable(Callable::Class(_)))
-    ));
-    assert!(matches!(env_get(&inter, "inst"), Ok(Value::Instance(_))));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert!(matches!(
+            env_get(&inter, "MyClass"),
+            Ok(Value::Callable(Callable::Class(_)))
+        ));
+        assert!(matches!(env_get(&inter, "inst"), Ok(Value::Instance(_))));
+    }
 
-#[test]
-fn test_class_instance_fields() {
-    let input = r#"
+    #[test]
+    fn test_class_instance_fields() {
+        let input = r#"
     class MyClass {}
     var inst = MyClass();
     inst.x = 3;
     var x = inst.x;
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(3.into()), env_get(&inter, "x"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(3.into()), env_get(&inter, "x"));
+    }
 
-#[test]
-fn test_class_methods() {
-    let input = r#"
+    #[test]
+    fn test_class_methods() {
+        let input = r#"
     class MyClass {
         a_method() { return 1; }
         b_method(name) { return "Hello, " + name; }
@@ -753,15 +1563,15 @@ fn test_class_methods() {
     var x = inst.a_method();
     var y = inst.b_method("World");
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(1.into()), env_get(&inter, "x"));
-    assert_eq!(Ok("Hello, World".into()), env_get(&inter, "y"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(1.into()), env_get(&inter, "x"));
+        assert_eq!(Ok("Hello, World".into()), env_get(&inter, "y"));
+    }
 
-#[test]
-fn test_class_static_methods() {
-    let input = r#"
+    #[test]
+    fn test_class_static_methods() {
+        let input = r#"
     class MyClass {
         class a_method() { return 1; }
         class b_method(name) { return "Hello, " + name; }
@@ -769,15 +1579,15 @@ fn test_class_static_methods() {
     var x = MyClass.a_method();
     var y = MyClass.b_method("World");
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(1.into()), env_get(&inter, "x"));
-    assert_eq!(Ok("Hello, World".into()), env_get(&inter, "y"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(1.into()), env_get(&inter, "x"));
+        assert_eq!(Ok("Hello, World".into()), env_get(&inter, "y"));
+    }
 
-#[test]
-fn test_class_this() {
-    let input = r#"
+    #[test]
+    fn test_class_this() {
+        let input = r#"
     class MyClass {
         get_x() { return this.x; }
         set_x(x) { this.x = x; }
@@ -786,14 +1596,14 @@ fn test_class_this() {
     inst.set_x(3);
     var x = inst.get_x();
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(3.into()), env_get(&inter, "x"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(3.into()), env_get(&inter, "x"));
+    }
 
-#[test]
-fn test_class_init() {
-    let input = r#"
+    #[test]
+    fn test_class_init() {
+        let input = r#"
     class MyClass {
         init(x) { this.x = x; }
         get_x() { return this.x; }
@@ -806,16 +1616,16 @@ fn test_class_init() {
     inst.init(-1);
     var z = inst.get_x();
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(1.into()), env_get(&inter, "x"));
-    assert_eq!(Ok(3.into()), env_get(&inter, "y"));
-    assert_eq!(Ok((-1).into()), env_get(&inter, "z"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(1.into()), env_get(&inter, "x"));
+        assert_eq!(Ok(3.into()), env_get(&inter, "y"));
+        assert_eq!(Ok((-1).into()), env_get(&inter, "z"));
+    }
 
-#[test]
-fn test_class_out_of_scope() {
-    let input = r#"var inst;
+    #[test]
+    fn test_class_out_of_scope() {
+        let input = r#"var inst;
     {
         class MyClass {
             foo() { return "bar"; }
@@ -825,15 +1635,15 @@ fn test_class_out_of_scope() {
 
     var foo = inst.foo();
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert!(matches!(env_get(&inter, "inst"), Ok(Value::Instance(_))));
-    assert_eq!(Ok("bar".into()), env_get(&inter, "foo"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert!(matches!(env_get(&inter, "inst"), Ok(Value::Instance(_))));
+        assert_eq!(Ok("bar".into()), env_get(&inter, "foo"));
+    }
 
-#[test]
-fn test_class_getters() {
-    let input = r#"
+    #[test]
+    fn test_class_getters() {
+        let input = r#"
     class Square {
         init(x) { this.x = x; }
         area { return this.x * this.x; }
@@ -841,14 +1651,14 @@ fn test_class_getters() {
     var inst = Square(5);
     var area = inst.area;
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(25.into()), env_get(&inter, "area"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(25.into()), env_get(&inter, "area"));
+    }
 
-#[test]
-fn test_class_getter_init() {
-    let input = r#"
+    #[test]
+    fn test_class_getter_init() {
+        let input = r#"
     class MyClass {
         init { this.x = 5; return 3; }
     }
@@ -856,15 +1666,15 @@ fn test_class_getter_init() {
     var y = inst.init;
     var x = inst.x;
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(5.into()), env_get(&inter, "x"));
-    assert_eq!(Ok(3.into()), env_get(&inter, "y"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(5.into()), env_get(&inter, "x"));
+        assert_eq!(Ok(3.into()), env_get(&inter, "y"));
+    }
 
-#[test]
-fn test_class_inheritance() {
-    let input = r#"
+    #[test]
+    fn test_class_inheritance() {
+        let input = r#"
     class Rectangle {
         init(x, y) {
             this.x = x;
@@ -884,15 +1694,15 @@ fn test_class_inheritance() {
     var x = square.x;
     var area = square.area;
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(3.into()), env_get(&inter, "x"));
-    assert_eq!(Ok(9.into()), env_get(&inter, "area"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(3.into()), env_get(&inter, "x"));
+        assert_eq!(Ok(9.into()), env_get(&inter, "area"));
+    }
 
-#[test]
-fn test_class_inheritance_method_shadowing() {
-    let input = r#"
+    #[test]
+    fn test_class_inheritance_method_shadowing() {
+        let input = r#"
     class A {
         init() {
             this.x = "";
@@ -916,15 +1726,15 @@ fn test_class_inheritance_method_shadowing() {
     b.method();
     var b_x = b.x;
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok("A".into()), env_get(&inter, "a_x"));
-    assert_eq!(Ok("B".into()), env_get(&inter, "b_x"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("A".into()), env_get(&inter, "a_x"));
+        assert_eq!(Ok("B".into()), env_get(&inter, "b_x"));
+    }
 
-#[test]
-fn test_class_static_wrappers() {
-    let input = r#"
+    #[test]
+    fn test_class_static_wrappers() {
+        let input = r#"
     class A {
         method(param) {
             this.data = param;
@@ -940,112 +1750,112 @@ fn test_class_static_wrappers() {
     var y = A.method(a, 2);
     var y2 = A.getter(a);
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(1.into()), env_get(&inter, "x"));
-    assert_eq!(Ok(1.into()), env_get(&inter, "x2"));
-    assert_eq!(Ok(2.into()), env_get(&inter, "y"));
-    assert_eq!(Ok(2.into()), env_get(&inter, "y2"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(1.into()), env_get(&inter, "x"));
+        assert_eq!(Ok(1.into()), env_get(&inter, "x2"));
+        assert_eq!(Ok(2.into()), env_get(&inter, "y"));
+        assert_eq!(Ok(2.into()), env_get(&inter, "y2"));
+    }
 
-#[test]
-fn test_array_creation() {
-    let input = r#"
+    #[test]
+    fn test_array_creation() {
+        let input = r#"
     var arr = [1, 2, 3];
     var len = arr.length;
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(3.into()), env_get(&inter, "len"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(3.into()), env_get(&inter, "len"));
+    }
 
-#[test]
-fn test_array_get() {
-    let input = r#"
+    #[test]
+    fn test_array_get() {
+        let input = r#"
     var arr = [1, 2, 3];
     var x = arr[1];
     var y = arr.get(2);
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(2.into()), env_get(&inter, "x"));
-    assert_eq!(Ok(3.into()), env_get(&inter, "y"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(2.into()), env_get(&inter, "x"));
+        assert_eq!(Ok(3.into()), env_get(&inter, "y"));
+    }
 
-#[test]
-fn test_array_set() {
-    let input = r#"
+    #[test]
+    fn test_array_set() {
+        let input = r#"
     var arr = [1, 2, 3];
     arr[1] = 10;
     arr.set(0, "string");
     var x = arr[1];
     var y = arr.get(0);
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(10.into()), env_get(&inter, "x"));
-    assert_eq!(Ok("string".into()), env_get(&inter, "y"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(10.into()), env_get(&inter, "x"));
+        assert_eq!(Ok("string".into()), env_get(&inter, "y"));
+    }
 
-#[test]
-fn test_array_push() {
-    let input = r#"
+    #[test]
+    fn test_array_push() {
+        let input = r#"
     var arr = [1, 2, 3];
     arr.push(4);
     var x = arr[3];
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(4.into()), env_get(&inter, "x"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(4.into()), env_get(&inter, "x"));
+    }
 
-#[test]
-fn test_array_pop() {
-    let input = r#"
+    #[test]
+    fn test_array_pop() {
+        let input = r#"
     var arr = [1, 2, 3];
     arr.pop();
     var len = arr.length;
     var x = arr[0];
     var y = arr[1];
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(2.into()), env_get(&inter, "len"));
-    assert_eq!(Ok(1.into()), env_get(&inter, "x"));
-    assert_eq!(Ok(2.into()), env_get(&inter, "y"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(2.into()), env_get(&inter, "len"));
+        assert_eq!(Ok(1.into()), env_get(&inter, "x"));
+        assert_eq!(Ok(2.into()), env_get(&inter, "y"));
+    }
 
-#[test]
-fn test_array_constructor() {
-    let input = r#"
+    #[test]
+    fn test_array_constructor() {
+        let input = r#"
     var arr = Array(3, 1);
     var len = arr.length;
     var x = arr[0];
     var y = arr[1];
     var z = arr[2];
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(3.into()), env_get(&inter, "len"));
-    assert_eq!(Ok(1.into()), env_get(&inter, "x"));
-    assert_eq!(Ok(1.into()), env_get(&inter, "y"));
-    assert_eq!(Ok(1.into()), env_get(&inter, "z"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(3.into()), env_get(&inter, "len"));
+        assert_eq!(Ok(1.into()), env_get(&inter, "x"));
+        assert_eq!(Ok(1.into()), env_get(&inter, "y"));
+        assert_eq!(Ok(1.into()), env_get(&inter, "z"));
+    }
 
-#[test]
-fn test_array_new() {
-    let input = r#"
+    #[test]
+    fn test_array_new() {
+        let input = r#"
     var arr = Array.new();
     var len = arr.length;
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(0.into()), env_get(&inter, "len"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(0.into()), env_get(&inter, "len"));
+    }
 
-#[test]
-fn test_array_static_wrappers() {
-    let input = r#"
+    #[test]
+    fn test_array_static_wrappers() {
+        let input = r#"
     var arr = [];
     Array.push(arr, 1);
     Array.push(arr, 2);
@@ -1053,140 +1863,397 @@ fn test_array_static_wrappers() {
     var len = Array.length(arr);
     var x = arr[0];
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(Ok(()), inter.interpret(&stmts));
-    assert_eq!(Ok(2.into()), env_get(&inter, "len"));
-    assert_eq!(Ok(5.into()), env_get(&inter, "x"));
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(2.into()), env_get(&inter, "len"));
+        assert_eq!(Ok(5.into()), env_get(&inter, "x"));
+    }
 
-#[test]
-fn test_undefined_variable() {
-    let input = r#"hello;"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::UndefinedVariable(
-            Loc::new(0, 0),
-            String::from("hello")
-        )),
-        inter.interpret(&stmts)
-    );
-}
+    #[test]
+    fn test_array_higher_order_methods() {
+        let input = r#"
+    var arr = [3, 1, 4, 1, 5];
+    var doubled = str(arr.map(fun(x) { return x * 2; }));
+    var odd = str(arr.filter(fun(x) { return x % 2 == 1; }));
+    var sum = arr.reduce(fun(acc, x) { return acc + x; }, 0);
+    var indices = 0;
+    arr.forEach(fun(x, i) { indices += i; });
+    var found = arr.find(fun(x) { return x > 3; });
+    var notFound = arr.find(fun(x) { return x > 5; });
+    var foundIndex = arr.findIndex(fun(x, i, a) { return a[i] == 4; });
+    var some = arr.some(fun(x) { return x == 5; });
+    var every = arr.every(fun(x) { return x > 1; });
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("[6, 2, 8, 2, 10]".into()), env_get(&inter, "doubled"));
+        assert_eq!(Ok("[3, 1, 1, 5]".into()), env_get(&inter, "odd"));
+        assert_eq!(Ok(14.into()), env_get(&inter, "sum"));
+        assert_eq!(Ok(10.into()), env_get(&inter, "indices"));
+        assert_eq!(Ok(4.into()), env_get(&inter, "found"));
+        assert_eq!(Ok(Nil), env_get(&inter, "notFound"));
+        assert_eq!(Ok(2.into()), env_get(&inter, "foundIndex"));
+        assert_eq!(Ok(true.into()), env_get(&inter, "some"));
+        assert_eq!(Ok(false.into()), env_get(&inter, "every"));
+    }
 
-#[test]
-fn test_undefined_variable_in_assignment() {
-    let input = r#"hello = "world";"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::UndefinedVariable(
-            Loc::new(0, 0),
-            String::from("hello")
-        )),
-        inter.interpret(&stmts)
-    );
-}
+    #[test]
+    fn test_array_sort() {
+        let input = r#"
+    var numbers = str([3, 1.5, 2, -1].sort());
+    var strings = str(["b", "c", "a"].sort());
+    var desc = str([3, 1, 2].sort(fun(a, b) { return b - a; }));
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("[-1, 1.5, 2, 3]".into()), env_get(&inter, "numbers"));
+        assert_eq!(Ok("[a, b, c]".into()), env_get(&inter, "strings"));
+        assert_eq!(Ok("[3, 2, 1]".into()), env_get(&inter, "desc"));
+    }
 
-#[test]
-fn test_not_a_callable() {
-    let input = r#""world"();"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::NotACallable(
-            Loc::new(0, 8),
-            String::from(STRING)
-        )),
-        inter.interpret(&stmts)
-    );
-}
+    #[test]
+    fn test_array_sort_incomparable() {
+        let input = r#"[1, "a"].sort();"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(unsup_ops("<", INT, STRING, (0, 14))),
+            inter.interpret(&stmts)
+        );
+    }
 
-#[test]
-fn test_too_few_arguments() {
-    let input = r#"str();"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::MismatchingArity(Loc::new(0, 4), 1, 0)),
-        inter.interpret(&stmts)
-    );
-}
+    #[test]
+    fn test_array_methods() {
+        let input = r#"
+    var arr = [1, 2, 3];
+    var reversed = str(arr.reverse());
+    var slice = str(arr.slice(1, 3));
+    var concat = str(arr.concat([4, 5]));
+    var index = arr.indexOf(2);
+    var missing = arr.indexOf(7);
+    var contains = arr.contains(1.0);
+    arr.insert(3, 0);
+    var removed = arr.remove(0);
+    var joined = arr.join("-");
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("[3, 2, 1]".into()), env_get(&inter, "reversed"));
+        assert_eq!(Ok("[2, 1]".into()), env_get(&inter, "slice"));
+        assert_eq!(Ok("[3, 2, 1, 4, 5]".into()), env_get(&inter, "concat"));
+        assert_eq!(Ok(1.into()), env_get(&inter, "index"));
+        assert_eq!(Ok((-1).into()), env_get(&inter, "missing"));
+        assert_eq!(Ok(true.into()), env_get(&inter, "contains"));
+        assert_eq!(Ok(3.into()), env_get(&inter, "removed"));
+        assert_eq!(Ok("2-1-0".into()), env_get(&inter, "joined"));
+    }
 
-#[test]
-fn test_too_many_arguments() {
-    let input = r#"clock(1);"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::MismatchingArity(Loc::new(0, 7), 0, 1)),
-        inter.interpret(&stmts)
-    );
-}
+    #[test]
+    fn test_array_callback_arity() {
+        let input = r#"[1].map(fun(a, b, c, d) { return a; });"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::MismatchingArity(
+                Loc::new(0, 37),
+                Arity::Fixed(4),
+                3
+            )),
+            inter.interpret(&stmts)
+        );
+    }
 
-#[test]
-fn test_mismatching_arity_static_wrapper() {
-    let input = r#"Array.push(1);"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::MismatchingArity(Loc::new(0, 12), 2, 1)),
-        inter.interpret(&stmts)
-    );
-}
+    #[test]
+    fn test_map_creation() {
+        let input = r#"
+    var key = "b";
+    var map = {"a": 1, key: 2, 3: true, 4.5: nil, nil: "nil"};
+    var len = map.length;
+    var empty = Map().length;
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(5.into()), env_get(&inter, "len"));
+        assert_eq!(Ok(0.into()), env_get(&inter, "empty"));
+    }
 
-#[test]
-fn test_no_properties() {
-    let input = r#"1.field;"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::NoProperties(
-            Loc::new(0, 1),
-            String::from(INT)
-        )),
-        inter.interpret(&stmts)
-    );
-}
+    #[test]
+    fn test_map_subscript() {
+        let input = r#"
+    var map = {"a": 1, 2: "two", false: 0.5};
+    map["a"] += 10;
+    map[nil] = "new";
+    var a = map["a"];
+    var two = map[2];
+    var f = map[false];
+    var n = map[nil];
+    var len = map.length;
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(11.into()), env_get(&inter, "a"));
+        assert_eq!(Ok("two".into()), env_get(&inter, "two"));
+        assert_eq!(Ok(0.5.into()), env_get(&inter, "f"));
+        assert_eq!(Ok("new".into()), env_get(&inter, "n"));
+        assert_eq!(Ok(4.into()), env_get(&inter, "len"));
+    }
 
-#[test]
-fn test_undefined_property() {
-    let input = r#"
+    #[test]
+    fn test_map_methods() {
+        let input = r#"
+    var map = Map();
+    map.set("x", 1);
+    map.set("y", 2);
+    map.set("z", 3);
+    var x = map.get("x");
+    var missing = map.get("w");
+    var has_y = map.has("y");
+    var removed = map.remove("y");
+    var has_y_after = map.has("y");
+    var keys = str(map.keys());
+    var values = str(map.values());
+    var entries = str(map.entries());
+    var len = Map.length(map);
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(1.into()), env_get(&inter, "x"));
+        assert_eq!(Ok(Nil), env_get(&inter, "missing"));
+        assert_eq!(Ok(true.into()), env_get(&inter, "has_y"));
+        assert_eq!(Ok(2.into()), env_get(&inter, "removed"));
+        assert_eq!(Ok(false.into()), env_get(&inter, "has_y_after"));
+        assert_eq!(Ok("[x, z]".into()), env_get(&inter, "keys"));
+        assert_eq!(Ok("[1, 3]".into()), env_get(&inter, "values"));
+        assert_eq!(Ok("[[x, 1], [z, 3]]".into()), env_get(&inter, "entries"));
+        assert_eq!(Ok(2.into()), env_get(&inter, "len"));
+    }
+
+    #[test]
+    fn test_map_key_types() {
+        let input = r#"
+    var map = {1: "int", "1": "string", true: "bool", 1.5: "float"};
+    var len = map.length;
+    var int = map[1.0];
+    var float = map[1.5];
+    var zero = {0.0: "zero"}[-0];
+    var keys = str({2.0: nil, 0.5: nil}.keys());
+    var big = {1e300: "big"}[1e300];
+    map[1.0] = "same";
+    var same = map[1];
+    var same_len = map.length;
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(4.into()), env_get(&inter, "len"));
+        assert_eq!(Ok("int".into()), env_get(&inter, "int"));
+        assert_eq!(Ok("float".into()), env_get(&inter, "float"));
+        assert_eq!(Ok("zero".into()), env_get(&inter, "zero"));
+        assert_eq!(Ok("[2, 0.5]".into()), env_get(&inter, "keys"));
+        assert_eq!(Ok("big".into()), env_get(&inter, "big"));
+        assert_eq!(Ok("same".into()), env_get(&inter, "same"));
+        assert_eq!(Ok(4.into()), env_get(&inter, "same_len"));
+    }
+
+    #[test]
+    fn test_float_key_not_found() {
+        let input = r#"var x = {1: "a"}[2.5];"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::KeyNotFound(Loc::new(0, 20), String::from("2.5"))),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_string_methods() {
+        let input = r#"
+    var s = "  Hello, World  ".trim();
+    var len = s.length;
+    var sub = s.substring(7, 12);
+    var index = s.indexOf("World");
+    var missing = s.indexOf("Lox");
+    var contains = s.contains("lo, W");
+    var starts = s.startsWith("Hello");
+    var ends = s.endsWith("Hello");
+    var parts = str(s.split(", "));
+    var upper = s.toUpper();
+    var lower = s.toLower();
+    var replaced = s.replace("l", "L");
+    var repeated = "ab".repeat(3);
+    var chars = str("añb".chars());
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok(12.into()), env_get(&inter, "len"));
+        assert_eq!(Ok("World".into()), env_get(&inter, "sub"));
+        assert_eq!(Ok(7.into()), env_get(&inter, "index"));
+        assert_eq!(Ok((-1).into()), env_get(&inter, "missing"));
+        assert_eq!(Ok(true.into()), env_get(&inter, "contains"));
+        assert_eq!(Ok(true.into()), env_get(&inter, "starts"));
+        assert_eq!(Ok(false.into()), env_get(&inter, "ends"));
+        assert_eq!(Ok("[Hello, World]".into()), env_get(&inter, "parts"));
+        assert_eq!(Ok("HELLO, WORLD".into()), env_get(&inter, "upper"));
+        assert_eq!(Ok("hello, world".into()), env_get(&inter, "lower"));
+        assert_eq!(Ok("HeLLo, WorLd".into()), env_get(&inter, "replaced"));
+        assert_eq!(Ok("ababab".into()), env_get(&inter, "repeated"));
+        assert_eq!(Ok("[a, ñ, b]".into()), env_get(&inter, "chars"));
+    }
+
+    #[test]
+    fn test_string_subscript() {
+        let input = r#"
+    var s = "añb";
+    var x = s[1];
+    var y = s[2];
+    var z = String.length(s);
+    var w = String(12) + "3";
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        assert_eq!(Ok("ñ".into()), env_get(&inter, "x"));
+        assert_eq!(Ok("b".into()), env_get(&inter, "y"));
+        assert_eq!(Ok(3.into()), env_get(&inter, "z"));
+        assert_eq!(Ok("123".into()), env_get(&inter, "w"));
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let input = r#"hello;"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::UndefinedVariable(
+                Loc::new(0, 0),
+                String::from("hello")
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable_in_assignment() {
+        let input = r#"hello = "world";"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::UndefinedVariable(
+                Loc::new(0, 0),
+                String::from("hello")
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_not_a_callable() {
+        let input = r#""world"();"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::NotACallable(
+                Loc::new(0, 8),
+                String::from(STRING)
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_too_few_arguments() {
+        let input = r#"str();"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::MismatchingArity(
+                Loc::new(0, 4),
+                Arity::Fixed(1),
+                0
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_too_many_arguments() {
+        let input = r#"clock(1);"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::MismatchingArity(
+                Loc::new(0, 7),
+                Arity::Fixed(0),
+                1
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_mismatching_arity_static_wrapper() {
+        let input = r#"Array.push(1);"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::MismatchingArity(
+                Loc::new(0, 12),
+                Arity::Fixed(2),
+                1
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_no_properties() {
+        let input = r#"1.field;"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::NoProperties(
+                Loc::new(0, 1),
+                String::from(INT)
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_undefined_property() {
+        let input = r#"
     class MyClass {}
     var inst = MyClass();
     print inst.field;
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::UndefinedProperty(
-            Loc::new(3, 14),
-            String::from("field")
-        )),
-        inter.interpret(&stmts)
-    );
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::UndefinedProperty(
+                Loc::new(3, 14),
+                String::from("field")
+            )),
+            inter.interpret(&stmts)
+        );
+    }
 
-#[test]
-fn test_no_fields() {
-    let input = r#"1.field = 3;"#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::NoFields(Loc::new(0, 1), String::from(INT))),
-        inter.interpret(&stmts)
-    );
-}
+    #[test]
+    fn test_no_fields() {
+        let input = r#"1.field = 3;"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::NoFields(Loc::new(0, 1), String::from(INT))),
+            inter.interpret(&stmts)
+        );
+    }
 
-#[test]
-fn test_superclass_is_not_class() {
-    let input = r#"
+    #[test]
+    fn test_superclass_is_not_class() {
+        let input = r#"
     var A = "a";
     class B < A {}
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::SuperclassIsNotClass(
-            Loc::new(2, 14),
-            String::from(STRING)
-        )),
-        inter.interpret(&stmts)
-    );
-}
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::SuperclassIsNotClass(
+                Loc::new(2, 14),
+                String::from(STRING)
+            )),
+            inter.interpret(&stmts)
+        );
+    }
 
-#[test]
-fn test_incorrect_type_in_static_wrapper() {
-    let input = r#"
+    #[test]
+    fn test_incorrect_type_in_static_wrapper() {
+        let input = r#"
     class A {
         method(param) {
             print param;
@@ -1195,13 +2262,107 @@ fn test_incorrect_type_in_static_wrapper() {
 
     A.method(3, "the param");
     "#;
-    let (stmts, mut inter) = get_stmts(input);
-    assert_eq!(
-        Err(RuntimeError::ExpectedType(
-            Loc::new(7, 27),
-            String::from("A"),
-            String::from(INT)
-        )),
-        inter.interpret(&stmts)
-    );
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::ExpectedType(
+                Loc::new(7, 27),
+                String::from("A"),
+                String::from(INT)
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_invalid_map_key() {
+        let input = r#"var map = {[]: 1};"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::InvalidMapKey(
+                Loc::new(0, 10),
+                String::from(ARRAY)
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_map_key_not_found() {
+        let input = r#"
+    var map = {"a": 1};
+    map["b"];
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::KeyNotFound(
+                Loc::new(2, 11),
+                String::from("b")
+            )),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_string_index_out_of_bounds() {
+        let input = r#""abc"[3];"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::IndexOutOfBounds(Loc::new(0, 7), 3, 3)),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_immutable_string() {
+        let input = r#"var s = "abc"; s[0] = "x";"#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(
+            Err(RuntimeError::ImmutableString(Loc::new(0, 18))),
+            inter.interpret(&stmts)
+        );
+    }
+
+    #[test]
+    fn test_stack_overflow_with_default_limits() {
+        // Each call takes several frames of the tree walker, so this runs out of
+        // the test thread's stack long before the default maximum depth
+        let input = r#"
+    class A {
+        m(n) {
+            for (var i in [1]) {
+                { if (n > 0) { try { return [this.m(n + 1)][0]; } finally {} } }
+            }
+        }
+    }
+    fun f(n) { return f(n + 1); }
+    var kinds = [];
+    try { f(0); } catch (e) { kinds.push(e.kind); }
+    try { A().m(1); } catch (e) { kinds.push(e.kind); }
+    "#;
+        let (stmts, mut inter) = get_stmts(input);
+        assert_eq!(DEFAULT_MAX_CALL_DEPTH, inter.max_call_depth);
+        assert_eq!(Ok(()), inter.interpret(&stmts));
+        let kinds = env_get(&inter, "kinds").unwrap().to_string();
+        assert_eq!("[RecursionError, RecursionError]", kinds);
+    }
+
+    #[test]
+    fn test_long_chains() {
+        // Each operator nests the tree one level deeper, which takes more stack
+        // than the test thread has in debug builds, so this runs on a stack as
+        // big as the command line's
+        let thread = std::thread::Builder::new().stack_size(64 * 1024 * 1024);
+        let values = thread.spawn(|| {
+            let sum = vec!["1"; 3000].join(" + ");
+            let all = vec!["true"; 3000].join(" and ");
+            let input = format!("var sum = {}; var all = {};", sum, all);
+            let (stmts, mut inter) = get_stmts(&input);
+            assert_eq!(Ok(()), inter.interpret(&stmts));
+            let sum = env_get(&inter, "sum").unwrap().to_string();
+            let all = env_get(&inter, "all").unwrap().to_string();
+            (sum, all)
+        });
+        let values = values.unwrap().join().unwrap();
+        assert_eq!((String::from("3000"), String::from("true")), values);
+    }
 }
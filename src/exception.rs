@@ -0,0 +1,111 @@
+use crate::class::{Class, ClassInstance, GenericClass, LoxClass, MethodMap, NativeMethod};
+use crate::constants::{
+    ARITHMETIC_ERROR, ERROR_CLASS, IMPORT_ERROR, INDEX_ERROR, INIT_METHOD, KEY_ERROR, NAME_ERROR,
+    RECURSION_ERROR, TYPE_ERROR,
+};
+use crate::eval::RuntimeError;
+use crate::location::Loc;
+use crate::value::Value;
+use std::rc::Rc;
+
+const ERROR_SUBCLASSES: [&str; 7] = [
+    TYPE_ERROR,
+    INDEX_ERROR,
+    KEY_ERROR,
+    NAME_ERROR,
+    ARITHMETIC_ERROR,
+    IMPORT_ERROR,
+    RECURSION_ERROR,
+];
+
+/// The native `Error` class and its subclasses, one for each kind of runtime
+/// error that scripts can catch.
+#[derive(Debug)]
+pub struct ErrorClasses {
+    base: Rc<GenericClass>,
+    subclasses: Vec<Rc<GenericClass>>,
+}
+
+impl ErrorClasses {
+    pub fn new() -> Self {
+        let mut methods = MethodMap::new();
+        Self::add_init_method(&mut methods);
+        let base = Rc::new(GenericClass::new(
+            ERROR_CLASS,
+            None,
+            methods,
+            MethodMap::new(),
+            MethodMap::new(),
+        ));
+
+        let subclasses = ERROR_SUBCLASSES
+            .iter()
+            .map(|name| {
+                let superclass = Class::from(Rc::clone(&base));
+                Rc::new(GenericClass::new(
+                    name,
+                    Some(superclass),
+                    MethodMap::new(),
+                    MethodMap::new(),
+                    MethodMap::new(),
+                ))
+            })
+            .collect();
+
+        Self { base, subclasses }
+    }
+
+    fn add_init_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new(INIT_METHOD, 1, |_inter, mut args, instance, loc| {
+                let message = args.pop().unwrap();
+                set_error_fields(instance, message, loc);
+                Ok(Value::Nil)
+            }),
+        )
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = &Rc<GenericClass>> {
+        std::iter::once(&self.base).chain(self.subclasses.iter())
+    }
+
+    fn get(&self, kind: &str) -> &Rc<GenericClass> {
+        self.subclasses
+            .iter()
+            .find(|class| class.name() == kind)
+            .unwrap_or(&self.base)
+    }
+
+    /// Creates the instance a `catch` clause receives for a built-in error.
+    pub fn instance_from(&self, error: &RuntimeError) -> Value {
+        let loc = error.loc();
+        let message = error.to_string();
+        let prefix = format!("[{}] ", loc);
+        let message = message.strip_prefix(&prefix).unwrap_or(&message);
+
+        let mut instance = ClassInstance::new_generic(self.get(error.kind()));
+        set_error_fields(&mut instance, message.into(), loc);
+        Value::Instance(instance.into())
+    }
+}
+
+fn set_error_fields(instance: &mut ClassInstance, message: Value, loc: Loc) {
+    let kind = instance.class_name().to_string();
+    instance.set("message", message, loc);
+    instance.set("kind", kind.into(), loc);
+    instance.set("line", Value::Integer(loc.line as i64 + 1), loc);
+    instance.set("column", Value::Integer(loc.column as i64), loc);
+}
+
+/// Describes a thrown value for the error reported when nothing catches it.
+pub fn describe(val: &Value) -> String {
+    if let Value::Instance(instance) = val {
+        let instance = instance.borrow();
+        if let (Some(kind), Some(message)) = (instance.field("kind"), instance.field("message")) {
+            return format!("{}: {}", kind, message);
+        }
+    }
+
+    val.to_string()
+}
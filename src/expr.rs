@@ -1,6 +1,7 @@
 use crate::lexer::{Literal, TokenKind};
 use crate::location::{Loc, Located};
 use crate::stmt::Stmt;
+use std::cell::Cell;
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 
 #[derive(PartialEq, Clone)]
@@ -13,21 +14,45 @@ pub enum ExprKind {
     Grouping(Box<Expr>),
     Comma(Box<Expr>, Box<Expr>),
     Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
-    Variable(String),
-    Assign(String, Box<Expr>),
+    Variable(String, Resolution),
+    Assign(String, Box<Expr>, Resolution),
     Call(Box<Expr>, Vec<Expr>),
     Get(Box<Expr>, String),
     Set(Box<Expr>, String, Box<Expr>),
     Array(Vec<Expr>),
+    Map(Vec<(Expr, Expr)>),
     SubscriptGet(Box<Expr>, Box<Expr>),
     SubscriptSet(Box<Expr>, Box<Expr>, Box<Expr>),
-    This,
-    Super(String),
+    This(Resolution),
+    Super(String, Resolution),
 }
 
 pub type Expr = Located<ExprKind>;
 pub type Param = Located<String>;
 
+/// Where a local variable is stored: how many scopes out from where it is
+/// used, and its index among the variables of that scope.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ResolvedLocal {
+    pub depth: usize,
+    pub index: usize,
+}
+
+/// The variable an expression refers to, filled in by the resolver. It is
+/// left empty for globals, which are looked up by name.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Resolution(Cell<Option<ResolvedLocal>>);
+
+impl Resolution {
+    pub fn get(&self) -> Option<ResolvedLocal> {
+        self.0.get()
+    }
+
+    pub fn set(&self, depth: usize, index: usize) {
+        self.0.set(Some(ResolvedLocal { depth, index }));
+    }
+}
+
 pub trait Visitor<Res> {
     type Error;
     type Result = std::result::Result<Res, Self::Error>;
@@ -61,9 +86,20 @@ pub trait Visitor<Res> {
     fn visit_cond_expr(&mut self, cond: &Expr, left: &Expr, right: &Expr, loc: Loc)
         -> Self::Result;
 
-    fn visit_variable_expr(&mut self, name: &str, loc: Loc) -> Self::Result;
+    fn visit_variable_expr(
+        &mut self,
+        name: &str,
+        resolution: &Resolution,
+        loc: Loc,
+    ) -> Self::Result;
 
-    fn visit_assign_expr(&mut self, name: &str, expr: &Expr, loc: Loc) -> Self::Result;
+    fn visit_assign_expr(
+        &mut self,
+        name: &str,
+        expr: &Expr,
+        resolution: &Resolution,
+        loc: Loc,
+    ) -> Self::Result;
 
     fn visit_call_expr(&mut self, callee: &Expr, args: &[Expr], loc: Loc) -> Self::Result;
 
@@ -73,6 +109,8 @@ pub trait Visitor<Res> {
 
     fn visit_array_expr(&mut self, elements: &[Expr], loc: Loc) -> Self::Result;
 
+    fn visit_map_expr(&mut self, entries: &[(Expr, Expr)], loc: Loc) -> Self::Result;
+
     fn visit_subscript_get_expr(&mut self, obj: &Expr, index: &Expr, loc: Loc) -> Self::Result;
 
     fn visit_subscript_set_expr(
@@ -83,9 +121,12 @@ pub trait Visitor<Res> {
         loc: Loc,
     ) -> Self::Result;
 
-    fn visit_this_expr(&mut self, loc: Loc) -> Self::Result;
+    fn visit_this_expr(&mut self, resolution: &Resolution, loc: Loc) -> Self::Result;
 
-    fn visit_super_expr(&mut self, method: &str, loc: Loc) -> Self::Result;
+    /// `resolution` refers to `super`. `this` is in the scope right inside
+    /// the one of `super`, as its only variable.
+    fn visit_super_expr(&mut self, method: &str, resolution: &Resolution, loc: Loc)
+        -> Self::Result;
 }
 
 impl Expr {
@@ -144,11 +185,17 @@ impl Expr {
     }
 
     pub fn variable(name: &str, loc: Loc) -> Self {
-        Expr::new(ExprKind::Variable(String::from(name)), loc)
+        Expr::new(
+            ExprKind::Variable(String::from(name), Resolution::default()),
+            loc,
+        )
     }
 
     pub fn assign(name: String, expr: Expr, loc: Loc) -> Self {
-        Expr::new(ExprKind::Assign(name, Box::new(expr)), loc)
+        Expr::new(
+            ExprKind::Assign(name, Box::new(expr), Resolution::default()),
+            loc,
+        )
     }
 
     pub fn call(callee: Expr, args: Vec<Expr>, loc: Loc) -> Self {
@@ -167,6 +214,10 @@ impl Expr {
         Expr::new(ExprKind::Array(elements), loc)
     }
 
+    pub fn map(entries: Vec<(Expr, Expr)>, loc: Loc) -> Self {
+        Expr::new(ExprKind::Map(entries), loc)
+    }
+
     pub fn subscript_get(obj: Expr, index: Expr, loc: Loc) -> Self {
         Expr::new(ExprKind::SubscriptGet(Box::new(obj), Box::new(index)), loc)
     }
@@ -176,11 +227,14 @@ impl Expr {
     }
 
     pub fn this(loc: Loc) -> Self {
-        Expr::new(ExprKind::This, loc)
+        Expr::new(ExprKind::This(Resolution::default()), loc)
     }
 
     pub fn super_expr(method: &str, loc: Loc) -> Self {
-        Expr::new(ExprKind::Super(String::from(method)), loc)
+        Expr::new(
+            ExprKind::Super(String::from(method), Resolution::default()),
+            loc,
+        )
     }
 
     pub fn accept<Vis, Res, Error>(&self, visitor: &mut Vis) -> Vis::Result
@@ -197,18 +251,21 @@ impl Expr {
             Grouping(expr) => visitor.visit_grouping_expr(expr, self.loc),
             Comma(left, right) => visitor.visit_comma_expr(left, right, self.loc),
             Conditional(cond, left, right) => visitor.visit_cond_expr(cond, left, right, self.loc),
-            Variable(name) => visitor.visit_variable_expr(name, self.loc),
-            Assign(name, expr) => visitor.visit_assign_expr(name, expr, self.loc),
+            Variable(name, resolution) => visitor.visit_variable_expr(name, resolution, self.loc),
+            Assign(name, expr, resolution) => {
+                visitor.visit_assign_expr(name, expr, resolution, self.loc)
+            }
             Call(callee, args) => visitor.visit_call_expr(callee, args, self.loc),
             Get(obj, name) => visitor.visit_get_expr(obj, name, self.loc),
             Set(obj, name, expr) => visitor.visit_set_expr(obj, name, expr, self.loc),
             Array(elements) => visitor.visit_array_expr(elements, self.loc),
+            Map(entries) => visitor.visit_map_expr(entries, self.loc),
             SubscriptGet(obj, index) => visitor.visit_subscript_get_expr(obj, index, self.loc),
             SubscriptSet(obj, index, expr) => {
                 visitor.visit_subscript_set_expr(obj, index, expr, self.loc)
             }
-            This => visitor.visit_this_expr(self.loc),
-            Super(method) => visitor.visit_super_expr(method, self.loc),
+            This(resolution) => visitor.visit_this_expr(resolution, self.loc),
+            Super(method, resolution) => visitor.visit_super_expr(method, resolution, self.loc),
         }
     }
 }
@@ -231,16 +288,17 @@ impl Debug for ExprKind {
             Grouping(expr) => parenthesize("group", &[expr]),
             Comma(left, right) => parenthesize("comma", &[left, right]),
             Conditional(cond, left, right) => parenthesize("?:", &[cond, left, right]),
-            Variable(name) => format!("(var {})", name),
-            Assign(name, expr) => format!("(= {} {:?})", name, expr),
+            Variable(name, _) => format!("(var {})", name),
+            Assign(name, expr, _) => format!("(= {} {:?})", name, expr),
             Call(callee, args) => format!("(call {:?} {:?})", callee, args),
             Get(obj, name) => format!("(get {:?} {})", obj, name),
             Set(obj, name, expr) => format!("(set {:?} {} {:?})", obj, name, expr),
             Array(elements) => format!("(array {:?})", elements),
+            Map(entries) => format!("(map {:?})", entries),
             SubscriptGet(obj, index) => format!("(s-get {:?} {:?})", obj, index),
             SubscriptSet(obj, index, expr) => format!("(s-set {:?} {:?} {:?})", obj, index, expr),
-            This => String::from("this"),
-            Super(method) => format!("(super {})", method),
+            This(_) => String::from("this"),
+            Super(method, _) => format!("(super {})", method),
         };
 
         write!(f, "{}", string)
@@ -288,7 +346,7 @@ pub enum UnOp {
 }
 
 impl UnOp {
-    fn to_string(&self) -> &'static str {
+    pub fn to_string(&self) -> &'static str {
         use UnOp::*;
         match self {
             Negate => "-",
@@ -325,7 +383,7 @@ pub enum BinOp {
 }
 
 impl BinOp {
-    fn to_string(&self) -> &'static str {
+    pub fn to_string(&self) -> &'static str {
         use BinOp::*;
         match self {
             Add => "+",
@@ -370,7 +428,7 @@ pub enum LogOp {
 }
 
 impl LogOp {
-    fn to_string(&self) -> &'static str {
+    pub fn to_string(&self) -> &'static str {
         use LogOp::*;
         match self {
             And => "and",
@@ -0,0 +1,646 @@
+#[cfg(test)]
+mod tests;
+
+use crate::expr::{Expr, ExprKind, Param, UnOp};
+use crate::lexer::{Comment, Scanner, Token, TokenKind};
+use crate::location::Loc;
+use crate::parser::Parser;
+use crate::source::FileId;
+use crate::stmt::{FunctionKind, ImportKind, Stmt, StmtKind};
+use failure::Fallible;
+
+const INDENT: &str = "    ";
+
+/// Lists that would make a line longer than this are split, one element
+/// per line.
+const MAX_WIDTH: usize = 80;
+
+/// Formats `source`, which must be valid Lox, in the canonical style.
+/// Comments are kept, and so are single blank lines between statements.
+pub fn format_source(source: &str, file: FileId) -> Fallible<String> {
+    let mut scanner = Scanner::new(source, file);
+    let tokens = scanner.scan_tokens()?;
+    let stmts = Parser::new(&tokens).parse()?;
+
+    let mut formatter = Formatter::new(source, &tokens, &scanner.comments);
+    formatter.stmts(&stmts, source.len());
+    Ok(formatter.out)
+}
+
+/// Prints the AST back into source. The parser desugars some constructs,
+/// like `while` loops and `+=`, so the formatter looks at the source text
+/// under their spans to tell which one it is printing.
+struct Formatter<'a> {
+    source: &'a str,
+    tokens: &'a [Token<'a>],
+    comments: &'a [Comment<'a>],
+    /// The first comment not written yet.
+    next_comment: usize,
+    out: String,
+    indent: usize,
+    /// Indentation is only written along with the text of a line, so blank
+    /// lines stay empty.
+    at_line_start: bool,
+    /// The column `out` starts at, for formatters rendering part of a line.
+    base_column: usize,
+    /// Whether lists are kept on one line, while trying the layout where a
+    /// list and the ones it contains all are.
+    flat: bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str, tokens: &'a [Token<'a>], comments: &'a [Comment<'a>]) -> Self {
+        Formatter {
+            source,
+            tokens,
+            comments,
+            next_comment: 0,
+            out: String::new(),
+            indent: 0,
+            at_line_start: true,
+            base_column: 0,
+            flat: false,
+        }
+    }
+
+    /// An empty formatter which continues the current line, to try a
+    /// layout before committing to it with `append`.
+    fn nested(&mut self) -> Formatter<'a> {
+        self.write("");
+        Formatter {
+            source: self.source,
+            tokens: self.tokens,
+            comments: self.comments,
+            next_comment: self.next_comment,
+            out: String::new(),
+            indent: self.indent,
+            at_line_start: false,
+            base_column: self.column(),
+            flat: true,
+        }
+    }
+
+    fn append(&mut self, nested: Formatter<'a>) {
+        self.out.push_str(&nested.out);
+        self.at_line_start = nested.at_line_start;
+        self.next_comment = nested.next_comment;
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.at_line_start {
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+            self.at_line_start = false;
+        }
+
+        self.out.push_str(text);
+    }
+
+    fn new_line(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    fn column(&self) -> usize {
+        if self.at_line_start {
+            return self.indent * INDENT.len();
+        }
+
+        match self.out.rfind('\n') {
+            Some(newline) => self.out[newline + 1..].chars().count(),
+            None => self.base_column + self.out.chars().count(),
+        }
+    }
+
+    /// The source text under `loc`.
+    fn text(&self, loc: Loc) -> &'a str {
+        self.source.get(loc.start..loc.end).unwrap_or("")
+    }
+
+    /// The first token of the given kind at or after the offset `from`.
+    fn find(&self, kind: TokenKind, from: usize) -> &'a Token<'a> {
+        let tokens = self.tokens;
+        let index = tokens.partition_point(|token| token.loc.start < from);
+        tokens[index..]
+            .iter()
+            .find(|token| token.kind == kind)
+            .unwrap_or_else(|| tokens.last().expect("Tokens always end with EOF"))
+    }
+
+    /// Writes statements on lines of their own, followed by the comments
+    /// left before the offset `end`.
+    fn stmts(&mut self, stmts: &[Stmt], end: usize) {
+        let mut previous_end = None;
+        for stmt in stmts {
+            self.comments_before(stmt.loc.start, &mut previous_end);
+            self.blank_line(previous_end, stmt.loc.start);
+            self.stmt(stmt);
+            previous_end = Some(self.trailing_comment(stmt.loc.end));
+            self.new_line();
+        }
+
+        self.comments_before(end, &mut previous_end);
+    }
+
+    fn comments_before(&mut self, offset: usize, previous_end: &mut Option<usize>) {
+        let comments = self.comments;
+        while let Some(comment) = comments.get(self.next_comment) {
+            if comment.loc.start >= offset {
+                break;
+            }
+
+            self.blank_line(*previous_end, comment.loc.start);
+            self.write(comment.text);
+            self.new_line();
+            *previous_end = Some(comment.loc.end);
+            self.next_comment += 1;
+        }
+    }
+
+    /// Writes the comment that follows `end` on the same line, if there is
+    /// one, and returns where the line's content ends.
+    fn trailing_comment(&mut self, end: usize) -> usize {
+        let comments = self.comments;
+        match comments.get(self.next_comment) {
+            Some(comment)
+                if comment.loc.start >= end
+                    && !self.source[end..comment.loc.start].contains('\n') =>
+            {
+                self.write(" ");
+                self.write(comment.text);
+                self.next_comment += 1;
+                comment.loc.end
+            }
+            _ => end,
+        }
+    }
+
+    /// Writes the comments left before the offset `end` in place, inside a
+    /// line of code. Line comments end the line, and block comments are
+    /// followed by a space if `space_after` is set.
+    fn inline_comments(&mut self, end: usize, space_after: bool) {
+        let comments = self.comments;
+        while let Some(comment) = comments.get(self.next_comment) {
+            if comment.loc.start >= end {
+                break;
+            }
+
+            if !self.at_line_start && !self.out.ends_with([' ', '(', '[', '{']) {
+                self.write(" ");
+            }
+            self.write(comment.text);
+            if comment.text.starts_with("//") {
+                self.new_line();
+            } else if space_after {
+                self.write(" ");
+            }
+            self.next_comment += 1;
+        }
+    }
+
+    /// Whether a line comment was written since the comment at `from`.
+    fn wrote_line_comment(&self, from: usize) -> bool {
+        self.comments[from..self.next_comment]
+            .iter()
+            .any(|comment| comment.text.starts_with("//"))
+    }
+
+    /// Keeps one blank line where the source had any between what ended at
+    /// `previous_end` and what starts at `start`.
+    fn blank_line(&mut self, previous_end: Option<usize>, start: usize) {
+        if let Some(end) = previous_end {
+            if self.source[end.min(start)..start].matches('\n').count() > 1 {
+                self.new_line();
+            }
+        }
+    }
+
+    /// Writes statements between braces, the opening one being at the
+    /// offset `open`, and returns the offset of the closing one.
+    fn body(&mut self, stmts: &[Stmt], open: usize) -> usize {
+        let after = stmts.last().map_or(open + 1, |stmt| stmt.loc.end);
+        let close = self.find(TokenKind::RightBrace, after).loc.start;
+        let has_comments = self
+            .comments
+            .get(self.next_comment)
+            .is_some_and(|comment| comment.loc.start < close);
+
+        if stmts.is_empty() && !has_comments {
+            self.write("{}");
+            return close;
+        }
+
+        let flat = std::mem::replace(&mut self.flat, false);
+        self.write("{");
+        self.new_line();
+        self.indent += 1;
+        self.stmts(stmts, close);
+        self.indent -= 1;
+        self.write("}");
+        self.flat = flat;
+        close
+    }
+
+    /// Whether `stmt` is a `for` loop with an initializer, which the parser
+    /// wraps in a block.
+    fn is_for_with_init(&self, stmt: &Stmt) -> bool {
+        matches!(stmt.kind, StmtKind::Block(_)) && self.text(stmt.loc).starts_with("for")
+    }
+
+    /// Writes the body of an `if` or a loop, on the same line unless it is
+    /// a block.
+    fn branch(&mut self, stmt: &Stmt) {
+        self.write(" ");
+        match &stmt.kind {
+            StmtKind::Block(stmts) if !self.is_for_with_init(stmt) => {
+                self.body(stmts, stmt.loc.start);
+            }
+            _ => self.stmt(stmt),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                self.expr(expr);
+                self.write(";");
+            }
+            StmtKind::Print(expr) => {
+                self.write("print ");
+                self.expr(expr);
+                self.write(";");
+            }
+            StmtKind::Var(name, init) => {
+                self.write("var ");
+                self.write(name);
+                if let Some(init) = init {
+                    self.write(" = ");
+                    self.expr(init);
+                }
+                self.write(";");
+            }
+            StmtKind::Block(stmts) if self.is_for_with_init(stmt) => {
+                self.for_stmt(Some(&stmts[0]), &stmts[1]);
+            }
+            StmtKind::Block(stmts) => {
+                self.body(stmts, stmt.loc.start);
+            }
+            StmtKind::If(cond, then_branch, else_branch) => {
+                self.write("if (");
+                self.expr(cond);
+                self.write(")");
+                self.branch(then_branch);
+
+                if let Some(else_branch) = else_branch {
+                    if matches!(then_branch.kind, StmtKind::Block(_))
+                        && !self.is_for_with_init(then_branch)
+                    {
+                        self.write(" else");
+                    } else {
+                        self.new_line();
+                        self.write("else");
+                    }
+
+                    if let StmtKind::If(..) = else_branch.kind {
+                        self.write(" ");
+                        self.stmt(else_branch);
+                    } else {
+                        self.branch(else_branch);
+                    }
+                }
+            }
+            StmtKind::For(cond, _, body) if self.text(stmt.loc).starts_with("while") => {
+                self.write("while (");
+                self.expr(cond);
+                self.write(")");
+                self.branch(body);
+            }
+            StmtKind::For(..) => self.for_stmt(None, stmt),
+            StmtKind::ForIn(var, iterable, body) => {
+                self.write("for (var ");
+                self.write(&var.kind);
+                self.write(" in ");
+                self.expr(iterable);
+                self.write(")");
+                self.branch(body);
+            }
+            StmtKind::Function(name, params, body, kind) => {
+                match kind {
+                    FunctionKind::Function => self.write("fun "),
+                    FunctionKind::StaticMethod => self.write("class "),
+                    FunctionKind::Method | FunctionKind::Getter => (),
+                }
+                self.write(name);
+                if *kind != FunctionKind::Getter {
+                    self.params(params, stmt.loc.start);
+                }
+                self.write(" ");
+
+                let after = params.last().map_or(stmt.loc.start, |param| param.loc.end);
+                let open = self.find(TokenKind::LeftBrace, after).loc.start;
+                self.body(body, open);
+            }
+            StmtKind::Return(value) => {
+                self.write("return");
+                if let Some(value) = value {
+                    self.write(" ");
+                    self.expr(value);
+                }
+                self.write(";");
+            }
+            StmtKind::Class(name, superclass, methods) => {
+                self.write("class ");
+                self.write(name);
+                if let Some(superclass) = superclass {
+                    self.write(" < ");
+                    self.expr(superclass);
+                }
+                self.write(" ");
+
+                let open = self.find(TokenKind::LeftBrace, stmt.loc.start).loc.start;
+                self.body(methods, open);
+            }
+            StmtKind::Break => self.write("break;"),
+            StmtKind::Continue => self.write("continue;"),
+            StmtKind::Throw(value) => {
+                self.write("throw ");
+                self.expr(value);
+                self.write(";");
+            }
+            StmtKind::Try(body, catch, finally) => {
+                self.write("try ");
+                let open = self.find(TokenKind::LeftBrace, stmt.loc.start).loc.start;
+                let mut close = self.body(body, open);
+
+                if let Some((var, body)) = catch {
+                    self.write(" catch (");
+                    self.write(&var.kind);
+                    self.write(") ");
+                    let open = self.find(TokenKind::LeftBrace, var.loc.end).loc.start;
+                    close = self.body(body, open);
+                }
+
+                if let Some(body) = finally {
+                    self.write(" finally ");
+                    let open = self.find(TokenKind::LeftBrace, close + 1).loc.start;
+                    self.body(body, open);
+                }
+            }
+            StmtKind::Import(_, kind) => {
+                let path = self.find(TokenKind::Str, stmt.loc.start).lexeme;
+                match kind {
+                    ImportKind::Module(name) => {
+                        self.write("import ");
+                        self.write(path);
+                        self.write(" as ");
+                        self.write(&name.kind);
+                    }
+                    ImportKind::Names(names) => {
+                        self.write("import { ");
+                        let names: Vec<_> = names.iter().map(|name| name.kind.as_str()).collect();
+                        self.write(&names.join(", "));
+                        self.write(" } from ");
+                        self.write(path);
+                    }
+                }
+                self.write(";");
+            }
+        }
+    }
+
+    fn for_stmt(&mut self, init: Option<&Stmt>, for_stmt: &Stmt) {
+        let (cond, increment, body) = match &for_stmt.kind {
+            StmtKind::For(cond, increment, body) => (cond, increment, body),
+            _ => unreachable!(),
+        };
+
+        self.write("for (");
+        match init {
+            Some(init) => self.stmt(init),
+            None => self.write(";"),
+        }
+
+        // A missing condition is parsed as `true` at the semicolon
+        if self.text(cond.loc) != ";" {
+            self.write(" ");
+            self.expr(cond);
+        }
+        self.write(";");
+
+        if let Some(increment) = increment {
+            self.write(" ");
+            self.expr(increment);
+        }
+        self.write(")");
+        self.branch(body);
+    }
+
+    fn params(&mut self, params: &[Param], start: usize) {
+        let item = |f: &mut Self, param: &Param| f.write(&param.kind);
+        self.list("(", params, ")", start, |param| param.loc, item);
+    }
+
+    /// Writes `items` separated by commas on the current line, or one per
+    /// line if that would make the line too long or a line comment is among
+    /// them. The list starts at or after the offset `start`, and comments
+    /// are kept next to the items they were written by.
+    fn list<T>(
+        &mut self,
+        open: &str,
+        items: &[T],
+        close: &str,
+        start: usize,
+        loc: impl Fn(&T) -> Loc,
+        item: impl Fn(&mut Self, &T),
+    ) {
+        let close_kind = match close {
+            ")" => TokenKind::RightParen,
+            "]" => TokenKind::RightBracket,
+            _ => TokenKind::RightBrace,
+        };
+        let after = items.last().map_or(start, |last| loc(last).end);
+        let close_offset = self.find(close_kind, after).loc.start;
+
+        let mut flat = self.nested();
+        flat.write(open);
+        for (i, element) in items.iter().enumerate() {
+            if i > 0 {
+                flat.write(", ");
+            }
+            flat.inline_comments(loc(element).start, true);
+            item(&mut flat, element);
+        }
+        flat.inline_comments(close_offset, false);
+        flat.write(close);
+
+        // Leaves room for the `;` or `,` which usually follows
+        let first_line = flat.out.split('\n').next().unwrap_or("");
+        let width = flat.base_column + first_line.chars().count();
+        let broken = flat.wrote_line_comment(self.next_comment);
+        if items.is_empty() || self.flat || (width < MAX_WIDTH && !broken) {
+            self.append(flat);
+            return;
+        }
+
+        self.write(open);
+        self.new_line();
+        self.indent += 1;
+        for (i, element) in items.iter().enumerate() {
+            self.inline_comments(loc(element).start, true);
+            item(self, element);
+            if i + 1 < items.len() {
+                self.write(",");
+            }
+            self.trailing_comment(loc(element).end);
+            self.new_line();
+        }
+        self.comments_before(close_offset, &mut None);
+        self.indent -= 1;
+        self.write(close);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        self.inline_comments(expr.loc.start, true);
+        match &expr.kind {
+            ExprKind::Literal(_) => self.write(self.text(expr.loc)),
+            ExprKind::Function(params, body) => {
+                self.write("fun");
+                self.params(params, expr.loc.start);
+                self.write(" ");
+
+                let after = params.last().map_or(expr.loc.start, |param| param.loc.end);
+                let open = self.find(TokenKind::LeftBrace, after).loc.start;
+                self.body(body, open);
+            }
+            ExprKind::Unary(op, operand) => {
+                self.write(op.to_string());
+                // Keeps `- -x` from becoming `--x`
+                if *op == UnOp::Negate && self.text(operand.loc).starts_with('-') {
+                    self.write(" ");
+                }
+                self.expr(operand);
+            }
+            ExprKind::Binary(left, op, right) => {
+                self.expr(left);
+                self.write(" ");
+                self.write(op.to_string());
+                self.write(" ");
+                self.expr(right);
+            }
+            ExprKind::Logical(left, op, right) => {
+                self.expr(left);
+                self.write(" ");
+                self.write(op.to_string());
+                self.write(" ");
+                self.expr(right);
+            }
+            ExprKind::Grouping(expr) => {
+                self.write("(");
+                self.expr(expr);
+                self.write(")");
+            }
+            ExprKind::Comma(left, right) if matches!(self.text(right.loc), "++" | "--") => {
+                // `x++` is parsed as `x = x + 1, x - 1`
+                self.assign_target(left);
+                self.write(self.text(right.loc));
+            }
+            ExprKind::Comma(left, right) => {
+                self.expr(left);
+                self.write(", ");
+                self.expr(right);
+            }
+            ExprKind::Conditional(cond, then_expr, else_expr) => {
+                self.expr(cond);
+                self.write(" ? ");
+                self.expr(then_expr);
+                self.write(" : ");
+                self.expr(else_expr);
+            }
+            ExprKind::Variable(name, _) => self.write(name),
+            ExprKind::Assign(_, value, _)
+            | ExprKind::Set(_, _, value)
+            | ExprKind::SubscriptSet(_, _, value) => self.assignment(expr, value),
+            ExprKind::Call(callee, args) => {
+                self.expr(callee);
+                let start = callee.loc.end;
+                self.list("(", args, ")", start, |arg| arg.loc, Self::expr);
+            }
+            ExprKind::Get(obj, name) => {
+                self.expr(obj);
+                self.write(".");
+                self.write(name);
+            }
+            ExprKind::Array(elements) => {
+                let start = expr.loc.start;
+                self.list("[", elements, "]", start, |element| element.loc, Self::expr);
+            }
+            ExprKind::Map(entries) => {
+                let loc = |(key, value): &(Expr, Expr)| key.loc.extend(value.loc);
+                self.list("{", entries, "}", expr.loc.start, loc, |f, (key, value)| {
+                    f.expr(key);
+                    f.write(": ");
+                    f.expr(value);
+                });
+            }
+            ExprKind::SubscriptGet(obj, index) => {
+                self.expr(obj);
+                self.write("[");
+                self.expr(index);
+                self.write("]");
+            }
+            ExprKind::This(_) => self.write("this"),
+            ExprKind::Super(method, _) => {
+                self.write("super.");
+                self.write(method);
+            }
+        }
+    }
+
+    /// Writes an assignment, which the parser also makes out of compound
+    /// assignments and prefix increments. Their value is then a binary
+    /// expression located at the operator.
+    fn assignment(&mut self, expr: &Expr, value: &Expr) {
+        match &value.kind {
+            ExprKind::Binary(_, _, right) => match self.text(value.loc) {
+                op @ ("++" | "--") => {
+                    self.write(op);
+                    self.assign_target(expr);
+                }
+                op @ ("+=" | "-=" | "*=" | "/=" | "%=") => {
+                    self.assign_target(expr);
+                    self.write(" ");
+                    self.write(op);
+                    self.write(" ");
+                    self.expr(right);
+                }
+                _ => self.simple_assignment(expr, value),
+            },
+            _ => self.simple_assignment(expr, value),
+        }
+    }
+
+    fn simple_assignment(&mut self, expr: &Expr, value: &Expr) {
+        self.assign_target(expr);
+        self.write(" = ");
+        self.expr(value);
+    }
+
+    fn assign_target(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Assign(name, _, _) => self.write(name),
+            ExprKind::Set(obj, name, _) => {
+                self.expr(obj);
+                self.write(".");
+                self.write(name);
+            }
+            ExprKind::SubscriptSet(obj, index, _) => {
+                self.expr(obj);
+                self.write("[");
+                self.expr(index);
+                self.write("]");
+            }
+            _ => unreachable!(),
+        }
+    }
+}
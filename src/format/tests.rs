@@ -0,0 +1,186 @@
+use super::*;
+
+fn format(source: &str) -> String {
+    format_source(source, FileId::default()).unwrap()
+}
+
+fn assert_formatted(source: &str) {
+    assert_eq!(source, format(source));
+}
+
+#[test]
+fn test_indentation_and_spacing() {
+    let source = "fun add(a,b){var sum=a+b*2;\nif(sum>10){print sum;}else print -sum;return sum;}";
+    let expected = r#"fun add(a, b) {
+    var sum = a + b * 2;
+    if (sum > 10) {
+        print sum;
+    } else print -sum;
+    return sum;
+}
+"#;
+    assert_eq!(expected, format(source));
+}
+
+#[test]
+fn test_loops() {
+    assert_formatted(
+        r#"while (i < 10) i = i + 1;
+for (var i = 0; i < 10; i++) {
+    print i;
+}
+for (;;) break;
+for (; x;) {}
+for (var x in [1, 2]) print x;
+"#,
+    );
+}
+
+#[test]
+fn test_desugared_expressions() {
+    assert_formatted(
+        r#"x += 1;
+a.b *= 2;
+a[0] -= 3;
+++x;
+a.b--;
+a[i]++;
+print - -x;
+print x ? "a" : "b", {"a": 1, 2: true};
+"#,
+    );
+}
+
+#[test]
+fn test_classes_and_functions() {
+    assert_formatted(
+        r#"class A < B {
+    init(x) {
+        super.init();
+        this.x = x;
+    }
+
+    class create() {
+        return A(1);
+    }
+
+    size {
+        return this.x;
+    }
+}
+
+var f = fun(a) {
+    return a;
+};
+"#,
+    );
+}
+
+#[test]
+fn test_try_and_imports() {
+    assert_formatted(
+        r#"import "lib.lox" as lib;
+import { f, g } from "lib.lox";
+
+try {
+    throw Error("oops");
+} catch (e) {
+    print e;
+} finally {}
+"#,
+    );
+}
+
+#[test]
+fn test_else_after_single_statement() {
+    let source = "if (a) print 1; else if (b) { print 2; } else print 3;";
+    let expected = "if (a) print 1;\nelse if (b) {\n    print 2;\n} else print 3;\n";
+    assert_eq!(expected, format(source));
+}
+
+#[test]
+fn test_comments_are_kept() {
+    let source = r#"// Header
+
+// About x
+var x = 1;   // trailing
+
+
+
+/* block */ fun f() {
+  // only a comment
+}
+{
+    print x;
+    // at the end
+}
+"#;
+    let expected = r#"// Header
+
+// About x
+var x = 1; // trailing
+
+/* block */
+fun f() {
+    // only a comment
+}
+{
+    print x;
+    // at the end
+}
+"#;
+    assert_eq!(expected, format(source));
+    assert_formatted(expected);
+}
+
+#[test]
+fn test_comments_in_argument_lists() {
+    assert_formatted("print f(1, /* c */ 2);\nprint g(/* none */);\nfun h(a, /* b */ c) {}\n");
+
+    let source = "print f(\n  1, // first\n  // before two\n  2 // two\n  // after\n);";
+    let expected = r#"print f(
+    1, // first
+    // before two
+    2 // two
+    // after
+);
+"#;
+    assert_eq!(expected, format(source));
+    assert_formatted(expected);
+}
+
+#[test]
+fn test_comments_in_arrays_and_maps() {
+    assert_formatted("var m = {\"a\": 1, /* b */ \"b\": 2 /* end */};\n");
+
+    let source = "var a = [1, // one\n 2];\nprint a;";
+    let expected = r#"var a = [
+    1, // one
+    2
+];
+print a;
+"#;
+    assert_eq!(expected, format(source));
+    assert_formatted(expected);
+}
+
+#[test]
+fn test_long_argument_lists_are_split() {
+    let source =
+        r#"print callback(first_argument, second_argument, third_argument, [1, 2, 3], {"a": 1});"#;
+    let expected = r#"print callback(
+    first_argument,
+    second_argument,
+    third_argument,
+    [1, 2, 3],
+    {"a": 1}
+);
+"#;
+    assert_eq!(expected, format(source));
+    assert_formatted(expected);
+}
+
+#[test]
+fn test_syntax_errors_are_returned() {
+    assert!(format_source("var = 1;", FileId::default()).is_err());
+}
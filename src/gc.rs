@@ -0,0 +1,231 @@
+#[cfg(test)]
+mod tests;
+
+use crate::callable::Callable;
+use crate::class::{Class, Method};
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// Number of allocations after which the heap is collected automatically.
+pub const DEFAULT_GC_THRESHOLD: usize = 10_000;
+
+/// Size of the registry at which the references to freed objects are first
+/// dropped from it.
+const MIN_PRUNE_SIZE: usize = 1024;
+
+/// A heap object that can hold references to other heap objects, and so
+/// can be part of a reference cycle.
+pub trait Trace {
+    /// Reports every reference this object holds to other heap objects.
+    /// Returns `false`, without reporting anything, if the object is
+    /// borrowed mutably and can't be inspected.
+    fn trace(&self, tracer: &mut Tracer) -> bool;
+
+    /// Drops the references this object holds, which breaks any cycle
+    /// going through it.
+    fn clear(&self) {}
+}
+
+pub struct Tracer<'a> {
+    visit: &'a mut dyn FnMut(usize),
+}
+
+/// Every object that can be part of a cycle is registered here when it is
+/// allocated. The registry only holds weak references, so objects that are
+/// not part of a cycle are still freed as soon as they are dropped.
+struct Heap {
+    objects: Vec<Weak<dyn Trace>>,
+    allocations: usize,
+    threshold: usize,
+    next_collection: usize,
+    /// Size of `objects` at which the references to freed objects are
+    /// dropped, which collections do too. Without it, the registry would
+    /// grow forever when automatic collection is disabled.
+    next_prune: usize,
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::new());
+}
+
+impl Heap {
+    fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            allocations: 0,
+            threshold: DEFAULT_GC_THRESHOLD,
+            next_collection: DEFAULT_GC_THRESHOLD,
+            next_prune: MIN_PRUNE_SIZE,
+        }
+    }
+
+    /// Drops the references to freed objects, and waits for the registry to
+    /// double in size before doing it again.
+    fn prune(&mut self) {
+        self.objects.retain(|obj| obj.strong_count() > 0);
+        self.next_prune = MIN_PRUNE_SIZE.max(2 * self.objects.len());
+    }
+}
+
+impl<'a> Tracer<'a> {
+    fn new(visit: &'a mut dyn FnMut(usize)) -> Self {
+        Self { visit }
+    }
+
+    pub fn rc<T: ?Sized>(&mut self, rc: &Rc<T>) {
+        (self.visit)(address(rc));
+    }
+
+    pub fn value(&mut self, val: &Value) {
+        match val {
+            Value::Callable(callable) => self.callable(callable),
+            Value::Instance(instance) => self.rc(instance),
+            Value::Array(array) => self.rc(array),
+            Value::Map(map) => self.rc(map),
+            _ => (),
+        }
+    }
+
+    pub fn callable(&mut self, callable: &Callable) {
+        match callable {
+            Callable::Native(_) => (),
+            Callable::Function(function) => self.rc(function),
+            Callable::Closure(closure) => self.rc(closure),
+            Callable::BoundMethod(bound) => {
+                self.method(bound.method());
+                self.rc(bound.receiver());
+            }
+            Callable::Class(class) => self.class(class),
+        }
+    }
+
+    pub fn class(&mut self, class: &Class) {
+        if let Class::Generic(class) = class {
+            self.rc(class);
+        }
+    }
+
+    pub fn method(&mut self, method: &Method) {
+        match method {
+            Method::Native(_) => (),
+            Method::Function(function) => self.rc(function),
+            Method::Closure(closure) => self.rc(closure),
+            Method::StaticWrapper(wrapper) => self.rc(wrapper),
+        }
+    }
+}
+
+/// Moves `obj` to the heap and registers it with the collector. May run a
+/// collection if enough objects were allocated since the last one.
+pub fn alloc<T: Trace + 'static>(obj: T) -> Rc<T> {
+    let rc = Rc::new(obj);
+    let weak: Weak<dyn Trace> = Rc::downgrade(&rc) as Weak<dyn Trace>;
+    let should_collect = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects.push(weak);
+        if heap.objects.len() >= heap.next_prune {
+            heap.prune();
+        }
+        heap.allocations += 1;
+        heap.threshold > 0 && heap.allocations >= heap.next_collection
+    });
+
+    if should_collect {
+        collect();
+    }
+
+    rc
+}
+
+/// Sets how many allocations trigger an automatic collection. A threshold of
+/// zero disables automatic collection, leaving only explicit `gc()` calls.
+pub fn set_threshold(threshold: usize) {
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.threshold = threshold;
+        heap.next_collection = threshold;
+    });
+}
+
+/// Frees the objects that are only kept alive by reference cycles, and
+/// returns how many there were.
+///
+/// This uses trial deletion: the references that tracked objects hold to
+/// each other are subtracted from their reference counts. Whatever is left
+/// comes from outside the heap (the interpreter, the VM stack, natives), so
+/// objects with references left, and everything reachable from them, are
+/// alive. The rest can only be reached through cycles, which are broken by
+/// clearing the objects.
+pub fn collect() -> usize {
+    let objects: Vec<Rc<dyn Trace>> = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.allocations = 0;
+        heap.objects.iter().filter_map(Weak::upgrade).collect()
+    });
+
+    let index: HashMap<usize, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, obj)| (address(obj), i))
+        .collect();
+
+    // Don't count the reference held by `objects`.
+    let mut refs: Vec<usize> = objects
+        .iter()
+        .map(|obj| Rc::strong_count(obj) - 1)
+        .collect();
+    let mut traced = Vec::with_capacity(objects.len());
+    for obj in &objects {
+        traced.push(obj.trace(&mut Tracer::new(&mut |addr| {
+            if let Some(&i) = index.get(&addr) {
+                refs[i] -= 1;
+            }
+        })));
+    }
+
+    let mut reachable = vec![false; objects.len()];
+    let mut pending = Vec::new();
+    for i in 0..objects.len() {
+        if refs[i] > 0 || !traced[i] {
+            reachable[i] = true;
+            pending.push(i);
+        }
+    }
+
+    while let Some(i) = pending.pop() {
+        objects[i].trace(&mut Tracer::new(&mut |addr| {
+            if let Some(&j) = index.get(&addr) {
+                if !reachable[j] {
+                    reachable[j] = true;
+                    pending.push(j);
+                }
+            }
+        }));
+    }
+
+    let mut collected = 0;
+    for (obj, reachable) in objects.iter().zip(reachable) {
+        if !reachable {
+            obj.clear();
+            collected += 1;
+        }
+    }
+
+    drop(objects);
+
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.prune();
+        // Wait for the heap to double in size before collecting again, so
+        // that collections stay proportional to the number of allocations.
+        heap.next_collection = heap.threshold.max(heap.objects.len());
+    });
+
+    collected
+}
+
+fn address<T: ?Sized>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc) as *const () as usize
+}
@@ -0,0 +1,36 @@
+use super::*;
+
+struct Leaf;
+
+impl Trace for Leaf {
+    fn trace(&self, _tracer: &mut Tracer) -> bool {
+        true
+    }
+}
+
+fn registered() -> usize {
+    HEAP.with(|heap| heap.borrow().objects.len())
+}
+
+#[test]
+fn test_freed_objects_are_unregistered_without_collections() {
+    set_threshold(0);
+    let kept: Vec<Rc<Leaf>> = (0..10).map(|_| alloc(Leaf)).collect();
+    for _ in 0..100_000 {
+        alloc(Leaf);
+    }
+
+    assert!(registered() <= MIN_PRUNE_SIZE, "{}", registered());
+    assert!(kept.iter().all(|leaf| Rc::strong_count(leaf) == 1));
+    set_threshold(DEFAULT_GC_THRESHOLD);
+}
+
+#[test]
+fn test_registry_keeps_live_objects() {
+    set_threshold(0);
+    let kept: Vec<Rc<Leaf>> = (0..3 * MIN_PRUNE_SIZE).map(|_| alloc(Leaf)).collect();
+    assert_eq!(kept.len(), registered());
+    assert_eq!(0, collect());
+    assert_eq!(kept.len(), registered());
+    set_threshold(DEFAULT_GC_THRESHOLD);
+}
@@ -0,0 +1,147 @@
+use crate::callable::{Arity, Callable, LoxCallable};
+use crate::class::{Class, ClassInstance, InstanceRc, LoxClass, MethodMap, NativeMethod};
+use crate::eval::{Interpreter, RuntimeError, ValueRes};
+use crate::location::Loc;
+use crate::value::Value;
+use std::any::Any;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// A class defined by the application embedding the interpreter. Its
+/// instances carry Rust state, which native methods reach through
+/// `ClassInstance::data` and `ClassInstance::data_mut`.
+pub trait HostClass {
+    fn name(&self) -> &str;
+
+    /// The number of arguments the constructor takes.
+    fn arity(&self) -> Arity {
+        Arity::Fixed(0)
+    }
+
+    /// Creates the state of a new instance from the constructor arguments.
+    fn construct(
+        &self,
+        inter: &mut Interpreter,
+        args: Vec<Value>,
+        loc: Loc,
+    ) -> Result<Box<dyn Any>, RuntimeError>;
+
+    fn methods(&self) -> Vec<NativeMethod> {
+        Vec::new()
+    }
+
+    fn getters(&self) -> Vec<NativeMethod> {
+        Vec::new()
+    }
+
+    /// Methods called on the class itself, which receive the metainstance.
+    fn static_methods(&self) -> Vec<NativeMethod> {
+        Vec::new()
+    }
+}
+
+pub struct NativeClass {
+    name: String,
+    uuid: Uuid,
+    methods: MethodMap,
+    getters: MethodMap,
+    metainstance: InstanceRc,
+    host: Box<dyn HostClass>,
+}
+
+impl NativeClass {
+    pub fn new(host: Box<dyn HostClass>) -> Self {
+        let uuid = Uuid::new_v4();
+        let name = String::from(host.name());
+        let methods = Self::method_map(host.methods());
+        let getters = Self::method_map(host.getters());
+        let static_methods = Self::method_map(host.static_methods());
+
+        let metainstance =
+            Class::create_metainstance(&name, uuid, None, static_methods, &methods, &getters);
+
+        Self {
+            name,
+            uuid,
+            methods,
+            getters,
+            metainstance,
+            host,
+        }
+    }
+
+    fn method_map(natives: Vec<NativeMethod>) -> MethodMap {
+        let mut methods = MethodMap::new();
+        for native in natives {
+            Class::add_native_method(&mut methods, native);
+        }
+        methods
+    }
+}
+
+impl LoxClass for Rc<NativeClass> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    fn superclass(&self) -> Option<&Class> {
+        None
+    }
+
+    fn methods(&self) -> &MethodMap {
+        &self.methods
+    }
+
+    fn getters(&self) -> &MethodMap {
+        &self.getters
+    }
+
+    fn metainstance(&self) -> Option<InstanceRc> {
+        Some(Rc::clone(&self.metainstance))
+    }
+}
+
+impl LoxCallable for Rc<NativeClass> {
+    fn arity(&self) -> Arity {
+        self.host.arity()
+    }
+
+    fn call(&self, inter: &mut Interpreter, args: Vec<Value>, loc: Loc) -> ValueRes {
+        let data = self.host.construct(inter, args, loc)?;
+        Ok(ClassInstance::from_host(self, data).into())
+    }
+}
+
+impl From<Rc<NativeClass>> for Callable {
+    fn from(class: Rc<NativeClass>) -> Self {
+        Callable::Class(Class::Native(class))
+    }
+}
+
+impl From<Rc<NativeClass>> for Class {
+    fn from(class: Rc<NativeClass>) -> Self {
+        Class::Native(class)
+    }
+}
+
+impl Display for NativeClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+impl Debug for NativeClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("NativeClass")
+            .field("name", &self.name)
+            .field("uuid", &self.uuid)
+            .field("methods", &self.methods)
+            .field("getters", &self.getters)
+            .finish()
+    }
+}
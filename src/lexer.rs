@@ -1,7 +1,9 @@
 #[cfg(test)]
 mod tests;
 
+use crate::lint::AllowedLints;
 use crate::location::Loc;
+use crate::source::FileId;
 use crate::utils::*;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
@@ -52,20 +54,26 @@ pub enum TokenKind {
     // Keywords
     And,
     Break,
+    Catch,
     Class,
     Continue,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -87,6 +95,14 @@ pub struct Token<'a> {
     pub loc: Loc,
 }
 
+/// A line or block comment, which the parser never sees but the formatter
+/// keeps.
+#[derive(PartialEq, Debug)]
+pub struct Comment<'a> {
+    pub text: &'a str,
+    pub loc: Loc,
+}
+
 pub struct Scanner<'a> {
     input: &'a str,
     chars: std::str::Chars<'a>,
@@ -94,6 +110,10 @@ pub struct Scanner<'a> {
     current: usize,
     start_loc: Loc,
     current_loc: Loc,
+    /// The lints allowed by comments found so far.
+    pub allowed_lints: AllowedLints,
+    /// The comments found so far, in the order they appear.
+    pub comments: Vec<Comment<'a>>,
 }
 
 #[derive(Debug, PartialEq, Fail)]
@@ -139,14 +159,22 @@ impl Display for Token<'_> {
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(input: &'a str) -> Self {
+    /// Creates a scanner whose tokens are located in `file`.
+    pub fn new(input: &'a str, file: FileId) -> Self {
+        let loc = Loc {
+            file,
+            ..Loc::new(0, 0)
+        };
+
         Self {
             input,
             chars: input.chars(),
             start: 0,
             current: 0,
-            start_loc: Loc::new(0, 0),
-            current_loc: Loc::new(0, 0),
+            start_loc: loc,
+            current_loc: loc,
+            allowed_lints: AllowedLints::default(),
+            comments: vec![],
         }
     }
 
@@ -174,7 +202,8 @@ impl<'a> Scanner<'a> {
 
         match errors.len() {
             0 => {
-                tokens.push(Token::eof(self.current_loc));
+                let loc = self.current_loc.with_span(self.current, self.current);
+                tokens.push(Token::eof(loc));
                 Ok(tokens)
             }
             1 => Err(errors.pop().unwrap()),
@@ -259,9 +288,12 @@ impl<'a> Scanner<'a> {
             '/' => {
                 if self.matches('/') {
                     self.advance_while(|ch| ch != '\n');
+                    self.record_allowed_lints();
+                    self.record_comment();
                     return Ok(None);
                 } else if self.matches('*') {
                     self.skip_block_comment()?;
+                    self.record_comment();
                     return Ok(None);
                 } else {
                     let kind = if self.matches('=') { SlashEqual } else { Slash };
@@ -414,6 +446,24 @@ impl<'a> Scanner<'a> {
         Ok(self.create_token(kind))
     }
 
+    /// Records the lints allowed by the line comment just scanned, which
+    /// apply to the next line if the comment is on a line of its own.
+    fn record_allowed_lints(&mut self) {
+        let comment = &self.input[self.start + 2..self.current];
+        let before = &self.input[..self.start];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let own_line = before[line_start..].trim().is_empty();
+        let line = self.start_loc.line + own_line as usize;
+        self.allowed_lints.add_comment(line, comment);
+    }
+
+    fn record_comment(&mut self) {
+        self.comments.push(Comment {
+            text: self.get_lexeme(),
+            loc: self.token_loc(),
+        });
+    }
+
     fn skip_block_comment(&mut self) -> Result<(), ScanningError> {
         let mut depth = 1usize;
         while let Some(ch) = self.advance() {
@@ -442,12 +492,22 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    /// The location of the token being scanned.
+    fn token_loc(&self) -> Loc {
+        self.start_loc.with_span(self.start, self.current)
+    }
+
+    /// The location the scanner reached, spanning the token being scanned.
+    fn end_loc(&self) -> Loc {
+        self.current_loc.with_span(self.start, self.current)
+    }
+
     fn create_token(&self, kind: TokenKind) -> Token<'a> {
         Token {
             kind,
             lexeme: self.get_lexeme(),
             literal: None,
-            loc: self.start_loc,
+            loc: self.token_loc(),
         }
     }
 
@@ -456,46 +516,58 @@ impl<'a> Scanner<'a> {
             kind,
             lexeme: &self.get_lexeme(),
             literal: Some(literal),
-            loc: self.start_loc,
+            loc: self.token_loc(),
         }
     }
 }
 
 fn unrecognized_character(scanner: &Scanner, character: char) -> ScanningError {
-    ScanningError::UnrecognizedCharacter(character, scanner.start_loc)
+    ScanningError::UnrecognizedCharacter(character, scanner.token_loc())
 }
 
 fn unterminated_string(scanner: &Scanner) -> ScanningError {
-    ScanningError::UnterminatedString(scanner.current_loc)
+    ScanningError::UnterminatedString(scanner.end_loc())
 }
 
 fn invalid_number(scanner: &Scanner, number: &str) -> ScanningError {
-    ScanningError::InvalidNumber(String::from(number), scanner.start_loc)
+    ScanningError::InvalidNumber(String::from(number), scanner.token_loc())
 }
 
 fn unterminated_block_comment(scanner: &Scanner) -> ScanningError {
-    ScanningError::UnterminatedBlockComment(scanner.current_loc)
+    ScanningError::UnterminatedBlockComment(scanner.end_loc())
 }
 
+pub const KEYWORDS: [&str; 24] = [
+    "and", "break", "catch", "class", "continue", "else", "false", "finally", "for", "fun", "if",
+    "import", "in", "nil", "or", "print", "return", "super", "this", "throw", "true", "try", "var",
+    "while",
+];
+
 fn keyword_to_kind(keyword: &str) -> Option<TokenKind> {
     use TokenKind::*;
     Some(match keyword {
         "and" => And,
         "break" => Break,
+        "catch" => Catch,
         "class" => Class,
         "continue" => Continue,
         "else" => Else,
         "false" => False,
+        "finally" => Finally,
         "for" => For,
         "fun" => Fun,
         "if" => If,
+        "import" => Import,
+        "in" => In,
         "nil" => Nil,
         "or" => Or,
         "print" => Print,
         "return" => Return,
         "super" => Super,
         "this" => This,
+        "throw" => Throw,
         "true" => True,
+        "try" => Try,
         "var" => Var,
         "while" => While,
         _ => return None,
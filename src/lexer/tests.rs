@@ -54,7 +54,7 @@ const LEXEME_KINDS: [(&str, TokenKind); 49] = [
 ];
 
 fn get_tokens<'a>(input: &'a str) -> ScanningRes<'a> {
-    let mut scanner = Scanner::new(input);
+    let mut scanner = Scanner::new(input, FileId::default());
     scanner.scan_tokens()
 }
 
@@ -138,6 +138,18 @@ fn test_block_comment() {
     assert_eq!(Ok(no_token(1, 18)), tokens);
 }
 
+#[test]
+fn test_comments_are_kept() {
+    let mut scanner = Scanner::new("x; // end\n/* a\nblock */ y;", FileId::default());
+    scanner.scan_tokens().unwrap();
+    let comments: Vec<_> = scanner
+        .comments
+        .iter()
+        .map(|comment| (comment.text, comment.loc.line, comment.loc.start))
+        .collect();
+    assert_eq!(vec![("// end", 0, 3), ("/* a\nblock */", 1, 10)], comments);
+}
+
 #[test]
 fn test_multiple_tokens() {
     let tokens = get_tokens("(){},.-+;*!=!%:==<=</>>=?=//this should be ignored");
@@ -9,41 +9,81 @@ extern crate failure_derive;
 
 mod array;
 mod callable;
+mod chunk;
 mod class;
+mod compiler;
 mod constants;
+mod diagnostic;
+mod dump;
 pub mod error;
 mod eval;
+mod exception;
 mod expr;
+mod format;
+mod gc;
+mod host;
 mod lexer;
+mod lint;
 mod location;
+mod lsp;
+mod map;
+mod module;
 mod parser;
+mod repl;
 mod resolver;
 mod scriptable;
+mod source;
 mod stmt;
+mod string;
 #[cfg(test)]
 mod test_utils;
 mod utils;
 mod value;
+mod vm;
 
 use ansi_term::Color::{Blue, Cyan, Green, Purple, Yellow};
 use ansi_term::Style;
-use error::print_err;
-use eval::Interpreter;
-use failure::{Fallible, ResultExt};
+use failure::{err_msg, Error, Fallible, ResultExt};
 use lexer::Scanner;
 use parser::Parser;
+use repl::ReplHelper;
 use resolver::Resolver;
 use rustyline::{config::Configurer, error::ReadlineError, Editor};
+use std::cell::RefCell;
 use std::ffi::OsStr;
-use std::io::{stdin, Read};
+use std::io::{self, stdin, Read, Write};
 use std::path::Path;
+use std::rc::Rc;
+use std::time::Instant;
 use stmt::{Stmt, StmtKind};
-use value::Value;
+
+pub use callable::Arity;
+pub use class::{ClassInstance, NativeMethod};
+pub use diagnostic::ErrorFormat;
+pub use dump::{AstFormat, Dump};
+pub use eval::{Backend, Interpreter, RuntimeError, ValueRes};
+pub use host::HostClass;
+pub use lint::{Lint, LintLevel};
+pub use location::Loc;
+pub use lsp::LanguageServer;
+pub use value::{FromValue, IntoValue, Value};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const REPL_HELP: &str = "Commands:
+    :help           Shows this help
+    :load <file>    Runs a script in the session
+    :reset          Starts over with a new interpreter
+    :env            Lists the globals defined in the session and their types
+    :type <expr>    Shows the type of an expression
+    :time <code>    Runs code and shows how long it took, keeping it in the session
+    :ast <code>     Shows the syntax tree of code
+    :save <file>    Writes the code run in the session to a script";
+
 pub struct Lox {
     inter: Interpreter,
+    /// The code run successfully at the prompt, for `:save`.
+    session: Vec<String>,
 }
 
 impl Lox {
@@ -51,105 +91,498 @@ impl Lox {
     pub fn new() -> Self {
         Lox {
             inter: Interpreter::new(),
+            session: Vec::new(),
         }
     }
 
+    pub fn with_backend(backend: Backend) -> Self {
+        let mut lox = Self::new();
+        lox.inter.backend = backend;
+        lox
+    }
+
+    /// Redirects what scripts print, which goes to stdout by default. It is
+    /// flushed whenever a script or a call from the host is done.
+    pub fn set_output<W: Write + 'static>(&mut self, output: W) {
+        self.inter.output = Box::new(output);
+    }
+
+    /// Redirects warnings and errors, which go to stderr by default. They
+    /// are written without colors unless `set_colored_diagnostics` is used.
+    pub fn set_diagnostics<W: Write + 'static>(&mut self, diagnostics: W) {
+        self.inter.diagnostics = Box::new(diagnostics);
+        self.inter.colored_diagnostics = false;
+    }
+
+    /// Sets whether warnings and errors are colored, which they are by
+    /// default only when written to a terminal.
+    pub fn set_colored_diagnostics(&mut self, colored: bool) {
+        self.inter.colored_diagnostics = colored;
+    }
+
+    /// Makes warnings and errors be written as one JSON object per line, for
+    /// editors and other tools to consume.
+    pub fn set_error_format(&mut self, format: ErrorFormat) {
+        self.inter.error_format = format;
+    }
+
+    /// Sets whether warnings of a kind are reported, and whether they are
+    /// reported as errors that stop the code from running.
+    pub fn set_lint_level(&mut self, lint: Lint, level: LintLevel) {
+        self.inter.lint_levels.set(lint, level);
+    }
+
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.inter.max_call_depth = depth;
+    }
+
+    /// Sets how many bytes of the native stack calls may use before raising
+    /// a `RecursionError`. The default fits any thread spawned by
+    /// `std::thread`, and can be raised for threads with a larger stack.
+    pub fn set_max_stack_size(&mut self, size: usize) {
+        self.inter.max_stack_size = size;
+    }
+
+    /// Sets how many allocations trigger an automatic cycle collection, or
+    /// disables it with 0. The heap is shared by every `Lox` on the thread.
+    pub fn set_gc_threshold(&mut self, threshold: usize) {
+        gc::set_threshold(threshold);
+    }
+
+    /// Defines a global function implemented in Rust. Errors it returns are
+    /// raised in the script like any other runtime error.
+    pub fn register_fn<F>(&mut self, name: &str, arity: Arity, fun: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Value>, Loc) -> ValueRes + 'static,
+    {
+        self.inter.register_fn(name, arity, fun);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.inter.get_global(name)
+    }
+
+    /// Defines or overwrites a global variable of the main script.
+    pub fn set_global<T: IntoValue>(&mut self, name: &str, val: T) {
+        let val = val.into_value(&self.inter);
+        self.inter.set_global(name, val);
+    }
+
+    /// Converts a Rust value into a value that can be passed to `call`.
+    pub fn value<T: IntoValue>(&self, val: T) -> Value {
+        val.into_value(&self.inter)
+    }
+
+    /// Calls the global function `name`, typically one defined by a script
+    /// that was run before.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Fallible<Value> {
+        let callee = self
+            .get_global(name)
+            .ok_or_else(|| RuntimeError::undefined_variable(Loc::default(), name))?;
+        self.call_value(callee, args)
+    }
+
+    /// Calls any callable value, such as a function returned by a script.
+    pub fn call_value(&mut self, callee: Value, args: Vec<Value>) -> Fallible<Value> {
+        let res = self.inter.call(callee, args);
+        self.inter.output.flush()?;
+        res.map_err(|error| self.inter.traceback(error).into())
+    }
+
+    /// Defines a global class implemented in Rust, whose instances carry the
+    /// state built by `HostClass::construct`.
+    pub fn register_class<C: HostClass + 'static>(&mut self, class: C) {
+        self.inter.register_class(class);
+    }
+
     pub fn run(&mut self, input: &str) -> Fallible<()> {
-        let mut scanner = Scanner::new(input);
+        self.run_named("<script>", input)
+    }
+
+    /// Runs `input`, reporting its errors as coming from the file `name`.
+    fn run_named(&mut self, name: &str, input: &str) -> Fallible<()> {
+        let res = self.run_source(name, input);
+        self.inter.output.flush()?;
+        res
+    }
+
+    fn run_source(&mut self, name: &str, input: &str) -> Fallible<()> {
+        let file = self.inter.sources.add(name, input);
+        let mut scanner = Scanner::new(input, file);
         let tokens = scanner.scan_tokens()?;
 
         let mut parser = Parser::new(&tokens);
         let stmts = parser.parse()?;
 
-        let mut resolver = Resolver::new(&mut self.inter);
+        let allowed_lints = std::mem::take(&mut scanner.allowed_lints);
+        let mut resolver = Resolver::with_lints(self.inter.lint_levels.clone(), allowed_lints);
+        resolver.set_known_globals(self.inter.globals.borrow().signatures());
         resolver.resolve(&stmts)?;
+        let warnings = resolver.warnings;
 
-        error::print_warns(&resolver.warnings);
+        error::print_warns(
+            &mut self.inter.diagnostics,
+            &warnings,
+            &self.inter.sources,
+            self.inter.error_format,
+            self.inter.colored_diagnostics,
+        )?;
 
-        self.inter.interpret(&stmts)?;
+        self.interpret(&stmts)?;
 
         Ok(())
     }
 
     pub fn run_file(&mut self, path: &OsStr) -> Fallible<()> {
-        let content = if path == "-" {
-            let mut content = String::new();
-            stdin()
-                .lock()
-                .read_to_string(&mut content)
-                .context("Could not read from stdin")?;
-            content
-        } else {
-            let path = Path::new(path);
-            let context = format!("Could not read '{}'", path.display());
-            std::fs::read_to_string(path).context(context)?
+        self.load_file(path).map(|_content| ())
+    }
+
+    /// Runs the file at `path`, returning its content.
+    fn load_file(&mut self, path: &OsStr) -> Fallible<String> {
+        let (name, content) = read_source(path)?;
+        if path != "-" {
+            self.inter
+                .modules
+                .set_current_path(Path::new(path).canonicalize().ok());
+        }
+
+        self.run_named(&name, &content)?;
+        Ok(content)
+    }
+
+    /// Formats `input` in the canonical style, reporting its syntax errors
+    /// as coming from the file `name`.
+    pub fn format(&mut self, name: &str, input: &str) -> Fallible<String> {
+        let file = self.inter.sources.add(name, input);
+        format::format_source(input, file)
+    }
+
+    /// Rewrites the file at `path` in the canonical style, or with `check`
+    /// only tells whether it already is. Returns whether the file was
+    /// formatted already. `-` formats stdin to the output.
+    pub fn format_file(&mut self, path: &OsStr, check: bool) -> Fallible<bool> {
+        let (name, content) = read_source(path)?;
+        let formatted = self.format(&name, &content)?;
+        let unchanged = formatted == content;
+
+        if check {
+            return Ok(unchanged);
+        }
+
+        if path == "-" {
+            write!(self.inter.output, "{}", formatted)?;
+            self.inter.output.flush()?;
+        } else if !unchanged {
+            let context = format!("Could not write '{}'", name);
+            std::fs::write(path, formatted).context(context)?;
+        }
+
+        Ok(unchanged)
+    }
+
+    /// Prints the tokens or the syntax tree of `input` instead of running
+    /// it, reporting its errors as coming from the file `name`.
+    pub fn dump(&mut self, name: &str, input: &str, dump: Dump) -> Fallible<()> {
+        let res = self.dump_source(name, input, dump);
+        self.inter.output.flush()?;
+        res
+    }
+
+    fn dump_source(&mut self, name: &str, input: &str, dump: Dump) -> Fallible<()> {
+        let file = self.inter.sources.add(name, input);
+        let mut scanner = Scanner::new(input, file);
+        let tokens = scanner.scan_tokens()?;
+
+        let format = match dump {
+            Dump::Tokens => {
+                dump::dump_tokens(&mut self.inter.output, &tokens)?;
+                return Ok(());
+            }
+            Dump::Ast(format) | Dump::Resolved(format) => format,
         };
 
-        self.run(&content)
+        let mut parser = Parser::new(&tokens);
+        let stmts = parser.parse()?;
+
+        let resolved = matches!(dump, Dump::Resolved(_));
+        if resolved {
+            let allowed_lints = std::mem::take(&mut scanner.allowed_lints);
+            let mut resolver = Resolver::with_lints(self.inter.lint_levels.clone(), allowed_lints);
+            resolver.set_known_globals(self.inter.globals.borrow().signatures());
+            resolver.resolve(&stmts)?;
+        }
+
+        dump::dump_ast(&mut self.inter.output, &stmts, format, resolved)?;
+        Ok(())
+    }
+
+    /// Dumps the file at `path`, or stdin for `-`.
+    pub fn dump_file(&mut self, path: &OsStr, dump: Dump) -> Fallible<()> {
+        let (name, content) = read_source(path)?;
+        self.dump(&name, &content, dump)
     }
 
     pub fn run_prompt(&mut self) -> Fallible<()> {
-        let mut rl = Editor::<()>::new();
+        let mut rl = Editor::new();
         rl.set_auto_add_history(true);
+        rl.set_helper(Some(ReplHelper::new(Rc::clone(&self.inter.globals))));
+        let history = repl::history_path();
+        if let Some(history) = &history {
+            // There's no history yet on the first session
+            rl.load_history(history).ok();
+        }
 
-        println!("Lox {}", VERSION);
-        println!("Press Ctrl+D to exit\n");
+        writeln!(self.inter.output, "Lox {}", VERSION)?;
+        writeln!(self.inter.output, "Press Ctrl+D to exit\n")?;
+        self.inter.output.flush()?;
 
         let prompt = format!("{}> ", Blue.bold().paint("lox"));
 
         loop {
             match rl.readline(&prompt) {
                 Ok(line) if line.is_empty() => (),
-                Ok(line) => match self.run_prompt_line(&line) {
-                    Ok(()) => (),
-                    Err(err) => print_err(&err),
-                },
+                Ok(line) => {
+                    let res = self.run_prompt_line(&line);
+                    self.inter.output.flush()?;
+                    if let Err(err) = res {
+                        self.print_err(&err)?;
+                    }
+                    // `:reset` replaces the globals
+                    if let Some(helper) = rl.helper_mut() {
+                        helper.globals = Rc::clone(&self.inter.globals);
+                    }
+                }
                 Err(ReadlineError::Interrupted) => (),
                 Err(ReadlineError::Eof) => break,
                 Err(err) => return Err(err.into()),
             }
         }
 
+        if let Some(history) = &history {
+            rl.save_history(history)?;
+        }
+
         Ok(())
     }
 
     fn run_prompt_line(&mut self, input: &str) -> Fallible<()> {
-        let mut scanner = Scanner::new(input);
+        if let Some(command) = input.trim_start().strip_prefix(':') {
+            return self.run_command(command.trim_end());
+        }
+
+        let stmts = self.parse_prompt_line(input)?;
+        if let [Stmt {
+            kind: StmtKind::Expression(expr),
+            ..
+        }] = stmts.as_slice()
+        {
+            let val = self
+                .inter
+                .evaluate(expr)
+                .map_err(|error| self.inter.traceback(error))?;
+            let out = &mut self.inter.output;
+            write!(out, "=> ")?;
+            print_value(out, &val)?;
+            writeln!(out)?;
+
+            // The prompt takes an expression without a semicolon, but a
+            // script doesn't
+            let (code, rest) = input.split_at(expr.loc.end);
+            if !rest.trim_start().starts_with(';') {
+                self.session.push(format!("{};{}", code, rest));
+                return Ok(());
+            }
+        } else {
+            self.interpret(&stmts)?;
+        }
+
+        self.session.push(String::from(input));
+        Ok(())
+    }
+
+    /// Scans, parses and resolves code typed at the prompt, which may be an
+    /// expression without a semicolon.
+    fn parse_prompt_line(&mut self, input: &str) -> Fallible<Vec<Stmt>> {
+        let file = self.inter.sources.add("<repl>", input);
+        let mut scanner = Scanner::new(input, file);
         let tokens = scanner.scan_tokens()?;
 
         let mut parser = Parser::new(&tokens);
         parser.allow_expression = true;
         let stmts = parser.parse()?;
 
-        let mut resolver = Resolver::new(&mut self.inter);
+        // A global used at the prompt is either reported by the runtime error
+        // when the line runs, or defined on a later line before a function
+        // using it is called, so the warning would only repeat or mislead.
+        let mut lint_levels = self.inter.lint_levels.clone();
+        if lint_levels.get(Lint::UndefinedVariable) == LintLevel::Warn {
+            lint_levels.set(Lint::UndefinedVariable, LintLevel::Allow);
+        }
+
+        let allowed_lints = std::mem::take(&mut scanner.allowed_lints);
+        let mut resolver = Resolver::with_lints(lint_levels, allowed_lints);
+        resolver.set_known_globals(self.inter.globals.borrow().signatures());
         resolver.resolve(&stmts)?;
+        let warnings = resolver.warnings;
 
-        error::print_warns(&resolver.warnings);
-
-        if stmts.len() == 1 {
-            match stmts.first().unwrap() {
-                Stmt {
-                    kind: StmtKind::Expression(expr),
-                    ..
-                } => {
-                    let val = self.inter.evaluate(expr)?;
-                    print!("=> ");
-                    print_value(&val);
-                    println!();
-                }
-                _ => {
-                    self.inter.interpret(&stmts)?;
-                }
+        error::print_warns(
+            &mut self.inter.diagnostics,
+            &warnings,
+            &self.inter.sources,
+            self.inter.error_format,
+            self.inter.colored_diagnostics,
+        )?;
+
+        Ok(stmts)
+    }
+
+    /// Runs a `:command` typed at the prompt, given without its colon.
+    fn run_command(&mut self, command: &str) -> Fallible<()> {
+        let (name, arg) = match command.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (command, ""),
+        };
+
+        let usage = match name {
+            "load" | "save" => Some("<file>"),
+            "type" => Some("<expr>"),
+            "time" | "ast" => Some("<code>"),
+            _ => None,
+        };
+        if let Some(usage) = usage.filter(|_usage| arg.is_empty()) {
+            return Err(err_msg(format!("Usage: :{} {}", name, usage)));
+        }
+
+        match name {
+            "help" => writeln!(self.inter.output, "{}", REPL_HELP)?,
+            "load" => {
+                let content = self.load_file(OsStr::new(arg))?;
+                self.session.push(content);
+            }
+            "reset" => self.reset(),
+            "env" => self.print_env()?,
+            "type" => {
+                let stmts = self.parse_prompt_line(arg)?;
+                let expr = match stmts.as_slice() {
+                    [Stmt {
+                        kind: StmtKind::Expression(expr),
+                        ..
+                    }] => expr,
+                    _ => return Err(err_msg("Usage: :type <expr>")),
+                };
+                let val = self
+                    .inter
+                    .evaluate(expr)
+                    .map_err(|error| self.inter.traceback(error))?;
+                writeln!(self.inter.output, "{}", val.get_type())?;
+            }
+            // The timed code is part of the session like any other, so
+            // `:save` keeps it
+            "time" => {
+                let start = Instant::now();
+                self.run_prompt_line(arg)?;
+                writeln!(self.inter.output, "Took {:?}", start.elapsed())?;
+            }
+            "ast" => {
+                let file = self.inter.sources.add("<repl>", arg);
+                let tokens = Scanner::new(arg, file).scan_tokens()?;
+                let mut parser = Parser::new(&tokens);
+                parser.allow_expression = true;
+                let stmts = parser.parse()?;
+                dump::dump_ast(&mut self.inter.output, &stmts, AstFormat::Tree, false)?;
+            }
+            "save" => {
+                // Lines read from a pipe and loaded files end with a newline
+                let entries: Vec<&str> = self.session.iter().map(|entry| entry.trim()).collect();
+                let mut script = entries.join("\n");
+                script.push('\n');
+                let context = format!("Could not write '{}'", arg);
+                std::fs::write(arg, script).context(context)?;
+            }
+            _ => {
+                let message = format!("Unknown command ':{}', see :help", name);
+                return Err(err_msg(message));
             }
-        } else {
-            self.inter.interpret(&stmts)?;
         }
 
         Ok(())
     }
+
+    /// Starts the session over with a new interpreter, which keeps the
+    /// settings and what the host registered.
+    fn reset(&mut self) {
+        let old = std::mem::take(&mut self.inter);
+        self.inter.natives.host_functions = old.natives.host_functions;
+        self.inter.natives.host_classes = old.natives.host_classes;
+        self.inter.globals = Rc::new(RefCell::new(self.inter.builtin_globals()));
+        self.inter.backend = old.backend;
+        self.inter.max_call_depth = old.max_call_depth;
+        self.inter.max_stack_size = old.max_stack_size;
+        self.inter.output = old.output;
+        self.inter.diagnostics = old.diagnostics;
+        self.inter.error_format = old.error_format;
+        self.inter.colored_diagnostics = old.colored_diagnostics;
+        self.inter.lint_levels = old.lint_levels;
+        self.session.clear();
+    }
+
+    /// Lists the globals defined in the session, leaving out the builtins.
+    fn print_env(&mut self) -> io::Result<()> {
+        let builtins = self.inter.builtin_globals();
+        let builtins: Vec<&String> = builtins.names().collect();
+        let globals = self.inter.globals.borrow();
+        let mut names: Vec<&String> = globals
+            .names()
+            .filter(|name| !builtins.contains(name))
+            .collect();
+        names.sort();
+
+        for name in names {
+            let val = globals.get(name, Loc::default()).unwrap();
+            writeln!(self.inter.output, "{}: {}", name, val.get_type())?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints an error returned by `run` or `call` to the diagnostics sink,
+    /// showing the code where it happened.
+    pub fn print_err(&mut self, err: &Error) -> io::Result<()> {
+        error::print_err(
+            &mut self.inter.diagnostics,
+            err,
+            &self.inter.sources,
+            self.inter.error_format,
+            self.inter.colored_diagnostics,
+        )
+    }
+
+    fn interpret(&mut self, stmts: &[Stmt]) -> Fallible<()> {
+        self.inter
+            .interpret(stmts)
+            .map_err(|error| self.inter.traceback(error).into())
+    }
+}
+
+/// Reads the file at `path`, or stdin for `-`, returning the name to show
+/// in errors along with the content.
+fn read_source(path: &OsStr) -> Fallible<(String, String)> {
+    if path == "-" {
+        let mut content = String::new();
+        stdin()
+            .lock()
+            .read_to_string(&mut content)
+            .context("Could not read from stdin")?;
+        Ok((String::from("<stdin>"), content))
+    } else {
+        let path = Path::new(path);
+        let context = format!("Could not read '{}'", path.display());
+        let content = std::fs::read_to_string(path).context(context)?;
+        Ok((path.display().to_string(), content))
+    }
 }
 
-fn print_value(val: &Value) {
+fn print_value(out: &mut dyn Write, val: &Value) -> io::Result<()> {
     let output = match val {
         Value::Integer(int) => Blue.paint(int.to_string()),
         Value::Float(float) => Cyan.paint(float.to_string()),
@@ -160,17 +593,31 @@ fn print_value(val: &Value) {
         Value::Instance(instance) => Yellow.paint(instance.borrow().to_string()),
         Value::Array(array) => {
             let len = array.borrow().len();
-            print!("[");
+            write!(out, "[")?;
             for (i, el) in array.borrow().iter().enumerate() {
-                print_value(el);
+                print_value(out, el)?;
                 if i < len - 1 {
-                    print!(", ");
+                    write!(out, ", ")?;
                 }
             }
 
             Style::new().paint("]")
         }
+        Value::Map(map) => {
+            let len = map.borrow().len();
+            write!(out, "{{")?;
+            for (i, (key, val)) in map.borrow().iter().enumerate() {
+                print_value(out, &key.into())?;
+                write!(out, ": ")?;
+                print_value(out, val)?;
+                if i < len - 1 {
+                    write!(out, ", ")?;
+                }
+            }
+
+            Style::new().paint("}")
+        }
     };
 
-    print!("{}", output);
+    write!(out, "{}", output)
 }
@@ -0,0 +1,111 @@
+use crate::error::Warning;
+use std::collections::{HashMap, HashSet};
+
+/// A kind of warning the resolver reports, which can be allowed or denied
+/// on its own.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    UnusedFunction,
+    UnreachableCode,
+    ShadowedVariable,
+    AssignmentInCondition,
+    SelfComparison,
+    UnassignedVariable,
+    UndefinedVariable,
+    ArityMismatch,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LintLevel {
+    /// The warning isn't reported.
+    Allow,
+    Warn,
+    /// The warning is reported as an error, so the code isn't run.
+    Deny,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 10] = [
+        Lint::UnusedVariable,
+        Lint::UnusedParameter,
+        Lint::UnusedFunction,
+        Lint::UnreachableCode,
+        Lint::ShadowedVariable,
+        Lint::AssignmentInCondition,
+        Lint::SelfComparison,
+        Lint::UnassignedVariable,
+        Lint::UndefinedVariable,
+        Lint::ArityMismatch,
+    ];
+
+    /// The name used for the lint in command line flags and comments.
+    pub fn name(self) -> &'static str {
+        use Lint::*;
+        match self {
+            UnusedVariable => "unused_variable",
+            UnusedParameter => "unused_parameter",
+            UnusedFunction => "unused_function",
+            UnreachableCode => "unreachable_code",
+            ShadowedVariable => "shadowed_variable",
+            AssignmentInCondition => "assignment_in_condition",
+            SelfComparison => "self_comparison",
+            UnassignedVariable => "unassigned_variable",
+            UndefinedVariable => "undefined_variable",
+            ArityMismatch => "arity_mismatch",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+}
+
+/// The level of every lint, which is `Warn` unless it was changed.
+#[derive(Clone, Default, Debug)]
+pub struct LintLevels {
+    levels: HashMap<Lint, LintLevel>,
+}
+
+impl LintLevels {
+    pub fn get(&self, lint: Lint) -> LintLevel {
+        self.levels.get(&lint).copied().unwrap_or(LintLevel::Warn)
+    }
+
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        self.levels.insert(lint, level);
+    }
+}
+
+/// The lints allowed by `// lox:allow(lint, ...)` comments in a source. A
+/// comment at the end of a line allows them on that line, and one on a line
+/// of its own allows them on the next line.
+#[derive(Default, Debug)]
+pub struct AllowedLints {
+    lines: HashMap<usize, HashSet<Lint>>,
+}
+
+impl AllowedLints {
+    /// Records the lints `comment`, the text after `//` of a comment,
+    /// allows on `line`. Unknown lint names are ignored.
+    pub fn add_comment(&mut self, line: usize, comment: &str) {
+        let names = comment
+            .trim()
+            .strip_prefix("lox:allow(")
+            .and_then(|rest| rest.split(')').next());
+
+        if let Some(names) = names {
+            let lints = names
+                .split(',')
+                .filter_map(|name| Lint::from_name(name.trim()));
+            self.lines.entry(line).or_default().extend(lints);
+        }
+    }
+
+    pub fn allows(&self, warn: &Warning) -> bool {
+        self.lines
+            .get(&warn.loc().line)
+            .is_some_and(|lints| lints.contains(&warn.lint()))
+    }
+}
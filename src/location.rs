@@ -1,14 +1,51 @@
+use crate::source::FileId;
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::hash::{Hash, Hasher};
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+/// Where some code is: the line and column it's reported at, the file it's
+/// in and the byte range it spans in that file's source.
+///
+/// Two locations are equal when they are reported at the same place of the
+/// same file, regardless of their spans.
+#[derive(Copy, Clone, Debug, Default)]
 pub struct Loc {
     pub line: usize,
     pub column: usize,
+    pub file: FileId,
+    /// Byte offset where the code starts in the source.
+    pub start: usize,
+    /// Byte offset right after the end of the code.
+    pub end: usize,
 }
 
 impl Loc {
     pub fn new(line: usize, column: usize) -> Self {
-        Loc { line, column }
+        Loc {
+            line,
+            column,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_span(self, start: usize, end: usize) -> Self {
+        Self { start, end, ..self }
+    }
+
+    /// This location, with its span grown to cover `other` too.
+    pub fn extend(self, other: Loc) -> Self {
+        if self.file != other.file {
+            return self;
+        }
+
+        Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+            ..self
+        }
+    }
+
+    pub fn has_span(&self) -> bool {
+        self.end > self.start
     }
 
     pub fn advance(&mut self) {
@@ -21,6 +58,20 @@ impl Loc {
     }
 }
 
+impl PartialEq for Loc {
+    fn eq(&self, other: &Self) -> bool {
+        (self.line, self.column, self.file) == (other.line, other.column, other.file)
+    }
+}
+
+impl Eq for Loc {}
+
+impl Hash for Loc {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.line, self.column, self.file).hash(state);
+    }
+}
+
 impl Display for Loc {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(f, "{}:{}", self.line + 1, self.column)
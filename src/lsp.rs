@@ -0,0 +1,676 @@
+#[cfg(test)]
+mod tests;
+
+use crate::callable::Arity;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::error;
+use crate::eval::Interpreter;
+use crate::lexer::{Scanner, Token, TokenKind, KEYWORDS};
+use crate::lint::LintLevels;
+use crate::location::Loc;
+use crate::parser::Parser;
+use crate::resolver::{BindingKind, Resolver};
+use crate::source::SourceMap;
+use crate::stmt::{FunctionKind, Stmt, StmtKind};
+use failure::{err_msg, Fallible};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+
+/// Error codes defined by JSON-RPC.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Kinds of document symbols, as numbered by the protocol.
+const SYMBOL_CLASS: u32 = 5;
+const SYMBOL_METHOD: u32 = 6;
+const SYMBOL_FUNCTION: u32 = 12;
+
+/// Kinds of completion items, as numbered by the protocol.
+const COMPLETION_METHOD: u32 = 2;
+const COMPLETION_FUNCTION: u32 = 3;
+const COMPLETION_VARIABLE: u32 = 6;
+const COMPLETION_CLASS: u32 = 7;
+const COMPLETION_MODULE: u32 = 9;
+const COMPLETION_KEYWORD: u32 = 14;
+
+/// A range of bytes in a document.
+type Span = (usize, usize);
+
+/// A Language Server Protocol server, which checks the documents an editor
+/// has open and answers questions about their names.
+pub struct LanguageServer {
+    documents: HashMap<String, Document>,
+    /// The builtins, with the arity of the callable ones.
+    globals: HashMap<String, Option<Arity>>,
+    shut_down: bool,
+    exited: bool,
+}
+
+struct Document {
+    text: String,
+    analysis: Analysis,
+}
+
+/// What is known about a document, found when it changes.
+#[derive(Default)]
+struct Analysis {
+    diagnostics: Vec<Diagnostic>,
+    /// Whether the document has no syntax errors. Otherwise the names are
+    /// the ones of the last version which had none.
+    parsed: bool,
+    definitions: Vec<Definition>,
+    symbols: Vec<Symbol>,
+    identifiers: Vec<(Span, String)>,
+}
+
+/// A name declared in a document.
+struct Definition {
+    name: String,
+    kind: BindingKind,
+    /// Where the name is written in its declaration.
+    span: Span,
+    /// The code the name is visible in, or `None` for globals.
+    scope: Option<Span>,
+    references: Vec<Span>,
+}
+
+/// A function, class or method, with the ones declared in its body.
+struct Symbol {
+    name: String,
+    kind: u32,
+    span: Span,
+    name_span: Span,
+    children: Vec<Symbol>,
+}
+
+type RequestRes = Result<Value, (i64, String)>;
+
+impl LanguageServer {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        LanguageServer {
+            documents: HashMap::new(),
+            globals: Interpreter::new().builtin_globals().signatures(),
+            shut_down: false,
+            exited: false,
+        }
+    }
+
+    /// Answers the messages read from `input` until the client says to exit
+    /// or closes it.
+    pub fn run(&mut self, input: &mut dyn BufRead, output: &mut dyn Write) -> Fallible<()> {
+        while let Some(body) = read_message(input)? {
+            let replies = match serde_json::from_slice(&body) {
+                Ok(message) => self.handle(&message),
+                Err(err) => vec![error_reply(&Value::Null, PARSE_ERROR, err.to_string())],
+            };
+
+            for reply in replies {
+                write_message(output, &reply)?;
+            }
+
+            if self.exited {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the client asked the server to shut down, which it should do
+    /// before telling it to exit.
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down
+    }
+
+    /// Handles a request or notification from the client, returning the
+    /// messages to send back.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = match message["method"].as_str() {
+            Some(method) => method,
+            // A reply to a request, which the server never sends
+            None => return vec![],
+        };
+        let params = &message["params"];
+
+        match message.get("id") {
+            Some(id) => vec![match self.request(method, params) {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                Err((code, message)) => error_reply(id, code, message),
+            }],
+            None => self.notification(method, params),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> RequestRes {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": {"triggerCharacters": ["."]},
+                },
+                "serverInfo": {"name": "lox", "version": env!("CARGO_PKG_VERSION")},
+            })),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            "textDocument/completion" => self.completion(params),
+            method => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let document = &params["textDocument"];
+        let uri = document["uri"].as_str().unwrap_or_default();
+
+        match method {
+            "exit" => {
+                self.exited = true;
+                vec![]
+            }
+            "textDocument/didOpen" => {
+                let text = document["text"].as_str().unwrap_or_default();
+                vec![self.update(uri, text)]
+            }
+            "textDocument/didChange" => {
+                // The server asks for the whole text on every change
+                let changes = params["contentChanges"].as_array();
+                match changes.and_then(|changes| changes.last()) {
+                    Some(change) => {
+                        let text = change["text"].as_str().unwrap_or_default();
+                        vec![self.update(uri, text)]
+                    }
+                    None => vec![],
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                vec![publish_diagnostics(uri, vec![])]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Analyzes the new text of a document, returning the notification with
+    /// its diagnostics.
+    fn update(&mut self, uri: &str, text: &str) -> Value {
+        let mut analysis = analyze(uri, text, &self.globals);
+        if !analysis.parsed {
+            // Keeps answering while the code is being typed, such as after
+            // `object.` when completing a method
+            if let Some(previous) = self.documents.remove(uri) {
+                analysis.definitions = previous.analysis.definitions;
+                analysis.symbols = previous.analysis.symbols;
+                analysis.identifiers = previous.analysis.identifiers;
+            }
+        }
+
+        let document = Document {
+            text: String::from(text),
+            analysis,
+        };
+        let diagnostics = document
+            .analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| lsp_diagnostic(diagnostic, uri, text))
+            .collect();
+
+        self.documents.insert(String::from(uri), document);
+        publish_diagnostics(uri, diagnostics)
+    }
+
+    /// The document and byte offset of the position in `params`.
+    fn position(&self, params: &Value) -> Result<(&str, &Document, usize), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let (uri, document) = self
+            .documents
+            .get_key_value(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document '{}'", uri)))?;
+        let offset = offset(&document.text, &params["position"])
+            .ok_or_else(|| (INVALID_PARAMS, String::from("Invalid position")))?;
+        Ok((uri, document, offset))
+    }
+
+    fn definition(&self, params: &Value) -> RequestRes {
+        let (uri, document, offset) = self.position(params)?;
+        Ok(match document.analysis.definition_at(offset) {
+            Some(definition) => location(uri, &document.text, definition.span),
+            None => Value::Null,
+        })
+    }
+
+    fn references(&self, params: &Value) -> RequestRes {
+        let (uri, document, offset) = self.position(params)?;
+        let definition = match document.analysis.definition_at(offset) {
+            Some(definition) => definition,
+            None => return Ok(json!([])),
+        };
+
+        let declaration = params["context"]["includeDeclaration"].as_bool() == Some(true);
+        let locations: Vec<Value> = declaration
+            .then_some(definition.span)
+            .into_iter()
+            .chain(definition.references.iter().copied())
+            .map(|span| location(uri, &document.text, span))
+            .collect();
+        Ok(json!(locations))
+    }
+
+    fn hover(&self, params: &Value) -> RequestRes {
+        let (_, document, offset) = self.position(params)?;
+        let analysis = &document.analysis;
+
+        let (span, description) = if let Some(definition) = analysis.definition_at(offset) {
+            let span = definition
+                .references
+                .iter()
+                .copied()
+                .find(|span| contains(*span, offset))
+                .unwrap_or(definition.span);
+            (span, describe(&definition.name, definition.kind))
+        } else {
+            let identifier = analysis
+                .identifiers
+                .iter()
+                .find(|(span, name)| contains(*span, offset) && self.globals.contains_key(name));
+            match identifier {
+                Some((span, name)) => {
+                    let description = match self.globals[name] {
+                        Some(arity) => format!("builtin {}\n\nTakes {}", name, arguments(arity)),
+                        None => format!("builtin {}", name),
+                    };
+                    (*span, description)
+                }
+                None => return Ok(Value::Null),
+            }
+        };
+
+        Ok(json!({
+            "contents": {"kind": "markdown", "value": description},
+            "range": range(&document.text, span),
+        }))
+    }
+
+    fn document_symbols(&self, params: &Value) -> RequestRes {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document '{}'", uri)))?;
+
+        let symbols: Vec<Value> = document
+            .analysis
+            .symbols
+            .iter()
+            .map(|symbol| symbol.to_json(&document.text))
+            .collect();
+        Ok(json!(symbols))
+    }
+
+    /// The names visible where the cursor is, or the methods declared in
+    /// the document after a `.`.
+    fn completion(&self, params: &Value) -> RequestRes {
+        let (_, document, offset) = self.position(params)?;
+        let before = &document.text[..offset];
+        let word_start = before
+            .rfind(|c: char| !c.is_alphanumeric() && c != '_')
+            .map_or(0, |i| i + 1);
+
+        let mut items = Vec::new();
+        let mut seen = HashSet::new();
+        let mut add = |name: &str, kind: u32, detail: String| {
+            if seen.insert(String::from(name)) {
+                items.push(json!({"label": name, "kind": kind, "detail": detail}));
+            }
+        };
+
+        if before[..word_start].ends_with('.') {
+            let classes = document.analysis.symbols.iter();
+            for method in classes.flat_map(|class| &class.children) {
+                if method.kind == SYMBOL_METHOD {
+                    add(&method.name, COMPLETION_METHOD, String::from("method"));
+                }
+            }
+            return Ok(json!(items));
+        }
+
+        for definition in &document.analysis.definitions {
+            if definition.is_visible_at(offset) {
+                let kind = match definition.kind {
+                    BindingKind::Function(_) => COMPLETION_FUNCTION,
+                    BindingKind::Class(_) => COMPLETION_CLASS,
+                    BindingKind::Import => COMPLETION_MODULE,
+                    BindingKind::Variable | BindingKind::Parameter => COMPLETION_VARIABLE,
+                };
+                add(
+                    &definition.name,
+                    kind,
+                    describe(&definition.name, definition.kind),
+                );
+            }
+        }
+
+        let mut globals: Vec<_> = self.globals.iter().collect();
+        globals.sort_by_key(|(name, _)| name.as_str());
+        for (name, arity) in globals {
+            let kind = match arity {
+                Some(_) => COMPLETION_FUNCTION,
+                None => COMPLETION_VARIABLE,
+            };
+            add(name, kind, format!("builtin {}", name));
+        }
+
+        for keyword in KEYWORDS.iter() {
+            add(keyword, COMPLETION_KEYWORD, String::from("keyword"));
+        }
+
+        Ok(json!(items))
+    }
+}
+
+impl Analysis {
+    /// The definition whose name, or one of its uses, is at `offset`.
+    fn definition_at(&self, offset: usize) -> Option<&Definition> {
+        self.definitions.iter().find(|definition| {
+            contains(definition.span, offset)
+                || definition
+                    .references
+                    .iter()
+                    .any(|span| contains(*span, offset))
+        })
+    }
+}
+
+impl Definition {
+    fn is_visible_at(&self, offset: usize) -> bool {
+        match self.scope {
+            None => true,
+            Some(scope) => contains(scope, offset) && self.span.0 <= offset,
+        }
+    }
+}
+
+impl Symbol {
+    fn to_json(&self, text: &str) -> Value {
+        let children: Vec<Value> = self
+            .children
+            .iter()
+            .map(|child| child.to_json(text))
+            .collect();
+        json!({
+            "name": self.name,
+            "kind": self.kind,
+            "range": range(text, self.span),
+            "selectionRange": range(text, self.name_span),
+            "children": children,
+        })
+    }
+}
+
+/// Scans, parses and resolves a document, keeping what the requests need.
+fn analyze(uri: &str, text: &str, globals: &HashMap<String, Option<Arity>>) -> Analysis {
+    let mut analysis = Analysis::default();
+    let mut sources = SourceMap::default();
+    let file = sources.add(uri, text);
+
+    let mut scanner = Scanner::new(text, file);
+    let tokens = match scanner.scan_tokens() {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            analysis.diagnostics = error::diagnostics(&err.into());
+            return analysis;
+        }
+    };
+
+    let stmts = match Parser::new(&tokens).parse() {
+        Ok(stmts) => stmts,
+        Err(err) => {
+            analysis.diagnostics = error::diagnostics(&err.into());
+            return analysis;
+        }
+    };
+
+    analysis.parsed = true;
+    let allowed_lints = std::mem::take(&mut scanner.allowed_lints);
+    let mut resolver = Resolver::with_lints(LintLevels::default(), allowed_lints);
+    resolver.set_known_globals(globals.clone());
+    if let Err(err) = resolver.resolve(&stmts) {
+        analysis.diagnostics = error::diagnostics(&err.into());
+    }
+    let warnings = resolver.warnings.iter().map(error::warning_diagnostic);
+    analysis.diagnostics.extend(warnings);
+
+    analysis.definitions = resolver
+        .bindings
+        .iter()
+        .map(|binding| Definition {
+            name: binding.name.clone(),
+            kind: binding.kind,
+            span: name_span(&tokens, &binding.name, binding.loc),
+            scope: binding.scope.map(|scope| (scope.start, scope.end)),
+            references: binding
+                .references
+                .iter()
+                .map(|loc| name_span(&tokens, &binding.name, *loc))
+                .collect(),
+        })
+        .collect();
+    analysis.symbols = symbols(&stmts, &tokens);
+    analysis.identifiers = tokens
+        .iter()
+        .filter(|token| token.kind == TokenKind::Identifier)
+        .map(|token| ((token.loc.start, token.loc.end), String::from(token.lexeme)))
+        .collect();
+
+    analysis
+}
+
+fn symbols(stmts: &[Stmt], tokens: &[Token]) -> Vec<Symbol> {
+    stmts
+        .iter()
+        .filter_map(|stmt| {
+            let (name, kind, body) = match &stmt.kind {
+                StmtKind::Function(name, _, body, FunctionKind::Function) => {
+                    (name, SYMBOL_FUNCTION, body)
+                }
+                StmtKind::Function(name, _, body, _) => (name, SYMBOL_METHOD, body),
+                StmtKind::Class(name, _, methods) => (name, SYMBOL_CLASS, methods),
+                _ => return None,
+            };
+
+            Some(Symbol {
+                name: name.clone(),
+                kind,
+                span: (stmt.loc.start, stmt.loc.end),
+                name_span: name_span(tokens, name, stmt.loc),
+                children: symbols(body, tokens),
+            })
+        })
+        .collect()
+}
+
+/// Where `name` is first written in the code at `loc`, which can start
+/// before it, like the `var` of a declaration.
+fn name_span(tokens: &[Token], name: &str, loc: Loc) -> Span {
+    let first = tokens.partition_point(|token| token.loc.start < loc.start);
+    tokens[first..]
+        .iter()
+        .take_while(|token| token.loc.start < loc.end)
+        .find(|token| token.kind == TokenKind::Identifier && token.lexeme == name)
+        .map_or((loc.start, loc.end), |token| {
+            (token.loc.start, token.loc.end)
+        })
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.0 <= offset && offset <= span.1
+}
+
+fn describe(name: &str, kind: BindingKind) -> String {
+    match kind {
+        BindingKind::Variable => format!("var {}", name),
+        BindingKind::Parameter => format!("parameter {}", name),
+        BindingKind::Function(arity) => format!("fun {}\n\nTakes {}", name, arguments(arity)),
+        BindingKind::Class(Some(arity)) => format!("class {}\n\nTakes {}", name, arguments(arity)),
+        BindingKind::Class(None) => format!("class {}", name),
+        BindingKind::Import => format!("import {}", name),
+    }
+}
+
+fn arguments(arity: Arity) -> String {
+    match arity {
+        Arity::Fixed(1) => String::from("1 argument"),
+        arity => format!("{} arguments", arity),
+    }
+}
+
+fn lsp_diagnostic(diagnostic: &Diagnostic, uri: &str, text: &str) -> Value {
+    let span = diagnostic.loc().map_or((0, 0), |loc| loc_span(text, loc));
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push('\n');
+        message.push_str(note);
+    }
+
+    let related: Vec<Value> = diagnostic
+        .labels
+        .iter()
+        .filter(|label| !label.primary)
+        .map(|label| {
+            json!({
+                "location": location(uri, text, loc_span(text, label.loc)),
+                "message": label.message,
+            })
+        })
+        .collect();
+
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+    json!({
+        "range": range(text, span),
+        "severity": severity,
+        "code": diagnostic.code,
+        "source": "lox",
+        "message": message,
+        "relatedInformation": related,
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+fn error_reply(id: &Value, code: i64, message: String) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// The bytes `loc` covers, or where it is if it has no span.
+fn loc_span(text: &str, loc: Loc) -> Span {
+    if loc.has_span() {
+        return (loc.start, loc.end);
+    }
+
+    let line_start = match loc.line {
+        0 => 0,
+        line => text
+            .match_indices('\n')
+            .nth(line - 1)
+            .map_or(text.len(), |(newline, _)| newline + 1),
+    };
+    let offset = text[line_start..]
+        .char_indices()
+        .nth(loc.column)
+        .map_or(text.len(), |(i, _)| line_start + i);
+    (offset, offset)
+}
+
+fn location(uri: &str, text: &str, span: Span) -> Value {
+    json!({"uri": uri, "range": range(text, span)})
+}
+
+fn range(text: &str, span: Span) -> Value {
+    json!({"start": position(text, span.0), "end": position(text, span.1)})
+}
+
+/// The protocol position of a byte offset. Its character counts UTF-16 code
+/// units.
+fn position(text: &str, offset: usize) -> Value {
+    let before = text.get(..offset).unwrap_or(text);
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let line = before.matches('\n').count();
+    let character = before[line_start..].encode_utf16().count();
+    json!({"line": line, "character": character})
+}
+
+/// The byte offset of a protocol position.
+fn offset(text: &str, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+    let line_start = match line {
+        0 => 0,
+        line => text.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+
+    Some(text.len())
+}
+
+/// Reads the body of a message framed by a `Content-Length` header, or
+/// returns `None` at the end of the input.
+fn read_message(input: &mut dyn BufRead) -> Fallible<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| err_msg("Message without a Content-Length header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut dyn Write, message: &Value) -> Fallible<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use super::*;
+use std::io::Cursor;
+
+const URI: &str = "file:///test.lox";
+
+fn open(server: &mut LanguageServer, text: &str) -> Vec<Value> {
+    server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {"textDocument": {"uri": URI, "languageId": "lox", "version": 1, "text": text}},
+    }))
+}
+
+fn request(server: &mut LanguageServer, method: &str, line: usize, character: usize) -> Value {
+    let mut replies = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": {
+            "textDocument": {"uri": URI},
+            "position": {"line": line, "character": character},
+            "context": {"includeDeclaration": true},
+        },
+    }));
+    assert_eq!(1, replies.len());
+    replies.remove(0)["result"].take()
+}
+
+fn range(start_line: usize, start: usize, end_line: usize, end: usize) -> Value {
+    json!({
+        "start": {"line": start_line, "character": start},
+        "end": {"line": end_line, "character": end},
+    })
+}
+
+const SOURCE: &str = r#"fun add(a, b) {
+    return a + b;
+}
+var total = add(1, 2);
+{
+    var local = total;
+    print local;
+}
+"#;
+
+#[test]
+fn test_diagnostics_are_published() {
+    let mut server = LanguageServer::new();
+    let replies = open(&mut server, "var x = 1;\nprint y;\nvar = 2;");
+    let diagnostics = &replies[0]["params"]["diagnostics"];
+    assert_eq!("textDocument/publishDiagnostics", replies[0]["method"]);
+    assert_eq!(1, diagnostics.as_array().unwrap().len());
+    assert_eq!(1, diagnostics[0]["severity"]);
+    assert_eq!(range(2, 4, 2, 5), diagnostics[0]["range"]);
+
+    let replies = open(&mut server, "{ var x = 1; }\nprint y;");
+    let diagnostics = &replies[0]["params"]["diagnostics"];
+    let codes: Vec<_> = diagnostics
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|diagnostic| (diagnostic["code"].clone(), diagnostic["severity"].clone()))
+        .collect();
+    assert_eq!(
+        vec![(json!("W0001"), json!(2)), (json!("W0009"), json!(2))],
+        codes
+    );
+    assert_eq!(range(1, 6, 1, 7), diagnostics[1]["range"]);
+}
+
+#[test]
+fn test_definition() {
+    let mut server = LanguageServer::new();
+    open(&mut server, SOURCE);
+
+    let definition = request(&mut server, "textDocument/definition", 3, 13);
+    assert_eq!(json!({"uri": URI, "range": range(0, 4, 0, 7)}), definition);
+
+    let definition = request(&mut server, "textDocument/definition", 1, 11);
+    assert_eq!(json!({"uri": URI, "range": range(0, 8, 0, 9)}), definition);
+
+    let definition = request(&mut server, "textDocument/definition", 1, 6);
+    assert_eq!(Value::Null, definition);
+}
+
+#[test]
+fn test_references() {
+    let mut server = LanguageServer::new();
+    open(&mut server, SOURCE);
+
+    let references = request(&mut server, "textDocument/references", 5, 16);
+    let expected = json!([
+        {"uri": URI, "range": range(3, 4, 3, 9)},
+        {"uri": URI, "range": range(5, 16, 5, 21)},
+    ]);
+    assert_eq!(expected, references);
+}
+
+#[test]
+fn test_hover() {
+    let mut server = LanguageServer::new();
+    open(&mut server, SOURCE);
+
+    let hover = request(&mut server, "textDocument/hover", 3, 13);
+    assert_eq!("fun add\n\nTakes 2 arguments", hover["contents"]["value"]);
+    assert_eq!(range(3, 12, 3, 15), hover["range"]);
+
+    open(&mut server, "print clock();");
+    let hover = request(&mut server, "textDocument/hover", 0, 7);
+    assert_eq!(
+        "builtin clock\n\nTakes 0 arguments",
+        hover["contents"]["value"]
+    );
+}
+
+#[test]
+fn test_document_symbols() {
+    let mut server = LanguageServer::new();
+    open(&mut server, "class A {\n    m() {}\n}\nfun f() {}");
+
+    let symbols = request(&mut server, "textDocument/documentSymbol", 0, 0);
+    let names: Vec<_> = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| (symbol["name"].clone(), symbol["kind"].clone()))
+        .collect();
+    assert_eq!(vec![(json!("A"), json!(5)), (json!("f"), json!(12))], names);
+    assert_eq!("m", symbols[0]["children"][0]["name"]);
+    assert_eq!(
+        range(1, 4, 1, 5),
+        symbols[0]["children"][0]["selectionRange"]
+    );
+}
+
+#[test]
+fn test_completion() {
+    let mut server = LanguageServer::new();
+    open(&mut server, SOURCE);
+
+    let labels = |items: Value| -> Vec<String> {
+        let items = items.as_array().unwrap().iter();
+        items
+            .map(|item| String::from(item["label"].as_str().unwrap()))
+            .collect()
+    };
+
+    let inside_block = labels(request(&mut server, "textDocument/completion", 6, 10));
+    let outside_block = labels(request(&mut server, "textDocument/completion", 3, 0));
+    for name in ["local", "total", "add", "clock", "while"].iter() {
+        assert!(inside_block.iter().any(|label| label == name), "{}", name);
+    }
+    assert!(!outside_block.iter().any(|label| label == "local"));
+    assert!(!outside_block.iter().any(|label| label == "a"));
+
+    open(
+        &mut server,
+        "class A { size { return 1; } }\nvar a = A();\na;",
+    );
+    open(
+        &mut server,
+        "class A { size { return 1; } }\nvar a = A();\na.",
+    );
+    let methods = labels(request(&mut server, "textDocument/completion", 2, 2));
+    assert_eq!(vec!["size"], methods);
+}
+
+#[test]
+fn test_unknown_method() {
+    let mut server = LanguageServer::new();
+    let replies = server.handle(&json!({"jsonrpc": "2.0", "id": 7, "method": "unknown"}));
+    assert_eq!(METHOD_NOT_FOUND, replies[0]["error"]["code"]);
+    assert_eq!(7, replies[0]["id"]);
+}
+
+#[test]
+fn test_session_over_stdio() {
+    let messages = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"}),
+        json!({"jsonrpc": "2.0", "method": "exit"}),
+    ];
+    let mut input = String::new();
+    for message in messages.iter() {
+        let body = message.to_string();
+        input.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    }
+
+    let mut server = LanguageServer::new();
+    let mut output = Vec::new();
+    server.run(&mut Cursor::new(input), &mut output).unwrap();
+    assert!(server.is_shut_down());
+
+    let mut output = Cursor::new(output);
+    let initialize: Value =
+        serde_json::from_slice(&read_message(&mut output).unwrap().unwrap()).unwrap();
+    assert_eq!(
+        true,
+        initialize["result"]["capabilities"]["definitionProvider"]
+    );
+    let shutdown: Value =
+        serde_json::from_slice(&read_message(&mut output).unwrap().unwrap()).unwrap();
+    assert_eq!(json!({"jsonrpc": "2.0", "id": 2, "result": null}), shutdown);
+    assert!(read_message(&mut output).unwrap().is_none());
+}
+
+#[test]
+fn test_positions_count_utf16_units() {
+    let text = "var é = \"😀\"; x";
+    let offset = text.find('x').unwrap();
+    assert_eq!(json!({"line": 0, "character": 14}), position(text, offset));
+    assert_eq!(
+        Some(offset),
+        super::offset(text, &json!({"line": 0, "character": 14}))
+    );
+}
@@ -1,28 +1,179 @@
 extern crate lox;
 
-use failure::Fallible;
 use lox::error::*;
 use lox::*;
+use std::ffi::OsString;
+
+const USAGE: &str = "Usage: lox [--vm] [--error-format=human|json] \
+                     [--allow|--warn|--deny=<lint|all>]... [script]
+       lox --dump-tokens|--dump-ast[=<format>]|--dump-resolved[=<format>] <script|->
+           where <format> is tree (default), sexp, json or dot
+       lox fmt [--check] <file|->...
+       lox lsp";
+
+/// Parses a `--allow=<lint>` like option into the lints it sets and their
+/// level.
+fn lint_option(option: &str) -> Option<(Vec<Lint>, LintLevel)> {
+    let (flag, name) = option.split_once('=')?;
+    let level = match flag {
+        "--allow" => LintLevel::Allow,
+        "--warn" => LintLevel::Warn,
+        "--deny" => LintLevel::Deny,
+        _ => return None,
+    };
+    let lints = match name {
+        "all" => Lint::ALL.to_vec(),
+        name => vec![Lint::from_name(name)?],
+    };
+    Some((lints, level))
+}
+
+/// Parses a `--dump-ast=<format>` like option into what it dumps.
+fn dump_option(option: &str) -> Option<Dump> {
+    if option == "--dump-tokens" {
+        return Some(Dump::Tokens);
+    }
+
+    let (flag, format) = match option.split_once('=') {
+        Some((flag, name)) => (flag, AstFormat::from_name(name)?),
+        None => (option, AstFormat::Tree),
+    };
+    match flag {
+        "--dump-ast" => Some(Dump::Ast(format)),
+        "--dump-resolved" => Some(Dump::Resolved(format)),
+        _ => None,
+    }
+}
+
+/// Runs `lox fmt`, which rewrites files in the canonical style, or with
+/// `--check` lists the ones which aren't and fails.
+fn format_files(args: impl Iterator<Item = OsString>) -> i32 {
+    let mut check = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.to_str() {
+            Some("--check") => check = true,
+            Some(option) if option.starts_with("--") => {
+                eprintln!("Unknown option '{}'\n{}", option, USAGE);
+                return 64;
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return 64;
+    }
 
-fn program() -> Fallible<()> {
-    let args = std::env::args_os().skip(1).collect::<Vec<_>>();
     let mut lox = Lox::new();
-    match args.len() {
-        0 => lox.run_prompt(),
-        1 => lox.run_file(&args[0]),
-        _ => {
-            eprintln!("Usage: lox [script]");
-            std::process::exit(64);
+    let mut code = 0;
+    for path in paths {
+        match lox.format_file(&path, check) {
+            Ok(true) => (),
+            Ok(false) if check => {
+                println!("Would reformat '{}'", path.to_string_lossy());
+                code = code.max(1);
+            }
+            Ok(false) => (),
+            Err(ref err) => {
+                lox.print_err(err).ok();
+                code = code.max(exit_code(err));
+            }
         }
     }
+
+    code
 }
 
-fn main() {
-    match program() {
-        Ok(()) => (),
+/// Runs `lox lsp`, which serves the Language Server Protocol over stdin
+/// and stdout.
+fn serve_language_server() -> i32 {
+    let mut server = LanguageServer::new();
+    let res = server.run(&mut std::io::stdin().lock(), &mut std::io::stdout().lock());
+
+    match res {
+        Ok(()) if server.is_shut_down() => 0,
+        // The client exited without asking for a shutdown first
+        Ok(()) => 1,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn program() -> i32 {
+    let mut args = std::env::args_os().skip(1).peekable();
+    if args.peek().is_some_and(|arg| arg == "fmt") {
+        return format_files(args.skip(1));
+    }
+    if args.peek().is_some_and(|arg| arg == "lsp") {
+        return serve_language_server();
+    }
+
+    let mut backend = Backend::TreeWalker;
+    let mut error_format = ErrorFormat::Human;
+    let mut lint_levels = Vec::new();
+    let mut dump = None;
+    let mut scripts = Vec::new();
+    for arg in args {
+        match arg.to_str() {
+            Some("--vm") => backend = Backend::Vm,
+            Some("--error-format=human") => error_format = ErrorFormat::Human,
+            Some("--error-format=json") => error_format = ErrorFormat::Json,
+            Some(option) if lint_option(option).is_some() => {
+                lint_levels.extend(lint_option(option));
+            }
+            Some(option) if dump_option(option).is_some() => dump = dump_option(option),
+            Some(option) if option.starts_with("--") => {
+                eprintln!("Unknown option '{}'\n{}", option, USAGE);
+                return 64;
+            }
+            _ => scripts.push(arg),
+        }
+    }
+
+    let mut lox = Lox::with_backend(backend);
+    // Leaves room for the frames below the script's, and for unwinding
+    lox.set_max_stack_size(STACK_SIZE / 4 * 3);
+    lox.set_error_format(error_format);
+    for (lints, level) in lint_levels {
+        for lint in lints {
+            lox.set_lint_level(lint, level);
+        }
+    }
+    let res = match (scripts.len(), dump) {
+        (1, Some(dump)) => lox.dump_file(&scripts[0], dump),
+        (0, None) => lox.run_prompt(),
+        (1, None) => lox.run_file(&scripts[0]),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 64;
+        }
+    };
+
+    match res {
+        Ok(()) => 0,
         Err(ref err) => {
-            print_err(err);
-            std::process::exit(exit_code(err));
+            lox.print_err(err).ok();
+            exit_code(err)
         }
     }
 }
+
+/// The tree walker recurses on the native stack, so scripts run in a thread
+/// with room for the maximum call depth even in debug builds.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+fn main() {
+    let program = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(program)
+        .expect("Could not spawn the interpreter thread");
+
+    let code = program.join().expect("The interpreter thread panicked");
+    if code != 0 {
+        std::process::exit(code);
+    }
+}
@@ -0,0 +1,342 @@
+use crate::array::Array;
+use crate::callable::{Arity, Callable, LoxCallable};
+use crate::class::{Class, InstanceRc, LoxClass, MethodMap, NativeMethod};
+use crate::constants::MAP_CLASS;
+use crate::eval::{Interpreter, RuntimeError, ValueRes};
+use crate::gc::{Trace, Tracer};
+use crate::location::Loc;
+use crate::value::Value;
+use indexmap::map::Iter;
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::rc::Rc;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct Map {
+    class: Rc<MapClass>,
+    data: IndexMap<MapKey, Value>,
+}
+
+pub type MapRc = Rc<RefCell<Map>>;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Integer(i64),
+    Float(u64),
+    Str(String),
+    Boolean(bool),
+    Nil,
+}
+
+impl Map {
+    pub fn new(inter: &Interpreter, data: IndexMap<MapKey, Value>) -> Self {
+        let class = Rc::clone(&inter.natives.map_class);
+        Self { class, data }
+    }
+
+    pub fn class(&self) -> Rc<MapClass> {
+        Rc::clone(&self.class)
+    }
+
+    pub fn class_name(&self) -> &'static str {
+        MAP_CLASS
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn iter(&self) -> Iter<'_, MapKey, Value> {
+        self.data.iter()
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<Value> {
+        self.data.get(key).cloned()
+    }
+
+    pub fn set(&mut self, key: MapKey, val: Value) {
+        self.data.insert(key, val);
+    }
+
+    pub fn has(&self, key: &MapKey) -> bool {
+        self.data.contains_key(key)
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<Value> {
+        self.data.shift_remove(key)
+    }
+
+    pub fn keys(&self) -> Vec<Value> {
+        self.data.keys().map(Value::from).collect()
+    }
+
+    pub fn values(&self) -> Vec<Value> {
+        self.data.values().cloned().collect()
+    }
+}
+
+impl Trace for RefCell<Map> {
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        match self.try_borrow() {
+            Ok(map) => {
+                map.data.values().for_each(|val| tracer.value(val));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut map) = self.try_borrow_mut() {
+            map.data.clear();
+        }
+    }
+}
+
+impl Display for Map {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let len = self.data.len();
+        write!(f, "{{")?;
+        for (i, (key, val)) in self.data.iter().enumerate() {
+            if i < len - 1 {
+                write!(f, "{}: {}, ", key, val)?;
+            } else {
+                write!(f, "{}: {}", key, val)?;
+            }
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl MapKey {
+    pub fn from_value(val: Value, loc: Loc) -> Result<Self, RuntimeError> {
+        Ok(match val {
+            Value::Integer(int) => MapKey::Integer(int),
+            // Integral floats compare equal to the integers they hold (and
+            // -0.0 to 0.0), so they must hash to the same key
+            Value::Float(float) if is_integral(float) => MapKey::Integer(float as i64),
+            Value::Float(float) => MapKey::Float(float.to_bits()),
+            Value::Str(string) => MapKey::Str(string),
+            Value::Boolean(boolean) => MapKey::Boolean(boolean),
+            Value::Nil => MapKey::Nil,
+            val => return Err(RuntimeError::invalid_map_key(loc, val)),
+        })
+    }
+}
+
+/// Whether `float` holds a whole number that fits in an `i64`.
+fn is_integral(float: f64) -> bool {
+    float.fract() == 0.0 && float >= i64::MIN as f64 && float < -(i64::MIN as f64)
+}
+
+impl From<&MapKey> for Value {
+    fn from(key: &MapKey) -> Self {
+        match key {
+            MapKey::Integer(int) => Value::Integer(*int),
+            MapKey::Float(bits) => Value::Float(f64::from_bits(*bits)),
+            MapKey::Str(string) => Value::Str(string.clone()),
+            MapKey::Boolean(boolean) => Value::Boolean(*boolean),
+            MapKey::Nil => Value::Nil,
+        }
+    }
+}
+
+impl Display for MapKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Value::from(self).fmt(f)
+    }
+}
+
+#[derive(Debug)]
+pub struct MapClass {
+    uuid: Uuid,
+    methods: MethodMap,
+    getters: MethodMap,
+    metainstance: InstanceRc,
+}
+
+impl MapClass {
+    pub fn new() -> Self {
+        let uuid = Uuid::new_v4();
+        let static_methods = MethodMap::new();
+
+        let mut getters = MethodMap::new();
+        Self::add_length_getter(&mut getters);
+        let mut methods = MethodMap::new();
+        Self::add_get_method(&mut methods);
+        Self::add_set_method(&mut methods);
+        Self::add_has_method(&mut methods);
+        Self::add_remove_method(&mut methods);
+        Self::add_keys_method(&mut methods);
+        Self::add_values_method(&mut methods);
+        Self::add_entries_method(&mut methods);
+
+        let metainstance =
+            Class::create_metainstance(MAP_CLASS, uuid, None, static_methods, &methods, &getters);
+
+        Self {
+            uuid,
+            methods,
+            getters,
+            metainstance,
+        }
+    }
+
+    fn add_length_getter(getters: &mut MethodMap) {
+        Class::add_native_method(
+            getters,
+            NativeMethod::new("length", 0, |_inter, _args, instance, _loc| {
+                let map = instance.map().unwrap();
+                let len = map.borrow().len();
+                Ok(Value::Integer(len as i64))
+            }),
+        )
+    }
+
+    fn add_get_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("get", 1, |_inter, mut args: Vec<Value>, instance, loc| {
+                let map = instance.map().unwrap();
+                let key = MapKey::from_value(args.pop().unwrap(), loc)?;
+                let val = map.borrow().get(&key);
+                Ok(val.into())
+            }),
+        )
+    }
+
+    fn add_set_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("set", 2, |_inter, mut args: Vec<Value>, instance, loc| {
+                let map = instance.map().unwrap();
+                let val = args.pop().unwrap();
+                let key = MapKey::from_value(args.pop().unwrap(), loc)?;
+                map.borrow_mut().set(key, val.clone());
+                Ok(val)
+            }),
+        )
+    }
+
+    fn add_has_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("has", 1, |_inter, mut args: Vec<Value>, instance, loc| {
+                let map = instance.map().unwrap();
+                let key = MapKey::from_value(args.pop().unwrap(), loc)?;
+                let has = map.borrow().has(&key);
+                Ok(has.into())
+            }),
+        )
+    }
+
+    fn add_remove_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new(
+                "remove",
+                1,
+                |_inter, mut args: Vec<Value>, instance, loc| {
+                    let map = instance.map().unwrap();
+                    let key = MapKey::from_value(args.pop().unwrap(), loc)?;
+                    let val = map.borrow_mut().remove(&key);
+                    Ok(val.into())
+                },
+            ),
+        )
+    }
+
+    fn add_keys_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("keys", 0, |inter, _args, instance, _loc| {
+                let map = instance.map().unwrap();
+                let keys = map.borrow().keys();
+                Ok(Array::new(inter, keys).into())
+            }),
+        )
+    }
+
+    fn add_values_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("values", 0, |inter, _args, instance, _loc| {
+                let map = instance.map().unwrap();
+                let values = map.borrow().values();
+                Ok(Array::new(inter, values).into())
+            }),
+        )
+    }
+
+    fn add_entries_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("entries", 0, |inter, _args, instance, _loc| {
+                let map = instance.map().unwrap();
+                let entries = map
+                    .borrow()
+                    .iter()
+                    .map(|(key, val)| Array::new(inter, vec![key.into(), val.clone()]).into())
+                    .collect();
+                Ok(Array::new(inter, entries).into())
+            }),
+        )
+    }
+}
+
+impl LoxClass for Rc<MapClass> {
+    fn name(&self) -> &str {
+        MAP_CLASS
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    fn superclass(&self) -> Option<&Class> {
+        None
+    }
+
+    fn methods(&self) -> &MethodMap {
+        &self.methods
+    }
+
+    fn getters(&self) -> &MethodMap {
+        &self.getters
+    }
+
+    fn metainstance(&self) -> Option<InstanceRc> {
+        Some(Rc::clone(&self.metainstance))
+    }
+}
+
+impl LoxCallable for Rc<MapClass> {
+    fn arity(&self) -> Arity {
+        Arity::Fixed(0)
+    }
+
+    fn call(&self, inter: &mut Interpreter, _args: Vec<Value>, _loc: Loc) -> ValueRes {
+        Ok(Map::new(inter, IndexMap::new()).into())
+    }
+}
+
+impl From<Rc<MapClass>> for Callable {
+    fn from(class: Rc<MapClass>) -> Self {
+        Callable::Class(Class::Map(class))
+    }
+}
+
+impl From<Rc<MapClass>> for Class {
+    fn from(class: Rc<MapClass>) -> Self {
+        Class::Map(class)
+    }
+}
+
+impl Display for MapClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "<class {}>", MAP_CLASS)
+    }
+}
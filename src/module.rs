@@ -0,0 +1,91 @@
+use crate::eval::{Interpreter, RuntimeError, ValueRes};
+use crate::lexer::Scanner;
+use crate::location::Loc;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::stmt::Stmt;
+use crate::value::Value;
+use failure::Fallible;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Keeps track of the modules that have been imported, so that each one only
+/// runs once no matter how many files import it.
+#[derive(Debug, Default)]
+pub struct ModuleRegistry {
+    cache: HashMap<PathBuf, Value>,
+    loading: HashSet<PathBuf>,
+    current_path: Option<PathBuf>,
+}
+
+impl ModuleRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the file that relative imports are resolved against, returning the previous one.
+    pub fn set_current_path(&mut self, path: Option<PathBuf>) -> Option<PathBuf> {
+        std::mem::replace(&mut self.current_path, path)
+    }
+
+    fn resolve_path(&self, path: &str) -> std::io::Result<PathBuf> {
+        let base = self
+            .current_path
+            .as_ref()
+            .and_then(|current| current.parent())
+            .unwrap_or_else(|| Path::new("."));
+        base.join(path).canonicalize()
+    }
+}
+
+pub fn import(inter: &mut Interpreter, path: &str, loc: Loc) -> ValueRes {
+    let import_error = |reason: &str| RuntimeError::import_error(loc, path, reason);
+    let canonical = inter
+        .modules
+        .resolve_path(path)
+        .map_err(|err| import_error(&err.to_string()))?;
+
+    if let Some(module) = inter.modules.cache.get(&canonical) {
+        return Ok(module.clone());
+    }
+
+    if !inter.modules.loading.insert(canonical.clone()) {
+        return Err(import_error("Circular import"));
+    }
+
+    let res = load(inter, &canonical)
+        .map_err(|err| import_error(&err.to_string()))
+        .and_then(|stmts| inter.run_module(&stmts, canonical.clone()));
+    inter.modules.loading.remove(&canonical);
+
+    let module = res?;
+    inter.modules.cache.insert(canonical, module.clone());
+    Ok(module)
+}
+
+fn load(inter: &mut Interpreter, path: &Path) -> Fallible<Vec<Stmt>> {
+    let source = std::fs::read_to_string(path)?;
+    let file = inter.sources.add(&path.display().to_string(), &source);
+
+    let mut scanner = Scanner::new(&source, file);
+    let tokens = scanner.scan_tokens()?;
+
+    let mut parser = Parser::new(&tokens);
+    let stmts = parser.parse()?;
+
+    let allowed_lints = std::mem::take(&mut scanner.allowed_lints);
+    let mut resolver = Resolver::with_lints(inter.lint_levels.clone(), allowed_lints);
+    resolver.set_known_globals(inter.builtin_globals().signatures());
+    resolver.resolve(&stmts)?;
+    let warnings = resolver.warnings;
+
+    crate::error::print_warns(
+        &mut inter.diagnostics,
+        &warnings,
+        &inter.sources,
+        inter.error_format,
+        inter.colored_diagnostics,
+    )?;
+
+    Ok(stmts)
+}
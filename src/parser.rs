@@ -2,14 +2,26 @@
 mod tests;
 
 use crate::expr::{BinOp, Expr, ExprKind, Param};
-use crate::lexer::Token;
 use crate::lexer::TokenKind::{self, *};
+use crate::lexer::{Literal, Token};
 use crate::location::Loc;
-use crate::stmt::{FunctionKind, Stmt};
+use crate::stmt::{FunctionKind, ImportKind, Stmt};
+
+/// How deep expressions, statements and functions may nest, so that the
+/// recursive descent, and every pass over the tree after it, stays well
+/// within the native stack.
+pub const MAX_NESTING_DEPTH: usize = 255;
+
+/// How many binary and logical operators may be chained in an expression,
+/// operands included. The tree still nests once per operator, but flat
+/// chains are common enough in real code to get a much higher limit.
+pub const MAX_CHAIN_LENGTH: usize = 4096;
 
 pub struct Parser<'a> {
     input: &'a [Token<'a>],
     current: usize,
+    depth: usize,
+    links: usize,
     errors: Vec<ParsingError>,
     pub(super) allow_expression: bool,
     found_expression: bool,
@@ -24,11 +36,17 @@ pub enum ParsingError {
     ExpectedCloseBrace(Loc, String, String),
     ExpectedCloseBracket(Loc, String, String),
     ExpectedColon(Loc, String),
+    ExpectedMapColon(Loc, String),
     ExpectedSemicolon(Loc, String, String),
     ExpectedDot(Loc, String, String),
     ExpectedName(Loc, String, String),
+    ExpectedCatchOrFinally(Loc, String),
+    ExpectedKeyword(Loc, String, String, String),
+    ExpectedModulePath(Loc, String),
     InvalidAssignmentTarget(Loc),
     MaximumArgumentsExceeded(Loc, String),
+    MaximumNestingExceeded(Loc),
+    MaximumChainExceeded(Loc),
     Multiple(Vec<ParsingError>),
 }
 
@@ -43,6 +61,8 @@ impl<'a> Parser<'a> {
         Parser {
             input,
             current: 0,
+            depth: 0,
+            links: 0,
             errors: vec![],
             allow_expression: false,
             found_expression: false,
@@ -92,6 +112,12 @@ impl<'a> Parser<'a> {
         &self.input[self.current - 1]
     }
 
+    /// Extends `loc` to span up to the last token consumed, which is where
+    /// the statement or expression it belongs to ends.
+    fn spanned(&self, loc: Loc) -> Loc {
+        loc.extend(self.previous().loc)
+    }
+
     fn matches(&mut self, kinds: &[TokenKind]) -> OptTokenRef<'a> {
         for kind in kinds {
             if self.check(*kind) {
@@ -125,6 +151,41 @@ impl<'a> Parser<'a> {
         self.matches(&[kind]).ok_or_else(|| err_fn(self))
     }
 
+    fn nested<T, F>(&mut self, parse: F) -> Result<T, ParsingError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, ParsingError>,
+    {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(ParsingError::MaximumNestingExceeded(self.peek().loc));
+        }
+
+        self.depth += 1;
+        let res = parse(self);
+        self.depth -= 1;
+        res
+    }
+
+    /// Parses a left-associative chain of operators, where each `link`
+    /// nests the tree one level deeper.
+    fn chain<F>(&mut self, parse: F) -> ExprParseRes
+    where
+        F: FnOnce(&mut Self) -> ExprParseRes,
+    {
+        let links = self.links;
+        let res = parse(self);
+        self.links = links;
+        res
+    }
+
+    fn link(&mut self) -> Result<(), ParsingError> {
+        if self.links >= MAX_CHAIN_LENGTH {
+            return Err(ParsingError::MaximumChainExceeded(self.peek().loc));
+        }
+
+        self.links += 1;
+        Ok(())
+    }
+
     fn declaration(&mut self) -> StmtParseRes {
         let res = if self.matches(&[Var]).is_some() {
             self.var_declaration()
@@ -146,27 +207,36 @@ impl<'a> Parser<'a> {
     }
 
     fn statement(&mut self) -> StmtParseRes {
-        if self.matches(&[If]).is_some() {
-            self.if_statement()
-        } else if self.matches(&[Print]).is_some() {
-            self.print_statement()
-        } else if self.matches(&[While]).is_some() {
-            self.while_statement()
-        } else if self.matches(&[For]).is_some() {
-            self.for_statement()
-        } else if self.matches(&[Return]).is_some() {
-            self.return_statement()
-        } else if let Some(token) = self.matches(&[LeftBrace]) {
-            Ok(Stmt::block(self.block()?, token.loc))
-        } else if let Some(token) = self.matches(&[Break]) {
-            self.consume(Semicolon, |p| p.expected_semicolon_error("'break'"))?;
-            Ok(Stmt::break_stmt(token.loc))
-        } else if let Some(token) = self.matches(&[Continue]) {
-            self.consume(Semicolon, |p| p.expected_semicolon_error("'continue'"))?;
-            Ok(Stmt::continue_stmt(token.loc))
-        } else {
-            self.expression_statement()
-        }
+        self.nested(|p| {
+            if p.matches(&[If]).is_some() {
+                p.if_statement()
+            } else if p.matches(&[Print]).is_some() {
+                p.print_statement()
+            } else if p.matches(&[While]).is_some() {
+                p.while_statement()
+            } else if p.matches(&[For]).is_some() {
+                p.for_statement()
+            } else if p.matches(&[Return]).is_some() {
+                p.return_statement()
+            } else if p.matches(&[Throw]).is_some() {
+                p.throw_statement()
+            } else if p.matches(&[Try]).is_some() {
+                p.try_statement()
+            } else if p.matches(&[Import]).is_some() {
+                p.import_statement()
+            } else if let Some(token) = p.matches(&[LeftBrace]) {
+                let stmts = p.block()?;
+                Ok(Stmt::block(stmts, p.spanned(token.loc)))
+            } else if let Some(token) = p.matches(&[Break]) {
+                p.consume(Semicolon, |p| p.expected_semicolon_error("'break'"))?;
+                Ok(Stmt::break_stmt(p.spanned(token.loc)))
+            } else if let Some(token) = p.matches(&[Continue]) {
+                p.consume(Semicolon, |p| p.expected_semicolon_error("'continue'"))?;
+                Ok(Stmt::continue_stmt(p.spanned(token.loc)))
+            } else {
+                p.expression_statement()
+            }
+        })
     }
 
     fn if_statement(&mut self) -> StmtParseRes {
@@ -181,14 +251,19 @@ impl<'a> Parser<'a> {
             .map(|_| self.statement())
             .transpose()?;
 
-        Ok(Stmt::if_stmt(cond, then_branch, else_branch, *loc))
+        Ok(Stmt::if_stmt(
+            cond,
+            then_branch,
+            else_branch,
+            self.spanned(*loc),
+        ))
     }
 
     fn print_statement(&mut self) -> StmtParseRes {
         let Token { loc, .. } = self.previous();
         let expr = self.expression()?;
         self.consume(Semicolon, |p| p.expected_semicolon_error("value"))?;
-        Ok(Stmt::print(expr, *loc))
+        Ok(Stmt::print(expr, self.spanned(*loc)))
     }
 
     fn while_statement(&mut self) -> StmtParseRes {
@@ -201,7 +276,7 @@ impl<'a> Parser<'a> {
 
         let body = self.statement()?;
 
-        Ok(Stmt::for_stmt(cond, None, body, *loc))
+        Ok(Stmt::for_stmt(cond, None, body, self.spanned(*loc)))
     }
 
     fn for_statement(&mut self) -> StmtParseRes {
@@ -211,6 +286,10 @@ impl<'a> Parser<'a> {
         let init = if self.matches(&[Semicolon]).is_some() {
             None
         } else if self.matches(&[Var]).is_some() {
+            if self.check(Identifier) && self.check_next(In) {
+                return self.for_in_statement(*loc);
+            }
+
             Some(self.var_declaration()?)
         } else {
             Some(self.expression_statement()?)
@@ -234,15 +313,130 @@ impl<'a> Parser<'a> {
 
         let body = self.statement()?;
 
-        let for_stmt = Stmt::for_stmt(cond, increment, body, *loc);
+        let for_stmt = Stmt::for_stmt(cond, increment, body, self.spanned(*loc));
         Ok(if let Some(init_stmt) = init {
-            let init_loc = init_stmt.loc;
+            let init_loc = init_stmt.loc.extend(for_stmt.loc);
             Stmt::block(vec![init_stmt, for_stmt], init_loc)
         } else {
             for_stmt
         })
     }
 
+    fn for_in_statement(&mut self, loc: Loc) -> StmtParseRes {
+        let name = self.advance();
+        let var = Param::new(String::from(name.lexeme), name.loc);
+        self.advance();
+
+        let iterable = self.expression()?;
+        self.consume(RightParen, |p| {
+            p.expected_close_paren_error("for-in iterable")
+        })?;
+
+        let body = self.statement()?;
+
+        Ok(Stmt::for_in(var, iterable, body, self.spanned(loc)))
+    }
+
+    fn throw_statement(&mut self) -> StmtParseRes {
+        let Token { loc, .. } = self.previous();
+        let expr = self.expression()?;
+        self.consume(Semicolon, |p| p.expected_semicolon_error("thrown value"))?;
+        Ok(Stmt::throw(expr, self.spanned(*loc)))
+    }
+
+    fn try_statement(&mut self) -> StmtParseRes {
+        let Token { loc, .. } = self.previous();
+        self.consume(LeftBrace, |p| p.expected_open_brace_error("try body"))?;
+        let body = self.block()?;
+
+        let catch = if self.matches(&[Catch]).is_some() {
+            self.consume(LeftParen, |p| p.expected_open_paren_error("'catch'"))?;
+            let name = self.consume(Identifier, |p| p.expected_name_error("exception"))?;
+            let var = Param::new(String::from(name.lexeme), name.loc);
+            self.consume(RightParen, |p| {
+                p.expected_close_paren_error("catch variable")
+            })?;
+            self.consume(LeftBrace, |p| p.expected_open_brace_error("catch body"))?;
+            Some((var, self.block()?))
+        } else {
+            None
+        };
+
+        let finally = if self.matches(&[Finally]).is_some() {
+            self.consume(LeftBrace, |p| p.expected_open_brace_error("finally body"))?;
+            Some(self.block()?)
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            let token = self.peek();
+            return Err(ParsingError::ExpectedCatchOrFinally(
+                token.loc,
+                token.lexeme.to_string(),
+            ));
+        }
+
+        Ok(Stmt::try_stmt(body, catch, finally, self.spanned(*loc)))
+    }
+
+    fn import_statement(&mut self) -> StmtParseRes {
+        let Token { loc, .. } = self.previous();
+        let names = if self.matches(&[LeftBrace]).is_some() {
+            let mut names = vec![self.imported_name()?];
+            while self.matches(&[Comma]).is_some() {
+                names.push(self.imported_name()?);
+            }
+
+            self.consume(RightBrace, |p| {
+                p.expected_close_brace_error("imported names")
+            })?;
+            self.consume_keyword("from", "imported names")?;
+            Some(names)
+        } else {
+            None
+        };
+
+        let path = self.consume(Str, |p| p.expected_module_path_error())?;
+        let path = match &path.literal {
+            Some(Literal::Str(path)) => path.clone(),
+            _ => unreachable!(),
+        };
+
+        let kind = match names {
+            Some(names) => ImportKind::Names(names),
+            None => {
+                self.consume_keyword("as", "module path")?;
+                let alias = self.consume(Identifier, |p| p.expected_name_error("module"))?;
+                ImportKind::Module(Param::new(String::from(alias.lexeme), alias.loc))
+            }
+        };
+
+        self.consume(Semicolon, |p| p.expected_semicolon_error("import"))?;
+
+        Ok(Stmt::import(&path, kind, self.spanned(*loc)))
+    }
+
+    fn imported_name(&mut self) -> Result<Param, ParsingError> {
+        let name = self.consume(Identifier, |p| p.expected_name_error("imported"))?;
+        Ok(Param::new(String::from(name.lexeme), name.loc))
+    }
+
+    /// Consumes an identifier that acts as a keyword only in this position.
+    fn consume_keyword(&mut self, keyword: &str, after: &str) -> TokenRefRes<'a> {
+        if self.check(Identifier) && self.peek().lexeme == keyword {
+            Ok(self.advance())
+        } else {
+            let token = self.peek();
+            Err(ParsingError::ExpectedKeyword(
+                token.loc,
+                String::from(keyword),
+                String::from(after),
+                token.lexeme.to_string(),
+            ))
+        }
+    }
+
     fn block(&mut self) -> Result<Vec<Stmt>, ParsingError> {
         let mut stmts = Vec::new();
 
@@ -265,7 +459,7 @@ impl<'a> Parser<'a> {
 
         self.consume(Semicolon, |p| p.expected_semicolon_error("return value"))?;
 
-        Ok(Stmt::return_stmt(ret, *loc))
+        Ok(Stmt::return_stmt(ret, self.spanned(*loc)))
     }
 
     fn var_declaration(&mut self) -> StmtParseRes {
@@ -281,17 +475,25 @@ impl<'a> Parser<'a> {
             p.expected_semicolon_error("variable declaration")
         })?;
 
-        Ok(Stmt::var(name.lexeme, init, *loc))
+        Ok(Stmt::var(name.lexeme, init, self.spanned(*loc)))
     }
 
     fn function(&mut self, kind: FunctionKind) -> StmtParseRes {
-        let name = self.consume(Identifier, |p| p.expected_name_error(kind.to_string()))?;
-        let params = self.function_params(kind.name())?;
-
-        self.consume(LeftBrace, |p| p.expected_open_brace_error(kind.body()))?;
-        let body = self.block()?;
-
-        Ok(Stmt::function(name.lexeme, params, body, kind, name.loc))
+        self.nested(|p| {
+            let name = p.consume(Identifier, |p| p.expected_name_error(kind.to_string()))?;
+            let params = p.function_params(kind.name())?;
+
+            p.consume(LeftBrace, |p| p.expected_open_brace_error(kind.body()))?;
+            let body = p.block()?;
+
+            Ok(Stmt::function(
+                name.lexeme,
+                params,
+                body,
+                kind,
+                p.spanned(name.loc),
+            ))
+        })
     }
 
     fn function_params(&mut self, open_paren_after: &str) -> Result<Vec<Param>, ParsingError> {
@@ -345,7 +547,12 @@ impl<'a> Parser<'a> {
 
         self.consume(RightBrace, |p| p.expected_close_brace_error("class body"))?;
 
-        Ok(Stmt::class(name.lexeme, superclass, methods, *loc))
+        Ok(Stmt::class(
+            name.lexeme,
+            superclass,
+            methods,
+            self.spanned(*loc),
+        ))
     }
 
     fn getter_method(&mut self) -> StmtParseRes {
@@ -356,7 +563,13 @@ impl<'a> Parser<'a> {
         let body = self.block()?;
         let params = Vec::new();
 
-        Ok(Stmt::function(name.lexeme, params, body, kind, name.loc))
+        Ok(Stmt::function(
+            name.lexeme,
+            params,
+            body,
+            kind,
+            self.spanned(name.loc),
+        ))
     }
 
     fn expression_statement(&mut self) -> StmtParseRes {
@@ -373,8 +586,9 @@ impl<'a> Parser<'a> {
     pub fn expression(&mut self) -> ExprParseRes {
         let expr = self.assignment()?;
         if let Some(token) = self.matches(&[Comma]) {
-            let right = self.expression()?;
-            Ok(Expr::comma(expr, right, token.loc))
+            let right = self.nested(Self::expression)?;
+            let loc = token.loc.extend(expr.loc).extend(right.loc);
+            Ok(Expr::comma(expr, right, loc))
         } else {
             Ok(expr)
         }
@@ -391,7 +605,7 @@ impl<'a> Parser<'a> {
             SlashEqual,
             PercentEqual,
         ]) {
-            let value = self.expression()?;
+            let value = self.nested(Self::expression)?;
             return self.make_assign_expr(expr, token, value).or_else(|err| {
                 self.errors.push(err);
                 Ok(Expr::default())
@@ -403,13 +617,14 @@ impl<'a> Parser<'a> {
 
     fn make_assign_expr(&mut self, expr: Expr, op_token: &Token, value: Expr) -> ExprParseRes {
         let expr_loc = expr.loc;
-        Ok(if let ExprKind::Variable(name) = expr.kind {
+        let loc = expr.loc.extend(op_token.loc).extend(value.loc);
+        Ok(if let ExprKind::Variable(name, _) = expr.kind {
             let assign_value = Parser::assign_value(op_token, value, |value| {
                 let op = op_token.kind.into();
                 Expr::binary(Expr::variable(&name, expr_loc), op, value, op_token.loc)
             });
 
-            Expr::assign(name, assign_value, expr.loc)
+            Expr::assign(name, assign_value, loc)
         } else if let ExprKind::Get(obj, name) = expr.kind {
             let assign_value = Parser::assign_value(op_token, value, |value| {
                 let op = op_token.kind.into();
@@ -421,7 +636,7 @@ impl<'a> Parser<'a> {
                 )
             });
 
-            Expr::set(obj, name, assign_value, expr.loc)
+            Expr::set(obj, name, assign_value, loc)
         } else if let ExprKind::SubscriptGet(obj, index) = expr.kind {
             let assign_value = Parser::assign_value(op_token, value, |value| {
                 let op = op_token.kind.into();
@@ -433,7 +648,7 @@ impl<'a> Parser<'a> {
                 )
             });
 
-            Expr::subscript_set(obj, index, assign_value, expr.loc)
+            Expr::subscript_set(obj, index, assign_value, loc)
         } else {
             return Err(ParsingError::InvalidAssignmentTarget(expr.loc));
         })
@@ -456,53 +671,66 @@ impl<'a> Parser<'a> {
         if let Some(op_token) = self.matches(&[Question]) {
             let left = self.expression()?;
             self.consume(Colon, Self::expected_colon_error)?;
-            let right = self.conditional()?;
-            Ok(Expr::conditional(expr, left, right, op_token.loc))
+            let right = self.nested(Self::conditional)?;
+            let loc = op_token.loc.extend(expr.loc).extend(right.loc);
+            Ok(Expr::conditional(expr, left, right, loc))
         } else {
             Ok(expr)
         }
     }
 
     fn or(&mut self) -> ExprParseRes {
-        let mut expr = self.and()?;
+        self.chain(|p| {
+            let mut expr = p.and()?;
 
-        while let Some(op_token) = self.matches(&[Or]) {
-            let op = op_token.kind.into();
-            let right = self.and()?;
+            while let Some(op_token) = p.matches(&[Or]) {
+                p.link()?;
+                let op = op_token.kind.into();
+                let right = p.and()?;
 
-            expr = Expr::logical(expr, op, right, op_token.loc);
-        }
+                let loc = op_token.loc.extend(expr.loc).extend(right.loc);
+                expr = Expr::logical(expr, op, right, loc);
+            }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
     fn and(&mut self) -> ExprParseRes {
-        let mut expr = self.equality()?;
+        self.chain(|p| {
+            let mut expr = p.equality()?;
 
-        while let Some(op_token) = self.matches(&[And]) {
-            let op = op_token.kind.into();
-            let right = self.equality()?;
+            while let Some(op_token) = p.matches(&[And]) {
+                p.link()?;
+                let op = op_token.kind.into();
+                let right = p.equality()?;
 
-            expr = Expr::logical(expr, op, right, op_token.loc);
-        }
+                let loc = op_token.loc.extend(expr.loc).extend(right.loc);
+                expr = Expr::logical(expr, op, right, loc);
+            }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
     fn left_binary_expression<F>(&mut self, kinds: &[TokenKind], mut expr_fn: F) -> ExprParseRes
     where
         F: FnMut(&mut Parser<'a>) -> ExprParseRes,
     {
-        let mut expr = expr_fn(self)?;
+        self.chain(|p| {
+            let mut expr = expr_fn(p)?;
 
-        while let Some(op_token) = self.matches(kinds) {
-            let op = op_token.kind.into();
-            let right = expr_fn(self)?;
+            while let Some(op_token) = p.matches(kinds) {
+                p.link()?;
+                let op = op_token.kind.into();
+                let right = expr_fn(p)?;
 
-            expr = Expr::binary(expr, op, right, op_token.loc);
-        }
+                let loc = op_token.loc.extend(expr.loc).extend(right.loc);
+                expr = Expr::binary(expr, op, right, loc);
+            }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
     fn equality(&mut self) -> ExprParseRes {
@@ -524,10 +752,11 @@ impl<'a> Parser<'a> {
     fn unary(&mut self) -> ExprParseRes {
         if let Some(op_token) = self.matches(&[Bang, Minus]) {
             let op = op_token.kind.into();
-            let right = self.unary()?;
-            Ok(Expr::unary(op, right, op_token.loc))
+            let right = self.nested(Self::unary)?;
+            let loc = op_token.loc.extend(right.loc);
+            Ok(Expr::unary(op, right, loc))
         } else if let Some(op_token) = self.matches(&[PlusPlus, MinusMinus]) {
-            let right = self.unary()?;
+            let right = self.nested(Self::unary)?;
             // ++i generates i = i + 1
             self.make_assign_expr(right, op_token, Expr::integer(1, op_token.loc))
                 .or_else(|err| {
@@ -548,7 +777,7 @@ impl<'a> Parser<'a> {
             let (left, name) = match self.make_assign_expr(left, op_token, one) {
                 Ok(left) => {
                     let name = match &left.kind {
-                        ExprKind::Assign(name, _) => Expr::variable(name, left.loc),
+                        ExprKind::Assign(name, _, _) => Expr::variable(name, left.loc),
                         ExprKind::Set(obj, name, _) => Expr::get(*obj.clone(), name, left.loc),
                         ExprKind::SubscriptSet(obj, index, _) => {
                             Expr::subscript_get(*obj.clone(), *index.clone(), left.loc)
@@ -580,21 +809,25 @@ impl<'a> Parser<'a> {
     }
 
     fn call(&mut self) -> ExprParseRes {
-        let mut expr = self.primary()?;
-        loop {
-            expr = if self.matches(&[LeftParen]).is_some() {
-                self.finish_call(expr)?
-            } else if let Some(dot_token) = self.matches(&[Dot]) {
-                let name = self.consume(Identifier, |p| p.expected_name_error("property"))?;
-                Expr::get(expr, name.lexeme, dot_token.loc)
-            } else if self.matches(&[LeftBracket]).is_some() {
-                self.finish_subscript(expr)?
-            } else {
-                break;
+        // Brackets of any kind nest through here
+        self.nested(|p| {
+            let mut expr = p.primary()?;
+            loop {
+                expr = if p.matches(&[LeftParen]).is_some() {
+                    p.finish_call(expr)?
+                } else if let Some(dot_token) = p.matches(&[Dot]) {
+                    let name = p.consume(Identifier, |p| p.expected_name_error("property"))?;
+                    let loc = dot_token.loc.extend(expr.loc).extend(name.loc);
+                    Expr::get(expr, name.lexeme, loc)
+                } else if p.matches(&[LeftBracket]).is_some() {
+                    p.finish_subscript(expr)?
+                } else {
+                    break;
+                }
             }
-        }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
     fn finish_call(&mut self, callee: Expr) -> ExprParseRes {
@@ -615,7 +848,8 @@ impl<'a> Parser<'a> {
 
         let token = self.consume(RightParen, |p| p.expected_close_paren_error("arguments"))?;
 
-        Ok(Expr::call(callee, args, token.loc))
+        let loc = token.loc.extend(callee.loc);
+        Ok(Expr::call(callee, args, loc))
     }
 
     fn finish_subscript(&mut self, obj: Expr) -> ExprParseRes {
@@ -625,7 +859,8 @@ impl<'a> Parser<'a> {
             p.expected_close_bracket_error("subscript index")
         })?;
 
-        Ok(Expr::subscript_get(obj, index, token.loc))
+        let loc = token.loc.extend(obj.loc);
+        Ok(Expr::subscript_get(obj, index, loc))
     }
 
     fn primary(&mut self) -> ExprParseRes {
@@ -638,6 +873,7 @@ impl<'a> Parser<'a> {
             Str,
             LeftParen,
             LeftBracket,
+            LeftBrace,
             Identifier,
             This,
             Super,
@@ -658,16 +894,17 @@ impl<'a> Parser<'a> {
             LeftParen => {
                 let expr = self.expression()?;
                 self.consume(RightParen, |p| p.expected_close_paren_error("expression"))?;
-                Expr::grouping(expr, token.loc)
+                Expr::grouping(expr, self.spanned(token.loc))
             }
             LeftBracket => self.finish_array()?,
+            LeftBrace => self.finish_map()?,
             Identifier => Expr::variable(token.lexeme, token.loc),
             This => Expr::this(token.loc),
             Super => {
                 self.consume(Dot, |p| p.expected_dot_error("'super'"))?;
                 let method =
                     self.consume(Identifier, |p| p.expected_name_error("superclass method"))?;
-                Expr::super_expr(method.lexeme, token.loc)
+                Expr::super_expr(method.lexeme, token.loc.extend(method.loc))
             }
             Fun => self.anon_function()?,
             kind => panic!("Shouldn't have executed this. Kind: {:?}", kind),
@@ -684,7 +921,7 @@ impl<'a> Parser<'a> {
         })?;
         let body = self.block()?;
 
-        Ok(Expr::function(params, body, *loc))
+        Ok(Expr::function(params, body, self.spanned(*loc)))
     }
 
     fn finish_array(&mut self) -> ExprParseRes {
@@ -703,7 +940,31 @@ impl<'a> Parser<'a> {
             p.expected_close_bracket_error("array elements")
         })?;
 
-        Ok(Expr::array(elements, *loc))
+        Ok(Expr::array(elements, self.spanned(*loc)))
+    }
+
+    fn finish_map(&mut self) -> ExprParseRes {
+        let Token { loc, .. } = self.previous();
+
+        let mut entries = Vec::new();
+
+        if !self.check(RightBrace) {
+            entries.push(self.map_entry()?);
+            while self.matches(&[Comma]).is_some() {
+                entries.push(self.map_entry()?);
+            }
+        }
+
+        self.consume(RightBrace, |p| p.expected_close_brace_error("map entries"))?;
+
+        Ok(Expr::map(entries, self.spanned(*loc)))
+    }
+
+    fn map_entry(&mut self) -> Result<(Expr, Expr), ParsingError> {
+        let key = self.assignment()?;
+        self.consume(Colon, Self::expected_map_colon_error)?;
+        let val = self.assignment()?;
+        Ok((key, val))
     }
 
     fn synchronize(&mut self) {
@@ -714,7 +975,11 @@ impl<'a> Parser<'a> {
                 return;
             }
 
-            if [Class, Fun, Var, For, If, While, Print, Return].contains(&self.peek().kind) {
+            if [
+                Class, Fun, Var, For, If, While, Print, Return, Throw, Try, Import,
+            ]
+            .contains(&self.peek().kind)
+            {
                 return;
             }
 
@@ -757,6 +1022,11 @@ impl<'a> Parser<'a> {
         ParsingError::ExpectedColon(token.loc, token.lexeme.to_string())
     }
 
+    fn expected_map_colon_error(&self) -> ParsingError {
+        let token = self.peek();
+        ParsingError::ExpectedMapColon(token.loc, token.lexeme.to_string())
+    }
+
     fn expected_semicolon_error(&self, after: &str) -> ParsingError {
         let token = self.peek();
         ParsingError::ExpectedSemicolon(token.loc, String::from(after), token.lexeme.to_string())
@@ -767,6 +1037,11 @@ impl<'a> Parser<'a> {
         ParsingError::ExpectedDot(token.loc, String::from(after), token.lexeme.to_string())
     }
 
+    fn expected_module_path_error(&self) -> ParsingError {
+        let token = self.peek();
+        ParsingError::ExpectedModulePath(token.loc, token.lexeme.to_string())
+    }
+
     fn expected_name_error(&self, kind: &str) -> ParsingError {
         let token = self.peek();
         ParsingError::ExpectedName(token.loc, String::from(kind), token.lexeme.to_string())
@@ -370,6 +370,38 @@ fn test_nested_expr() {
     );
 }
 
+#[test]
+fn test_map_expr() {
+    let tokens = get_tokens(r#"{"a": 1, b: 2 + 3}"#);
+    let mut parser = Parser::new(&tokens);
+    assert_eq!(
+        Ok(map_expr(
+            vec![
+                (str_expr("a", (0, 1)), int_expr(1, (0, 6))),
+                (
+                    var_expr("b", (0, 9)),
+                    add_expr(int_expr(2, (0, 12)), int_expr(3, (0, 16)), (0, 14))
+                ),
+            ],
+            (0, 0)
+        )),
+        parser.expression()
+    );
+}
+
+#[test]
+fn test_missing_map_colon() {
+    let tokens = get_tokens(r#"{"a" 1}"#);
+    let mut parser = Parser::new(&tokens);
+    assert_eq!(
+        Err(ParsingError::ExpectedMapColon(
+            Loc::new(0, 5),
+            String::from("1")
+        )),
+        parser.expression()
+    );
+}
+
 #[test]
 fn test_expr_stmt() {
     let tokens = get_tokens("1996;");
@@ -486,6 +518,94 @@ fn test_for_stmt() {
     );
 }
 
+#[test]
+fn test_for_in_stmt() {
+    let input = r#"for (var x in xs) print x;"#;
+    let tokens = get_tokens(input);
+    let mut parser = Parser::new(&tokens);
+    assert_eq!(
+        Ok(vec![for_in_stmt(
+            "x",
+            (0, 9),
+            var_expr("xs", (0, 14)),
+            print_stmt(var_expr("x", (0, 24)), (0, 18)),
+            (0, 0)
+        )]),
+        parser.parse()
+    );
+}
+
+#[test]
+fn test_try_stmt() {
+    let input = r#"try { throw 1; } catch (e) {} finally {}"#;
+    let tokens = get_tokens(input);
+    let mut parser = Parser::new(&tokens);
+    assert_eq!(
+        Ok(vec![Stmt::try_stmt(
+            vec![Stmt::throw(int_expr(1, (0, 12)), Loc::new(0, 6))],
+            Some((Param::new(String::from("e"), Loc::new(0, 24)), vec![])),
+            Some(vec![]),
+            Loc::new(0, 0)
+        )]),
+        parser.parse()
+    );
+}
+
+#[test]
+fn test_try_without_catch_or_finally() {
+    let input = r#"try {}"#;
+    let tokens = get_tokens(input);
+    let mut parser = Parser::new(&tokens);
+    assert_eq!(
+        Err(ParsingError::ExpectedCatchOrFinally(
+            Loc::new(0, 6),
+            String::from("EOF")
+        )),
+        parser.parse()
+    );
+}
+
+#[test]
+fn test_import_stmt() {
+    let input = r#"import "a.lox" as a; import { b, c } from "d.lox";"#;
+    let tokens = get_tokens(input);
+    let mut parser = Parser::new(&tokens);
+    assert_eq!(
+        Ok(vec![
+            Stmt::import(
+                "a.lox",
+                ImportKind::Module(Param::new(String::from("a"), Loc::new(0, 18))),
+                Loc::new(0, 0)
+            ),
+            Stmt::import(
+                "d.lox",
+                ImportKind::Names(vec![
+                    Param::new(String::from("b"), Loc::new(0, 30)),
+                    Param::new(String::from("c"), Loc::new(0, 33)),
+                ]),
+                Loc::new(0, 21)
+            ),
+        ]),
+        parser.parse()
+    );
+}
+
+#[test]
+fn test_import_missing_as() {
+    let input = r#"import "a.lox";"#;
+    let tokens = get_tokens(input);
+    let mut parser = Parser::new(&tokens);
+    assert_eq!(
+        Err(ParsingError::ExpectedKeyword(
+            Loc::new(0, 14),
+            String::from("as"),
+            String::from("module path"),
+            String::from(";")
+        )),
+        parser.parse()
+    );
+}
+
 #[test]
 fn test_empty_for_stmt() {
     let input = r#"for (;;) {}"#;
@@ -671,3 +791,49 @@ fn test_missing_var_name() {
         parser.parse()
     );
 }
+
+#[test]
+fn test_maximum_nesting_exceeded() {
+    let input = format!("{}true;", "!".repeat(MAX_NESTING_DEPTH + 1));
+    let tokens = get_tokens(&input);
+    let mut parser = Parser::new(&tokens);
+    assert_eq!(
+        Err(ParsingError::MaximumNestingExceeded(Loc::new(0, 255))),
+        parser.parse()
+    );
+}
+
+#[test]
+fn test_maximum_chain_exceeded() {
+    for op in ["+", "*", "==", "<", "and", "or"] {
+        let terms = vec!["1"; 20_000].join(&format!(" {} ", op));
+        let input = format!("var x = {};", terms);
+        let tokens = get_tokens(&input);
+        let mut parser = Parser::new(&tokens);
+        assert!(
+            matches!(parser.parse(), Err(ParsingError::MaximumChainExceeded(_))),
+            "{}",
+            op
+        );
+    }
+
+    // Chains in the operands count towards the chain they are in
+    let terms = vec!["1"; MAX_CHAIN_LENGTH / 2 + 1].join(" + ");
+    let input = format!("var x = {} * ({});", terms, terms);
+    let tokens = get_tokens(&input);
+    let res = Parser::new(&tokens).parse();
+    assert!(matches!(res, Err(ParsingError::MaximumChainExceeded(_))));
+}
+
+#[test]
+fn test_long_chains() {
+    let terms = vec!["1"; MAX_CHAIN_LENGTH + 1].join(" + ");
+    let input = format!("var x = {};", terms);
+    let tokens = get_tokens(&input);
+    assert!(Parser::new(&tokens).parse().is_ok());
+
+    let terms = vec!["1"; MAX_NESTING_DEPTH * 2].join(" + ");
+    let input = format!("var x = {} * ({});", terms, terms);
+    let tokens = get_tokens(&input);
+    assert!(Parser::new(&tokens).parse().is_ok());
+}
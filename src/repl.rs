@@ -0,0 +1,209 @@
+#[cfg(test)]
+mod tests;
+
+use crate::class::Class;
+use crate::constants::STRING_CLASS;
+use crate::eval::GlobalEnv;
+use crate::lexer::{Scanner, ScanningError, Token, TokenKind, KEYWORDS};
+use crate::location::Loc;
+use crate::source::FileId;
+use crate::utils::is_alphanumeric;
+use crate::value::Value;
+use ansi_term::Color::{Blue, Cyan, Green, Purple};
+use ansi_term::Style;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+/// Makes the prompt read multi-line code, color it and complete names.
+pub struct ReplHelper {
+    pub globals: GlobalEnv,
+}
+
+/// Where the history of the prompt is kept between sessions.
+pub fn history_path() -> Option<PathBuf> {
+    dirs_next::home_dir().map(|home| home.join(".lox_history"))
+}
+
+impl ReplHelper {
+    pub fn new(globals: GlobalEnv) -> Self {
+        ReplHelper { globals }
+    }
+
+    /// The names that complete the word ending at `pos`, along with where
+    /// that word starts.
+    fn completions(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before = &line[..pos];
+        let start = before.trim_end_matches(is_alphanumeric).len();
+        let prefix = &before[start..];
+
+        let mut names = match before[..start].strip_suffix('.') {
+            Some(object) => self.member_names(object),
+            None => {
+                let globals = self.globals.borrow();
+                let keywords = KEYWORDS.iter().map(|keyword| String::from(*keyword));
+                globals.names().cloned().chain(keywords).collect()
+            }
+        };
+
+        names.retain(|name| name.starts_with(prefix));
+        names.sort();
+        names.dedup();
+        (start, names)
+    }
+
+    /// The fields, methods and getters of the value `object` ends with,
+    /// which must be a string literal or a global followed by field names.
+    /// Nothing is called to get it.
+    fn member_names(&self, object: &str) -> Vec<String> {
+        let val = if object.ends_with('"') {
+            Value::Str(String::new())
+        } else {
+            let path_start = object.trim_end_matches(|c| is_alphanumeric(c) || c == '.');
+            let mut path = object[path_start.len()..].split('.');
+            let global = path.next().unwrap_or_default();
+            let val = self.globals.borrow().get(global, Loc::default()).ok();
+            match path.try_fold(val, |val, name| Some(field(val?, name))) {
+                Some(Some(val)) => val,
+                _ => return Vec::new(),
+            }
+        };
+
+        let instance = match val {
+            Value::Str(_) => {
+                let class = self.globals.borrow().get(STRING_CLASS, Loc::default());
+                let class = class.ok().and_then(Value::into_class);
+                return class.map(|class| class.member_names()).unwrap_or_default();
+            }
+            Value::Array(array) => return Class::from(array.borrow().class()).member_names(),
+            Value::Map(map) => return Class::from(map.borrow().class()).member_names(),
+            Value::Instance(instance) => instance,
+            Value::Callable(callable) => match callable.into_instance() {
+                Some(instance) => instance,
+                None => return Vec::new(),
+            },
+            _ => return Vec::new(),
+        };
+
+        let instance = instance.borrow();
+        let fields = instance.field_names().cloned();
+        fields.chain(instance.class().member_names()).collect()
+    }
+}
+
+fn field(val: Value, name: &str) -> Option<Value> {
+    match val {
+        Value::Instance(instance) => instance.borrow().field(name),
+        Value::Callable(callable) => callable.into_instance()?.borrow().field(name),
+        _ => None,
+    }
+}
+
+/// Whether `input` needs more lines: it has unclosed braces, brackets or
+/// parens, or an unterminated string or comment.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut scanner = Scanner::new(input, FileId::default());
+    let tokens = match scanner.scan_tokens() {
+        Ok(tokens) => tokens,
+        Err(error) => return is_unterminated(&error),
+    };
+
+    let mut depth = 0;
+    for token in tokens {
+        match token.kind {
+            TokenKind::LeftParen | TokenKind::LeftBrace | TokenKind::LeftBracket => depth += 1,
+            TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket => depth -= 1,
+            _ => (),
+        }
+    }
+
+    depth > 0
+}
+
+fn is_unterminated(error: &ScanningError) -> bool {
+    match error {
+        ScanningError::UnterminatedString(_) | ScanningError::UnterminatedBlockComment(_) => true,
+        ScanningError::Multiple(errors) => errors.last().is_some_and(is_unterminated),
+        _ => false,
+    }
+}
+
+/// Colors `line` by the kind of its tokens, using the colors values are
+/// printed with. It is left as it is if it can't be scanned.
+pub fn highlight(line: &str) -> Cow<'_, str> {
+    let mut scanner = Scanner::new(line, FileId::default());
+    let tokens = match scanner.scan_tokens() {
+        Ok(tokens) => tokens,
+        Err(_) => return Cow::Borrowed(line),
+    };
+
+    let tokens = tokens.iter().map(|token| (token.loc, style(token)));
+    let comments = scanner.comments.iter();
+    let mut spans: Vec<(Loc, Style)> = tokens
+        .chain(comments.map(|comment| (comment.loc, Style::new().dimmed())))
+        .filter(|(_loc, style)| *style != Style::new())
+        .collect();
+    spans.sort_by_key(|(loc, _style)| loc.start);
+
+    let mut highlighted = String::with_capacity(line.len());
+    let mut last = 0;
+    for (loc, style) in spans {
+        highlighted.push_str(&line[last..loc.start]);
+        highlighted.push_str(&style.paint(&line[loc.start..loc.end]).to_string());
+        last = loc.end;
+    }
+    highlighted.push_str(&line[last..]);
+
+    Cow::Owned(highlighted)
+}
+
+fn style(token: &Token) -> Style {
+    match token.kind {
+        TokenKind::Str => Green.normal(),
+        TokenKind::Integer => Blue.normal(),
+        TokenKind::Float => Cyan.normal(),
+        _ if KEYWORDS.contains(&token.lexeme) => Purple.normal(),
+        _ => Style::new(),
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.completions(line, pos))
+    }
+}
+
+impl Hinter for ReplHelper {}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        highlight(line)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if is_incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Helper for ReplHelper {}
@@ -0,0 +1,215 @@
+use super::*;
+use crate::eval::Interpreter;
+use crate::test_utils::{get_stmts, SharedBuffer};
+use crate::{Arity, Lox};
+use std::rc::Rc;
+
+fn helper(source: &str) -> ReplHelper {
+    let (stmts, mut inter) = get_stmts(source);
+    inter.interpret(&stmts).unwrap();
+    ReplHelper::new(Rc::clone(&inter.globals))
+}
+
+#[test]
+fn test_unbalanced_input_is_incomplete() {
+    assert!(is_incomplete("class A {"));
+    assert!(is_incomplete("fun f() {\n    print [1, (2"));
+    assert!(is_incomplete("print \"two\nlines"));
+    assert!(is_incomplete("/* still"));
+    assert!(!is_incomplete("print \"{\";"));
+    assert!(!is_incomplete("fun f() {}"));
+    assert!(!is_incomplete("print 1);"));
+    assert!(!is_incomplete("print 1.x;"));
+}
+
+#[test]
+fn test_highlight() {
+    let highlighted = highlight("var x = \"a\"; // c");
+    let expected = format!(
+        "{} x = {}; {}",
+        Purple.paint("var"),
+        Green.paint("\"a\""),
+        Style::new().dimmed().paint("// c")
+    );
+    assert_eq!(expected, highlighted);
+    assert_eq!(Cow::Borrowed("\"open"), highlight("\"open"));
+}
+
+#[test]
+fn test_complete_globals_and_keywords() {
+    let helper = helper("var counter = 1; fun count() {}");
+    let (start, names) = helper.completions("print cou", 9);
+    assert_eq!(6, start);
+    assert_eq!(vec!["count", "counter"], names);
+
+    let (_, names) = helper.completions("whi", 3);
+    assert_eq!(vec!["while"], names);
+}
+
+#[test]
+fn test_complete_members() {
+    let helper = helper(
+        r#"class A { method() {} size { return 1; } class create() {} }
+class B < A { init() { this.field = 1; this.inner = A(); } }
+var b = B();"#,
+    );
+
+    let (start, names) = helper.completions("b.", 2);
+    assert_eq!(2, start);
+    assert_eq!(vec!["field", "init", "inner", "method", "size"], names);
+
+    let (_, names) = helper.completions("print b.inner.m", 15);
+    assert_eq!(vec!["method"], names);
+
+    let (_, names) = helper.completions("A.cr", 4);
+    assert_eq!(vec!["create"], names);
+
+    let (_, names) = helper.completions("\"text\".len", 10);
+    assert!(names.iter().any(|name| name.starts_with("len")));
+
+    let (_, names) = helper.completions("f().", 4);
+    assert!(names.is_empty());
+}
+
+#[test]
+fn test_unknown_global_has_no_members() {
+    let helper = ReplHelper::new(Interpreter::new().globals);
+    assert!(helper.completions("missing.", 8).1.is_empty());
+}
+
+fn prompt() -> (Lox, SharedBuffer) {
+    let mut lox = Lox::new();
+    let output = SharedBuffer::default();
+    lox.set_output(output.clone());
+    lox.set_diagnostics(SharedBuffer::default());
+    (lox, output)
+}
+
+fn run_lines(lox: &mut Lox, lines: &[&str]) {
+    for line in lines {
+        lox.run_prompt_line(line).unwrap();
+    }
+}
+
+#[test]
+fn test_env_and_type_commands() {
+    let (mut lox, output) = prompt();
+    run_lines(
+        &mut lox,
+        &["var count = 1;", "fun f() {}", ":env", ":type count / 2.0"],
+    );
+    assert_eq!("count: int\nf: function\nfloat\n", output.contents());
+}
+
+#[test]
+fn test_reset_command() {
+    let (mut lox, output) = prompt();
+    lox.register_fn("host", Arity::Fixed(0), |_inter, _args, _loc| {
+        Ok(Value::Nil)
+    });
+    run_lines(&mut lox, &["var a = 1;", ":reset", ":env"]);
+    assert_eq!("", output.contents());
+    assert!(lox.get_global("a").is_none());
+    assert!(lox.get_global("host").is_some());
+}
+
+#[test]
+fn test_ast_and_time_commands() {
+    let (mut lox, output) = prompt();
+    run_lines(&mut lox, &[":ast -x", ":time 1 + 1"]);
+    let contents = output.contents();
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!("Program", lines[0]);
+    assert_eq!("  Expression @1:0", lines[1]);
+    assert_eq!("    Unary - @1:0", lines[2]);
+    assert!(lines[5].starts_with("Took "), "{}", lines[5]);
+}
+
+#[test]
+fn test_save_and_load_commands() {
+    let path = std::env::temp_dir().join(format!("lox_save_{}.lox", std::process::id()));
+    let save = format!(":save {}", path.display());
+    let (mut lox, _output) = prompt();
+    run_lines(&mut lox, &["var a = 1;", "a = a + 1 // bump", "print a;"]);
+    assert!(lox.run_prompt_line("print b;").is_err());
+    run_lines(&mut lox, &[&save]);
+
+    let script = std::fs::read_to_string(&path).unwrap();
+    assert_eq!("var a = 1;\na = a + 1; // bump\nprint a;\n", script);
+
+    let (mut lox, output) = prompt();
+    run_lines(&mut lox, &[&format!(":load {}", path.display())]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!("2\n", output.contents());
+}
+
+#[test]
+fn test_save_trims_entries() {
+    let path = std::env::temp_dir().join(format!("lox_trim_{}.lox", std::process::id()));
+    let loaded = std::env::temp_dir().join(format!("lox_loaded_{}.lox", std::process::id()));
+    std::fs::write(&loaded, "var b = 2;\n\n").unwrap();
+    let (mut lox, _output) = prompt();
+    run_lines(
+        &mut lox,
+        &[
+            "var a = 1;\n",
+            &format!(":load {}", loaded.display()),
+            "a + b\n",
+            &format!(":save {}", path.display()),
+        ],
+    );
+
+    let script = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&loaded).unwrap();
+    assert_eq!("var a = 1;\nvar b = 2;\na + b;\n", script);
+}
+
+#[test]
+fn test_timed_code_is_saved() {
+    let path = std::env::temp_dir().join(format!("lox_time_{}.lox", std::process::id()));
+    let (mut lox, _output) = prompt();
+    run_lines(
+        &mut lox,
+        &[":time var a = 1;", &format!(":save {}", path.display())],
+    );
+
+    let script = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!("var a = 1;\n", script);
+}
+
+#[test]
+fn test_command_errors() {
+    let (mut lox, _output) = prompt();
+    let message = |lox: &mut Lox, line| lox.run_prompt_line(line).unwrap_err().to_string();
+    assert_eq!(
+        "Unknown command ':nope', see :help",
+        message(&mut lox, ":nope")
+    );
+    assert_eq!("Usage: :load <file>", message(&mut lox, ":load"));
+    assert_eq!("Usage: :type <expr>", message(&mut lox, ":type var a = 1;"));
+}
+
+#[test]
+fn test_prompt_warnings() {
+    let (mut lox, _output) = prompt();
+    let diagnostics = SharedBuffer::default();
+    lox.set_diagnostics(diagnostics.clone());
+    let err = lox.run_prompt_line("print y;").unwrap_err();
+    assert_eq!("[1:6] Undefined variable 'y'", err.to_string());
+    run_lines(
+        &mut lox,
+        &["fun f(a) { return g(a); }", "fun g(a) { return a; }"],
+    );
+    assert_eq!("", diagnostics.contents());
+
+    lox.run_prompt_line("f();").unwrap_err();
+    assert!(
+        diagnostics
+            .contents()
+            .contains("'f' expects 1 argument but got 0"),
+        "{}",
+        diagnostics.contents()
+    );
+}
@@ -1,30 +1,127 @@
 #[cfg(test)]
 mod tests;
 
+use crate::callable::Arity;
 use crate::constants::{INIT_METHOD, SUPER_KEYWORD, THIS_KEYWORD};
 use crate::error::Warning;
-use crate::eval::Interpreter;
-use crate::expr::{BinOp, Expr, ExprKind, LitExpr, LogOp, Param, UnOp, Visitor as ExprVisitor};
+use crate::expr::{
+    BinOp, Expr, ExprKind, LitExpr, LogOp, Param, Resolution, UnOp, Visitor as ExprVisitor,
+};
+use crate::lint::{AllowedLints, LintLevel, LintLevels};
 use crate::location::Loc;
-use crate::stmt::{FunctionKind, Stmt, StmtKind, Visitor as StmtVisitor};
-use std::collections::HashMap;
+use crate::stmt::{CatchClause, FunctionKind, ImportKind, Stmt, StmtKind, Visitor as StmtVisitor};
+use crate::utils::closest_name;
+use std::collections::{HashMap, HashSet};
 
-pub struct Resolver<'a> {
-    inter: &'a mut Interpreter,
+pub struct Resolver {
     scopes: Vec<HashMap<String, ResolvedVar>>,
     current_fun: FunctionType,
     current_class: ClassType,
     in_loop: bool,
     errors: Vec<ResolutionError>,
     pub warnings: Vec<Warning>,
+    lint_levels: LintLevels,
+    allowed_lints: AllowedLints,
+    /// Globals that exist before the code runs, such as builtins, along with
+    /// the arity of the callable ones.
+    known_globals: HashMap<String, Option<Arity>>,
+    /// Globals declared at the top level of the code.
+    declared_globals: HashMap<String, GlobalDecl>,
+    assigned_globals: HashSet<String>,
+    global_refs: Vec<GlobalRef>,
+    global_calls: Vec<GlobalCall>,
+    /// Every name declared in the code, with where it is used.
+    pub bindings: Vec<Binding>,
+    /// The binding of each global, by name.
+    global_bindings: HashMap<String, usize>,
+    /// The code each scope of `scopes` covers.
+    scope_locs: Vec<Loc>,
+}
+
+/// A name declared in the code along with the places it is used, for tools
+/// such as the language server.
+#[derive(Debug)]
+pub struct Binding {
+    pub name: String,
+    pub kind: BindingKind,
+    /// The declaration, which starts at or before the name.
+    pub loc: Loc,
+    /// The code the name is visible in, or `None` for globals.
+    pub scope: Option<Loc>,
+    pub references: Vec<Loc>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BindingKind {
+    Variable,
+    Parameter,
+    Function(Arity),
+    /// A class, with the arity of its constructor if it is known.
+    Class(Option<Arity>),
+    Import,
+}
+
+struct GlobalDecl {
+    loc: Loc,
+    signature: Signature,
+}
+
+/// What is known about the arguments a global takes when called.
+enum Signature {
+    Unknown,
+    Known(Arity),
+    /// A class without an `init` method, which takes the same arguments as
+    /// its superclass.
+    Inherited(String),
+}
+
+/// A use of a global, which must be declared somewhere in the code.
+struct GlobalRef {
+    name: String,
+    loc: Loc,
+    /// The local variable with the name closest to the global, if any.
+    similar_local: Option<String>,
+}
+
+struct GlobalCall {
+    name: String,
+    args: usize,
+    loc: Loc,
 }
 
 #[derive(Debug)]
 struct ResolvedVar {
     loc: Loc,
     index: usize,
+    /// The index of the variable in `bindings`, if it is declared in the
+    /// code.
+    binding: Option<usize>,
+    kind: VarKind,
     defined: bool,
     used: bool,
+    /// Whether the variable gets a value other than the implicit `nil`.
+    assigned: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum VarKind {
+    Variable,
+    Parameter,
+    Function,
+    /// `this` and `super`, which aren't declared in the code.
+    Special,
+}
+
+impl From<BindingKind> for VarKind {
+    fn from(kind: BindingKind) -> Self {
+        match kind {
+            BindingKind::Parameter => VarKind::Parameter,
+            BindingKind::Function(_) => VarKind::Function,
+            BindingKind::Variable | BindingKind::Class(_) | BindingKind::Import => {
+                VarKind::Variable
+            }
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -46,8 +143,10 @@ enum ClassType {
 #[derive(Debug, PartialEq, Fail)]
 pub enum ResolutionError {
     VarInInitalizer(Loc),
-    VarAlreadyInScope(Loc, String),
-    DuplicateArgumentName(Loc, String),
+    /// The second location is where the variable was first declared.
+    VarAlreadyInScope(Loc, String, Loc),
+    /// The second location is where the first argument with that name is.
+    DuplicateArgumentName(Loc, String, Loc),
     DuplicateMethod(Loc, String, String, String),
     ReturnOutsideFun(Loc),
     ThisOutsideClass(Loc),
@@ -58,29 +157,61 @@ pub enum ResolutionError {
     SuperNoInSubclass(Loc),
     BreakOutsideLoop(Loc),
     ContinueOutsideLoop(Loc),
+    /// A warning whose lint was set to `LintLevel::Deny`.
+    DeniedLint(Box<Warning>),
     Multiple(Vec<ResolutionError>),
 }
 
 type ResolveRes = Result<(), !>;
 
-impl<'a> Resolver<'a> {
-    pub fn new(inter: &'a mut Interpreter) -> Self {
+impl Resolver {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
         Self {
-            inter,
             scopes: Vec::new(),
             current_fun: FunctionType::None,
             current_class: ClassType::None,
             in_loop: false,
             errors: vec![],
             warnings: vec![],
+            lint_levels: LintLevels::default(),
+            allowed_lints: AllowedLints::default(),
+            known_globals: HashMap::new(),
+            declared_globals: HashMap::new(),
+            assigned_globals: HashSet::new(),
+            global_refs: Vec::new(),
+            global_calls: Vec::new(),
+            bindings: Vec::new(),
+            global_bindings: HashMap::new(),
+            scope_locs: Vec::new(),
+        }
+    }
+
+    /// A resolver that reports warnings at the levels in `lint_levels`,
+    /// except for the ones allowed in the code by `allowed_lints`.
+    pub fn with_lints(lint_levels: LintLevels, allowed_lints: AllowedLints) -> Self {
+        Self {
+            lint_levels,
+            allowed_lints,
+            ..Self::new()
         }
     }
 
+    /// Sets the globals that exist before the code runs. Uses of other
+    /// globals that the code doesn't declare are reported.
+    pub fn set_known_globals(&mut self, globals: HashMap<String, Option<Arity>>) {
+        self.known_globals = globals;
+    }
+
     pub fn resolve(&mut self, stmts: &[Stmt]) -> Result<(), ResolutionError> {
         for stmt in stmts {
             let Ok(()) = self.resolve_stmt(stmt);
         }
 
+        self.link_global_refs();
+        self.check_globals();
+        self.apply_lint_levels();
+
         match self.errors.len() {
             0 => Ok(()),
             1 => Err(self.errors.pop().unwrap()),
@@ -113,11 +244,168 @@ impl<'a> Resolver<'a> {
             self.resolve_stmt(stmt)?;
         }
 
+        let jump = stmts.iter().position(|stmt| {
+            matches!(
+                stmt.kind,
+                StmtKind::Return(_) | StmtKind::Break | StmtKind::Continue
+            )
+        });
+        if let Some(jump) = jump {
+            if let Some(unreachable) = stmts.get(jump + 1) {
+                self.warnings
+                    .push(Warning::UnreachableCode(unreachable.loc, stmts[jump].loc));
+            }
+        }
+
         Ok(())
     }
 
-    fn begin_scope(&mut self) {
+    /// Adds the uses of globals to the bindings of their declarations.
+    fn link_global_refs(&mut self) {
+        for global in &self.global_refs {
+            if let Some(&binding) = self.global_bindings.get(&global.name) {
+                self.bindings[binding].references.push(global.loc);
+            }
+        }
+    }
+
+    /// Warns about uses of globals that are never declared, and calls to
+    /// globals with a number of arguments they don't take.
+    fn check_globals(&mut self) {
+        for global in std::mem::take(&mut self.global_refs) {
+            if self.is_global(&global.name) {
+                continue;
+            }
+
+            let candidates = self
+                .declared_globals
+                .keys()
+                .chain(self.known_globals.keys())
+                .chain(global.similar_local.as_ref())
+                .map(String::as_str);
+            let suggestion = closest_name(&global.name, candidates).map(String::from);
+            self.warnings.push(Warning::UndefinedVariable(
+                global.loc,
+                global.name,
+                suggestion,
+            ));
+        }
+
+        for call in std::mem::take(&mut self.global_calls) {
+            if let Some(arity) = self.global_arity(&call.name) {
+                if !arity.accepts(call.args) {
+                    let declared = self.declared_globals.get(&call.name).map(|decl| decl.loc);
+                    self.warnings.push(Warning::ArityMismatch(
+                        call.loc, call.name, arity, call.args, declared,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn is_global(&self, name: &str) -> bool {
+        self.declared_globals.contains_key(name) || self.known_globals.contains_key(name)
+    }
+
+    /// The arity of a global, if it is known not to change while the code
+    /// runs.
+    fn global_arity(&self, name: &str) -> Option<Arity> {
+        let mut name = name;
+        // Each step goes up a superclass, and there can't be more steps than
+        // classes unless a class inherits from itself.
+        for _ in 0..=self.declared_globals.len() {
+            if self.assigned_globals.contains(name) {
+                return None;
+            }
+
+            match self.declared_globals.get(name) {
+                Some(GlobalDecl { signature, .. }) => match signature {
+                    Signature::Unknown => return None,
+                    Signature::Known(arity) => return Some(*arity),
+                    Signature::Inherited(superclass) => name = superclass,
+                },
+                None => return self.known_globals.get(name).copied().flatten(),
+            }
+        }
+
+        None
+    }
+
+    /// Records a declaration at the top level. Globals declared more than
+    /// once can be anything when called.
+    fn declare_global(&mut self, name: &str, loc: Loc, signature: Signature, kind: BindingKind) {
+        if self.scopes.is_empty() {
+            if !self.global_bindings.contains_key(name) {
+                let binding = self.add_binding(name, loc, kind);
+                self.global_bindings.insert(String::from(name), binding);
+            }
+
+            let signature = if self.declared_globals.contains_key(name) {
+                Signature::Unknown
+            } else {
+                signature
+            };
+            self.declared_globals
+                .insert(String::from(name), GlobalDecl { loc, signature });
+        }
+    }
+
+    /// Records a use of `name` that isn't resolved to a local.
+    fn add_global_ref(&mut self, name: &str, loc: Loc) {
+        if self.known_globals.contains_key(name) {
+            return;
+        }
+
+        let locals = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.keys())
+            .map(String::as_str);
+        let similar_local = closest_name(name, locals).map(String::from);
+        self.global_refs.push(GlobalRef {
+            name: String::from(name),
+            loc,
+            similar_local,
+        });
+    }
+
+    /// Drops the warnings that are allowed and turns the denied ones into
+    /// errors. The rest are sorted by where they are.
+    fn apply_lint_levels(&mut self) {
+        let warnings = std::mem::take(&mut self.warnings);
+        for warn in warnings {
+            if self.allowed_lints.allows(&warn) {
+                continue;
+            }
+
+            match self.lint_levels.get(warn.lint()) {
+                LintLevel::Allow => (),
+                LintLevel::Warn => self.warnings.push(warn),
+                LintLevel::Deny => self
+                    .errors
+                    .push(ResolutionError::DeniedLint(Box::new(warn))),
+            }
+        }
+
+        self.warnings
+            .sort_by_key(|warn| (warn.loc().line, warn.loc().column));
+    }
+
+    fn add_binding(&mut self, name: &str, loc: Loc, kind: BindingKind) -> usize {
+        self.bindings.push(Binding {
+            name: String::from(name),
+            kind,
+            loc,
+            scope: self.scope_locs.last().copied(),
+            references: Vec::new(),
+        });
+        self.bindings.len() - 1
+    }
+
+    /// Starts a scope covering the code at `loc`.
+    fn begin_scope(&mut self, loc: Loc) {
         self.scopes.push(HashMap::new());
+        self.scope_locs.push(loc);
     }
 
     fn end_scope(&mut self) {
@@ -125,37 +413,76 @@ impl<'a> Resolver<'a> {
             .scopes
             .pop()
             .expect("There should be a scope to end here");
+        self.scope_locs.pop();
         for (name, resolved) in scope {
-            if !resolved.used {
-                self.warnings
-                    .push(Warning::UnusedVariable(resolved.loc, name));
+            // Names starting with '_' are unused on purpose.
+            let ResolvedVar { loc, kind, .. } = resolved;
+            if !resolved.used && !name.starts_with('_') {
+                self.warnings.push(match kind {
+                    VarKind::Parameter => Warning::UnusedParameter(loc, name),
+                    VarKind::Function => Warning::UnusedFunction(loc, name),
+                    VarKind::Variable | VarKind::Special => Warning::UnusedVariable(loc, name),
+                });
+            } else if resolved.used && !resolved.assigned {
+                self.warnings.push(Warning::UnassignedVariable(loc, name));
             }
         }
     }
 
-    fn declare_name<F>(&mut self, name: &str, loc: Loc, err_fn: F) -> ResolveRes
+    fn declare_name<F>(&mut self, name: &str, loc: Loc, kind: BindingKind, err_fn: F) -> ResolveRes
     where
-        F: FnOnce() -> ResolutionError,
+        F: FnOnce(Loc) -> ResolutionError,
     {
-        if let Some(scope) = self.scopes.last_mut() {
-            if scope.contains_key(name) {
-                self.errors.push(err_fn());
+        match self.scopes.last().map(|scope| scope.get(name)) {
+            None => return Ok(()),
+            Some(Some(declared)) => {
+                self.errors.push(err_fn(declared.loc));
                 return Ok(());
             }
-            scope.insert(String::from(name), ResolvedVar::new(loc, scope.len()));
+            Some(None) => (),
+        }
+
+        let binding = self.add_binding(name, loc, kind);
+        let (scope, outer_scopes) = self.scopes.split_last_mut().unwrap();
+        let mut resolved = ResolvedVar::new(loc, scope.len(), kind.into());
+        resolved.binding = Some(binding);
+        scope.insert(String::from(name), resolved);
+
+        let outer = outer_scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .filter(|outer| outer.kind != VarKind::Special);
+        if let Some(outer) = outer {
+            if !name.starts_with('_') {
+                self.warnings.push(Warning::ShadowedVariable(
+                    loc,
+                    String::from(name),
+                    outer.loc,
+                ));
+            }
         }
 
         Ok(())
     }
 
-    fn declare_var(&mut self, name: &str, loc: Loc) -> ResolveRes {
-        self.declare_name(name, loc, || {
-            ResolutionError::var_already_in_scope(loc, name)
+    /// Declares a variable, or a class or import, which are variables too.
+    fn declare_var(&mut self, name: &str, loc: Loc, kind: BindingKind) -> ResolveRes {
+        self.declare_name(name, loc, kind, |declared| {
+            ResolutionError::var_already_in_scope(loc, name, declared)
+        })
+    }
+
+    fn declare_fun(&mut self, name: &str, loc: Loc, arity: Arity) -> ResolveRes {
+        self.declare_name(name, loc, BindingKind::Function(arity), |declared| {
+            ResolutionError::var_already_in_scope(loc, name, declared)
         })
     }
 
     fn declare_param(&mut self, name: &str, loc: Loc) -> ResolveRes {
-        self.declare_name(name, loc, || ResolutionError::duplicate_arg_name(loc, name))
+        self.declare_name(name, loc, BindingKind::Parameter, |declared| {
+            ResolutionError::duplicate_arg_name(loc, name, declared)
+        })
     }
 
     fn define(&mut self, name: &str) {
@@ -174,25 +501,43 @@ impl<'a> Resolver<'a> {
             ResolvedVar {
                 loc,
                 index: scope.len(),
+                binding: None,
+                kind: VarKind::Special,
                 defined: true,
                 used: true,
+                assigned: true,
             },
         );
     }
 
-    fn resolve_local(&mut self, name: &str, loc: Loc, reading: bool) {
+    /// Resolves `name`, used at `loc`, to a local, or returns `false` if it's
+    /// a global.
+    fn resolve_local(
+        &mut self,
+        name: &str,
+        resolution: &Resolution,
+        reading: bool,
+        loc: Loc,
+    ) -> bool {
         if !self.scopes.is_empty() {
             let len = self.scopes.len();
             for i in (0..len).rev() {
                 if let Some(resolved) = self.scopes[i].get_mut(name) {
-                    self.inter.resolve(name, loc, len - 1 - i, resolved.index);
+                    resolution.set(len - 1 - i, resolved.index);
                     if reading {
                         resolved.used = true;
+                    } else {
+                        resolved.assigned = true;
+                    }
+                    if let Some(binding) = resolved.binding {
+                        self.bindings[binding].references.push(loc);
                     }
-                    return;
+                    return true;
                 }
             }
         }
+
+        false
     }
 
     fn resolve_function(
@@ -200,13 +545,14 @@ impl<'a> Resolver<'a> {
         params: &[Param],
         body: &[Stmt],
         fun_type: FunctionType,
+        loc: Loc,
     ) -> ResolveRes {
         let enclosing_fun = self.current_fun;
         let enclosing_loop = self.in_loop;
         self.current_fun = fun_type;
         self.in_loop = false;
 
-        self.begin_scope();
+        self.begin_scope(loc);
 
         for Param { kind: name, loc } in params {
             self.declare_param(name, *loc)?;
@@ -222,6 +568,14 @@ impl<'a> Resolver<'a> {
         Ok(())
     }
 
+    /// Warns about assignments used as the condition of an `if` or a loop,
+    /// which are usually meant to be comparisons.
+    fn check_condition(&mut self, cond: &Expr) {
+        if let ExprKind::Assign(_, _, _) = cond.kind {
+            self.warnings.push(Warning::AssignmentInCondition(cond.loc));
+        }
+    }
+
     fn function_kind_to_type(kind: FunctionKind, method_name: &str) -> FunctionType {
         use FunctionKind::*;
         match kind {
@@ -233,15 +587,15 @@ impl<'a> Resolver<'a> {
     }
 }
 
-impl ExprVisitor<()> for Resolver<'_> {
+impl ExprVisitor<()> for Resolver {
     type Error = !;
 
     fn visit_literal_expr(&mut self, _literal: &LitExpr, _loc: Loc) -> ResolveRes {
         Ok(())
     }
 
-    fn visit_function_expr(&mut self, params: &[Param], body: &[Stmt], _loc: Loc) -> ResolveRes {
-        self.resolve_function(params, body, FunctionType::Function)
+    fn visit_function_expr(&mut self, params: &[Param], body: &[Stmt], loc: Loc) -> ResolveRes {
+        self.resolve_function(params, body, FunctionType::Function, loc)
     }
 
     fn visit_grouping_expr(&mut self, expr: &Expr, _loc: Loc) -> ResolveRes {
@@ -252,13 +606,21 @@ impl ExprVisitor<()> for Resolver<'_> {
         self.resolve_expr(expr)
     }
 
-    fn visit_binary_expr(
-        &mut self,
-        left: &Expr,
-        _op: &BinOp,
-        right: &Expr,
-        _loc: Loc,
-    ) -> ResolveRes {
+    fn visit_binary_expr(&mut self, left: &Expr, op: &BinOp, right: &Expr, loc: Loc) -> ResolveRes {
+        use BinOp::*;
+        if let (ExprKind::Variable(left_name, _), ExprKind::Variable(right_name, _)) =
+            (&left.kind, &right.kind)
+        {
+            let comparison = matches!(
+                op,
+                Equal | NotEqual | Greater | GreaterEqual | Less | LessEqual
+            );
+            if comparison && left_name == right_name {
+                self.warnings
+                    .push(Warning::SelfComparison(loc, left_name.clone()));
+            }
+        }
+
         self.resolve_expr(left)?;
         self.resolve_expr(right)
     }
@@ -285,7 +647,7 @@ impl ExprVisitor<()> for Resolver<'_> {
         self.resolve_expr(right)
     }
 
-    fn visit_variable_expr(&mut self, name: &str, loc: Loc) -> ResolveRes {
+    fn visit_variable_expr(&mut self, name: &str, resolution: &Resolution, loc: Loc) -> ResolveRes {
         if let Some(scope) = self.scopes.last() {
             if let Some(ResolvedVar { defined: false, .. }) = scope.get(name) {
                 self.errors.push(ResolutionError::VarInInitalizer(loc));
@@ -293,19 +655,40 @@ impl ExprVisitor<()> for Resolver<'_> {
             }
         }
 
-        self.resolve_local(name, loc, true);
+        if !self.resolve_local(name, resolution, true, loc) {
+            self.add_global_ref(name, loc);
+        }
 
         Ok(())
     }
 
-    fn visit_assign_expr(&mut self, name: &str, expr: &Expr, loc: Loc) -> ResolveRes {
+    fn visit_assign_expr(
+        &mut self,
+        name: &str,
+        expr: &Expr,
+        resolution: &Resolution,
+        loc: Loc,
+    ) -> ResolveRes {
         self.resolve_expr(expr)?;
-        self.resolve_local(name, loc, false);
+        if !self.resolve_local(name, resolution, false, loc) {
+            self.add_global_ref(name, loc);
+            self.assigned_globals.insert(String::from(name));
+        }
         Ok(())
     }
 
-    fn visit_call_expr(&mut self, callee: &Expr, args: &[Expr], _loc: Loc) -> ResolveRes {
+    fn visit_call_expr(&mut self, callee: &Expr, args: &[Expr], loc: Loc) -> ResolveRes {
         self.resolve_expr(callee)?;
+        if let ExprKind::Variable(name, resolution) = &callee.kind {
+            if resolution.get().is_none() {
+                self.global_calls.push(GlobalCall {
+                    name: name.clone(),
+                    args: args.len(),
+                    loc,
+                });
+            }
+        }
+
         self.resolve_exprs(args)
     }
 
@@ -322,6 +705,15 @@ impl ExprVisitor<()> for Resolver<'_> {
         self.resolve_exprs(elements)
     }
 
+    fn visit_map_expr(&mut self, entries: &[(Expr, Expr)], _loc: Loc) -> ResolveRes {
+        for (key, val) in entries {
+            self.resolve_expr(key)?;
+            self.resolve_expr(val)?;
+        }
+
+        Ok(())
+    }
+
     fn visit_subscript_get_expr(&mut self, obj: &Expr, index: &Expr, _loc: Loc) -> ResolveRes {
         self.resolve_expr(obj)?;
         self.resolve_expr(index)
@@ -339,25 +731,24 @@ impl ExprVisitor<()> for Resolver<'_> {
         self.resolve_expr(expr)
     }
 
-    fn visit_this_expr(&mut self, loc: Loc) -> ResolveRes {
+    fn visit_this_expr(&mut self, resolution: &Resolution, loc: Loc) -> ResolveRes {
         if self.current_class == ClassType::None {
             self.errors.push(ResolutionError::ThisOutsideClass(loc));
         } else if self.current_fun == FunctionType::StaticMethod {
             self.errors.push(ResolutionError::ThisInStaticMethod(loc));
         } else {
-            self.resolve_local(THIS_KEYWORD, loc, true);
+            self.resolve_local(THIS_KEYWORD, resolution, true, loc);
         }
 
         Ok(())
     }
 
-    fn visit_super_expr(&mut self, _method: &str, loc: Loc) -> ResolveRes {
+    fn visit_super_expr(&mut self, _method: &str, resolution: &Resolution, loc: Loc) -> ResolveRes {
         match self.current_class {
             ClassType::None => self.errors.push(ResolutionError::SuperOutsideClass(loc)),
             ClassType::Class => self.errors.push(ResolutionError::SuperNoInSubclass(loc)),
             ClassType::Subclass => {
-                self.resolve_local(SUPER_KEYWORD, loc, true);
-                self.resolve_local(THIS_KEYWORD, loc, true);
+                self.resolve_local(SUPER_KEYWORD, resolution, true, loc);
             }
         }
 
@@ -365,7 +756,7 @@ impl ExprVisitor<()> for Resolver<'_> {
     }
 }
 
-impl StmtVisitor<()> for Resolver<'_> {
+impl StmtVisitor<()> for Resolver {
     type Error = !;
 
     fn visit_expression_stmt(&mut self, expr: &Expr, _loc: Loc) -> ResolveRes {
@@ -379,6 +770,7 @@ impl StmtVisitor<()> for Resolver<'_> {
         else_branch: &Option<Box<Stmt>>,
         _loc: Loc,
     ) -> ResolveRes {
+        self.check_condition(cond);
         self.resolve_expr(cond)?;
         self.resolve_stmt(then_branch)?;
         if let Some(else_stmt) = else_branch {
@@ -401,6 +793,7 @@ impl StmtVisitor<()> for Resolver<'_> {
     ) -> ResolveRes {
         let enclosing_loop = self.in_loop;
         self.in_loop = true;
+        self.check_condition(cond);
         self.resolve_expr(cond)?;
         if let Some(inc_expr) = inc {
             self.resolve_expr(inc_expr)?;
@@ -410,19 +803,45 @@ impl StmtVisitor<()> for Resolver<'_> {
         Ok(())
     }
 
+    fn visit_for_in_stmt(
+        &mut self,
+        var: &Param,
+        iterable: &Expr,
+        body: &Stmt,
+        loc: Loc,
+    ) -> ResolveRes {
+        self.resolve_expr(iterable)?;
+
+        let enclosing_loop = self.in_loop;
+        self.in_loop = true;
+        self.begin_scope(loc);
+        self.declare_var(&var.kind, var.loc, BindingKind::Variable)?;
+        self.define(&var.kind);
+        self.resolve_stmt(body)?;
+        self.end_scope();
+        self.in_loop = enclosing_loop;
+        Ok(())
+    }
+
     fn visit_var_stmt(&mut self, name: &str, init: &Option<Expr>, loc: Loc) -> ResolveRes {
-        self.declare_var(name, loc)?;
+        self.declare_global(name, loc, Signature::Unknown, BindingKind::Variable);
+        self.declare_var(name, loc, BindingKind::Variable)?;
         if let Some(init_expr) = init {
             self.resolve_expr(init_expr)?;
         }
 
         self.define(name);
+        if init.is_none() {
+            if let Some(resolved) = self.scopes.last_mut().and_then(|scope| scope.get_mut(name)) {
+                resolved.assigned = false;
+            }
+        }
 
         Ok(())
     }
 
-    fn visit_block_stmt(&mut self, stmts: &[Stmt], _loc: Loc) -> ResolveRes {
-        self.begin_scope();
+    fn visit_block_stmt(&mut self, stmts: &[Stmt], loc: Loc) -> ResolveRes {
+        self.begin_scope(loc);
         self.resolve_stmts(stmts)?;
         self.end_scope();
         Ok(())
@@ -436,10 +855,13 @@ impl StmtVisitor<()> for Resolver<'_> {
         _kind: FunctionKind,
         loc: Loc,
     ) -> ResolveRes {
-        self.declare_var(name, loc)?;
+        let arity = Arity::Fixed(params.len());
+        let kind = BindingKind::Function(arity);
+        self.declare_global(name, loc, Signature::Known(arity), kind);
+        self.declare_fun(name, loc, arity)?;
         self.define(name);
 
-        self.resolve_function(params, body, FunctionType::Function)
+        self.resolve_function(params, body, FunctionType::Function, loc)
     }
 
     fn visit_return_stmt(&mut self, ret: &Option<Expr>, loc: Loc) -> ResolveRes {
@@ -468,16 +890,38 @@ impl StmtVisitor<()> for Resolver<'_> {
         let enclosing_class = self.current_class;
         self.current_class = ClassType::Class;
 
-        self.declare_var(name, loc)?;
+        let init_params = methods.iter().find_map(|method| match &method.kind {
+            StmtKind::Function(method_name, params, _, FunctionKind::Method)
+                if method_name == INIT_METHOD =>
+            {
+                Some(params.len())
+            }
+            _ => None,
+        });
+        let signature = match (init_params, superclass) {
+            (Some(params), _) => Signature::Known(Arity::Fixed(params)),
+            (None, None) => Signature::Known(Arity::Fixed(0)),
+            (None, Some(superclass)) => match &superclass.kind {
+                ExprKind::Variable(supername, _) => Signature::Inherited(supername.clone()),
+                _ => Signature::Unknown,
+            },
+        };
+        let kind = BindingKind::Class(match signature {
+            Signature::Known(arity) => Some(arity),
+            Signature::Unknown | Signature::Inherited(_) => None,
+        });
+        self.declare_global(name, loc, signature, kind);
+
+        self.declare_var(name, loc, kind)?;
         self.define(name);
 
         if let Some(superclass) = superclass {
             match &superclass.kind {
-                ExprKind::Variable(supername) if supername == name => {
+                ExprKind::Variable(supername, _) if supername == name => {
                     self.errors
                         .push(ResolutionError::class_inherits_itself(loc, name));
                 }
-                ExprKind::Variable(_supername) => {}
+                ExprKind::Variable(_, _) => {}
                 _ => unreachable!(),
             }
 
@@ -485,11 +929,11 @@ impl StmtVisitor<()> for Resolver<'_> {
 
             self.resolve_expr(superclass)?;
 
-            self.begin_scope();
+            self.begin_scope(loc);
             self.declare_define_special(SUPER_KEYWORD, superclass.loc);
         }
 
-        self.begin_scope();
+        self.begin_scope(loc);
         self.declare_define_special(THIS_KEYWORD, loc);
 
         let mut method_names = Vec::new();
@@ -499,7 +943,7 @@ impl StmtVisitor<()> for Resolver<'_> {
                 StmtKind::Function(method_name, params, body, kind) => {
                     let declaration = Resolver::function_kind_to_type(*kind, method_name);
 
-                    self.resolve_function(params, body, declaration)?;
+                    self.resolve_function(params, body, declaration, method.loc)?;
 
                     if method_names.contains(method_name) {
                         self.errors.push(ResolutionError::duplicate_method(
@@ -522,8 +966,6 @@ impl StmtVisitor<()> for Resolver<'_> {
             self.end_scope();
         }
 
-        self.resolve_local(name, loc, false);
-
         self.current_class = enclosing_class;
 
         Ok(())
@@ -544,26 +986,81 @@ impl StmtVisitor<()> for Resolver<'_> {
 
         Ok(())
     }
+
+    fn visit_import_stmt(&mut self, _path: &str, kind: &ImportKind, _loc: Loc) -> ResolveRes {
+        let names = match kind {
+            ImportKind::Module(alias) => std::slice::from_ref(alias),
+            ImportKind::Names(names) => names.as_slice(),
+        };
+
+        for name in names {
+            self.declare_global(
+                &name.kind,
+                name.loc,
+                Signature::Unknown,
+                BindingKind::Import,
+            );
+            self.declare_var(&name.kind, name.loc, BindingKind::Import)?;
+            self.define(&name.kind);
+        }
+
+        Ok(())
+    }
+
+    fn visit_throw_stmt(&mut self, expr: &Expr, _loc: Loc) -> ResolveRes {
+        self.resolve_expr(expr)
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        body: &[Stmt],
+        catch: &Option<CatchClause>,
+        finally: &Option<Vec<Stmt>>,
+        loc: Loc,
+    ) -> ResolveRes {
+        self.begin_scope(loc);
+        self.resolve_stmts(body)?;
+        self.end_scope();
+
+        if let Some((var, catch_body)) = catch {
+            self.begin_scope(loc);
+            self.declare_var(&var.kind, var.loc, BindingKind::Variable)?;
+            self.define(&var.kind);
+            self.resolve_stmts(catch_body)?;
+            self.end_scope();
+        }
+
+        if let Some(finally_body) = finally {
+            self.begin_scope(loc);
+            self.resolve_stmts(finally_body)?;
+            self.end_scope();
+        }
+
+        Ok(())
+    }
 }
 
 impl ResolvedVar {
-    pub fn new(loc: Loc, index: usize) -> Self {
+    fn new(loc: Loc, index: usize, kind: VarKind) -> Self {
         Self {
             loc,
             index,
+            binding: None,
+            kind,
             defined: false,
             used: false,
+            assigned: true,
         }
     }
 }
 
 impl ResolutionError {
-    fn var_already_in_scope(loc: Loc, name: &str) -> Self {
-        Self::VarAlreadyInScope(loc, String::from(name))
+    fn var_already_in_scope(loc: Loc, name: &str, declared: Loc) -> Self {
+        Self::VarAlreadyInScope(loc, String::from(name), declared)
     }
 
-    fn duplicate_arg_name(loc: Loc, name: &str) -> Self {
-        Self::DuplicateArgumentName(loc, String::from(name))
+    fn duplicate_arg_name(loc: Loc, name: &str, declared: Loc) -> Self {
+        Self::DuplicateArgumentName(loc, String::from(name), declared)
     }
 
     fn duplicate_method(loc: Loc, class_name: &str, mtype: &str, name: &str) -> Self {
@@ -1,6 +1,37 @@
 use super::*;
+use crate::eval::Interpreter;
+use crate::lexer::Scanner;
+use crate::lint::Lint;
+use crate::parser::Parser;
+use crate::source::FileId;
 use crate::test_utils::*;
 
+fn resolve_with_lints(input: &str, levels: LintLevels) -> Result<Vec<Warning>, ResolutionError> {
+    let mut scanner = Scanner::new(input, FileId::default());
+    let tokens = scanner.scan_tokens().unwrap();
+    let stmts = Parser::new(&tokens).parse().unwrap();
+
+    let allowed_lints = std::mem::take(&mut scanner.allowed_lints);
+    let mut resolver = Resolver::with_lints(levels, allowed_lints);
+    resolver.resolve(&stmts).map(|()| resolver.warnings)
+}
+
+fn warnings(input: &str) -> Vec<Warning> {
+    resolve_with_lints(input, LintLevels::default()).unwrap()
+}
+
+/// The warnings about globals in `input`, knowing about the builtins.
+fn global_warnings(input: &str) -> Vec<Warning> {
+    let tokens = get_tokens(input);
+    let stmts = Parser::new(&tokens).parse().unwrap();
+
+    let mut resolver = Resolver::new();
+    let builtins = Interpreter::new().builtin_globals();
+    resolver.set_known_globals(builtins.signatures());
+    resolver.resolve(&stmts).unwrap();
+    resolver.warnings
+}
+
 #[test]
 fn test_var_in_init() {
     let input = r#"{var x = x + 1;}"#;
@@ -16,7 +47,8 @@ fn test_var_already_in_scope() {
     assert_eq!(
         Err(ResolutionError::VarAlreadyInScope(
             Loc::new(0, 8),
-            String::from("x")
+            String::from("x"),
+            Loc::new(0, 1)
         )),
         resolve(input)
     )
@@ -28,7 +60,8 @@ fn test_duplicate_argument() {
     assert_eq!(
         Err(ResolutionError::DuplicateArgumentName(
             Loc::new(0, 18),
-            String::from("param")
+            String::from("param"),
+            Loc::new(0, 11)
         )),
         resolve(input)
     )
@@ -278,7 +311,283 @@ fn test_multiple() {
 
 #[test]
 fn test_unused_variable() {
-    let input = r#"{ var x = 5; }"#;
-    assert_eq!(Ok(()), resolve(input));
-    // TODO: Actually check if the warning was emitted
+    let input = r#"{ var x = 5; var _y = 6; }"#;
+    assert_eq!(
+        vec![Warning::UnusedVariable(Loc::new(0, 2), String::from("x"))],
+        warnings(input)
+    );
+}
+
+#[test]
+fn test_unused_parameter_and_function() {
+    let input = r#"
+    fun outer(a, _b) {
+        fun helper() {}
+    }
+    "#;
+    assert_eq!(
+        vec![
+            Warning::UnusedParameter(Loc::new(1, 14), String::from("a")),
+            Warning::UnusedFunction(Loc::new(2, 12), String::from("helper")),
+        ],
+        warnings(input)
+    );
+}
+
+#[test]
+fn test_unreachable_code() {
+    let input = r#"
+    while (true) {
+        break;
+        print 1;
+        print 2;
+    }
+    "#;
+    assert_eq!(
+        vec![Warning::UnreachableCode(Loc::new(3, 8), Loc::new(2, 8))],
+        warnings(input)
+    );
+}
+
+#[test]
+fn test_shadowed_variable() {
+    let input = r#"
+    {
+        var x = 1;
+        fun f(x) { return x; }
+        f(x);
+    }
+    "#;
+    assert_eq!(
+        vec![Warning::ShadowedVariable(
+            Loc::new(3, 14),
+            String::from("x"),
+            Loc::new(2, 8)
+        )],
+        warnings(input)
+    );
+}
+
+#[test]
+fn test_assignment_in_condition() {
+    let input = r#"
+    var x;
+    if (x = 1) print x;
+    while ((x = 2)) print x;
+    "#;
+    assert_eq!(
+        vec![Warning::AssignmentInCondition(Loc::new(2, 8))],
+        warnings(input)
+    );
+}
+
+#[test]
+fn test_self_comparison() {
+    let input = r#"var x = 1; print x == x; print x == 1;"#;
+    assert_eq!(
+        vec![Warning::SelfComparison(Loc::new(0, 19), String::from("x"))],
+        warnings(input)
+    );
+}
+
+#[test]
+fn test_unassigned_variable() {
+    let input = r#"
+    {
+        var never;
+        var later;
+        later = 1;
+        print never + later;
+    }
+    "#;
+    assert_eq!(
+        vec![Warning::UnassignedVariable(
+            Loc::new(2, 8),
+            String::from("never")
+        )],
+        warnings(input)
+    );
+}
+
+#[test]
+fn test_allow_comments() {
+    let input = r#"
+    {
+        // lox:allow(unused_variable, shadowed_variable)
+        var x = 1;
+        var y = 2; // lox:allow(unused_variable)
+        var z = 3;
+    }
+    "#;
+    assert_eq!(
+        vec![Warning::UnusedVariable(Loc::new(5, 8), String::from("z"))],
+        warnings(input)
+    );
+}
+
+#[test]
+fn test_lint_levels() {
+    let input = r#"{ var x = 1; var y = 2; print x == x; }"#;
+    let mut levels = LintLevels::default();
+    levels.set(Lint::UnusedVariable, LintLevel::Allow);
+    assert_eq!(
+        Ok(vec![Warning::SelfComparison(
+            Loc::new(0, 32),
+            String::from("x")
+        )]),
+        resolve_with_lints(input, levels.clone())
+    );
+
+    levels.set(Lint::SelfComparison, LintLevel::Deny);
+    assert_eq!(
+        Err(ResolutionError::DeniedLint(Box::new(
+            Warning::SelfComparison(Loc::new(0, 32), String::from("x"))
+        ))),
+        resolve_with_lints(input, levels)
+    );
+}
+
+#[test]
+fn test_undefined_global() {
+    let input = r#"
+    fun add(a, b) { return a + b; }
+    fun main() {
+        var total = add(1, 2);
+        print totl;
+        print addd(total, later);
+        print str(missing);
+    }
+    var later = 1;
+    "#;
+    assert_eq!(
+        vec![
+            Warning::UndefinedVariable(
+                Loc::new(4, 14),
+                String::from("totl"),
+                Some(String::from("total"))
+            ),
+            Warning::UndefinedVariable(
+                Loc::new(5, 14),
+                String::from("addd"),
+                Some(String::from("add"))
+            ),
+            Warning::UndefinedVariable(Loc::new(6, 18), String::from("missing"), None),
+        ],
+        global_warnings(input)
+    );
+}
+
+#[test]
+fn test_short_names_get_no_suggestion() {
+    let input = r#"
+    class C {}
+    var ab = 1;
+    fun main() {
+        print y;
+        print a;
+        print abc;
+    }
+    "#;
+    assert_eq!(
+        vec![
+            Warning::UndefinedVariable(Loc::new(4, 14), String::from("y"), None),
+            Warning::UndefinedVariable(Loc::new(5, 14), String::from("a"), None),
+            Warning::UndefinedVariable(
+                Loc::new(6, 14),
+                String::from("abc"),
+                Some(String::from("ab"))
+            ),
+        ],
+        global_warnings(input)
+    );
+}
+
+#[test]
+fn test_arity_mismatch() {
+    let input = r#"
+    fun add(a, b) { return a + b; }
+    class Point { init(_x, _y) {} }
+    class Point3 < Point {}
+    add(1);
+    Point3(1, 2, 3);
+    clock(1);
+    Array(1, 2);
+    "#;
+    assert_eq!(
+        vec![
+            Warning::ArityMismatch(
+                Loc::new(4, 9),
+                String::from("add"),
+                Arity::Fixed(2),
+                1,
+                Some(Loc::new(1, 8))
+            ),
+            Warning::ArityMismatch(
+                Loc::new(5, 18),
+                String::from("Point3"),
+                Arity::Fixed(2),
+                3,
+                Some(Loc::new(3, 4))
+            ),
+            Warning::ArityMismatch(
+                Loc::new(6, 11),
+                String::from("clock"),
+                Arity::Fixed(0),
+                1,
+                None
+            ),
+        ],
+        global_warnings(input)
+    );
+}
+
+#[test]
+fn test_arity_of_reassigned_global() {
+    let input = r#"
+    fun add(a, b) { return a + b; }
+    fun pick(a) { return a; }
+    pick(1);
+    pick = add;
+    pick(1, 2);
+    var add = fun(a) { return a; };
+    add(1);
+    "#;
+    assert_eq!(Vec::<Warning>::new(), global_warnings(input));
+}
+
+#[test]
+fn test_bindings() {
+    let input = r#"fun add(a, b) { return a + b; }
+var total = add(1, 2);
+{ var total = 0; total = total + 1; }
+print total;"#;
+    let tokens = get_tokens(input);
+    let stmts = Parser::new(&tokens).parse().unwrap();
+    let mut resolver = Resolver::new();
+    resolver.resolve(&stmts).unwrap();
+
+    let bindings: Vec<_> = resolver
+        .bindings
+        .iter()
+        .map(|binding| {
+            let references: Vec<_> = binding.references.iter().map(|loc| loc.start).collect();
+            let scope = binding.scope.map(|scope| (scope.start, scope.end));
+            (binding.name.as_str(), binding.kind, scope, references)
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                "add",
+                BindingKind::Function(Arity::Fixed(2)),
+                None,
+                vec![44]
+            ),
+            ("a", BindingKind::Parameter, Some((4, 31)), vec![23]),
+            ("b", BindingKind::Parameter, Some((4, 31)), vec![27]),
+            ("total", BindingKind::Variable, None, vec![99]),
+            ("total", BindingKind::Variable, Some((55, 92)), vec![80, 72]),
+        ],
+        bindings
+    );
 }
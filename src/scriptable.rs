@@ -1,11 +1,15 @@
 use crate::array::{Array, ArrayRc};
 use crate::eval::{RuntimeError, ValueRes};
 use crate::location::Loc;
+use crate::map::{Map, MapKey, MapRc};
+use crate::string::char_at;
 use crate::value::Value;
 
 #[derive(Clone, Debug)]
 pub enum Scriptable {
     Array(ArrayRc),
+    Map(MapRc),
+    Str(String),
 }
 
 pub trait LoxScriptable {
@@ -17,12 +21,19 @@ impl LoxScriptable for Scriptable {
     fn subscript_get(&self, index: Value, loc: Loc) -> ValueRes {
         match self {
             Scriptable::Array(array) => array.borrow().subscript_get(index, loc),
+            Scriptable::Map(map) => map.borrow().subscript_get(index, loc),
+            Scriptable::Str(string) => match index {
+                Value::Integer(index) => char_at(string, index, loc),
+                index => Err(RuntimeError::string_index_not_int(loc, index)),
+            },
         }
     }
 
     fn subscript_set(&mut self, index: Value, val: Value, loc: Loc) -> ValueRes {
         match self {
             Scriptable::Array(array) => array.borrow_mut().subscript_set(index, val, loc),
+            Scriptable::Map(map) => map.borrow_mut().subscript_set(index, val, loc),
+            Scriptable::Str(_string) => Err(RuntimeError::ImmutableString(loc)),
         }
     }
 }
@@ -45,3 +56,17 @@ impl LoxScriptable for Array {
         }
     }
 }
+
+impl LoxScriptable for Map {
+    fn subscript_get(&self, index: Value, loc: Loc) -> ValueRes {
+        let key = MapKey::from_value(index, loc)?;
+        self.get(&key)
+            .ok_or_else(|| RuntimeError::key_not_found(loc, &key))
+    }
+
+    fn subscript_set(&mut self, index: Value, val: Value, loc: Loc) -> ValueRes {
+        let key = MapKey::from_value(index, loc)?;
+        self.set(key, val.clone());
+        Ok(val)
+    }
+}
@@ -0,0 +1,71 @@
+use crate::location::Loc;
+
+/// Identifies a source registered in a `SourceMap`. The default id belongs
+/// to code that wasn't registered, such as the one built by tests.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct FileId(usize);
+
+pub struct SourceFile {
+    /// The path of the file, or a placeholder such as `<repl>`.
+    pub name: String,
+    pub source: String,
+}
+
+/// Every piece of code run by an interpreter, so that locations can be shown
+/// with the file they are in and the code they point to.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn add(&mut self, name: &str, source: &str) -> FileId {
+        self.files.push(SourceFile {
+            name: String::from(name),
+            source: String::from(source),
+        });
+        FileId(self.files.len())
+    }
+
+    pub fn get(&self, file: FileId) -> Option<&SourceFile> {
+        file.0
+            .checked_sub(1)
+            .and_then(|index| self.files.get(index))
+    }
+
+    pub fn name(&self, file: FileId) -> Option<&str> {
+        self.get(file).map(|file| file.name.as_str())
+    }
+
+    /// The line and column, both starting at 0, of the byte `offset` in the
+    /// source of `file`.
+    pub fn position(&self, file: FileId, offset: usize) -> Option<(usize, usize)> {
+        let before = self.get(file)?.source.get(..offset)?;
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let line = before.matches('\n').count();
+        Some((line, before[line_start..].chars().count()))
+    }
+
+    /// `loc` with the line and column where its span starts, rather than the
+    /// ones of the token it was reported at.
+    pub fn span_start(&self, loc: Loc) -> Loc {
+        let start = loc.has_span().then(|| self.position(loc.file, loc.start));
+        match start.flatten() {
+            Some((line, column)) => Loc {
+                line,
+                column,
+                ..loc
+            },
+            None => loc,
+        }
+    }
+
+    /// Shows `loc` as `name:line:column`, or just `line:column` if its file
+    /// is unknown.
+    pub fn display_loc(&self, loc: Loc) -> String {
+        match self.name(loc.file) {
+            Some(name) => format!("{}:{}", name, loc),
+            None => loc.to_string(),
+        }
+    }
+}
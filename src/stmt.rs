@@ -7,6 +7,7 @@ pub enum StmtKind {
     If(Expr, Box<Stmt>, Option<Box<Stmt>>),
     Print(Expr),
     For(Expr, Option<Expr>, Box<Stmt>),
+    ForIn(Param, Expr, Box<Stmt>),
     Var(String, Option<Expr>),
     Block(Vec<Stmt>),
     Function(String, Vec<Param>, Vec<Stmt>, FunctionKind),
@@ -14,10 +15,21 @@ pub enum StmtKind {
     Class(String, Option<Expr>, Vec<Stmt>),
     Break,
     Continue,
+    Throw(Expr),
+    Try(Vec<Stmt>, Option<CatchClause>, Option<Vec<Stmt>>),
+    Import(String, ImportKind),
 }
 
 pub type Stmt = Located<StmtKind>;
 
+pub type CatchClause = (Param, Vec<Stmt>);
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ImportKind {
+    Module(Param),
+    Names(Vec<Param>),
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum FunctionKind {
     Function,
@@ -50,6 +62,14 @@ pub trait Visitor<Res> {
         loc: Loc,
     ) -> Self::Result;
 
+    fn visit_for_in_stmt(
+        &mut self,
+        var: &Param,
+        iterable: &Expr,
+        body: &Stmt,
+        loc: Loc,
+    ) -> Self::Result;
+
     fn visit_var_stmt(&mut self, name: &str, init: &Option<Expr>, loc: Loc) -> Self::Result;
 
     fn visit_block_stmt(&mut self, stmts: &[Stmt], loc: Loc) -> Self::Result;
@@ -75,6 +95,17 @@ pub trait Visitor<Res> {
 
     fn visit_break_stmt(&mut self, loc: Loc) -> Self::Result;
     fn visit_continue_stmt(&mut self, loc: Loc) -> Self::Result;
+    fn visit_throw_stmt(&mut self, expr: &Expr, loc: Loc) -> Self::Result;
+
+    fn visit_try_stmt(
+        &mut self,
+        body: &[Stmt],
+        catch: &Option<CatchClause>,
+        finally: &Option<Vec<Stmt>>,
+        loc: Loc,
+    ) -> Self::Result;
+
+    fn visit_import_stmt(&mut self, path: &str, kind: &ImportKind, loc: Loc) -> Self::Result;
 }
 
 impl Stmt {
@@ -97,6 +128,10 @@ impl Stmt {
         Stmt::new(StmtKind::For(cond, inc, Box::new(body)), loc)
     }
 
+    pub fn for_in(var: Param, iterable: Expr, body: Stmt, loc: Loc) -> Self {
+        Stmt::new(StmtKind::ForIn(var, iterable, Box::new(body)), loc)
+    }
+
     pub fn var(name: &str, init: Option<Expr>, loc: Loc) -> Self {
         Stmt::new(StmtKind::Var(String::from(name), init), loc)
     }
@@ -137,6 +172,23 @@ impl Stmt {
         Stmt::new(StmtKind::Continue, loc)
     }
 
+    pub fn throw(expr: Expr, loc: Loc) -> Self {
+        Stmt::new(StmtKind::Throw(expr), loc)
+    }
+
+    pub fn try_stmt(
+        body: Vec<Stmt>,
+        catch: Option<CatchClause>,
+        finally: Option<Vec<Stmt>>,
+        loc: Loc,
+    ) -> Self {
+        Stmt::new(StmtKind::Try(body, catch, finally), loc)
+    }
+
+    pub fn import(path: &str, kind: ImportKind, loc: Loc) -> Self {
+        Stmt::new(StmtKind::Import(String::from(path), kind), loc)
+    }
+
     pub fn accept<Vis, Res, Error>(&self, visitor: &mut Vis) -> Vis::Result
     where
         Vis: Visitor<Res, Error = Error>,
@@ -149,6 +201,7 @@ impl Stmt {
             }
             Print(expr) => visitor.visit_print_stmt(expr, self.loc),
             For(expr, inc, body) => visitor.visit_for_stmt(expr, inc, body, self.loc),
+            ForIn(var, iterable, body) => visitor.visit_for_in_stmt(var, iterable, body, self.loc),
             Var(name, init) => visitor.visit_var_stmt(name, init, self.loc),
             Block(stmts) => visitor.visit_block_stmt(stmts, self.loc),
             Function(name, params, body, kind) => {
@@ -160,6 +213,9 @@ impl Stmt {
             }
             Break => visitor.visit_break_stmt(self.loc),
             Continue => visitor.visit_continue_stmt(self.loc),
+            Throw(expr) => visitor.visit_throw_stmt(expr, self.loc),
+            Try(body, catch, finally) => visitor.visit_try_stmt(body, catch, finally, self.loc),
+            Import(path, kind) => visitor.visit_import_stmt(path, kind, self.loc),
         }
     }
 }
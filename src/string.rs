@@ -0,0 +1,320 @@
+use crate::array::Array;
+use crate::callable::{Arity, Callable, LoxCallable};
+use crate::class::{Class, InstanceRc, LoxClass, MethodMap, NativeMethod};
+use crate::constants::STRING_CLASS;
+use crate::eval::{Interpreter, RuntimeError, ValueRes};
+use crate::location::Loc;
+use crate::value::{
+    types::{INT, STRING},
+    Value,
+};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::rc::Rc;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct StringClass {
+    uuid: Uuid,
+    methods: MethodMap,
+    getters: MethodMap,
+    metainstance: InstanceRc,
+}
+
+impl StringClass {
+    pub fn new() -> Self {
+        let uuid = Uuid::new_v4();
+        let static_methods = MethodMap::new();
+
+        let mut getters = MethodMap::new();
+        Self::add_length_getter(&mut getters);
+        let mut methods = MethodMap::new();
+        Self::add_substring_method(&mut methods);
+        Self::add_index_of_method(&mut methods);
+        Self::add_contains_method(&mut methods);
+        Self::add_starts_with_method(&mut methods);
+        Self::add_ends_with_method(&mut methods);
+        Self::add_split_method(&mut methods);
+        Self::add_trim_method(&mut methods);
+        Self::add_to_upper_method(&mut methods);
+        Self::add_to_lower_method(&mut methods);
+        Self::add_replace_method(&mut methods);
+        Self::add_repeat_method(&mut methods);
+        Self::add_chars_method(&mut methods);
+
+        let metainstance = Class::create_metainstance(
+            STRING_CLASS,
+            uuid,
+            None,
+            static_methods,
+            &methods,
+            &getters,
+        );
+
+        Self {
+            uuid,
+            methods,
+            getters,
+            metainstance,
+        }
+    }
+
+    fn add_length_getter(getters: &mut MethodMap) {
+        Class::add_native_method(
+            getters,
+            NativeMethod::new("length", 0, |_inter, _args, instance, _loc| {
+                let string = instance.string().unwrap();
+                Ok(Value::Integer(string.chars().count() as i64))
+            }),
+        )
+    }
+
+    fn add_substring_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("substring", 2, |_inter, mut args, instance, loc| {
+                let string = instance.string().unwrap();
+                let end = expect_int(args.pop().unwrap(), loc)?;
+                let start = expect_int(args.pop().unwrap(), loc)?;
+                let len = string.chars().count();
+                check_bounds(start, len, loc)?;
+                check_bounds(end, len, loc)?;
+
+                let substring: String = string
+                    .chars()
+                    .skip(start as usize)
+                    .take((end - start).max(0) as usize)
+                    .collect();
+                Ok(substring.into())
+            }),
+        )
+    }
+
+    fn add_index_of_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("indexOf", 1, |_inter, mut args, instance, loc| {
+                let string = instance.string().unwrap();
+                let pattern = expect_str(args.pop().unwrap(), loc)?;
+                let index = string
+                    .find(&pattern)
+                    .map(|byte_index| string[..byte_index].chars().count() as i64)
+                    .unwrap_or(-1);
+                Ok(index.into())
+            }),
+        )
+    }
+
+    fn add_contains_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("contains", 1, |_inter, mut args, instance, loc| {
+                let string = instance.string().unwrap();
+                let pattern = expect_str(args.pop().unwrap(), loc)?;
+                Ok(string.contains(&pattern).into())
+            }),
+        )
+    }
+
+    fn add_starts_with_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("startsWith", 1, |_inter, mut args, instance, loc| {
+                let string = instance.string().unwrap();
+                let pattern = expect_str(args.pop().unwrap(), loc)?;
+                Ok(string.starts_with(&pattern).into())
+            }),
+        )
+    }
+
+    fn add_ends_with_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("endsWith", 1, |_inter, mut args, instance, loc| {
+                let string = instance.string().unwrap();
+                let pattern = expect_str(args.pop().unwrap(), loc)?;
+                Ok(string.ends_with(&pattern).into())
+            }),
+        )
+    }
+
+    fn add_split_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("split", 1, |inter, mut args, instance, loc| {
+                let string = instance.string().unwrap();
+                let separator = expect_str(args.pop().unwrap(), loc)?;
+                let parts = if separator.is_empty() {
+                    chars(string)
+                } else {
+                    string.split(&separator).map(Value::from).collect()
+                };
+                Ok(Array::new(inter, parts).into())
+            }),
+        )
+    }
+
+    fn add_trim_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("trim", 0, |_inter, _args, instance, _loc| {
+                let string = instance.string().unwrap();
+                Ok(string.trim().into())
+            }),
+        )
+    }
+
+    fn add_to_upper_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("toUpper", 0, |_inter, _args, instance, _loc| {
+                let string = instance.string().unwrap();
+                Ok(string.to_uppercase().into())
+            }),
+        )
+    }
+
+    fn add_to_lower_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("toLower", 0, |_inter, _args, instance, _loc| {
+                let string = instance.string().unwrap();
+                Ok(string.to_lowercase().into())
+            }),
+        )
+    }
+
+    fn add_replace_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("replace", 2, |_inter, mut args, instance, loc| {
+                let string = instance.string().unwrap();
+                let to = expect_str(args.pop().unwrap(), loc)?;
+                let from = expect_str(args.pop().unwrap(), loc)?;
+                Ok(string.replace(&from, &to).into())
+            }),
+        )
+    }
+
+    fn add_repeat_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("repeat", 1, |_inter, mut args, instance, loc| {
+                let string = instance.string().unwrap();
+                let times = expect_int(args.pop().unwrap(), loc)?;
+                if times < 0 {
+                    return Err(RuntimeError::generic(
+                        loc,
+                        "Negative value given for the repetitions",
+                    ));
+                }
+
+                Ok(string.repeat(times as usize).into())
+            }),
+        )
+    }
+
+    fn add_chars_method(methods: &mut MethodMap) {
+        Class::add_native_method(
+            methods,
+            NativeMethod::new("chars", 0, |inter, _args, instance, _loc| {
+                let string = instance.string().unwrap();
+                Ok(Array::new(inter, chars(string)).into())
+            }),
+        )
+    }
+}
+
+pub fn char_at(string: &str, index: i64, loc: Loc) -> ValueRes {
+    let len = string.chars().count();
+    if index >= 0 && (index as usize) < len {
+        Ok(string
+            .chars()
+            .nth(index as usize)
+            .unwrap()
+            .to_string()
+            .into())
+    } else {
+        Err(RuntimeError::IndexOutOfBounds(loc, index, len))
+    }
+}
+
+pub fn chars(string: &str) -> Vec<Value> {
+    string.chars().map(|ch| ch.to_string().into()).collect()
+}
+
+fn check_bounds(index: i64, len: usize, loc: Loc) -> Result<(), RuntimeError> {
+    if index >= 0 && (index as usize) <= len {
+        Ok(())
+    } else {
+        Err(RuntimeError::IndexOutOfBounds(loc, index, len))
+    }
+}
+
+fn expect_int(val: Value, loc: Loc) -> Result<i64, RuntimeError> {
+    match val {
+        Value::Integer(int) => Ok(int),
+        val => Err(RuntimeError::expected_type(loc, INT, val)),
+    }
+}
+
+fn expect_str(val: Value, loc: Loc) -> Result<String, RuntimeError> {
+    match val {
+        Value::Str(string) => Ok(string),
+        val => Err(RuntimeError::expected_type(loc, STRING, val)),
+    }
+}
+
+impl LoxClass for Rc<StringClass> {
+    fn name(&self) -> &str {
+        STRING_CLASS
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    fn superclass(&self) -> Option<&Class> {
+        None
+    }
+
+    fn methods(&self) -> &MethodMap {
+        &self.methods
+    }
+
+    fn getters(&self) -> &MethodMap {
+        &self.getters
+    }
+
+    fn metainstance(&self) -> Option<InstanceRc> {
+        Some(Rc::clone(&self.metainstance))
+    }
+}
+
+impl LoxCallable for Rc<StringClass> {
+    fn arity(&self) -> Arity {
+        Arity::Fixed(1)
+    }
+
+    fn call(&self, _inter: &mut Interpreter, mut args: Vec<Value>, _loc: Loc) -> ValueRes {
+        let val = args.pop().unwrap();
+        Ok(val.to_string().into())
+    }
+}
+
+impl From<Rc<StringClass>> for Callable {
+    fn from(class: Rc<StringClass>) -> Self {
+        Callable::Class(Class::Str(class))
+    }
+}
+
+impl From<Rc<StringClass>> for Class {
+    fn from(class: Rc<StringClass>) -> Self {
+        Class::Str(class)
+    }
+}
+
+impl Display for StringClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "<class {}>", STRING_CLASS)
+    }
+}
@@ -1,13 +1,38 @@
 use crate::eval::{Interpreter, RuntimeError};
-use crate::expr::{BinOp, Expr, ExprKind, LitExpr, LogOp, UnOp};
+use crate::expr::{BinOp, Expr, ExprKind, LitExpr, LogOp, Param, UnOp};
 use crate::lexer::{Scanner, Token};
 use crate::location::Loc;
 use crate::parser::Parser;
 use crate::resolver::{ResolutionError, Resolver};
+use crate::source::FileId;
 use crate::stmt::Stmt;
+use std::cell::RefCell;
+use std::io::{Result as IoResult, Write};
+use std::rc::Rc;
+
+/// An output sink whose contents can still be read once it was handed to
+/// the interpreter.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
 
 pub fn get_tokens<'a>(input: &'a str) -> Vec<Token<'a>> {
-    let mut scanner = Scanner::new(input);
+    let mut scanner = Scanner::new(input, FileId::default());
     scanner.scan_tokens().unwrap()
 }
 
@@ -22,8 +47,7 @@ pub fn resolve(input: &str) -> Result<(), ResolutionError> {
     let mut parser = Parser::new(&tokens);
     let stmts = parser.parse().unwrap();
 
-    let mut inter = Interpreter::new();
-    let mut resolver = Resolver::new(&mut inter);
+    let mut resolver = Resolver::new();
     resolver.resolve(&stmts)
 }
 
@@ -32,11 +56,10 @@ pub fn get_stmts(input: &str) -> (Vec<Stmt>, Interpreter) {
     let mut parser = Parser::new(&tokens);
     let stmts = parser.parse().unwrap();
 
-    let mut inter = Interpreter::new();
-    let mut resolver = Resolver::new(&mut inter);
+    let mut resolver = Resolver::new();
     resolver.resolve(&stmts).unwrap();
 
-    (stmts, inter)
+    (stmts, Interpreter::new())
 }
 
 pub fn int_expr(int: i64, (line, col): (usize, usize)) -> Expr {
@@ -151,6 +174,10 @@ pub fn assign_expr(name: &str, expr: Expr, (line, col): (usize, usize)) -> Expr
     Expr::assign(String::from(name), expr, Loc::new(line, col))
 }
 
+pub fn map_expr(entries: Vec<(Expr, Expr)>, (line, col): (usize, usize)) -> Expr {
+    Expr::map(entries, Loc::new(line, col))
+}
+
 pub fn expr_stmt(expr: Expr, (line, col): (usize, usize)) -> Stmt {
     Stmt::expression(expr, Loc::new(line, col))
 }
@@ -188,6 +215,17 @@ pub fn for_stmt(
     }
 }
 
+pub fn for_in_stmt(
+    var: &str,
+    var_loc: (usize, usize),
+    iterable: Expr,
+    body: Stmt,
+    (line, col): (usize, usize),
+) -> Stmt {
+    let var = Param::new(String::from(var), Loc::new(var_loc.0, var_loc.1));
+    Stmt::for_in(var, iterable, body, Loc::new(line, col))
+}
+
 pub fn var_stmt(name: &str, init: Option<Expr>, (line, col): (usize, usize)) -> Stmt {
     Stmt::var(name, init, Loc::new(line, col))
 }
@@ -49,3 +49,36 @@ pub fn is_digit(ch: char) -> bool {
 pub fn is_alphanumeric(ch: char) -> bool {
     is_alpha(ch) || is_digit(ch)
 }
+
+/// The number of characters to insert, remove or replace to turn `a` into
+/// `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let replace = previous[j] + (a_char != *b_char) as usize;
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// The candidate that `name` is most likely a misspelling of, if any is
+/// close enough. A candidate may be at most one edit away for every three
+/// characters of `name`, so names shorter than that get no suggestion.
+pub fn closest_name<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = name.chars().count() / 3;
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance > 0 && distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
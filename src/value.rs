@@ -1,9 +1,14 @@
 use crate::array::{Array, ArrayRc};
 use crate::callable::{Callable, LoxCallable};
 use crate::class::{Class, ClassInstance, InstanceRc};
+use crate::eval::{Interpreter, RuntimeError};
 use crate::expr::LitExpr;
+use crate::gc;
+use crate::location::Loc;
+use crate::map::{Map, MapRc};
 use crate::scriptable::Scriptable;
 use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::rc::Rc;
 
@@ -17,6 +22,7 @@ pub enum Value {
     Callable(Callable),
     Instance(InstanceRc),
     Array(ArrayRc),
+    Map(MapRc),
 }
 
 pub mod types {
@@ -27,6 +33,7 @@ pub mod types {
     pub const NIL: &str = "nil";
     pub const INSTANCE: &str = "instance";
     pub const ARRAY: &str = "array";
+    pub const MAP: &str = "map";
 }
 
 impl Value {
@@ -54,15 +61,21 @@ impl Value {
             Callable(callable) => callable.get_type(),
             Instance(_) => types::INSTANCE,
             Array(_) => types::ARRAY,
+            Map(_) => types::MAP,
         }
     }
 
-    pub fn into_instance(self) -> Option<InstanceRc> {
+    pub fn into_instance(self, inter: &Interpreter) -> Option<InstanceRc> {
         use Value::*;
         match self {
+            Str(string) => {
+                let class = &inter.natives.string_class;
+                Some(ClassInstance::from_string(class, string).into())
+            }
             Instance(instance) => Some(instance),
             Callable(callable) => callable.into_instance(),
             Array(array) => Some(ClassInstance::from_array(array).into()),
+            Map(map) => Some(ClassInstance::from_map(map).into()),
             _ => None,
         }
     }
@@ -77,6 +90,8 @@ impl Value {
     pub fn into_scriptable(self) -> Result<Scriptable, Value> {
         match self {
             Value::Array(array) => Ok(Scriptable::Array(array)),
+            Value::Map(map) => Ok(Scriptable::Map(map)),
+            Value::Str(string) => Ok(Scriptable::Str(string)),
             val => Err(val),
         }
     }
@@ -133,13 +148,19 @@ impl<T: LoxCallable> From<T> for Value {
 
 impl From<ClassInstance> for Value {
     fn from(input: ClassInstance) -> Self {
-        Value::Instance(Rc::new(RefCell::new(input)))
+        Value::Instance(gc::alloc(RefCell::new(input)))
     }
 }
 
 impl From<Array> for Value {
     fn from(input: Array) -> Self {
-        Value::Array(Rc::new(RefCell::new(input)))
+        Value::Array(gc::alloc(RefCell::new(input)))
+    }
+}
+
+impl From<Map> for Value {
+    fn from(input: Map) -> Self {
+        Value::Map(gc::alloc(RefCell::new(input)))
     }
 }
 
@@ -152,6 +173,153 @@ impl<T: Into<Value>> From<Option<T>> for Value {
     }
 }
 
+/// Converts values received from scripts into Rust types, for hosts calling
+/// Lox functions or implementing native ones.
+pub trait FromValue: Sized {
+    fn from_value(val: Value, loc: Loc) -> Result<Self, RuntimeError>;
+}
+
+/// Converts Rust types into values that can be passed to scripts. Arrays
+/// need the interpreter to find their class. Integers that don't fit in an
+/// `i64` panic rather than wrap.
+pub trait IntoValue {
+    fn into_value(self, inter: &Interpreter) -> Value;
+}
+
+impl FromValue for Value {
+    fn from_value(val: Value, _loc: Loc) -> Result<Self, RuntimeError> {
+        Ok(val)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(val: Value, loc: Loc) -> Result<Self, RuntimeError> {
+        match val {
+            Value::Boolean(boolean) => Ok(boolean),
+            val => Err(RuntimeError::expected_type(loc, types::BOOL, val)),
+        }
+    }
+}
+
+macro_rules! integer_conversions {
+    ($($int:ty),*) => {
+        $(
+            impl FromValue for $int {
+                fn from_value(val: Value, loc: Loc) -> Result<Self, RuntimeError> {
+                    match val {
+                        Value::Integer(int) => <$int>::try_from(int).map_err(|_| {
+                            let message = format!("Integer {} does not fit in {}", int, stringify!($int));
+                            RuntimeError::generic(loc, &message)
+                        }),
+                        val => Err(RuntimeError::expected_type(loc, types::INT, val)),
+                    }
+                }
+            }
+
+            impl IntoValue for $int {
+                fn into_value(self, _inter: &Interpreter) -> Value {
+                    let int = i64::try_from(self)
+                        .unwrap_or_else(|_| panic!("Integer {} does not fit in i64", self));
+                    Value::Integer(int)
+                }
+            }
+        )*
+    };
+}
+
+integer_conversions!(i64, i32, u32, usize);
+
+impl FromValue for f64 {
+    fn from_value(val: Value, loc: Loc) -> Result<Self, RuntimeError> {
+        val.number()
+            .ok_or_else(|| RuntimeError::expected_type(loc, types::FLOAT, val))
+    }
+}
+
+impl FromValue for String {
+    fn from_value(val: Value, loc: Loc) -> Result<Self, RuntimeError> {
+        match val {
+            Value::Str(string) => Ok(string),
+            val => Err(RuntimeError::expected_type(loc, types::STRING, val)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(val: Value, loc: Loc) -> Result<Self, RuntimeError> {
+        match val {
+            Value::Nil => Ok(None),
+            val => T::from_value(val, loc).map(Some),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(val: Value, loc: Loc) -> Result<Self, RuntimeError> {
+        match val {
+            Value::Array(array) => array
+                .borrow()
+                .elements()
+                .into_iter()
+                .map(|el| T::from_value(el, loc))
+                .collect(),
+            val => Err(RuntimeError::expected_type(loc, types::ARRAY, val)),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self, _inter: &Interpreter) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self, _inter: &Interpreter) -> Value {
+        Value::Nil
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self, _inter: &Interpreter) -> Value {
+        self.into()
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self, _inter: &Interpreter) -> Value {
+        self.into()
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self, _inter: &Interpreter) -> Value {
+        self.into()
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self, _inter: &Interpreter) -> Value {
+        self.into()
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self, inter: &Interpreter) -> Value {
+        match self {
+            Some(val) => val.into_value(inter),
+            None => Value::Nil,
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self, inter: &Interpreter) -> Value {
+        let elements = self.into_iter().map(|el| el.into_value(inter)).collect();
+        Array::new(inter, elements).into()
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         use Value::*;
@@ -164,6 +332,7 @@ impl Display for Value {
             Callable(callable) => callable.fmt(f),
             Instance(instance) => instance.borrow().fmt(f),
             Array(array) => array.borrow().fmt(f),
+            Map(map) => map.borrow().fmt(f),
         }
     }
 }
@@ -179,6 +348,7 @@ impl PartialEq for Value {
             (Nil, Nil) => true,
             (Callable(left), Callable(right)) => left == right,
             (Array(left), Array(right)) => Rc::ptr_eq(left, right),
+            (Map(left), Map(right)) => Rc::ptr_eq(left, right),
             (_, _) => false,
         }
     }
@@ -0,0 +1,753 @@
+use crate::array::Array;
+use crate::callable::{Arity, Callable, LoxCallable};
+use crate::chunk::{Chunk, OpCode, Prototype};
+use crate::class::{ClassInstance, GenericClass, Method, MethodMap};
+use crate::compiler;
+use crate::constants::{HAS_NEXT_METHOD, ITERATOR_METHOD, NEXT_METHOD};
+use crate::eval::{GlobalEnv, Interpreter, RuntimeError, RuntimeInterrupt, ValueRes};
+use crate::expr::Expr;
+use crate::gc::{self, Trace, Tracer};
+use crate::location::Loc;
+use crate::map::{Map, MapKey};
+use crate::module;
+use crate::scriptable::LoxScriptable;
+use crate::stmt::{FunctionKind, Stmt};
+use crate::string;
+use crate::value::Value;
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::rc::Rc;
+
+/// The state of the bytecode VM. It lives in the `Interpreter`, so that
+/// natives calling back into compiled closures run on the same stack.
+#[derive(Debug, Default)]
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    open_upvalues: Vec<UpvalueRef>,
+    handlers: Vec<Handler>,
+    pending: Vec<RuntimeInterrupt>,
+}
+
+#[derive(Debug)]
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    slots: usize,
+}
+
+/// A compiled function along with the variables it captured.
+#[derive(Debug)]
+pub struct Closure {
+    proto: Rc<Prototype>,
+    upvalues: Vec<UpvalueRef>,
+    globals: GlobalEnv,
+}
+
+/// A captured variable. It points into the stack while the variable is in
+/// scope, and holds the value itself once the scope is gone.
+#[derive(Debug)]
+enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+type UpvalueRef = Rc<RefCell<Upvalue>>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum HandlerKind {
+    Catch,
+    Finally,
+}
+
+#[derive(Debug)]
+struct Handler {
+    kind: HandlerKind,
+    frame: usize,
+    stack_len: usize,
+    pending_len: usize,
+    call_depth: usize,
+    target: usize,
+}
+
+type OpRes = Result<(), RuntimeInterrupt>;
+
+pub fn run_script(
+    inter: &mut Interpreter,
+    stmts: &[Stmt],
+    globals: &GlobalEnv,
+) -> Result<(), RuntimeError> {
+    let proto = compiler::compile_script(stmts)?;
+    run_proto(inter, proto, globals).map(|_| ())
+}
+
+pub fn evaluate(inter: &mut Interpreter, expr: &Expr) -> ValueRes {
+    let proto = compiler::compile_expr(expr)?;
+    let globals = Rc::clone(&inter.globals);
+    run_proto(inter, proto, &globals)
+}
+
+fn run_proto(inter: &mut Interpreter, proto: Prototype, globals: &GlobalEnv) -> ValueRes {
+    let closure = gc::alloc(Closure::new(Rc::new(proto), Vec::new(), globals));
+    closure.invoke(inter, Value::Nil, Vec::new(), Loc::default())
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn chunk(&self) -> &Chunk {
+        &self.frame().closure.proto.chunk
+    }
+
+    fn closure(&self) -> Rc<Closure> {
+        Rc::clone(&self.frame().closure)
+    }
+
+    fn fetch(&mut self) -> (OpCode, Loc) {
+        let frame = self.frames.last_mut().unwrap();
+        let chunk = &frame.closure.proto.chunk;
+        let res = (chunk.code[frame.ip], chunk.locs[frame.ip]);
+        frame.ip += 1;
+        res
+    }
+
+    fn jump(&mut self, offset: u16) {
+        self.frame_mut().ip += offset as usize;
+    }
+
+    fn push(&mut self, val: Value) {
+        self.stack.push(val);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("Expected a value in the stack")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn pop_many(&mut self, count: usize) -> Vec<Value> {
+        self.stack.split_off(self.stack.len() - count)
+    }
+
+    fn slot(&self, slot: u16) -> usize {
+        self.frame().slots + slot as usize
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> UpvalueRef {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(&*upvalue.borrow(), Upvalue::Open(open) if *open == slot));
+
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+
+        let upvalue = gc::alloc(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+        upvalue
+    }
+
+    /// Moves the values of the captured variables at or above `from` out of
+    /// the stack, before their slots are popped.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let slot = match &*upvalue.borrow() {
+                Upvalue::Open(slot) => *slot,
+                Upvalue::Closed(_) => return false,
+            };
+
+            if slot >= from {
+                upvalue.replace(Upvalue::Closed(stack[slot].clone()));
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    fn get_upvalue(&self, upvalue: &UpvalueRef) -> Value {
+        match &*upvalue.borrow() {
+            Upvalue::Open(slot) => self.stack[*slot].clone(),
+            Upvalue::Closed(val) => val.clone(),
+        }
+    }
+
+    fn set_upvalue(&mut self, upvalue: &UpvalueRef, val: Value) {
+        match &mut *upvalue.borrow_mut() {
+            Upvalue::Open(slot) => self.stack[*slot] = val,
+            Upvalue::Closed(closed) => *closed = val,
+        }
+    }
+
+    fn push_frame(
+        &mut self,
+        closure: Rc<Closure>,
+        argc: usize,
+        loc: Loc,
+    ) -> Result<(), RuntimeError> {
+        if closure.proto.arity != argc {
+            return Err(RuntimeError::mismatching_arity(loc, closure.arity(), argc));
+        }
+
+        let slots = self.stack.len() - argc - 1;
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots,
+        });
+
+        Ok(())
+    }
+
+    fn setup_handler(&mut self, kind: HandlerKind, offset: u16, call_depth: usize) {
+        let handler = Handler {
+            kind,
+            frame: self.frames.len() - 1,
+            stack_len: self.stack.len(),
+            pending_len: self.pending.len(),
+            call_depth,
+            target: self.frame().ip + offset as usize,
+        };
+
+        self.handlers.push(handler);
+    }
+}
+
+/// Runs frames until the one at index `base` returns, unwinding to the
+/// handlers that frame and the ones above it set up.
+fn run(inter: &mut Interpreter, base: usize) -> ValueRes {
+    let pending_len = inter.vm.pending.len();
+    let call_depth = inter.call_depth();
+    loop {
+        let interrupt = match execute(inter, base) {
+            Ok(val) => return Ok(val),
+            Err(interrupt) => interrupt,
+        };
+
+        match inter.vm.handlers.last() {
+            Some(handler) if handler.frame >= base => catch(inter, interrupt),
+            _ => {
+                let vm = &mut inter.vm;
+                let slots = vm.frames[base].slots;
+                vm.close_upvalues(slots);
+                vm.stack.truncate(slots);
+                vm.frames.truncate(base);
+                vm.pending.truncate(pending_len);
+                inter.record_traceback();
+                inter.unwind_frames(call_depth);
+
+                return Err(match interrupt {
+                    RuntimeInterrupt::Throw(val, loc) => inter.stash_exception(val, loc),
+                    interrupt => interrupt.expect_error(),
+                });
+            }
+        }
+    }
+}
+
+fn catch(inter: &mut Interpreter, interrupt: RuntimeInterrupt) {
+    let vm = &mut inter.vm;
+    let handler = vm.handlers.pop().unwrap();
+    vm.close_upvalues(handler.stack_len);
+    vm.stack.truncate(handler.stack_len);
+    vm.frames.truncate(handler.frame + 1);
+    vm.pending.truncate(handler.pending_len);
+    vm.frame_mut().ip = handler.target;
+    inter.unwind_frames(handler.call_depth);
+
+    match handler.kind {
+        HandlerKind::Catch => {
+            let exception = inter.exception_value(interrupt);
+            inter.vm.push(exception);
+        }
+        HandlerKind::Finally => {
+            let interrupt = inter.detach_exception(interrupt);
+            inter.vm.pending.push(interrupt);
+        }
+    }
+}
+
+fn execute(inter: &mut Interpreter, base: usize) -> Result<Value, RuntimeInterrupt> {
+    loop {
+        let (op, loc) = inter.vm.fetch();
+        let vm = &mut inter.vm;
+
+        match op {
+            OpCode::Constant(index) => {
+                let val = vm.chunk().value(index);
+                vm.push(val);
+            }
+            OpCode::Nil => vm.push(Value::Nil),
+            OpCode::True => vm.push(Value::Boolean(true)),
+            OpCode::False => vm.push(Value::Boolean(false)),
+            OpCode::Pop => {
+                vm.pop();
+            }
+            OpCode::GetLocal(slot) => {
+                let val = vm.stack[vm.slot(slot)].clone();
+                vm.push(val);
+            }
+            OpCode::SetLocal(slot) => {
+                let slot = vm.slot(slot);
+                vm.stack[slot] = vm.peek(0).clone();
+            }
+            OpCode::GetGlobal(index) => {
+                let closure = vm.closure();
+                let name = closure.proto.chunk.name(index);
+                let val = closure.globals.borrow().get(name, loc)?;
+                vm.push(val);
+            }
+            OpCode::DefineGlobal(index) => {
+                let closure = vm.closure();
+                let name = closure.proto.chunk.name(index);
+                let val = vm.pop();
+                closure.globals.borrow_mut().define(name, val);
+            }
+            OpCode::SetGlobal(index) => {
+                let closure = vm.closure();
+                let name = closure.proto.chunk.name(index);
+                let val = vm.peek(0).clone();
+                closure.globals.borrow_mut().assign(name, val, loc)?;
+            }
+            OpCode::GetUpvalue(index) => {
+                let val = vm.get_upvalue(&vm.frame().closure.upvalues[index as usize]);
+                vm.push(val);
+            }
+            OpCode::SetUpvalue(index) => {
+                let upvalue = Rc::clone(&vm.frame().closure.upvalues[index as usize]);
+                let val = vm.peek(0).clone();
+                vm.set_upvalue(&upvalue, val);
+            }
+            OpCode::GetProperty(index) => {
+                let closure = vm.closure();
+                let name = closure.proto.chunk.name(index);
+                let obj = vm.pop();
+                let val_type = obj.get_type();
+                let instance = obj
+                    .into_instance(inter)
+                    .ok_or_else(|| RuntimeError::no_properties(loc, val_type))?;
+                let val = ClassInstance::get(inter, &instance, name, loc)
+                    .unwrap_or_else(|| Err(RuntimeError::undefined_property(loc, name)))?;
+                inter.vm.push(val);
+            }
+            OpCode::SetProperty(index) => {
+                let closure = vm.closure();
+                let name = closure.proto.chunk.name(index);
+                let val = vm.pop();
+                let obj = vm.pop();
+                let val_type = obj.get_type();
+                let instance = obj
+                    .into_instance(inter)
+                    .ok_or_else(|| RuntimeError::no_fields(loc, val_type))?;
+                instance.borrow_mut().set(name, val.clone(), loc);
+                inter.vm.push(val);
+            }
+            OpCode::GetSuper(index) => {
+                let closure = vm.closure();
+                let name = closure.proto.chunk.name(index);
+                let superclass = vm.pop().into_class().unwrap();
+                let instance = vm.pop().into_instance(inter).unwrap();
+                let val = superclass
+                    .get_and_bind(&instance, inter, name, loc)
+                    .unwrap_or_else(|| Err(RuntimeError::undefined_property(loc, name)))?;
+                inter.vm.push(val);
+            }
+            OpCode::GetIndex => {
+                let index = vm.pop();
+                let val = match vm.pop().into_scriptable() {
+                    Ok(scriptable) => scriptable.subscript_get(index, loc)?,
+                    Err(obj) => return Err(RuntimeError::not_a_scriptable(loc, obj).into()),
+                };
+                vm.push(val);
+            }
+            OpCode::SetIndex => {
+                let val = vm.pop();
+                let index = vm.pop();
+                let val = match vm.pop().into_scriptable() {
+                    Ok(mut scriptable) => scriptable.subscript_set(index, val, loc)?,
+                    Err(obj) => return Err(RuntimeError::not_a_scriptable(loc, obj).into()),
+                };
+                vm.push(val);
+            }
+            OpCode::Equal => binary(vm, |left, right| Ok(left.equal(&right)))?,
+            OpCode::NotEqual => binary(vm, |left, right| Ok(left.not_eq(&right)))?,
+            OpCode::Greater => binary(vm, |left, right| left.greater(&right, loc))?,
+            OpCode::GreaterEqual => binary(vm, |left, right| left.greater_eq(&right, loc))?,
+            OpCode::Less => binary(vm, |left, right| left.less(&right, loc))?,
+            OpCode::LessEqual => binary(vm, |left, right| left.less_eq(&right, loc))?,
+            OpCode::Add => binary(vm, |left, right| left.add(right, loc))?,
+            OpCode::Subtract => binary(vm, |left, right| left.sub(right, loc))?,
+            OpCode::Multiply => binary(vm, |left, right| left.mul(right, loc))?,
+            OpCode::Divide => binary(vm, |left, right| left.div(right, loc))?,
+            OpCode::Remainder => binary(vm, |left, right| left.rem(right, loc))?,
+            OpCode::Not => {
+                let val = vm.pop();
+                vm.push(!val);
+            }
+            OpCode::Negate => {
+                let val = vm.pop().negate(loc)?;
+                vm.push(val);
+            }
+            OpCode::Print => {
+                let val = vm.pop();
+                inter.print(&val, loc)?;
+            }
+            OpCode::Jump(offset) => vm.jump(offset),
+            OpCode::JumpIfFalse(offset) => {
+                if !vm.peek(0).is_truthy() {
+                    vm.jump(offset);
+                }
+            }
+            OpCode::Loop(offset) => vm.frame_mut().ip -= offset as usize,
+            OpCode::Call(argc) => call(inter, argc as usize, loc)?,
+            OpCode::Closure(index) => {
+                let closure = vm.closure();
+                let proto = Rc::clone(closure.proto.chunk.function(index));
+                let slots = vm.frame().slots;
+                let upvalues = proto
+                    .upvalues
+                    .iter()
+                    .map(|upvalue| {
+                        if upvalue.is_local {
+                            vm.capture_upvalue(slots + upvalue.index as usize)
+                        } else {
+                            Rc::clone(&closure.upvalues[upvalue.index as usize])
+                        }
+                    })
+                    .collect();
+
+                let function = Closure::new(proto, upvalues, &closure.globals);
+                vm.push(Value::Callable(gc::alloc(function).into()));
+            }
+            OpCode::CloseUpvalue => {
+                vm.close_upvalues(vm.stack.len() - 1);
+                vm.pop();
+            }
+            OpCode::Return => {
+                let val = vm.pop();
+                let frame = vm.frames.pop().unwrap();
+                vm.close_upvalues(frame.slots);
+                vm.stack.truncate(frame.slots);
+                let frames = vm.frames.len();
+                while matches!(vm.handlers.last(), Some(handler) if handler.frame >= frames) {
+                    vm.handlers.pop();
+                }
+
+                if frames == base {
+                    return Ok(val);
+                }
+
+                vm.push(val);
+                inter.pop_frame();
+            }
+            OpCode::Array(len) => {
+                let elements = vm.pop_many(len as usize);
+                let array = Array::new(inter, elements);
+                inter.vm.push(array.into());
+            }
+            OpCode::Map(len) => {
+                let entries = vm.pop_many(len as usize * 2);
+                let mut data = IndexMap::with_capacity(len as usize);
+                let mut entries = entries.into_iter();
+                while let (Some(key), Some(val)) = (entries.next(), entries.next()) {
+                    data.insert(MapKey::from_value(key, loc)?, val);
+                }
+
+                let map = Map::new(inter, data);
+                inter.vm.push(map.into());
+            }
+            OpCode::Inherit => {
+                if vm.peek(0).clone().into_class().is_none() {
+                    let val_type = vm.peek(0).get_type();
+                    return Err(RuntimeError::superclass_is_not_class(loc, val_type).into());
+                }
+            }
+            OpCode::Class(index, count, inherits) => {
+                let closure = vm.closure();
+                let name = closure.proto.chunk.name(index);
+                let class_methods = vm.pop_many(count as usize);
+                let superclass = if inherits {
+                    vm.peek(0).clone().into_class()
+                } else {
+                    None
+                };
+
+                let mut methods = MethodMap::new();
+                let mut getters = MethodMap::new();
+                let mut static_methods = MethodMap::new();
+                for method in class_methods {
+                    let method = match method {
+                        Value::Callable(Callable::Closure(method)) => method,
+                        val => panic!("Expected a method closure. Got: {:?}", val),
+                    };
+
+                    let name = String::from(method.name().unwrap());
+                    let kind = method.proto.kind;
+                    let method = Method::Closure(method);
+                    match kind {
+                        FunctionKind::Method => methods.insert(name, method),
+                        FunctionKind::Getter => getters.insert(name, method),
+                        FunctionKind::StaticMethod => static_methods.insert(name, method),
+                        FunctionKind::Function => unreachable!(),
+                    };
+                }
+
+                let class = GenericClass::new(name, superclass, methods, getters, static_methods);
+                vm.push(Value::Callable(class.into()));
+            }
+            OpCode::IterInit => {
+                let iterable = vm.pop();
+                let (sequence, cursor) = match iterable {
+                    Value::Array(array) => {
+                        let elements = array.borrow().elements();
+                        (Array::new(inter, elements).into(), Value::Integer(0))
+                    }
+                    Value::Str(string) => (
+                        Array::new(inter, string::chars(&string)).into(),
+                        Value::Integer(0),
+                    ),
+                    Value::Map(map) => {
+                        let keys = map.borrow().keys();
+                        (Array::new(inter, keys).into(), Value::Integer(0))
+                    }
+                    Value::Instance(instance) => {
+                        let iterator_fn =
+                            ClassInstance::get(inter, &instance, ITERATOR_METHOD, loc)
+                                .unwrap_or_else(|| {
+                                    Err(RuntimeError::not_iterable(loc, Value::Instance(instance)))
+                                })?;
+                        (inter.call_value(iterator_fn, vec![], loc)?, Value::Nil)
+                    }
+                    val => return Err(RuntimeError::not_iterable(loc, val).into()),
+                };
+
+                inter.vm.push(sequence);
+                inter.vm.push(cursor);
+            }
+            OpCode::IterNext(offset) => {
+                let next = match (vm.peek(1).clone(), vm.peek(0).clone()) {
+                    (Value::Array(array), Value::Integer(index)) => {
+                        let array = array.borrow();
+                        if (index as usize) < array.len() {
+                            let top = vm.stack.len() - 1;
+                            vm.stack[top] = Value::Integer(index + 1);
+                            Some(array.get(index, loc)?)
+                        } else {
+                            None
+                        }
+                    }
+                    (iterator, _) => {
+                        if inter
+                            .call_method(iterator.clone(), HAS_NEXT_METHOD, loc)?
+                            .is_truthy()
+                        {
+                            Some(inter.call_method(iterator, NEXT_METHOD, loc)?)
+                        } else {
+                            None
+                        }
+                    }
+                };
+
+                match next {
+                    Some(val) => inter.vm.push(val),
+                    None => inter.vm.jump(offset),
+                }
+            }
+            OpCode::Import(index) => {
+                let closure = vm.closure();
+                let path = closure.proto.chunk.name(index);
+                let module = module::import(inter, path, loc)?;
+                inter.vm.push(module);
+            }
+            OpCode::ImportName(index, path_index) => {
+                let closure = vm.closure();
+                let name = closure.proto.chunk.name(index);
+                let path = closure.proto.chunk.name(path_index);
+                let module = vm.pop().into_instance(inter).unwrap();
+                let val = module.borrow().field(name).ok_or_else(|| {
+                    RuntimeError::UndefinedExport(loc, String::from(name), String::from(path))
+                })?;
+                inter.vm.push(val);
+            }
+            OpCode::Throw => {
+                let val = vm.pop();
+                return Err(RuntimeInterrupt::Throw(val, loc));
+            }
+            OpCode::SetupCatch(offset) => {
+                let call_depth = inter.call_depth();
+                inter
+                    .vm
+                    .setup_handler(HandlerKind::Catch, offset, call_depth);
+            }
+            OpCode::SetupFinally(offset) => {
+                let call_depth = inter.call_depth();
+                inter
+                    .vm
+                    .setup_handler(HandlerKind::Finally, offset, call_depth);
+            }
+            OpCode::PopHandler => {
+                vm.handlers.pop();
+            }
+            OpCode::EndFinally => {
+                let interrupt = vm.pending.pop().unwrap();
+                return Err(interrupt);
+            }
+        }
+    }
+}
+
+fn binary<F>(vm: &mut Vm, op: F) -> Result<(), RuntimeError>
+where
+    F: FnOnce(Value, Value) -> ValueRes,
+{
+    let right = vm.pop();
+    let left = vm.pop();
+    vm.push(op(left, right)?);
+    Ok(())
+}
+
+/// Calls the value below the arguments on the stack. Compiled closures get a
+/// new frame in the current run, anything else is called right away.
+fn call(inter: &mut Interpreter, argc: usize, loc: Loc) -> OpRes {
+    let vm = &mut inter.vm;
+    let callee_slot = vm.stack.len() - argc - 1;
+
+    let closure = match &vm.stack[callee_slot] {
+        Value::Callable(Callable::Closure(closure)) => Rc::clone(closure),
+        Value::Callable(Callable::BoundMethod(method)) if method.closure().is_some() => {
+            let closure = Rc::clone(method.closure().unwrap());
+            vm.stack[callee_slot] = Value::Instance(Rc::clone(method.receiver()));
+            closure
+        }
+        _ => {
+            let args = vm.pop_many(argc);
+            let callee = vm.pop();
+            let val = inter.call_value(callee, args, loc)?;
+            inter.vm.push(val);
+            return Ok(());
+        }
+    };
+
+    vm.push_frame(Rc::clone(&closure), argc, loc)?;
+    inter.push_frame(closure.frame_name(), loc)?;
+    Ok(())
+}
+
+impl Closure {
+    fn new(proto: Rc<Prototype>, upvalues: Vec<UpvalueRef>, globals: &GlobalEnv) -> Self {
+        Self {
+            proto,
+            upvalues,
+            globals: Rc::clone(globals),
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.proto.name.as_deref()
+    }
+
+    fn frame_name(&self) -> &str {
+        self.name().unwrap_or("<anonymous fn>")
+    }
+
+    /// Runs the closure with `receiver` in its first slot, which is where
+    /// methods find `this`.
+    pub fn call_with_receiver(
+        self: &Rc<Self>,
+        inter: &mut Interpreter,
+        receiver: Value,
+        args: Vec<Value>,
+        loc: Loc,
+    ) -> ValueRes {
+        inter.with_frame(self.frame_name(), loc, |inter| {
+            self.invoke(inter, receiver, args, loc)
+        })
+    }
+
+    fn invoke(
+        self: &Rc<Self>,
+        inter: &mut Interpreter,
+        receiver: Value,
+        args: Vec<Value>,
+        loc: Loc,
+    ) -> ValueRes {
+        let base = inter.vm.frames.len();
+        let slots = inter.vm.stack.len();
+        let argc = args.len();
+        inter.vm.push(receiver);
+        inter.vm.stack.extend(args);
+        if let Err(error) = inter.vm.push_frame(Rc::clone(self), argc, loc) {
+            inter.vm.stack.truncate(slots);
+            return Err(error);
+        }
+
+        run(inter, base)
+    }
+}
+
+impl Trace for Closure {
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        self.upvalues.iter().for_each(|upvalue| tracer.rc(upvalue));
+        true
+    }
+}
+
+impl Trace for RefCell<Upvalue> {
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        match self.try_borrow().as_deref() {
+            Ok(Upvalue::Open(_slot)) => true,
+            Ok(Upvalue::Closed(val)) => {
+                tracer.value(val);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut upvalue) = self.try_borrow_mut() {
+            *upvalue = Upvalue::Closed(Value::Nil);
+        }
+    }
+}
+
+impl LoxCallable for Rc<Closure> {
+    fn arity(&self) -> Arity {
+        Arity::Fixed(self.proto.arity)
+    }
+
+    fn call(&self, inter: &mut Interpreter, args: Vec<Value>, loc: Loc) -> ValueRes {
+        let callee = Value::Callable(Rc::clone(self).into());
+        self.call_with_receiver(inter, callee, args, loc)
+    }
+}
+
+impl From<Rc<Closure>> for Callable {
+    fn from(closure: Rc<Closure>) -> Self {
+        Callable::Closure(closure)
+    }
+}
+
+impl Display for Closure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.name() {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<anonymous fn>"),
+        }
+    }
+}